@@ -0,0 +1,97 @@
+//! Compares a loop of `add` calls with `add_batch` on a pre-sized index, and measures what
+//! pre-sizing alone saves on a 100k-document load.
+//!
+//! Run with `cargo bench --bench add_batch`.
+
+use inverted_index::InvertedIndex;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Counts every allocation and reallocation made by the process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const DOCS: usize = 20_000;
+const LARGE_DOCS: usize = 100_000;
+
+fn corpus(docs: usize) -> Vec<(usize, String)> {
+    (0..docs)
+        .map(|id| {
+            let content = format!(
+                "document {id} talks about topic{} and subject{} in a rather long sentence",
+                id % 300,
+                id % 17
+            );
+            (id, content)
+        })
+        .collect()
+}
+
+fn measure(name: &str, f: impl FnOnce() -> InvertedIndex) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let index = f();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{name:<28} {elapsed:>10.2?} {allocations:>10} allocations ({} terms)",
+        index.term_count()
+    );
+}
+
+fn main() {
+    let docs = corpus(DOCS);
+    measure("add loop", || {
+        let mut index = InvertedIndex::new();
+        for (id, content) in &docs {
+            index.add(*id, content);
+        }
+        index
+    });
+
+    let owned = docs.clone();
+    measure("with_capacity + add_batch", move || {
+        let mut index = InvertedIndex::with_capacity(DOCS, 400);
+        index.add_batch(owned);
+        index
+    });
+
+    // Every document has a unique "document {id}" term, so the term dictionary grows with
+    // the document table.
+    let large = corpus(LARGE_DOCS);
+    measure("100k add loop", || {
+        let mut index = InvertedIndex::new();
+        for (id, content) in &large {
+            index.add(*id, content);
+        }
+        index
+    });
+    measure("100k with_capacity + add loop", || {
+        let mut index = InvertedIndex::with_capacity(LARGE_DOCS, LARGE_DOCS + 400);
+        for (id, content) in &large {
+            index.add(*id, content);
+        }
+        index
+    });
+}
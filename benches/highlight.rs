@@ -0,0 +1,63 @@
+//! Compares highlighting a thousand hits with `highlight`, which compiles its pattern for
+//! every document, against one `Highlighter` reused for all of them, and times `query`,
+//! which builds a `Highlighter` once per query.
+//!
+//! Run with `cargo bench --bench highlight`.
+
+use inverted_index::{Highlighter, InvertedIndex, highlight};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DOCS: usize = 1_000;
+const ROUNDS: u32 = 20;
+
+fn corpus() -> Vec<String> {
+    (0..DOCS)
+        .map(|id| {
+            format!(
+                "document {id} is about rust and topic{}, and rust again near the end of \
+                 a sentence long enough to make scanning it take a little while",
+                id % 50
+            )
+        })
+        .collect()
+}
+
+/// Prints the average time of one round of `f`.
+fn measure(name: &str, mut f: impl FnMut() -> usize) {
+    let mut total = Duration::ZERO;
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        bytes = black_box(f());
+        total += start.elapsed();
+    }
+    println!(
+        "{name:<32} {:>10.2?} per {DOCS} documents ({bytes} bytes)",
+        total / ROUNDS
+    );
+}
+
+fn main() {
+    // Highlight the codes even when the output is piped, as the results are not printed.
+    colored::control::set_override(true);
+    let docs = corpus();
+
+    measure("highlight per document", || {
+        docs.iter().map(|doc| highlight("rust", doc).len()).sum()
+    });
+    measure("one Highlighter", || {
+        let highlighter = Highlighter::new("rust").unwrap();
+        docs.iter()
+            .map(|doc| highlighter.highlight(doc).len())
+            .sum()
+    });
+
+    let mut index = InvertedIndex::new();
+    for (id, doc) in docs.iter().enumerate() {
+        index.add(id, doc);
+    }
+    measure("InvertedIndex::query", || {
+        index.query("rust").iter().map(String::len).sum()
+    });
+}
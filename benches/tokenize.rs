@@ -0,0 +1,87 @@
+//! Compares how much tokenizing a large document allocates when the whole text is lowercased
+//! first and every token then copied, against `tokenize_normalized`, which lowercases each
+//! token as it is copied out, and against the `tokenize` iterator, which allocates nothing,
+//! and times `add` of the same document.
+//!
+//! Run with `cargo bench --bench tokenize`.
+
+use inverted_index::{InvertedIndex, tokenize, tokenize_normalized, tokenize_vec};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const WORDS: usize = 200_000;
+const ROUNDS: u32 = 10;
+
+/// Counts the allocations and allocated bytes of the whole process.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        // SAFETY: forwarded unchanged to the system allocator.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: `ptr` was allocated by `alloc` above with this layout.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn document() -> String {
+    (0..WORDS)
+        .map(|i| format!("Word{} Rust", i % 997))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Prints the average time, allocations and allocated bytes of one round of `f`.
+fn measure(name: &str, mut f: impl FnMut() -> usize) {
+    let mut total = Duration::ZERO;
+    let (allocations, bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        BYTES.load(Ordering::Relaxed),
+    );
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        black_box(f());
+        total += start.elapsed();
+    }
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ROUNDS as usize;
+    let bytes = (BYTES.load(Ordering::Relaxed) - bytes) / ROUNDS as usize;
+    println!(
+        "{name:<32} {:>10.2?} {allocations:>8} allocations {bytes:>10} bytes",
+        total / ROUNDS
+    );
+}
+
+fn main() {
+    let doc = document();
+    println!("{} words, {} bytes", WORDS * 2, doc.len());
+
+    measure("lowercase, then tokenize", || {
+        let lowercase = doc.to_lowercase();
+        let tokens: Vec<String> = tokenize_vec(&lowercase)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        tokens.len()
+    });
+    measure("tokenize_normalized", || tokenize_normalized(&doc).len());
+    measure("tokenize_vec", || tokenize_vec(&doc).len());
+    measure("tokenize", || tokenize(&doc).map(|token| token.end).sum());
+    measure("InvertedIndex::add", || {
+        let mut index = InvertedIndex::new();
+        index.add(1, &doc);
+        index.term_count()
+    });
+}
@@ -0,0 +1,354 @@
+use crate::filters::TokenFilters;
+use crate::stemmer::stem;
+use crate::tokenizer::{SimpleTokenizer, Tokenizer, TokenizerKind, tokenize, tokenize_normalized};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Turns text into index terms. The same analyzer runs over documents at `add` time and over
+/// queries, so both sides agree on what a term is.
+#[derive(Debug, Clone)]
+pub(crate) struct Analyzer {
+    /// Splits text into tokens before they are normalized.
+    pub(crate) tokenizer: Arc<dyn Tokenizer>,
+    /// Which built-in tokenizer `tokenizer` is, `None` for a custom one.
+    pub(crate) tokenizer_kind: Option<TokenizerKind>,
+    /// Keep the original casing of tokens instead of lowercasing them.
+    pub(crate) case_sensitive: bool,
+    /// Lowercased words that are never indexed.
+    pub(crate) stop_words: HashSet<String>,
+    /// Further tokens that are never indexed, such as too long or numeric ones.
+    pub(crate) filters: TokenFilters,
+    /// Reduce tokens to their stem with the Porter stemmer.
+    pub(crate) stemming: bool,
+    /// Stems computed while indexing, keyed by the case-folded token.
+    pub(crate) stem_cache: HashMap<String, String>,
+}
+
+/// The `(position, term)` pairs of a document analyzed at index time, with the terms
+/// stored end to end in one string so that none of them is allocated on its own.
+#[derive(Debug, Default)]
+pub(crate) struct TermBuffer {
+    text: String,
+    /// The position of each term and where it ends in `text`, in order.
+    entries: Vec<(usize, usize)>,
+}
+
+impl TermBuffer {
+    pub(crate) fn clear(&mut self) {
+        self.text.clear();
+        self.entries.clear();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn push(&mut self, position: usize, term: &str) {
+        self.text.push_str(term);
+        self.entries.push((position, self.text.len()));
+    }
+
+    /// The `i`th pair.
+    ///
+    /// # Panics
+    /// If `i` is out of bounds.
+    pub(crate) fn get(&self, i: usize) -> (usize, &str) {
+        let start = i.checked_sub(1).map_or(0, |before| self.entries[before].1);
+        let (position, end) = self.entries[i];
+        (position, &self.text[start..end])
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Binary searches the pairs, which must be in ascending position order, for the term
+    /// at `position`, like [`slice::binary_search`].
+    pub(crate) fn search(&self, position: usize) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by_key(&position, |(position, _)| *position)
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            tokenizer: Arc::new(SimpleTokenizer),
+            tokenizer_kind: Some(TokenizerKind::Simple),
+            case_sensitive: false,
+            stop_words: HashSet::new(),
+            filters: TokenFilters::default(),
+            stemming: false,
+            stem_cache: HashMap::new(),
+        }
+    }
+}
+
+impl Analyzer {
+    /// Splits `text` into normalized terms, in order, skipping stop words and filtered tokens.
+    pub(crate) fn analyze(&self, text: &str) -> Vec<String> {
+        if self.tokenizer_kind == Some(TokenizerKind::Simple) && !self.case_sensitive {
+            // Same terms, with each token lowercased while it is copied out.
+            return tokenize_normalized(text)
+                .into_iter()
+                .filter(|term| !self.stop_words.contains(term) && self.filters.keep(term))
+                .map(|term| self.stem_folded(term))
+                .collect();
+        }
+        self.tokenizer
+            .tokenize(text)
+            .into_iter()
+            .filter_map(|token| self.normalize(token))
+            .collect()
+    }
+
+    /// Like [`analyze`](Self::analyze), but pairs every term with its token position.
+    ///
+    /// Positions count every token, stop words included, so that the gap a dropped stop word
+    /// leaves between two terms is kept.
+    pub(crate) fn analyze_positions(&self, text: &str) -> Vec<(usize, String)> {
+        self.tokenizer
+            .tokenize(text)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(position, token)| Some((position, self.normalize(token)?)))
+            .collect()
+    }
+
+    /// The byte range in `text` of every token, stop words included, so that the range of
+    /// the token at position `p` is at index `p`.
+    #[cfg(any(test, feature = "parallel"))]
+    pub(crate) fn token_offsets(&self, text: &str) -> Vec<(usize, usize)> {
+        self.tokenizer
+            .tokenize(text)
+            .into_iter()
+            .map(|token| byte_range(text, token))
+            .collect()
+    }
+
+    /// Like [`analyze_positions`](Self::analyze_positions), but appends the terms to `out`,
+    /// numbering tokens from `first_position`, and remembers every computed stem so repeated
+    /// words are only stemmed once. Used at index time, where the same vocabulary recurs
+    /// constantly.
+    ///
+    /// The byte range in `text` of every token, stop words included, is appended to
+    /// `offsets`, see [`token_offsets`](Self::token_offsets).
+    ///
+    /// # Returns
+    /// The position after the last token of `text`, to continue numbering from.
+    pub(crate) fn analyze_into(
+        &mut self,
+        text: &str,
+        first_position: usize,
+        out: &mut TermBuffer,
+        offsets: &mut Vec<(usize, usize)>,
+    ) -> usize {
+        // Reused for every token, so that only new stems allocate.
+        let mut folded = String::new();
+        let mut position = first_position;
+        if self.tokenizer_kind == Some(TokenizerKind::Simple) {
+            // The default tokenizer's words without collecting them first.
+            for token in tokenize(text) {
+                position += 1;
+                offsets.push((token.start, token.end));
+                self.push_term(position - 1, token.text, &mut folded, out);
+            }
+            return position;
+        }
+        for token in self.tokenizer.tokenize(text) {
+            position += 1;
+            offsets.push(byte_range(text, token));
+            self.push_term(position - 1, token, &mut folded, out);
+        }
+        position
+    }
+
+    /// Appends the term of `token` to `out` unless it is dropped, stemming it through the
+    /// cache. `folded` is scratch space.
+    fn push_term(
+        &mut self,
+        position: usize,
+        token: &str,
+        folded: &mut String,
+        out: &mut TermBuffer,
+    ) {
+        if !self.fold_into(token, folded) {
+            return;
+        }
+        if !self.stemming {
+            out.push(position, folded);
+            return;
+        }
+        match self.stem_cache.get(folded.as_str()) {
+            Some(stemmed) => out.push(position, stemmed),
+            None => {
+                let stemmed = stem(folded);
+                out.push(position, &stemmed);
+                self.stem_cache.insert(folded.clone(), stemmed);
+            }
+        }
+    }
+
+    /// Normalizes every word of `text` and joins them with single spaces, or returns `None` if
+    /// `text` only holds stop words.
+    pub(crate) fn analyze_phrase(&self, text: &str) -> Option<String> {
+        let terms = self.analyze(text);
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// Normalizes a single token, or returns `None` if it is a stop word or filtered out.
+    pub(crate) fn normalize(&self, token: &str) -> Option<String> {
+        self.fold(token).map(|term| self.stem_folded(term))
+    }
+
+    /// Applies case folding, the stop-word filter and the token filters to a token.
+    fn fold(&self, token: &str) -> Option<String> {
+        if self.case_sensitive {
+            // Stop words still match case-insensitively, which takes a lowercase copy.
+            if !self.stop_words.is_empty() && self.stop_words.contains(&token.to_lowercase()) {
+                return None;
+            }
+            return self.filters.keep(token).then(|| token.to_string());
+        }
+        let lowercase = token.to_lowercase();
+        (!self.stop_words.contains(&lowercase) && self.filters.keep(&lowercase))
+            .then_some(lowercase)
+    }
+
+    /// Like [`fold`](Self::fold), but writes the folded token to `folded`, only allocating
+    /// for the rare tokens that are not ASCII.
+    ///
+    /// # Returns
+    /// `false` if the token is dropped.
+    fn fold_into(&self, token: &str, folded: &mut String) -> bool {
+        folded.clear();
+        if self.case_sensitive || !token.is_ascii() {
+            let Some(term) = self.fold(token) else {
+                return false;
+            };
+            folded.push_str(&term);
+            return true;
+        }
+        folded.push_str(token);
+        folded.make_ascii_lowercase();
+        !self.stop_words.contains(folded.as_str()) && self.filters.keep(folded)
+    }
+
+    /// Applies stemming to a folded term, reusing stems computed at index time.
+    fn stem_folded(&self, term: String) -> String {
+        if !self.stemming {
+            return term;
+        }
+        match self.stem_cache.get(&term) {
+            Some(stemmed) => stemmed.clone(),
+            None => stem(&term),
+        }
+    }
+}
+
+/// The range of `token`, a slice of `text` as the tokenizers return them, in bytes. Empty
+/// for a token that is not a slice of `text`.
+fn byte_range(text: &str, token: &str) -> (usize, usize) {
+    let start = (token.as_ptr() as usize).wrapping_sub(text.as_ptr() as usize);
+    if start
+        .checked_add(token.len())
+        .is_none_or(|end| end > text.len())
+    {
+        return (0, 0);
+    }
+    (start, start + token.len())
+}
+
+#[test]
+fn analyze_test() {
+    let analyzer = Analyzer {
+        case_sensitive: false,
+        stop_words: HashSet::from(["the".to_string()]),
+        stemming: true,
+        ..Analyzer::default()
+    };
+    assert_eq!(
+        analyzer.analyze("The Programs of the Programmer"),
+        vec!["program", "of", "programm"]
+    );
+    assert_eq!(Analyzer::default().analyze("The Rust"), vec!["the", "rust"]);
+    // The shortcut for the default tokenizer analyzes like the general path.
+    let text = "The Programs, the PROGRAMMER and Straße";
+    let general: Vec<String> = analyzer
+        .tokenizer
+        .tokenize(text)
+        .into_iter()
+        .filter_map(|token| analyzer.normalize(token))
+        .collect();
+    assert_eq!(analyzer.analyze(text), general);
+}
+
+#[test]
+fn stem_cache_test() {
+    let mut analyzer = Analyzer {
+        stemming: true,
+        ..Analyzer::default()
+    };
+    let text = "running runs running ran runs ".repeat(100);
+    let before = crate::stemmer::STEM_CALLS.with(|calls| calls.get());
+    let mut buffer = TermBuffer::default();
+    assert_eq!(
+        analyzer.analyze_into(&text, 0, &mut buffer, &mut Vec::new()),
+        500
+    );
+    let calls = crate::stemmer::STEM_CALLS.with(|calls| calls.get()) - before;
+
+    let terms: Vec<(usize, String)> = buffer
+        .iter()
+        .map(|(position, term)| (position, term.to_string()))
+        .collect();
+    assert_eq!(terms.len(), 500);
+    assert_eq!(terms[..2], [(0, "run".to_string()), (1, "run".to_string())]);
+    // One stemmer run per distinct word instead of one per token.
+    assert_eq!(calls, 3);
+    assert_eq!(analyzer.stem_cache.len(), 3);
+    assert_eq!(analyzer.analyze_positions(&text), terms);
+}
+
+#[test]
+fn analyze_positions_test() {
+    let mut analyzer = Analyzer {
+        stop_words: HashSet::from(["is".to_string()]),
+        ..Analyzer::default()
+    };
+    let expected = vec![(0, "rust".to_string()), (2, "fast".to_string())];
+    assert_eq!(analyzer.analyze_positions("Rust is fast"), expected);
+
+    let mut terms = TermBuffer::default();
+    let mut offsets = Vec::new();
+    let next = analyzer.analyze_into("Rust is", 10, &mut terms, &mut offsets);
+    assert_eq!(
+        analyzer.analyze_into("fast", next, &mut terms, &mut offsets),
+        13
+    );
+    assert_eq!(
+        terms.iter().collect::<Vec<_>>(),
+        vec![(10, "rust"), (12, "fast")]
+    );
+    assert_eq!(terms.search(12), Ok(1));
+    assert_eq!(terms.search(11), Err(1));
+    assert_eq!(offsets, vec![(0, 4), (5, 7), (0, 4)]);
+
+    // A custom tokenizer goes through the general path, with the same terms.
+    let mut custom = Analyzer {
+        tokenizer: Arc::new(crate::tokenizer::WordTokenizer),
+        tokenizer_kind: None,
+        case_sensitive: true,
+        ..Analyzer::default()
+    };
+    let mut terms = TermBuffer::default();
+    custom.analyze_into("Rust is fast", 0, &mut terms, &mut Vec::new());
+    assert_eq!(
+        terms.iter().collect::<Vec<_>>(),
+        vec![(0, "Rust"), (1, "is"), (2, "fast")]
+    );
+    assert_eq!(
+        analyzer.token_offsets("¡Olá, 🦀 café!"),
+        vec![(2, 6), (13, 18)]
+    );
+}
@@ -0,0 +1,145 @@
+//! Completing partly typed terms from the vocabulary.
+
+use crate::index::InvertedIndex;
+use std::cmp::Reverse;
+use std::sync::PoisonError;
+
+/// A completion offered by [`InvertedIndex::autocomplete`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// The indexed term, in its most frequent original
+    /// [spelling](crate::IndexBuilder::surface_forms).
+    pub term: String,
+    /// The number of documents containing the term.
+    pub doc_freq: usize,
+}
+
+/// The indexed terms in ascending order, as of one index generation.
+#[derive(Debug, Default)]
+pub(crate) struct TermDictionary {
+    /// The index generation the terms were collected at, `None` before the first lookup.
+    generation: Option<u64>,
+    terms: Vec<String>,
+}
+
+impl InvertedIndex {
+    /// Suggests indexed terms starting with `prefix`, such as completions for a search box.
+    ///
+    /// # Parameters
+    /// - `prefix`: The text typed so far. It is lowercased unless the index is case
+    ///   sensitive, but not stemmed, since a partial word has no stem.
+    /// - `limit`: The maximum number of suggestions.
+    ///
+    /// # Returns
+    /// The matching terms, in the most documents first and alphabetically on a tie. The
+    /// empty prefix matches every term, giving the overall top terms. Matching ignores
+    /// case, while each term is shown as the documents most often write it.
+    ///
+    /// # Notes
+    /// Lookups use a sorted term dictionary. It is rebuilt by the first call after the index
+    /// changes, so new terms are suggested as soon as their documents are added.
+    pub fn autocomplete(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        let prefix = if self.analyzer.case_sensitive {
+            prefix.to_string()
+        } else {
+            prefix.to_lowercase()
+        };
+        let mut dictionary = self
+            .dictionary
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if dictionary.generation != Some(self.generation) {
+            dictionary.terms = self.indexes.keys().cloned().collect();
+            dictionary.terms.sort_unstable();
+            dictionary.generation = Some(self.generation);
+        }
+
+        let start = dictionary
+            .terms
+            .partition_point(|term| term.as_str() < prefix.as_str());
+        let mut suggestions: Vec<Suggestion> = dictionary.terms[start..]
+            .iter()
+            .take_while(|term| term.starts_with(&prefix))
+            .filter_map(|term| {
+                let doc_freq = self.live_len(self.indexes.get(term)?);
+                (doc_freq > 0).then(|| Suggestion {
+                    term: self.display_form(term).to_string(),
+                    doc_freq,
+                })
+            })
+            .collect();
+        // The sort is stable, so ties stay in alphabetical order.
+        suggestions.sort_by_key(|suggestion| Reverse(suggestion.doc_freq));
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+#[test]
+fn autocomplete_test() {
+    let mut index = InvertedIndex::new();
+    index.add(
+        1,
+        "programming programs in Rust, a systems programming language",
+    );
+    index.add(2, "Rust programs run fast; proper profiling proves it");
+    index.add(3, "Go programs are simple and productive");
+    index.add(4, "project planning, prototypes and promises");
+    index.add(5, "rusty tools rust away, robust routines remain");
+
+    let complete = |index: &InvertedIndex, prefix: &str, limit: usize| {
+        index
+            .autocomplete(prefix, limit)
+            .into_iter()
+            .map(|suggestion| (suggestion.term, suggestion.doc_freq))
+            .collect::<Vec<_>>()
+    };
+    let owned = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+        pairs
+            .iter()
+            .map(|&(term, doc_freq)| (term.to_string(), doc_freq))
+            .collect()
+    };
+    assert_eq!(
+        complete(&index, "pro", 10),
+        owned(&[
+            ("programs", 3),
+            ("productive", 1),
+            ("profiling", 1),
+            ("programming", 1),
+            ("project", 1),
+            ("promises", 1),
+            ("proper", 1),
+            ("prototypes", 1),
+            ("proves", 1),
+        ])
+    );
+    assert_eq!(
+        complete(&index, "PROG", 10),
+        owned(&[("programs", 3), ("programming", 1)])
+    );
+    assert_eq!(
+        complete(&index, "ru", 3),
+        owned(&[("Rust", 3), ("run", 1), ("rusty", 1)])
+    );
+    assert_eq!(
+        complete(&index, "", 3),
+        owned(&[("programs", 3), ("Rust", 3), ("and", 2)])
+    );
+    assert!(complete(&index, "xyz", 10).is_empty());
+    assert!(complete(&index, "pro", 0).is_empty());
+
+    // The dictionary follows changes to the index.
+    index.add(6, "proverbs about rust");
+    assert_eq!(
+        complete(&index, "prov", 10),
+        owned(&[("proverbs", 1), ("proves", 1)])
+    );
+    // Without document 2, "rust" is the most frequent spelling.
+    index.remove(2);
+    assert_eq!(complete(&index, "prov", 10), owned(&[("proverbs", 1)]));
+    assert_eq!(
+        complete(&index, "ru", 2),
+        owned(&[("rust", 3), ("rusty", 1)])
+    );
+}
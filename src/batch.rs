@@ -0,0 +1,127 @@
+use crate::analysis::TermBuffer;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+
+impl InvertedIndex {
+    /// Creates an empty index with room for `docs` documents and `terms_hint` distinct terms
+    /// before its internal tables need to grow.
+    ///
+    /// Pre-sizing only saves the rehashing of the two tables, a few dozen resizes for 100k
+    /// documents; tokenizing and the posting lists dominate the cost of a load. The
+    /// `add_batch` bench puts a 100k-document load at roughly 0-15% faster this way, within
+    /// run-to-run noise on a busy machine.
+    pub fn with_capacity(docs: usize, terms_hint: usize) -> InvertedIndex {
+        let mut index = InvertedIndex::new();
+        index.documents.reserve(docs);
+        index.indexes.reserve(terms_hint);
+        index
+    }
+
+    /// Reserves room for at least `additional_docs` more documents.
+    pub fn reserve(&mut self, additional_docs: usize) {
+        self.documents.reserve(additional_docs);
+    }
+
+    /// Reserves room for at least `n` more documents.
+    #[deprecated(note = "renamed to `reserve`")]
+    pub fn reserve_documents(&mut self, n: usize) {
+        self.reserve(n);
+    }
+
+    /// Adds every `(doc_id, content)` pair, with the same result as calling
+    /// [`add`](Self::add) for each of them in order.
+    ///
+    /// The owned contents are stored without copying, one term buffer is reused across all
+    /// documents, and the document table is grown once up front from the iterator's size hint.
+    pub fn add_batch(&mut self, docs: impl IntoIterator<Item = (usize, String)>) {
+        let docs = docs.into_iter();
+        self.reserve(docs.size_hint().0);
+        let mut words = TermBuffer::default();
+        for (id, content) in docs {
+            self.record(LogEntry::Add {
+                id,
+                content: &content,
+            });
+            self.index_document(id, content, &mut words);
+        }
+    }
+}
+
+/// Adds each `(doc_id, content)` pair like [`InvertedIndex::add_batch`].
+impl Extend<(usize, String)> for InvertedIndex {
+    fn extend<I: IntoIterator<Item = (usize, String)>>(&mut self, docs: I) {
+        self.add_batch(docs);
+    }
+}
+
+/// Builds an index with the default configuration from `(doc_id, content)` pairs.
+impl FromIterator<(usize, String)> for InvertedIndex {
+    fn from_iter<I: IntoIterator<Item = (usize, String)>>(docs: I) -> Self {
+        let mut index = InvertedIndex::new();
+        index.extend(docs);
+        index
+    }
+}
+
+#[test]
+fn from_iter_and_extend_test() {
+    let docs = [
+        (2, "Rust is fast"),
+        (1, "Go is simple"),
+        (2, "Rust replaced"),
+        (3, "Rust and Go"),
+    ];
+    let mut single = InvertedIndex::new();
+    for (id, content) in docs {
+        single.add(id, content);
+    }
+
+    let collected: InvertedIndex = docs
+        .iter()
+        .map(|&(id, content)| (id, content.to_string()))
+        .collect();
+    assert_eq!(collected.indexes, single.indexes);
+    assert_eq!(collected.stats(), single.stats());
+
+    let mut extended: InvertedIndex = docs[..2]
+        .iter()
+        .map(|&(id, content)| (id, content.to_string()))
+        .collect();
+    extended.extend(
+        docs[2..]
+            .iter()
+            .map(|&(id, content)| (id, content.to_string())),
+    );
+    assert_eq!(extended.indexes, single.indexes);
+    assert_eq!(extended.stats(), single.stats());
+    assert_eq!(extended.get(2).as_deref(), Some("Rust replaced"));
+}
+
+#[test]
+fn add_batch_matches_add_test() {
+    let docs = vec![
+        (2, "Rust is fast".to_string()),
+        (1, "Go is simple".to_string()),
+        (2, "Rust replaced".to_string()),
+        (3, "Rust and Go".to_string()),
+    ];
+    let mut single = InvertedIndex::new();
+    for (id, content) in &docs {
+        single.add(*id, content);
+    }
+    let mut batch = InvertedIndex::with_capacity(3, 16);
+    batch.add_batch(docs);
+
+    assert_eq!(batch.indexes, single.indexes);
+    assert_eq!(batch.stats(), single.stats());
+    assert_eq!(batch.get(2).as_deref(), Some("Rust replaced"));
+}
+
+#[test]
+fn with_capacity_test() {
+    let mut index = InvertedIndex::with_capacity(100, 1000);
+    assert!(index.documents.capacity() >= 100);
+    assert!(index.indexes.capacity() >= 1000);
+    index.reserve(500);
+    assert!(index.documents.capacity() >= 500);
+}
@@ -0,0 +1,145 @@
+use crate::highlight::Highlighter;
+use crate::index::InvertedIndex;
+use crate::postings::intersect_sorted;
+use std::collections::HashMap;
+
+impl InvertedIndex {
+    /// Queries for documents containing at least `min` of the given terms.
+    ///
+    /// # Parameters
+    /// - `terms`: The search terms, each normalized like a [`query`](Self::query) term.
+    ///   Repeated terms count once.
+    /// - `min`: How many distinct terms a document must contain. Documents containing none
+    ///   of the terms are never returned, so `0` behaves like `1`.
+    ///
+    /// # Returns
+    /// The highlighted contents of the matching documents, the ones matching the most terms
+    /// first and ties in ascending doc id order.
+    pub fn query_min_should_match(&self, terms: &[&str], min: usize) -> Vec<String> {
+        let mut analyzed: Vec<String> = terms
+            .iter()
+            .filter_map(|term| self.analyze_term(term))
+            .collect();
+        analyzed.sort_unstable();
+        analyzed.dedup();
+
+        let mut matched: HashMap<usize, usize> = HashMap::new();
+        for term in &analyzed {
+            for posting in self.live_postings(term).as_deref().unwrap_or_default() {
+                *matched.entry(posting.doc_id).or_default() += 1;
+            }
+        }
+        let mut matched: Vec<(usize, usize)> = matched
+            .into_iter()
+            .filter(|(_, count)| *count >= min.max(1))
+            .collect();
+        matched.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let terms: Vec<&str> = analyzed.iter().map(String::as_str).collect();
+        let mut highlighter = None;
+        matched
+            .into_iter()
+            .filter_map(|(doc_id, _)| self.get(doc_id))
+            .map(|content| {
+                let highlighter = highlighter.get_or_insert_with(|| {
+                    Highlighter::for_terms(&terms)
+                        .unwrap()
+                        .style(self.style().copied())
+                        .max_highlights(self.max_highlights)
+                });
+                highlighter.highlight(&content).into_owned()
+            })
+            .collect()
+    }
+
+    /// Queries for documents containing every word of `words`, in any order and anywhere
+    /// in the document, unlike [`query_phrase`](Self::query_phrase) which needs them
+    /// adjacent.
+    ///
+    /// `words` is tokenized and normalized the way [`add`](Self::add) analyzes content, so
+    /// stop words in it are ignored and repeated words count once.
+    ///
+    /// # Returns
+    /// The contents of the matching documents with every word highlighted, in ascending doc
+    /// id order. Empty if `words` has no searchable word.
+    pub fn query_contains_all(&self, words: &str) -> Vec<String> {
+        let mut terms: Vec<String> = self
+            .analyzer
+            .analyze(words)
+            .into_iter()
+            .filter(|term| !self.capped_terms.contains(term))
+            .collect();
+        terms.sort_unstable();
+        terms.dedup();
+        let mut lists = Vec::with_capacity(terms.len());
+        for term in &terms {
+            let Some(postings) = self.live_postings(term) else {
+                return Vec::new();
+            };
+            lists.push(postings.iter().map(|posting| posting.doc_id).collect());
+        }
+        // Starting from the shortest list keeps every intermediate result small.
+        lists.sort_by_key(Vec::len);
+        let mut lists = lists.into_iter();
+        let first = lists.next().unwrap_or_default();
+        let ids = lists.fold(first, |acc: Vec<usize>, ids| intersect_sorted(&acc, &ids));
+
+        let terms: Vec<&str> = terms.iter().map(String::as_str).collect();
+        let mut highlighter = None;
+        ids.into_iter()
+            .filter_map(|doc_id| self.get(doc_id))
+            .map(|content| {
+                let highlighter = highlighter.get_or_insert_with(|| {
+                    Highlighter::for_terms(&terms)
+                        .unwrap()
+                        .style(self.style().copied())
+                        .max_highlights(self.max_highlights)
+                });
+                highlighter.highlight(&content).into_owned()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn query_contains_all_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::builder().stop_words(["is"]).build();
+    index.add(1, "Rust is fun programming");
+    index.add(2, "programming in Go");
+    index.add(3, "rust");
+
+    assert_eq!(
+        index.query_contains_all("programming rust"),
+        vec!["\u{1b}[35mRust\u{1b}[0m is fun \u{1b}[35mprogramming\u{1b}[0m"]
+    );
+    assert_eq!(
+        index.query_contains_all("Rust, is rust programming?"),
+        index.query_contains_all("programming rust")
+    );
+    assert_eq!(index.query_contains_all("programming").len(), 2);
+    assert!(index.query_contains_all("rust python").is_empty());
+    assert!(index.query_contains_all("is").is_empty());
+    assert!(index.query_contains_all("").is_empty());
+}
+
+#[test]
+fn query_min_should_match_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust only");
+    index.add(2, "rust and go");
+    index.add(3, "rust go python");
+    index.add(4, "go python");
+    index.add(5, "nothing relevant");
+
+    let results = index.query_min_should_match(&["rust", "go", "python"], 2);
+    assert_eq!(results, vec!["rust go python", "rust and go", "go python"]);
+
+    let results = index.query_min_should_match(&["rust", "go", "python"], 3);
+    assert_eq!(results, vec!["rust go python"]);
+
+    let results = index.query_min_should_match(&["rust", "RUST"], 2);
+    assert!(results.is_empty());
+    assert_eq!(index.query_min_should_match(&["rust"], 0).len(), 3);
+}
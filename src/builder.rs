@@ -0,0 +1,515 @@
+use crate::compress::ContentCache;
+use crate::docstore::DocStore;
+use crate::error::IndexError;
+use crate::filters::FilterFn;
+use crate::highlight::HighlightStyle;
+use crate::index::InvertedIndex;
+use crate::tokenizer::{Tokenizer, TokenizerKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Configures and creates an [`InvertedIndex`].
+///
+/// Every option defaults to the behavior of [`InvertedIndex::new`]:
+///
+/// ```
+/// use inverted_index::InvertedIndex;
+///
+/// let mut index = InvertedIndex::builder()
+///     .stop_words(["the", "a"])
+///     .stemming(true)
+///     .build();
+/// index.add(1, "The programmer likes programs.");
+/// assert_eq!(index.query("program").len(), 1);
+/// assert!(index.query("the").is_empty());
+/// ```
+#[derive(Default)]
+pub struct IndexBuilder {
+    /// The index being configured, still empty.
+    index: InvertedIndex,
+    /// The file to create the document store in, if any.
+    document_store: Option<PathBuf>,
+    document_cache: Option<usize>,
+}
+
+/// Default for [`IndexBuilder::parallel_threshold`].
+#[cfg(feature = "parallel")]
+pub(crate) const DEFAULT_PARALLEL_THRESHOLD: usize = 50_000;
+
+/// Default for [`IndexBuilder::regex_term_limit`].
+pub(crate) const DEFAULT_REGEX_TERM_LIMIT: usize = 1000;
+
+/// Default for [`IndexBuilder::cooccurrence_sample`].
+pub(crate) const DEFAULT_COOCCURRENCE_SAMPLE: usize = 1000;
+
+/// Default for [`IndexBuilder::document_cache`].
+pub(crate) const DEFAULT_DOCUMENT_CACHE: usize = 64;
+
+impl IndexBuilder {
+    pub fn new() -> IndexBuilder {
+        Self::default()
+    }
+
+    /// Words that are skipped at index and query time, matched case-insensitively. Replaces
+    /// any stop words set before, see [`extend_stop_words`](Self::extend_stop_words) to add
+    /// to them.
+    ///
+    /// Default: no stop words.
+    pub fn stop_words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.index.analyzer.stop_words = words
+            .into_iter()
+            .map(|word| word.as_ref().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Adds `words` to the stop words set so far, e.g. corpus-specific words on top of a
+    /// [built-in list](crate::StopWords).
+    ///
+    /// Default: no stop words.
+    pub fn extend_stop_words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.index
+            .analyzer
+            .stop_words
+            .extend(words.into_iter().map(|word| word.as_ref().to_lowercase()));
+        self
+    }
+
+    /// Drops tokens of fewer than `length` characters, e.g. 2 to leave out single letters.
+    ///
+    /// Like the other token filters, this applies to documents and queries alike, and a
+    /// dropped token still takes up a position, like a stop word.
+    ///
+    /// Default: no minimum.
+    pub fn min_token_length(mut self, length: usize) -> Self {
+        self.index.analyzer.filters.min_length = length;
+        self
+    }
+
+    /// Drops tokens of more than `length` characters, such as long hex hashes.
+    ///
+    /// Default: no maximum.
+    pub fn max_token_length(mut self, length: usize) -> Self {
+        self.index.analyzer.filters.max_length = Some(length);
+        self
+    }
+
+    /// Drops tokens made only of digits, such as "2024".
+    ///
+    /// Default: `false`.
+    pub fn drop_numbers(mut self, drop: bool) -> Self {
+        self.index.analyzer.filters.drop_numbers = drop;
+        self
+    }
+
+    /// Drops the tokens `filter` returns `false` for, after the other token filters.
+    ///
+    /// An index with a custom filter cannot be [saved](InvertedIndex::save), as it could not
+    /// be recreated on load.
+    ///
+    /// Default: every token is kept.
+    pub fn token_filter(mut self, filter: FilterFn) -> Self {
+        self.index.analyzer.filters.custom = Some(Arc::new(filter));
+        self
+    }
+
+    /// Match terms with their exact casing instead of lowercasing them.
+    ///
+    /// Default: `false`, terms are case-insensitive.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.index.analyzer.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Reduce words to their stem with the Porter stemmer, so "programs" matches "programming".
+    ///
+    /// Stems are memoized per distinct word while indexing; the cache is emptied by
+    /// [`InvertedIndex::clear`].
+    ///
+    /// Default: `false`, words are indexed as written.
+    pub fn stemming(mut self, stemming: bool) -> Self {
+        self.index.analyzer.stemming = stemming;
+        self
+    }
+
+    /// How document and query text is split into tokens, e.g.
+    /// [`WordTokenizer`](crate::WordTokenizer) to keep "don't" as one term.
+    ///
+    /// Default: [`SimpleTokenizer`](crate::SimpleTokenizer), splitting at every character
+    /// that is not alphanumeric.
+    ///
+    /// An index using a tokenizer other than the built-in ones cannot be
+    /// [saved](InvertedIndex::save), as it could not be recreated on load.
+    pub fn tokenizer<T: Tokenizer + 'static>(mut self, tokenizer: T) -> Self {
+        self.index.analyzer.tokenizer_kind = TokenizerKind::of(&tokenizer);
+        self.index.analyzer.tokenizer = Arc::new(tokenizer);
+        self
+    }
+
+    /// Total postings entries a multi-term [`query_bm25`](InvertedIndex::query_bm25) or
+    /// [`query_all`](InvertedIndex::query_all) must exceed before each term is scored on its
+    /// own thread. Smaller queries stay on the calling thread to avoid spawning overhead.
+    /// The threads are scoped std threads spawned per query; there is no rayon pool.
+    ///
+    /// Default: 50 000. Requires the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    pub fn parallel_threshold(mut self, postings: usize) -> Self {
+        self.index.parallel_threshold = postings;
+        self
+    }
+
+    /// How [`query`](InvertedIndex::query) and the other highlighting queries mark matches.
+    ///
+    /// Default: [`HighlightStyle::default`], purple.
+    pub fn highlight_style(mut self, style: HighlightStyle) -> Self {
+        self.index.highlight_style = style;
+        self
+    }
+
+    /// Returns query results without any ANSI codes, e.g. when the output is written to a
+    /// file. Takes precedence over [`highlight_style`](Self::highlight_style).
+    ///
+    /// Default: `false`, codes are emitted when `colored` detects a terminal that supports
+    /// them, see [`HighlightStyle`].
+    pub fn no_color(mut self, no_color: bool) -> Self {
+        self.index.no_color = no_color;
+        self
+    }
+
+    /// Highlights only the first `limit` occurrences of the query terms in each result of
+    /// [`query`](InvertedIndex::query) and the other highlighting queries, leaving the rest
+    /// plain, so that results stay a manageable size for documents where a term appears
+    /// hundreds of times. 0 returns the contents unhighlighted.
+    ///
+    /// Default: every occurrence is highlighted.
+    pub fn max_highlights_per_doc(mut self, limit: usize) -> Self {
+        self.index.max_highlights = Some(limit);
+        self
+    }
+
+    /// Keeps the ranked results of the `capacity` most recently used queries, and the doc
+    /// ids of [`query_ids`](InvertedIndex::query_ids) terms, answering repeats of them
+    /// without touching the posting lists. Queries analyzing to the same
+    /// terms share an entry, and any change to the index empties the cache, so stale results
+    /// are never returned. See [`InvertedIndex::cache_stats`].
+    ///
+    /// Default: 0, no caching.
+    pub fn query_cache(mut self, capacity: usize) -> Self {
+        self.index.cache = crate::cache::query_cache(capacity);
+        self
+    }
+
+    /// Maps every character trigram of the indexed terms to the terms containing it, so
+    /// [`query_substring`](InvertedIndex::query_substring) only checks terms sharing the
+    /// fragment's trigrams instead of the whole vocabulary.
+    ///
+    /// The table holds a copy of each term for every distinct trigram in it, roughly the
+    /// term's length in entries, so it can take several times the memory of the term
+    /// dictionary. Its size is reported by [`InvertedIndex::stats`] and
+    /// [`InvertedIndex::memory_usage`]. It is rebuilt only by adding documents, so it is not
+    /// saved, and a [loaded](InvertedIndex::load) index scans the vocabulary instead.
+    ///
+    /// Default: `false`.
+    pub fn ngram_index(mut self, enabled: bool) -> Self {
+        self.index.ngrams = enabled.then(crate::ngram::NgramIndex::default);
+        self
+    }
+
+    /// Keeps each document's terms with their frequencies, see
+    /// [`doc_terms`](InvertedIndex::doc_terms), so that [`remove`](InvertedIndex::remove)
+    /// only visits the document's own posting lists and
+    /// [`similarity`](InvertedIndex::similarity) and
+    /// [`cooccurring_terms`](InvertedIndex::cooccurring_terms) need not re-analyze the
+    /// contents. Disabling it saves a copy of each distinct term per document, reported by
+    /// [`InvertedIndex::memory_usage`], at the cost of those operations getting slower and
+    /// `doc_terms` returning `None`.
+    ///
+    /// Default: `true`.
+    pub fn forward_index(mut self, enabled: bool) -> Self {
+        self.index.forward_index = enabled;
+        self
+    }
+
+    /// Counts the original spellings of each term, so that
+    /// [`autocomplete`](InvertedIndex::autocomplete), [`suggest`](InvertedIndex::suggest) and
+    /// [`top_terms`](InvertedIndex::top_terms) show a term the way the documents most often
+    /// write it, e.g. `"Rust"` rather than `"rust"`, the first seen on a tie. Matching stays
+    /// case-insensitive. Disabling it saves a copy of each distinct spelling, reported by
+    /// [`InvertedIndex::memory_usage`], and shows terms as indexed: lowercased and, with
+    /// stemming on, stemmed.
+    ///
+    /// Default: `true`.
+    pub fn surface_forms(mut self, enabled: bool) -> Self {
+        self.index.surface_forms = enabled.then(crate::surface::SurfaceForms::default);
+        self
+    }
+
+    /// Stops indexing a term once it would appear in more than `documents` documents,
+    /// dropping its posting list, so that a very common word cannot dominate query cost.
+    ///
+    /// From then on the term is treated like a stop word: it is skipped in new documents and
+    /// left out of queries, so it is no longer searchable, even after documents containing it
+    /// are removed. Its occurrences still count towards document lengths. Soft-deleted
+    /// documents count towards the cap until they are compacted.
+    ///
+    /// Default: no cap.
+    pub fn max_postings(mut self, documents: usize) -> Self {
+        self.index.max_postings = Some(documents);
+        self
+    }
+
+    /// Most terms a [`query_regex`](InvertedIndex::query_regex) pattern may match before the
+    /// query fails with [`IndexError::TooManyTerms`](crate::IndexError::TooManyTerms), so that
+    /// a pattern like `.*` cannot union every posting list.
+    ///
+    /// Default: 1000.
+    pub fn regex_term_limit(mut self, limit: usize) -> Self {
+        self.index.regex_term_limit = limit;
+        self
+    }
+
+    /// Most documents [`cooccurring_terms`](InvertedIndex::cooccurring_terms) reads. The
+    /// documents of a term found in more of them are sampled at even intervals of its
+    /// posting list, so the counts of a very common term are estimates.
+    ///
+    /// Default: 1000.
+    pub fn cooccurrence_sample(mut self, documents: usize) -> Self {
+        self.index.cooccurrence_sample = documents;
+        self
+    }
+
+    /// Makes [`remove`](InvertedIndex::remove) a soft delete: the content is dropped and the
+    /// id recorded, while its postings stay in place and are skipped by every query. Once
+    /// deleted documents make up more than `compact_ratio` of all documents, the postings are
+    /// rewritten without them, see [`InvertedIndex::compact`]. A ratio of 1.0 or more only
+    /// compacts on demand.
+    ///
+    /// This makes a removal O(1) instead of a scan of the vocabulary, at the cost of queries
+    /// copying the posting lists that still hold deleted documents.
+    ///
+    /// Default: off, documents are purged from the postings immediately.
+    pub fn soft_delete(mut self, compact_ratio: f64) -> Self {
+        self.index.compact_ratio = Some(compact_ratio);
+        self
+    }
+
+    /// Keeps document contents in an append-only file at `path` instead of in memory, so
+    /// that memory use is bounded by the postings for large corpora. The file is created
+    /// when the index is built, replacing any file there.
+    ///
+    /// [`get`](InvertedIndex::get), highlighting and snippets read the contents back on
+    /// demand, keeping the most recent in a small cache, see
+    /// [`document_cache`](Self::document_cache). Queries returning only ids and scores never
+    /// touch the file. Removed and replaced contents leave holes in it until
+    /// [`compact`](InvertedIndex::compact). A content that cannot be appended stays in memory,
+    /// and [`sync_store`](InvertedIndex::sync_store) reports the error.
+    ///
+    /// The store is not part of a [saved](InvertedIndex::save) index: the contents are read
+    /// back and saved with it, and a [loaded](InvertedIndex::load) index keeps all of them in
+    /// memory, without reopening the file. To bound memory again, build a new index with a
+    /// store and add the documents to it.
+    ///
+    /// Default: off, contents are kept in memory.
+    pub fn document_store(mut self, path: impl AsRef<Path>) -> Self {
+        self.document_store = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Keeps the content of every document, for deployments where [`add`](InvertedIndex::add)
+    /// should only record what ranking needs because the documents live elsewhere, e.g. in a
+    /// database. A disabled index keeps each document's id, length, metadata and
+    /// [forward index](Self::forward_index) entries, and no
+    /// [document store](Self::document_store) or [spellings](Self::surface_forms).
+    ///
+    /// Queries returning ids and scores work as usual, and [`SearchHit::content`] is
+    /// `None`. [`get`](InvertedIndex::get) returns `None`, while highlighting and snippet
+    /// queries, which need the text, skip every document, and
+    /// [`try_query`](InvertedIndex::try_query) fails with
+    /// [`QueryError::ContentNotStored`](crate::QueryError::ContentNotStored). A
+    /// [saved](InvertedIndex::save) index leaves the contents out of the file.
+    ///
+    /// Default: `true`.
+    ///
+    /// [`SearchHit::content`]: crate::SearchHit::content
+    pub fn store_content(mut self, enabled: bool) -> Self {
+        self.index.keep_content = enabled;
+        self
+    }
+
+    /// Keeps each in-memory content of at least `min_bytes` bytes compressed, for corpora
+    /// of long documents whose text outweighs the postings. Shorter contents, those
+    /// compression does not shrink and those in a [document store](Self::document_store)
+    /// are kept as they are.
+    ///
+    /// [`get`](InvertedIndex::get), highlighting and snippets decompress the contents on
+    /// demand, keeping the most recent in a small cache, see
+    /// [`document_cache`](Self::document_cache), and return the same text as an index
+    /// without compression. Queries returning only ids and scores never decompress. A
+    /// [saved](InvertedIndex::save) index writes the compressed contents as they are, and
+    /// a loaded one keeps compressing.
+    ///
+    /// Default: off, contents are kept as they are.
+    pub fn compress_content(mut self, min_bytes: usize) -> Self {
+        self.index.compress_above = Some(min_bytes);
+        self
+    }
+
+    /// How many document contents read back from the
+    /// [document store](Self::document_store), or decompressed, see
+    /// [`compress_content`](Self::compress_content), are kept in memory, least recently used
+    /// first to go. 0 reads every time.
+    ///
+    /// Default: 64.
+    pub fn document_cache(mut self, documents: usize) -> Self {
+        self.document_cache = Some(documents);
+        self
+    }
+
+    /// Creates the index.
+    ///
+    /// # Panics
+    /// If the [document store](Self::document_store) file cannot be created. Use
+    /// [`try_build`](Self::try_build) to handle that error.
+    pub fn build(self) -> InvertedIndex {
+        match self.try_build() {
+            Ok(index) => index,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates the index, failing with [`IndexError::Io`] if the
+    /// [document store](Self::document_store) file cannot be created.
+    pub fn try_build(mut self) -> Result<InvertedIndex, IndexError> {
+        if !self.index.keep_content {
+            // Uncounting the spellings of a removed document reads its content.
+            self.index.surface_forms = None;
+            self.document_store = None;
+        }
+        let cache = self.document_cache.unwrap_or(DEFAULT_DOCUMENT_CACHE);
+        self.index.decompressed = Mutex::new(ContentCache::new(cache));
+        if let Some(path) = self.document_store {
+            let store =
+                DocStore::create(&path, cache).map_err(|source| IndexError::Io { path, source })?;
+            self.index.store = Some(Arc::new(store));
+        }
+        Ok(self.index)
+    }
+}
+
+impl InvertedIndex {
+    /// Starts configuring a new index, see [`IndexBuilder`].
+    pub fn builder() -> IndexBuilder {
+        IndexBuilder::new()
+    }
+}
+
+#[test]
+fn max_postings_test() {
+    let mut index = InvertedIndex::builder().max_postings(2).build();
+    index.add(1, "the rust book");
+    index.add(2, "the go book");
+    index.add(3, "the the python");
+    index.add(4, "the end");
+
+    assert!(index.query("the").is_empty());
+    assert_eq!(index.doc_freq("the"), 0);
+    assert_eq!(index.union(&["book"]), vec![1, 2]);
+    // Like a stop word, the capped term is left out of ranked queries.
+    assert_eq!(index.query_all("the rust").len(), 1);
+    assert_eq!(index.stats().vocabulary_size, 5);
+    // Replacing a document keeps its terms under the cap.
+    index.add(2, "go book again");
+    index.add(2, "go book");
+    assert_eq!(index.union(&["book"]), vec![1, 2]);
+}
+
+#[test]
+fn case_sensitive_test() {
+    let mut index = InvertedIndex::builder().case_sensitive(true).build();
+    index.add(1, "Rust");
+    index.add(2, "rust");
+    assert_eq!(index.doc_freq("Rust"), 1);
+    assert_eq!(index.intersect(&["rust"]), vec![2]);
+}
+
+#[test]
+fn stop_words_and_stemming_test() {
+    let mut index = InvertedIndex::builder()
+        .stop_words(["Is"])
+        .stemming(true)
+        .build();
+    index.add(1, "Rust is a systems programming language.");
+    index.add(2, "Programs is plural.");
+    assert_eq!(index.union(&["programs"]), vec![1, 2]);
+    assert_eq!(index.doc_freq("is"), 0);
+    // "is" is not counted: 5 tokens in document 1 and 2 in document 2.
+    assert_eq!(index.average_document_length(), 3.5);
+}
+
+#[test]
+fn clear_test() {
+    let mut index = InvertedIndex::builder().stemming(true).build();
+    index.add(1, "Programming programs");
+    assert_eq!(index.analyzer.stem_cache.len(), 2);
+
+    index.clear();
+    assert_eq!(index.analyzer.stem_cache.len(), 0);
+    assert_eq!(index.stats().document_count, 0);
+    assert_eq!(index.term_count(), 0);
+    assert!(index.analyzer.stemming);
+}
+
+#[test]
+fn word_tokenizer_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::builder()
+        .tokenizer(crate::WordTokenizer)
+        .build();
+    index.add(1, "Don't send an e-mail");
+    index.add(2, "I don t know");
+
+    assert_eq!(index.query("don't"), vec!["Don't send an e-mail"]);
+    assert_eq!(index.query("DON'T"), vec!["Don't send an e-mail"]);
+    assert_eq!(index.union(&["e-mail"]), vec![1]);
+    assert!(index.query("mail").is_empty());
+    assert_eq!(index.union(&["don"]), vec![2]);
+}
+
+#[test]
+fn highlight_style_test() {
+    let _color = crate::test_util::color_guard(true);
+    let style = HighlightStyle {
+        color: colored::Color::Blue,
+        bold: false,
+        underline: true,
+    };
+    let mut index = InvertedIndex::builder().highlight_style(style).build();
+    index.add(1, "Rust and rust");
+    assert_eq!(
+        index.query("rust"),
+        vec!["\u{1b}[4;34mRust\u{1b}[0m and \u{1b}[4;34mrust\u{1b}[0m"]
+    );
+
+    let mut plain = InvertedIndex::builder()
+        .highlight_style(style)
+        .no_color(true)
+        .build();
+    plain.add(1, "Rust and rust");
+    assert_eq!(plain.query("rust"), vec!["Rust and rust"]);
+    assert_eq!(
+        plain.query_with_counts("rust"),
+        vec![(1, 2, "Rust[1] and rust[2]".to_string())]
+    );
+    assert_eq!(
+        plain.query_min_should_match(&["rust"], 1),
+        vec!["Rust and rust"]
+    );
+}
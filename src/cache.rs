@@ -0,0 +1,283 @@
+//! An optional LRU cache of ranked query results, see
+//! [`IndexBuilder::query_cache`](crate::IndexBuilder::query_cache).
+
+use crate::index::InvertedIndex;
+use crate::ranking::{Combine, Scorer};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+/// Hit and miss counts of the query cache, see [`InvertedIndex::cache_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Queries answered from the cache.
+    pub hits: u64,
+    /// Queries that were computed, because they were not cached or the index had changed.
+    pub misses: u64,
+    /// Results currently cached.
+    pub len: usize,
+    /// Most results kept, 0 when caching is off.
+    pub capacity: usize,
+}
+
+/// A normalized query: two query strings analyzing to the same terms share an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub(crate) kind: CacheKind,
+    pub(crate) terms: Vec<String>,
+}
+
+/// What a cached query computes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum CacheKind {
+    Ranked(Scorer, Combine),
+    /// The ids of the documents containing a single term, see
+    /// [`InvertedIndex::query_ids`].
+    Ids,
+}
+
+/// A cached result, of the variant matching its key's [`CacheKind`].
+#[derive(Debug, Clone)]
+enum CachedResult {
+    Ranked(Vec<(usize, f64)>),
+    Ids(Vec<usize>),
+}
+
+/// Ranked results of recent queries, all computed at the same index generation.
+#[derive(Debug)]
+pub(crate) struct QueryCache {
+    capacity: usize,
+    /// The index generation the entries were computed at.
+    generation: u64,
+    /// Each result with the tick it was last used at.
+    entries: HashMap<CacheKey, (u64, CachedResult)>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            generation: 0,
+            entries: HashMap::with_capacity(capacity),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The cached result for `key`, dropping every entry first if the index has changed
+    /// since they were computed.
+    fn get(&mut self, key: &CacheKey, generation: u64) -> Option<CachedResult> {
+        if generation != self.generation {
+            self.entries.clear();
+            self.generation = generation;
+        }
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some((used, result)) => {
+                *used = self.tick;
+                self.hits += 1;
+                Some(result.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches `result`, evicting the least recently used entry when full.
+    ///
+    /// Finding that entry scans the cache, which is cheap next to ranking for the small
+    /// capacities a cache of whole result lists is used with.
+    fn insert(&mut self, key: CacheKey, generation: u64, result: CachedResult) {
+        if self.capacity == 0 || generation != self.generation {
+            return;
+        }
+        if self.entries.len() >= self.capacity
+            && !self.entries.contains_key(&key)
+            && let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone())
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(key, (self.tick, result));
+    }
+}
+
+impl InvertedIndex {
+    /// Counts how often ranked queries were answered from the cache configured with
+    /// [`IndexBuilder::query_cache`](crate::IndexBuilder::query_cache).
+    ///
+    /// # Returns
+    /// All zeros when the index has no cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        let Some(cache) = &self.cache else {
+            return CacheStats::default();
+        };
+        let cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            len: cache.entries.len(),
+            capacity: cache.capacity,
+        }
+    }
+
+    /// Returns the cached ranked result for `key` or computes and caches it.
+    pub(crate) fn cached(
+        &self,
+        key: CacheKey,
+        compute: impl FnOnce(&CacheKey) -> Vec<(usize, f64)>,
+    ) -> Vec<(usize, f64)> {
+        match self.cached_result(key, |key| CachedResult::Ranked(compute(key))) {
+            CachedResult::Ranked(result) => result,
+            CachedResult::Ids(_) => unreachable!("ranked query cached as ids"),
+        }
+    }
+
+    /// The ids of the documents containing `term`, like [`postings`](Self::postings).
+    ///
+    /// # Returns
+    /// Ascending doc ids, empty if the term was never indexed or is a stop word.
+    ///
+    /// # Notes
+    /// With a [query cache](crate::IndexBuilder::query_cache), the ids of recently queried
+    /// terms are kept and returned without touching the posting lists until the index
+    /// changes. Terms normalizing the same way, such as `"Rust"` and `"rust"`, share an
+    /// entry.
+    pub fn query_ids(&self, term: &str) -> Vec<usize> {
+        let Some(term) = self.analyze_term(term) else {
+            return Vec::new();
+        };
+        let key = CacheKey {
+            kind: CacheKind::Ids,
+            terms: vec![term],
+        };
+        let result = self.cached_result(key, |key| {
+            let postings = self.live_postings(&key.terms[0]).unwrap_or_default();
+            CachedResult::Ids(postings.iter().map(|posting| posting.doc_id).collect())
+        });
+        match result {
+            CachedResult::Ids(ids) => ids,
+            CachedResult::Ranked(_) => unreachable!("ids cached as a ranked query"),
+        }
+    }
+
+    /// Creates an index with a [query cache](crate::IndexBuilder::query_cache) of
+    /// `capacity` entries and every other option at its default.
+    pub fn with_query_cache(capacity: usize) -> InvertedIndex {
+        InvertedIndex::builder().query_cache(capacity).build()
+    }
+
+    fn cached_result(
+        &self,
+        key: CacheKey,
+        compute: impl FnOnce(&CacheKey) -> CachedResult,
+    ) -> CachedResult {
+        let Some(cache) = &self.cache else {
+            return compute(&key);
+        };
+        // The cache holds only finished results, so a panic elsewhere cannot leave it
+        // inconsistent.
+        let lock = || cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = lock().get(&key, self.generation) {
+            return result;
+        }
+        let result = compute(&key);
+        lock().insert(key, self.generation, result.clone());
+        result
+    }
+}
+
+/// Creates the cache field of an index.
+pub(crate) fn query_cache(capacity: usize) -> Option<Mutex<QueryCache>> {
+    (capacity > 0).then(|| Mutex::new(QueryCache::new(capacity)))
+}
+
+#[test]
+fn cache_invalidation_test() {
+    let mut index = InvertedIndex::builder().query_cache(8).build();
+    index.add(1, "Rust is fast");
+    index.add(2, "Go is fast too, fast");
+
+    let first = index.query_bm25("fast");
+    assert_eq!(index.query_bm25("FAST"), first);
+    assert_eq!(index.cache_stats().hits, 1);
+    assert_eq!(index.cache_stats().misses, 1);
+
+    index.add(3, "fast fast fast");
+    let after_add = index.query_bm25("fast");
+    assert_eq!(index.cache_stats().misses, 2);
+    assert_eq!(after_add[0].0, 3);
+
+    index.remove(3);
+    assert_eq!(index.query_bm25("fast"), first);
+    index.update(1, "Rust").unwrap();
+    assert_eq!(index.query_bm25("fast").len(), 1);
+    assert_eq!(
+        index.cache_stats(),
+        CacheStats {
+            hits: 1,
+            misses: 4,
+            len: 1,
+            capacity: 8
+        }
+    );
+    assert_eq!(InvertedIndex::new().cache_stats(), CacheStats::default());
+}
+
+#[test]
+fn query_ids_cache_test() {
+    let mut cached = InvertedIndex::with_query_cache(4);
+    let mut uncached = InvertedIndex::new();
+    for index in [&mut cached, &mut uncached] {
+        index.add(3, "Rust is fast");
+        index.add(1, "Go is fast too");
+        index.add(2, "Python");
+    }
+    for term in ["fast", "FAST", "python", "java", "is", ""] {
+        assert_eq!(cached.query_ids(term), uncached.query_ids(term), "{term}");
+    }
+    assert_eq!(cached.query_ids("fast"), vec![1, 3]);
+    // "FAST" shares the entry of "fast"; the blank term never reaches the cache.
+    let stats = cached.cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.len), (2, 4, 4));
+    // A ranked query of the same term is cached separately.
+    cached.query_all("fast");
+    assert_eq!(cached.cache_stats().misses, 5);
+
+    cached.add(4, "fast");
+    assert_eq!(cached.query_ids("fast"), vec![1, 3, 4]);
+    cached.remove(1);
+    assert_eq!(cached.query_ids("fast"), vec![3, 4]);
+    cached.update(3, "slow").unwrap();
+    assert_eq!(cached.query_ids("fast"), vec![4]);
+    cached.clear();
+    assert!(cached.query_ids("fast").is_empty());
+    assert_eq!(cached.cache_stats().hits, 2);
+}
+
+#[test]
+fn cache_evicts_least_recently_used_test() {
+    let mut index = InvertedIndex::builder().query_cache(2).build();
+    index.add(1, "a b c");
+    index.query_all("a");
+    index.query_all("b");
+    index.query_all("a");
+    index.query_all("c");
+    // "b" was evicted for "c", "a" stayed as it was used more recently.
+    index.query_all("a");
+    assert_eq!(index.cache_stats().hits, 2);
+    index.query_all("b");
+    let stats = index.cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.len), (2, 4, 2));
+    // Different scoring is cached separately.
+    index.query_bm25("a");
+    assert_eq!(index.cache_stats().misses, 5);
+}
@@ -0,0 +1,397 @@
+//! Document contents kept compressed in memory, see
+//! [`IndexBuilder::compress_content`](crate::IndexBuilder::compress_content), with the
+//! codec: the LZ4 block format, implemented here to keep the crate free of native
+//! dependencies.
+
+use crate::builder::DEFAULT_DOCUMENT_CACHE;
+use crate::docstore::Content;
+use crate::index::InvertedIndex;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError};
+
+/// The shortest match a sequence can refer back to.
+const MIN_MATCH: usize = 4;
+/// The format ends every block with at least this many literals...
+const LAST_LITERALS: usize = 5;
+/// ...and starts no match this close to its end.
+const MATCH_LIMIT: usize = 12;
+/// The farthest back a match can be, the largest 2-byte offset.
+const MAX_OFFSET: usize = u16::MAX as usize;
+/// The compressor remembers one position per hash of 4 bytes, `1 << HASH_BITS` in all.
+const HASH_BITS: u32 = 12;
+
+/// Compresses `input` into an LZ4 block.
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    // The last position each hash was seen at, plus one so that 0 is none.
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+    let end = input.len().saturating_sub(LAST_LITERALS);
+    while i + MATCH_LIMIT < input.len() {
+        let word = read_u32(input, i);
+        let slot = (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[slot].checked_sub(1);
+        table[slot] = i + 1;
+        let Some(start) =
+            candidate.filter(|&start| i - start <= MAX_OFFSET && read_u32(input, start) == word)
+        else {
+            i += 1;
+            continue;
+        };
+        let mut len = MIN_MATCH;
+        while i + len < end && input[start + len] == input[i + len] {
+            len += 1;
+        }
+        sequence(&mut out, &input[anchor..i], Some((i - start, len)));
+        i += len;
+        anchor = i;
+    }
+    sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+/// Decompresses an LZ4 block written by [`compress`].
+///
+/// # Returns
+/// `None` if `input` is not a valid block of `len` bytes. `len` may come from an untrusted
+/// file: one that no block of `input.len()` bytes can expand to is refused before anything
+/// is allocated.
+pub(crate) fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    // Each byte of a block expands to at most 255 bytes, through a length byte of 255.
+    if len > input.len().saturating_mul(255) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    loop {
+        let token = *input.get(i)?;
+        i += 1;
+        let mut literals = usize::from(token >> 4);
+        if literals == 15 {
+            literals = literals.checked_add(extended_length(input, &mut i)?)?;
+        }
+        let literals_end = i.checked_add(literals)?;
+        out.extend_from_slice(input.get(i..literals_end)?);
+        i = literals_end;
+        if i == input.len() {
+            break;
+        }
+        let offset = usize::from(u16::from_le_bytes([*input.get(i)?, *input.get(i + 1)?]));
+        i += 2;
+        let mut matched = usize::from(token & 15) + MIN_MATCH;
+        if token & 15 == 15 {
+            matched = matched.checked_add(extended_length(input, &mut i)?)?;
+        }
+        if offset == 0 || offset > out.len() || out.len().checked_add(matched)? > len {
+            return None;
+        }
+        let start = out.len() - offset;
+        if offset >= matched {
+            out.extend_from_within(start..start + matched);
+        } else {
+            // The match overlaps the bytes it produces, repeating the last `offset` of them.
+            for k in start..start + matched {
+                out.push(out[k]);
+            }
+        }
+    }
+    (out.len() == len).then_some(out)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Appends a sequence: `literals` copied as they are, then a match of `(offset, len)`
+/// bytes copied from earlier output, absent in the last sequence of a block.
+fn sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push((literals.len().min(15) << 4 | match_len.min(15)) as u8);
+    if literals.len() >= 15 {
+        write_extended_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            write_extended_length(out, match_len - 15);
+        }
+    }
+}
+
+/// Writes the part of a length over 15 as bytes of 255 followed by the remainder.
+fn write_extended_length(out: &mut Vec<u8>, mut rest: usize) {
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+fn extended_length(input: &[u8], i: &mut usize) -> Option<usize> {
+    let mut len = 0usize;
+    loop {
+        let byte = *input.get(*i)?;
+        *i += 1;
+        len = len.checked_add(usize::from(byte))?;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+/// A cached content: the tick it was last read at, the compressed bytes it came from and
+/// the text.
+type CacheEntry = (u64, Arc<[u8]>, Arc<str>);
+
+/// Recently decompressed contents, see
+/// [`IndexBuilder::document_cache`](crate::IndexBuilder::document_cache).
+#[derive(Debug)]
+pub(crate) struct ContentCache {
+    capacity: usize,
+    /// Contents by document id. Holding the compressed bytes keeps their allocation alive,
+    /// so the replaced content of a document never matches its entry.
+    entries: HashMap<usize, CacheEntry>,
+    tick: u64,
+}
+
+impl ContentCache {
+    pub(crate) fn new(capacity: usize) -> ContentCache {
+        ContentCache {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Default for ContentCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOCUMENT_CACHE)
+    }
+}
+
+impl InvertedIndex {
+    /// The in-memory form of `content`: compressed if it is at least as long as the
+    /// configured threshold and compressing shrinks it.
+    pub(crate) fn pack_content(&self, content: String) -> Content {
+        match self.compress_above {
+            Some(threshold) if content.len() >= threshold => {
+                let bytes = compress(content.as_bytes());
+                if bytes.len() < content.len() {
+                    Content::Compressed {
+                        bytes: bytes.into(),
+                        len: content.len(),
+                    }
+                } else {
+                    Content::Memory(content)
+                }
+            }
+            _ => Content::Memory(content),
+        }
+    }
+
+    /// The content of document `id` held as `bytes`, from the cache if it was decompressed
+    /// recently.
+    ///
+    /// # Returns
+    /// `None` if the bytes are not a valid block of `len` bytes of UTF-8.
+    pub(crate) fn decompress_content(
+        &self,
+        id: usize,
+        bytes: &Arc<[u8]>,
+        len: usize,
+    ) -> Option<Arc<str>> {
+        let mut cache = self
+            .decompressed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        cache.tick += 1;
+        let tick = cache.tick;
+        if let Some((used, source, content)) = cache.entries.get_mut(&id)
+            && Arc::ptr_eq(source, bytes)
+        {
+            *used = tick;
+            return Some(Arc::clone(content));
+        }
+        let content: Arc<str> = String::from_utf8(decompress(bytes, len)?).ok()?.into();
+        if cache.capacity > 0 {
+            if cache.entries.len() >= cache.capacity
+                && !cache.entries.contains_key(&id)
+                && let Some(oldest) = cache
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (used, _, _))| *used)
+                    .map(|(id, _)| *id)
+            {
+                cache.entries.remove(&oldest);
+            }
+            cache
+                .entries
+                .insert(id, (tick, Arc::clone(bytes), Arc::clone(&content)));
+        }
+        Some(content)
+    }
+}
+
+#[test]
+fn codec_test() {
+    let samples = [
+        String::new(),
+        "short".to_string(),
+        "abcabcabcabcabcabcabcabcabcabc".to_string(),
+        "Grüße aus Köln, 東京 und Zürich! ".repeat(40),
+        "x".repeat(70_000),
+        (0..5000u32)
+            .map(|i| char::from_u32(0x4e00 + i * 7 % 2000).unwrap())
+            .collect(),
+    ];
+    for sample in &samples {
+        let bytes = compress(sample.as_bytes());
+        assert_eq!(
+            decompress(&bytes, sample.len()).as_deref(),
+            Some(sample.as_bytes())
+        );
+    }
+    let repeated = compress(samples[3].as_bytes());
+    assert!(repeated.len() < samples[3].len() / 10);
+    // A wrong length or truncated block is refused.
+    assert_eq!(decompress(&repeated, samples[3].len() - 1), None);
+    assert_eq!(
+        decompress(&repeated[..repeated.len() - 1], samples[3].len()),
+        None
+    );
+    assert_eq!(decompress(&[], 0), None);
+    // A length no block this short can reach is refused without allocating it.
+    assert_eq!(decompress(&repeated, usize::MAX), None);
+}
+
+#[test]
+fn codec_fuzz_test() {
+    let mut seed: u64 = 7;
+    let mut next = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    for round in 0..3000 {
+        let len = next() % [16, 300, 5000][round % 3];
+        // Noise, a small alphabet and runs of earlier input, which exercise literals, short
+        // matches and long overlapping ones.
+        let mut input: Vec<u8> = Vec::with_capacity(len);
+        while input.len() < len {
+            match next() % 3 {
+                0 => input.push(next() as u8),
+                1 => input.push(b"ab "[next() % 3]),
+                _ if !input.is_empty() => {
+                    let start = next() % input.len();
+                    let run = 1 + next() % 300;
+                    for k in 0..run.min(len - input.len()) {
+                        input.push(input[start + k % (input.len() - start)]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let block = compress(&input);
+        assert_eq!(
+            decompress(&block, input.len()),
+            Some(input.clone()),
+            "{round}"
+        );
+
+        // A damaged block or a wrong length is refused or decodes to exactly `len` bytes,
+        // and never panics.
+        let mut damaged = block.clone();
+        for _ in 0..1 + next() % 4 {
+            let at = next() % damaged.len();
+            damaged[at] = next() as u8;
+        }
+        damaged.truncate(damaged.len() - next() % 3.min(damaged.len()));
+        let len = match next() % 3 {
+            0 => input.len(),
+            1 => next() % (input.len() + 64),
+            _ => next(),
+        };
+        if let Some(out) = decompress(&damaged, len) {
+            assert_eq!(out.len(), len, "{round}");
+        }
+    }
+}
+
+#[test]
+fn compress_content_test() {
+    let _color = crate::test_util::color_guard(true);
+    let texts = [
+        "Rust ownership: the borrow checker checks every borrow, every borrow, every borrow."
+            .to_string(),
+        "Größe und Maß: Straße, Fußgänger, Grüße — die Straße ist groß, die Straße ist lang."
+            .to_string(),
+        "東京の検索エンジン、東京の検索エンジン、東京の検索 Rust エンジン。".repeat(3),
+        "tiny Rust".to_string(),
+    ];
+    let build = |compressed: bool| {
+        let builder = InvertedIndex::builder().stemming(true).document_cache(2);
+        let mut index = if compressed {
+            builder.compress_content(32).build()
+        } else {
+            builder.build()
+        };
+        for (id, text) in texts.iter().enumerate() {
+            index.add(id, text);
+        }
+        index
+    };
+    let plain = build(false);
+    let compressed = build(true);
+    assert!(matches!(
+        compressed.documents[&0].content,
+        Content::Compressed { .. }
+    ));
+    // Below the threshold a content is kept as it is.
+    assert!(matches!(
+        compressed.documents[&3].content,
+        Content::Memory(_)
+    ));
+    assert!(compressed.stats().content_bytes < plain.stats().content_bytes);
+    for (id, text) in texts.iter().enumerate() {
+        assert_eq!(compressed.get(id).as_deref(), Some(text.as_str()));
+    }
+    for query in ["borrow", "straße", "rust", "東京の検索エンジン"] {
+        assert_eq!(compressed.query(query), plain.query(query), "{query}");
+        assert_eq!(
+            compressed.query_snippets_multi(query, 2),
+            plain.query_snippets_multi(query, 2),
+            "{query}"
+        );
+    }
+    assert_eq!(compressed.validate(), Ok(()));
+
+    // Replacing a cached document's content never reads the old one back.
+    let mut index = build(true);
+    assert!(index.get(1).is_some());
+    index.add(1, &"Neue Straße, neue Grüße. ".repeat(4));
+    assert_eq!(
+        index.get(1).as_deref(),
+        Some(&*"Neue Straße, neue Grüße. ".repeat(4))
+    );
+    assert_eq!(index.query_ids("grüße"), vec![1]);
+
+    // Saving keeps the compressed bytes, and the loaded index keeps compressing.
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let mut loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.documents, index.documents);
+    assert_eq!(loaded.query("borrow"), plain.query("borrow"));
+    loaded.add(5, &texts[0]);
+    assert!(matches!(
+        loaded.documents[&5].content,
+        Content::Compressed { .. }
+    ));
+}
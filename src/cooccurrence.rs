@@ -0,0 +1,114 @@
+//! Which terms tend to appear in the same documents as a given one.
+
+use crate::index::InvertedIndex;
+use std::collections::{HashMap, HashSet};
+
+impl InvertedIndex {
+    /// Finds the terms occurring together with `term`, e.g. to expand a query.
+    ///
+    /// # Parameters
+    /// - `term`: The word to look up, analyzed like query text.
+    /// - `limit`: The maximum number of terms returned.
+    ///
+    /// # Returns
+    /// `(term, documents)` pairs, where `documents` is the number of documents containing
+    /// both terms, the most first and ties in ascending term order. The term itself and stop
+    /// words are left out. Empty if no document contains `term`.
+    ///
+    /// # Notes
+    /// The terms of each document are taken from the
+    /// [forward index](crate::IndexBuilder::forward_index). With that disabled, each document
+    /// is re-analyzed from its stored content, so a query costs time linear in the length of
+    /// the documents read. Of a term in more documents than the
+    /// [sample size](crate::IndexBuilder::cooccurrence_sample), only that many, spread
+    /// evenly over its posting list, are read.
+    pub fn cooccurring_terms(&self, term: &str, limit: usize) -> Vec<(String, usize)> {
+        let Some(term) = self.analyze_term(term) else {
+            return Vec::new();
+        };
+        let Some(postings) = self.live_postings(&term) else {
+            return Vec::new();
+        };
+        let sample = self.cooccurrence_sample.min(postings.len());
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for i in 0..sample {
+            let posting = &postings[i * postings.len() / sample];
+            if let Some(terms) = self.doc_terms(posting.doc_id) {
+                for (other, _) in terms.filter(|(other, _)| *other != term) {
+                    *counts.entry(other.to_string()).or_insert(0) += 1;
+                }
+                continue;
+            }
+            let Some(doc) = self.documents.get(&posting.doc_id) else {
+                continue;
+            };
+            let Some(content) = self.content(doc) else {
+                continue;
+            };
+            let terms: HashSet<String> = self.analyzer.analyze(&content).into_iter().collect();
+            for other in terms {
+                // Capped terms are skipped like stop words.
+                if other != term && self.indexes.contains_key(&other) {
+                    *counts.entry(other).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+        terms.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(limit);
+        terms
+    }
+}
+
+#[test]
+fn cooccurring_terms_test() {
+    let mut index = InvertedIndex::builder().stop_words(["and", "is"]).build();
+    index.add(1, "Rust and Cargo");
+    index.add(2, "Rust is fast and Cargo is handy");
+    index.add(3, "Rust is fast");
+    index.add(4, "Go is fast");
+    index.add(5, "Cargo cult");
+
+    let owned = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+        pairs
+            .iter()
+            .map(|&(term, count)| (term.to_string(), count))
+            .collect()
+    };
+    assert_eq!(
+        index.cooccurring_terms("Rust", 10),
+        owned(&[("cargo", 2), ("fast", 2), ("handy", 1)])
+    );
+    assert_eq!(index.cooccurring_terms("rust", 1), owned(&[("cargo", 2)]));
+    assert_eq!(index.cooccurring_terms("go", 10), owned(&[("fast", 1)]));
+    assert!(index.cooccurring_terms("python", 10).is_empty());
+    assert!(index.cooccurring_terms("and", 10).is_empty());
+    index.remove(2);
+    assert_eq!(
+        index.cooccurring_terms("rust", 10),
+        owned(&[("cargo", 1), ("fast", 1)])
+    );
+}
+
+#[test]
+fn cooccurrence_sample_test() {
+    let build = |sample: usize| {
+        let mut index = InvertedIndex::builder()
+            .cooccurrence_sample(sample)
+            .forward_index(sample == 100)
+            .build();
+        for id in 0..100 {
+            index.add(id, &format!("common even{}", id % 2));
+        }
+        index
+    };
+    // Every tenth document is read, all of them even.
+    assert_eq!(
+        build(10).cooccurring_terms("common", 10),
+        vec![("even0".to_string(), 10)]
+    );
+    assert_eq!(
+        build(100).cooccurring_terms("common", 10),
+        vec![("even0".to_string(), 50), ("even1".to_string(), 50)]
+    );
+}
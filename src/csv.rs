@@ -0,0 +1,212 @@
+//! CSV ingestion, with a minimal RFC 4180 reader.
+
+use crate::analysis::TermBuffer;
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::io::{self, BufRead, BufReader, Read};
+
+impl InvertedIndex {
+    /// Indexes every row of a CSV stream as one document.
+    ///
+    /// # Parameters
+    /// - `reader`: The input. Quoted fields may contain commas, quotes and line breaks.
+    /// - `id_column`: The column holding the document id, a non-negative integer.
+    /// - `text_columns`: The columns to index, joined by newlines in this order into the
+    ///   document content.
+    /// - `has_headers`: Whether the first row names the columns. Without headers, columns are
+    ///   named by their 0-based position, e.g. `"0"`.
+    ///
+    /// # Returns
+    /// The number of ingested rows. A row with an already indexed id replaces that document,
+    /// as [`add`](Self::add) does. Fails with [`IndexError::MissingColumn`] naming the first
+    /// unknown column before anything is indexed, and with [`IndexError::InvalidId`] or
+    /// [`IndexError::Read`] at the first bad row, keeping the rows ingested before it.
+    pub fn add_csv<R: Read>(
+        &mut self,
+        reader: R,
+        id_column: &str,
+        text_columns: &[&str],
+        has_headers: bool,
+    ) -> Result<usize, IndexError> {
+        let mut csv = CsvReader::new(BufReader::new(reader));
+        let headers = if has_headers {
+            csv.next_record()
+                .map_err(IndexError::Read)?
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let column = |name: &str| {
+            let position = if has_headers {
+                headers.iter().position(|header| header == name)
+            } else {
+                name.parse().ok()
+            };
+            position.ok_or_else(|| IndexError::MissingColumn(name.to_string()))
+        };
+        let id_index = column(id_column)?;
+        let text_indexes = text_columns
+            .iter()
+            .map(|name| column(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut words = TermBuffer::default();
+        let mut added = 0;
+        while let Some(record) = csv.next_record().map_err(IndexError::Read)? {
+            let value = record.get(id_index).map_or("", String::as_str);
+            let id = value.trim().parse().map_err(|_| IndexError::InvalidId {
+                line: csv.line,
+                value: value.to_string(),
+            })?;
+            let content = text_indexes
+                .iter()
+                .filter_map(|&i| record.get(i).map(String::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.record(LogEntry::Add {
+                id,
+                content: &content,
+            });
+            self.index_document(id, content, &mut words);
+            added += 1;
+        }
+        Ok(added)
+    }
+}
+
+/// Reads comma-separated records, handling quoted fields that contain commas, quotes or
+/// line breaks.
+pub(crate) struct CsvReader<R> {
+    reader: R,
+    /// 1-based line number of the start of the last record read.
+    pub(crate) line: usize,
+    next_line: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            next_line: 1,
+        }
+    }
+
+    /// Reads the next record, or `None` at the end of the input. Empty lines are skipped.
+    pub(crate) fn next_record(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut fields = Vec::new();
+        let mut field = Vec::new();
+        let mut in_quotes = false;
+        let mut started = false;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                if in_quotes {
+                    return Err(invalid(self.line, "unterminated quoted field"));
+                }
+                if !started {
+                    return Ok(None);
+                }
+                fields.push(decode(field, self.line)?);
+                return Ok(Some(fields));
+            }
+            if !started {
+                if line == b"\n" || line == b"\r\n" {
+                    self.next_line += 1;
+                    continue;
+                }
+                started = true;
+                self.line = self.next_line;
+            }
+            self.next_line += 1;
+            let mut bytes = line.iter().copied().peekable();
+            while let Some(byte) = bytes.next() {
+                match (in_quotes, byte) {
+                    (true, b'"') if bytes.peek() == Some(&b'"') => {
+                        bytes.next();
+                        field.push(b'"');
+                    }
+                    (true, b'"') => in_quotes = false,
+                    (true, _) => field.push(byte),
+                    (false, b'"') if field.is_empty() => in_quotes = true,
+                    (false, b',') => fields.push(decode(std::mem::take(&mut field), self.line)?),
+                    (false, b'\r') if bytes.peek() == Some(&b'\n') => {}
+                    (false, b'\n') => {
+                        fields.push(decode(field, self.line)?);
+                        return Ok(Some(fields));
+                    }
+                    (false, _) => field.push(byte),
+                }
+            }
+        }
+    }
+}
+
+fn decode(field: Vec<u8>, line: usize) -> io::Result<String> {
+    String::from_utf8(field).map_err(|_| invalid(line, "field is not valid UTF-8"))
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+#[test]
+fn csv_reader_test() {
+    let input = "id,text\r\n1,\"a, b\"\n\n2,\"multi\nline \"\"quoted\"\"\"\n3,last";
+    let mut reader = CsvReader::new(input.as_bytes());
+    let mut records = Vec::new();
+    while let Some(record) = reader.next_record().unwrap() {
+        records.push((reader.line, record));
+    }
+    assert_eq!(
+        records,
+        vec![
+            (1, vec!["id".to_string(), "text".to_string()]),
+            (2, vec!["1".to_string(), "a, b".to_string()]),
+            (
+                4,
+                vec!["2".to_string(), "multi\nline \"quoted\"".to_string()]
+            ),
+            (6, vec!["3".to_string(), "last".to_string()]),
+        ]
+    );
+
+    let mut reader = CsvReader::new("1,\"open".as_bytes());
+    assert!(reader.next_record().is_err());
+}
+
+#[test]
+fn add_csv_test() {
+    let input = "id,title,body,views\n\
+                 1,Rust,\"fast, safe\nand fun\",10\n\
+                 2,Go,simple,20\n\
+                 1,Rust,replaced,30\n";
+    let mut index = InvertedIndex::new();
+    assert_eq!(
+        index
+            .add_csv(input.as_bytes(), "id", &["title", "body"], true)
+            .unwrap(),
+        3
+    );
+    assert_eq!(index.get(1).as_deref(), Some("Rust\nreplaced"));
+    assert_eq!(index.get(2).as_deref(), Some("Go\nsimple"));
+
+    let err = index
+        .add_csv(input.as_bytes(), "id", &["summary"], true)
+        .unwrap_err();
+    assert!(matches!(&err, IndexError::MissingColumn(column) if column == "summary"));
+    assert_eq!(err.to_string(), "no column named \"summary\"");
+
+    let mut index = InvertedIndex::new();
+    let input = "7,\"a, b\"\nx,c\n";
+    let err = index
+        .add_csv(input.as_bytes(), "0", &["1"], false)
+        .unwrap_err();
+    assert!(matches!(err, IndexError::InvalidId { line: 2, .. }));
+    assert_eq!(index.get(7).as_deref(), Some("a, b"));
+}
@@ -0,0 +1,300 @@
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Which files [`InvertedIndex::index_dir`] picks up.
+#[derive(Debug, Clone, Default)]
+pub struct DirIndexOptions {
+    /// Descend into subdirectories.
+    pub recursive: bool,
+    /// Only index files whose name matches this glob, e.g. `"*.txt"`. `*` matches any run
+    /// of characters and `?` a single character. `None` indexes every file.
+    pub pattern: Option<String>,
+}
+
+/// The outcome of [`InvertedIndex::index_dir`].
+#[derive(Debug, Default)]
+pub struct DirIndexReport {
+    /// The id and path of every indexed file, in path order.
+    pub indexed: Vec<(usize, PathBuf)>,
+    /// The files and subdirectories that could not be read, with the reason.
+    pub skipped: Vec<IndexError>,
+}
+
+impl InvertedIndex {
+    /// Indexes every UTF-8 text file directly inside `dir` as its own document.
+    ///
+    /// # Parameters
+    /// - `dir`: The directory to read. Subdirectories are ignored, see
+    ///   [`add_directory_recursive`](Self::add_directory_recursive).
+    ///
+    /// # Returns
+    /// The number of indexed files. Files get sequential ids following the largest id in the
+    /// index, in path order, and [`document_path`](Self::document_path) maps an id back to its
+    /// file. Files that are not valid UTF-8 are skipped.
+    pub fn add_directory(&mut self, dir: &Path) -> io::Result<usize> {
+        self.add_files(collect_files(dir, false)?)
+    }
+
+    /// Like [`add_directory`](Self::add_directory), but also indexes the files of all
+    /// subdirectories.
+    pub fn add_directory_recursive(&mut self, dir: &Path) -> io::Result<usize> {
+        self.add_files(collect_files(dir, true)?)
+    }
+
+    /// Indexes the text files of a directory, collecting the ones it cannot read instead of
+    /// giving up on the first.
+    ///
+    /// # Parameters
+    /// - `dir`: The directory to walk.
+    /// - `opts`: Whether to recurse and which file names to index.
+    ///
+    /// # Returns
+    /// The indexed files with their ids, assigned like [`add_directory`](Self::add_directory)
+    /// does, and the unreadable or non-UTF-8 files and subdirectories that were skipped.
+    /// Fails only if `dir` itself cannot be listed.
+    pub fn index_dir(
+        &mut self,
+        dir: &Path,
+        opts: DirIndexOptions,
+    ) -> Result<DirIndexReport, IndexError> {
+        let mut report = DirIndexReport::default();
+        let read_dir = |dir: &Path| {
+            fs::read_dir(dir).map_err(|source| IndexError::Io {
+                path: dir.to_path_buf(),
+                source,
+            })
+        };
+        let mut files = Vec::new();
+        let mut pending = vec![(dir.to_path_buf(), read_dir(dir)?)];
+        while let Some((dir, entries)) = pending.pop() {
+            for entry in entries {
+                let path = match entry {
+                    Ok(entry) => entry.path(),
+                    Err(source) => {
+                        let path = dir.clone();
+                        report.skipped.push(IndexError::Io { path, source });
+                        continue;
+                    }
+                };
+                if path.is_dir() {
+                    if opts.recursive {
+                        match read_dir(&path) {
+                            Ok(entries) => pending.push((path, entries)),
+                            Err(err) => report.skipped.push(err),
+                        }
+                    }
+                } else if path.is_file() && matches_pattern(&path, opts.pattern.as_deref()) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+
+        let mut id = self.next_doc_id();
+        for path in files {
+            let content = match fs::read(&path) {
+                Ok(bytes) => String::from_utf8(bytes),
+                Err(source) => {
+                    report.skipped.push(IndexError::Io { path, source });
+                    continue;
+                }
+            };
+            let Ok(content) = content else {
+                report.skipped.push(IndexError::InvalidUtf8 { path });
+                continue;
+            };
+            self.add(id, &content);
+            self.set_document_path(id, path.clone());
+            report.indexed.push((id, path));
+            id += 1;
+        }
+        Ok(report)
+    }
+
+    /// Returns the file a document was read from, if it was added from a directory.
+    pub fn document_path(&self, doc_id: usize) -> Option<&Path> {
+        self.documents.get(&doc_id)?.path.as_deref()
+    }
+
+    /// Records that document `id` was read from `path`.
+    pub(crate) fn set_document_path(&mut self, id: usize, path: PathBuf) {
+        self.record(LogEntry::Path { id, path: &path });
+        if let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) {
+            doc.path = Some(path);
+        }
+    }
+
+    /// The id the next document added without an explicit id should get.
+    pub(crate) fn next_doc_id(&self) -> usize {
+        self.documents.keys().max().map_or(0, |id| id + 1)
+    }
+
+    fn add_files(&mut self, files: Vec<PathBuf>) -> io::Result<usize> {
+        let mut id = self.next_doc_id();
+        let mut added = 0;
+        for path in files {
+            let Ok(content) = String::from_utf8(fs::read(&path)?) else {
+                continue;
+            };
+            self.add(id, &content);
+            self.set_document_path(id, path);
+            id += 1;
+            added += 1;
+        }
+        Ok(added)
+    }
+}
+
+/// Lists the regular files in `dir`, sorted by path.
+fn collect_files(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the file name of `path` matches the glob `pattern`, if any.
+fn matches_pattern(path: &Path, pattern: Option<&str>) -> bool {
+    let Some(pattern) = pattern else {
+        return true;
+    };
+    let name = path.file_name().map(|name| name.to_string_lossy());
+    name.is_some_and(|name| glob_match(pattern, &name))
+}
+
+/// Matches `text` against a glob supporting `*` and `?`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was seen, and the text position it currently stops at.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// Creates an empty scratch directory for a test.
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("inverted_index_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn add_directory_test() {
+    let dir = test_dir("add_directory");
+    fs::write(dir.join("a.txt"), "Rust is fast").unwrap();
+    fs::write(dir.join("b.txt"), "Go is simple").unwrap();
+    fs::write(dir.join("binary.dat"), [0xff, 0xfe, b'r']).unwrap();
+    fs::create_dir(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested").join("c.txt"), "Rust again").unwrap();
+
+    let mut index = InvertedIndex::new();
+    index.add(10, "existing document");
+    assert_eq!(index.add_directory(&dir).unwrap(), 2);
+    assert_eq!(index.document_path(11), Some(dir.join("a.txt").as_path()));
+    assert_eq!(index.document_path(12), Some(dir.join("b.txt").as_path()));
+    assert_eq!(index.document_path(10), None);
+    assert_eq!(index.union(&["rust"]), vec![11]);
+
+    let mut index = InvertedIndex::new();
+    assert_eq!(index.add_directory_recursive(&dir).unwrap(), 3);
+    assert_eq!(index.union(&["rust"]), vec![0, 2]);
+    assert_eq!(
+        index.document_path(2),
+        Some(dir.join("nested").join("c.txt").as_path())
+    );
+
+    assert!(index.add_directory(&dir.join("missing")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn glob_match_test() {
+    assert!(glob_match("*.txt", "notes.txt"));
+    assert!(glob_match("*.txt", ".txt"));
+    assert!(!glob_match("*.txt", "notes.txt.bak"));
+    assert!(glob_match("a?c*", "abcdef"));
+    assert!(!glob_match("a?c", "ac"));
+    assert!(glob_match("*", ""));
+}
+
+#[test]
+fn index_dir_test() {
+    let dir = test_dir("index_dir");
+    fs::write(dir.join("a.txt"), "Rust is fast").unwrap();
+    fs::write(dir.join("b.md"), "Go is simple").unwrap();
+    fs::write(dir.join("binary.txt"), [0xff, 0xfe, b'r']).unwrap();
+    fs::create_dir(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested").join("c.txt"), "Rust again").unwrap();
+
+    let mut index = InvertedIndex::new();
+    let opts = DirIndexOptions {
+        recursive: true,
+        pattern: Some("*.txt".to_string()),
+    };
+    let report = index.index_dir(&dir, opts).unwrap();
+    assert_eq!(
+        report.indexed,
+        vec![
+            (0, dir.join("a.txt")),
+            (1, dir.join("nested").join("c.txt"))
+        ]
+    );
+    assert!(matches!(
+        &report.skipped[..],
+        [IndexError::InvalidUtf8 { path }] if *path == dir.join("binary.txt")
+    ));
+    assert_eq!(
+        index.document_path(1),
+        Some(dir.join("nested").join("c.txt").as_path())
+    );
+    assert_eq!(index.union(&["rust"]), vec![0, 1]);
+
+    let mut index = InvertedIndex::new();
+    let report = index.index_dir(&dir, DirIndexOptions::default()).unwrap();
+    assert_eq!(report.indexed.len(), 2);
+    assert_eq!(index.union(&["go"]), vec![1]);
+
+    let missing = index.index_dir(&dir.join("missing"), DirIndexOptions::default());
+    assert!(matches!(missing, Err(IndexError::Io { .. })));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,461 @@
+//! An optional append-only file holding the document contents, so that memory only holds the
+//! postings and a small record per document, see
+//! [`IndexBuilder::document_store`](crate::IndexBuilder::document_store).
+
+use crate::error::IndexError;
+use crate::index::{Document, InvertedIndex};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Where the content of a document is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Content {
+    Memory(String),
+    /// `len` bytes at `offset` in the document store file.
+    Stored {
+        offset: u64,
+        len: usize,
+    },
+    /// Not kept, see [`IndexBuilder::store_content`](crate::IndexBuilder::store_content).
+    Omitted,
+    /// The LZ4 block of a `len`-byte content, see
+    /// [`IndexBuilder::compress_content`](crate::IndexBuilder::compress_content).
+    Compressed {
+        bytes: Arc<[u8]>,
+        len: usize,
+    },
+}
+
+/// The store file with a cache of recently read contents.
+#[derive(Debug)]
+pub(crate) struct DocStore {
+    path: PathBuf,
+    state: Mutex<StoreState>,
+}
+
+#[derive(Debug)]
+struct StoreState {
+    file: File,
+    /// The end of the file, where the next content is appended.
+    end: u64,
+    capacity: usize,
+    /// Contents by offset, with the tick they were last read at. Offsets are never reused
+    /// within a file, so replacing a document needs no invalidation.
+    cache: HashMap<u64, (u64, Arc<str>)>,
+    tick: u64,
+    /// The first failed append since the last [`sync_store`](InvertedIndex::sync_store).
+    error: Option<io::Error>,
+}
+
+impl DocStore {
+    /// Creates the store file at `path`, replacing any file there, keeping up to
+    /// `cache_capacity` recently read contents in memory.
+    pub(crate) fn create(path: &Path, cache_capacity: usize) -> io::Result<DocStore> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(DocStore {
+            path: path.to_path_buf(),
+            state: Mutex::new(StoreState {
+                file,
+                end: 0,
+                capacity: cache_capacity,
+                cache: HashMap::with_capacity(cache_capacity),
+                tick: 0,
+                error: None,
+            }),
+        })
+    }
+
+    /// Appends `content` to the file.
+    pub(crate) fn append(&self, content: &str) -> io::Result<Content> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let offset = state.end;
+        state.file.seek(SeekFrom::Start(offset))?;
+        state.file.write_all(content.as_bytes())?;
+        state.end += content.len() as u64;
+        Ok(Content::Stored {
+            offset,
+            len: content.len(),
+        })
+    }
+
+    /// Keeps `err` for [`sync_store`](InvertedIndex::sync_store), unless an earlier error
+    /// is still unreported.
+    fn fail(&self, err: io::Error) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.error.get_or_insert(err);
+    }
+
+    /// Reads the `len` bytes at `offset`, from the cache if they were read recently.
+    fn read(&self, offset: u64, len: usize) -> io::Result<Arc<str>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some((used, content)) = state.cache.get_mut(&offset) {
+            *used = tick;
+            return Ok(Arc::clone(content));
+        }
+        let mut bytes = vec![0; len];
+        state.file.seek(SeekFrom::Start(offset))?;
+        state.file.read_exact(&mut bytes)?;
+        let content: Arc<str> = String::from_utf8(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+            .into();
+        if state.capacity > 0 {
+            // A scan of the cache, which is cheap next to the read for small capacities.
+            if state.cache.len() >= state.capacity
+                && let Some(oldest) = state
+                    .cache
+                    .iter()
+                    .min_by_key(|(_, (used, _))| *used)
+                    .map(|(offset, _)| *offset)
+            {
+                state.cache.remove(&oldest);
+            }
+            state.cache.insert(offset, (tick, Arc::clone(&content)));
+        }
+        Ok(content)
+    }
+
+    /// The number of bytes in the file.
+    pub(crate) fn len(&self) -> u64 {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .end
+    }
+
+    /// Writes the given contents to a new file that replaces this one.
+    ///
+    /// # Returns
+    /// The new store and the new location of each content, in the order given.
+    fn rewrite(&self, contents: &[(u64, usize)]) -> io::Result<(DocStore, Vec<Content>)> {
+        let mut name = self.path.clone().into_os_string();
+        name.push(".compact");
+        let temporary = PathBuf::from(name);
+        let capacity = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .capacity;
+        let store = DocStore::create(&temporary, capacity)?;
+        let mut moved = Vec::with_capacity(contents.len());
+        for &(offset, len) in contents {
+            moved.push(store.append(&self.read(offset, len)?)?);
+        }
+        store
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .file
+            .sync_all()?;
+        fs::rename(&temporary, &self.path)?;
+        Ok((
+            DocStore {
+                path: self.path.clone(),
+                state: store.state,
+            },
+            moved,
+        ))
+    }
+}
+
+impl InvertedIndex {
+    /// The content of `doc`, read from the document store if it is kept there and
+    /// decompressed if it is kept compressed.
+    ///
+    /// # Returns
+    /// `None` if the store cannot be read or contents are not kept.
+    pub(crate) fn content<'a>(&'a self, doc: &'a Document) -> Option<Cow<'a, str>> {
+        match &doc.content {
+            Content::Memory(content) => Some(Cow::Borrowed(content)),
+            Content::Stored { offset, len } => {
+                let content = self.store.as_ref()?.read(*offset, *len).ok()?;
+                Some(Cow::Owned(content.to_string()))
+            }
+            Content::Compressed { bytes, len } => {
+                let content = self.decompress_content(doc.id, bytes, *len)?;
+                Some(Cow::Owned(content.to_string()))
+            }
+            Content::Omitted => None,
+        }
+    }
+
+    /// Keeps `content` in the document store if there is one, or in memory, compressed if
+    /// configured, if there is none or appending to it fails. Drops it if contents are not
+    /// kept. A failed append is reported by [`sync_store`](Self::sync_store).
+    pub(crate) fn store_content(&self, content: String) -> Content {
+        if !self.keep_content {
+            return Content::Omitted;
+        }
+        match &self.store {
+            Some(store) => match store.append(&content) {
+                Ok(stored) => stored,
+                Err(err) => {
+                    store.fail(err);
+                    self.pack_content(content)
+                }
+            },
+            None => self.pack_content(content),
+        }
+    }
+
+    /// Flushes the [document store](crate::IndexBuilder::document_store) to disk.
+    ///
+    /// # Returns
+    /// The first error appending a content to the store since the last call, as
+    /// [`IndexError::Io`]. The contents that could not be appended are kept in memory
+    /// instead, so the index is still complete. Does nothing if the index has no store.
+    pub fn sync_store(&self) -> Result<(), IndexError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let io_error = |source| IndexError::Io {
+            path: store.path.clone(),
+            source,
+        };
+        let mut state = store.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.error.take() {
+            Some(err) => Err(io_error(err)),
+            None => state.file.sync_data().map_err(io_error),
+        }
+    }
+
+    /// The token `offsets` to keep with a document: none if contents are not kept, since
+    /// the offsets only locate tokens in them.
+    pub(crate) fn store_offsets(&self, offsets: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        if self.keep_content {
+            offsets
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Rewrites the document store without the contents of removed and replaced documents.
+    ///
+    /// # Returns
+    /// The number of bytes reclaimed, 0 if there is no store or rewriting it failed.
+    pub(crate) fn compact_store(&mut self) -> u64 {
+        let Some(store) = &self.store else {
+            return 0;
+        };
+        let mut stored: Vec<(usize, u64, usize)> = self
+            .documents
+            .values()
+            .filter_map(|doc| match doc.content {
+                Content::Stored { offset, len } => Some((doc.id, offset, len)),
+                Content::Memory(_) | Content::Compressed { .. } | Content::Omitted => None,
+            })
+            .collect();
+        let live: u64 = stored.iter().map(|&(_, _, len)| len as u64).sum();
+        let before = store.len();
+        if live == before {
+            return 0;
+        }
+        stored.sort_unstable_by_key(|&(_, offset, _)| offset);
+        let locations: Vec<(u64, usize)> = stored
+            .iter()
+            .map(|&(_, offset, len)| (offset, len))
+            .collect();
+        // Snapshots keep reading the replaced file through their own handle.
+        let Ok((compacted, moved)) = store.rewrite(&locations) else {
+            return 0;
+        };
+        for ((id, _, _), content) in stored.into_iter().zip(moved) {
+            if let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) {
+                doc.content = content;
+            }
+        }
+        self.store = Some(Arc::new(compacted));
+        before - live
+    }
+}
+
+#[test]
+fn document_store_test() {
+    let _color = crate::test_util::color_guard(false);
+    let dir = crate::directory::test_dir("document_store");
+    let path = dir.join("documents.bin");
+    let mut index = InvertedIndex::builder()
+        .document_store(&path)
+        .document_cache(2)
+        .try_build()
+        .unwrap();
+    index.add(1, "Rust is fast");
+    index.add(2, "Go is simple");
+    index.add_fields(3, &[("title", "Rust"), ("body", "with fields")]);
+    assert!(matches!(
+        index.documents[&1].content,
+        Content::Stored { offset: 0, len: 12 }
+    ));
+    assert_eq!(fs::read_to_string(&path).unwrap().len(), 12 + 12 + 16);
+    assert_eq!(index.get(1).as_deref(), Some("Rust is fast"));
+    assert_eq!(index.get(3).as_deref(), Some("Rust\nwith fields"));
+    assert_eq!(index.query("simple"), vec!["Go is simple"]);
+    assert_eq!(
+        index.query_snippets_multi("fast", 1),
+        vec![(1, "Rust is fast".to_string())]
+    );
+
+    // Id-only queries answer from the postings alone.
+    let reads = |index: &InvertedIndex| index.store.as_ref().unwrap().state.lock().unwrap().tick;
+    let before = reads(&index);
+    assert_eq!(index.intersect(&["rust"]), vec![1, 3]);
+    assert_eq!(index.query_bm25("rust").len(), 2);
+    assert_eq!(index.query_ranked_multi("rust go").len(), 3);
+    assert_eq!(reads(&index), before);
+
+    // Removed and replaced contents leave holes until compaction.
+    let snapshot = index.snapshot();
+    index.remove(2);
+    index.add(1, "Rust is fast and safe");
+    assert_eq!(fs::metadata(&path).unwrap().len(), 12 + 12 + 16 + 21);
+    index.compact();
+    assert_eq!(fs::metadata(&path).unwrap().len(), 16 + 21);
+    assert_eq!(index.get(1).as_deref(), Some("Rust is fast and safe"));
+    assert_eq!(index.get(3).as_deref(), Some("Rust\nwith fields"));
+    assert_eq!(index.get(2).as_deref(), None);
+    if cfg!(unix) {
+        // The snapshot still reads the replaced file.
+        assert_eq!(snapshot.get(2).as_deref(), Some("Go is simple"));
+    }
+
+    // Saving reads the contents back into a plain index.
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.get(1).as_deref(), Some("Rust is fast and safe"));
+    assert!(matches!(loaded.documents[&1].content, Content::Memory(_)));
+
+    // A content that cannot be appended stays in memory, and the error is reported once.
+    index.sync_store().unwrap();
+    index.store.as_ref().unwrap().state.lock().unwrap().file = File::open(&path).unwrap();
+    index.add(4, "Rust on a read-only store");
+    assert!(matches!(index.documents[&4].content, Content::Memory(_)));
+    assert_eq!(index.get(4).as_deref(), Some("Rust on a read-only store"));
+    assert!(matches!(index.sync_store(), Err(IndexError::Io { path: at, .. }) if at == path));
+    if cfg!(unix) {
+        // Only the sync itself is left, which a read-only handle allows there.
+        index.sync_store().unwrap();
+    }
+    drop((index, snapshot));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_cache_test() {
+    let dir = crate::directory::test_dir("document_store_cache");
+    let store = DocStore::create(&dir.join("documents.bin"), 2).unwrap();
+    let locations: Vec<(u64, usize)> = ["first", "second", "third"]
+        .iter()
+        .map(|content| match store.append(content).unwrap() {
+            Content::Stored { offset, len } => (offset, len),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        &*store.read(locations[0].0, locations[0].1).unwrap(),
+        "first"
+    );
+    assert_eq!(
+        &*store.read(locations[1].0, locations[1].1).unwrap(),
+        "second"
+    );
+    assert_eq!(
+        &*store.read(locations[0].0, locations[0].1).unwrap(),
+        "first"
+    );
+    assert_eq!(
+        &*store.read(locations[2].0, locations[2].1).unwrap(),
+        "third"
+    );
+    let state = store.state.lock().unwrap();
+    // "second" was the least recently read.
+    let mut cached: Vec<u64> = state.cache.keys().copied().collect();
+    cached.sort_unstable();
+    assert_eq!(cached, vec![locations[0].0, locations[2].0]);
+    drop(state);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn store_content_test() {
+    use crate::query::QueryError;
+    use crate::search::SearchOptions;
+
+    let _color = crate::test_util::color_guard(false);
+    let build = |keep| {
+        let mut index = InvertedIndex::builder()
+            .stemming(true)
+            .store_content(keep)
+            .build();
+        index.add(1, "Rust is fast and Rust is safe");
+        index.add(2, "Go is simple");
+        index.add_fields(3, &[("title", "Rust"), ("body", "systems programming")]);
+        index.add_with_meta(
+            4,
+            "Notes on Rust",
+            HashMap::from([("author".to_string(), "alice".to_string())]),
+        );
+        index
+    };
+    let full = build(true);
+    let mut bare = build(false);
+
+    assert_eq!(bare.get(1), None);
+    assert_eq!(bare.query_ids("rust"), full.query_ids("rust"));
+    assert_eq!(bare.query_bm25("rust go"), full.query_bm25("rust go"));
+    let hits = bare.search("rust safe", &SearchOptions::default());
+    let expected = full.search("rust safe", &SearchOptions::default());
+    assert_eq!(hits.len(), expected.len());
+    for (hit, expected) in hits.iter().zip(&expected) {
+        assert_eq!((hit.doc_id, hit.score), (expected.doc_id, expected.score));
+        assert_eq!(hit.content, None);
+        assert!(expected.content.is_some());
+    }
+    assert_eq!(bare.metadata(4).unwrap()["author"], "alice");
+    assert_eq!(bare.field_at(3, 2), Some("body"));
+
+    // Nothing to highlight, and no panic either.
+    assert!(bare.query("rust").is_empty());
+    assert!(bare.query_with_counts("rust").is_empty());
+    assert!(bare.query_snippets_multi("rust", 2).is_empty());
+    assert!(bare.match_offsets(1, &["rust"]).is_empty());
+    assert_eq!(bare.try_query("rust"), Err(QueryError::ContentNotStored));
+    assert!(bare.surface_forms.is_none());
+    assert_eq!(bare.autocomplete("ru", 1)[0].term, "rust");
+
+    assert_eq!(bare.stats().content_bytes, 0);
+    assert!(full.stats().content_bytes > 0);
+    assert!(bare.memory_usage().documents < full.memory_usage().documents);
+    assert_eq!(bare.validate(), Ok(()));
+
+    let (mut saved, mut saved_full) = (Vec::new(), Vec::new());
+    bare.save(&mut saved).unwrap();
+    full.save(&mut saved_full).unwrap();
+    assert!(saved.len() < saved_full.len());
+    let loaded = InvertedIndex::load(saved.as_slice()).unwrap();
+    assert!(!loaded.keep_content);
+    assert_eq!(loaded.documents, bare.documents);
+    assert_eq!(loaded.indexes, bare.indexes);
+    assert_eq!(loaded.validate(), Ok(()));
+
+    assert!(bare.remove(1));
+    bare.update(2, "Go is fast").unwrap();
+    assert_eq!(bare.query_ids("fast"), vec![2]);
+    assert_eq!(bare.validate(), Ok(()));
+
+    // Documents are retained by id, with an empty content.
+    assert_eq!(bare.retain(|id, content| id == 2 && content.is_empty()), 2);
+    assert_eq!(bare.query_ids("rust"), Vec::<usize>::new());
+    assert_eq!(bare.retain(|_, _| false), 1);
+    assert!(bare.documents.is_empty() && bare.indexes.is_empty());
+}
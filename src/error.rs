@@ -0,0 +1,115 @@
+use crate::persist::IndexSection;
+use crate::query::QueryError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// An error raised while adding, changing, loading or querying documents.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IndexError {
+    /// A file or directory could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// A file is not valid UTF-8 text.
+    InvalidUtf8 { path: PathBuf },
+    /// Reading from a stream failed.
+    Read(io::Error),
+    /// Writing to a stream failed.
+    Write(io::Error),
+    /// A column named in the request is not in the input.
+    MissingColumn(String),
+    /// A record's id is not a non-negative integer.
+    InvalidId { line: usize, value: String },
+    /// The input is not a valid saved index.
+    Corrupt(String),
+    /// A saved index uses a format version this build cannot read: `found` is newer than
+    /// the `supported` version it writes, or too old to migrate.
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// A section of a saved index does not match the checksum saved with it: `expected` is
+    /// the saved checksum and `found` that of the bytes read.
+    ChecksumMismatch {
+        section: IndexSection,
+        expected: u32,
+        found: u32,
+    },
+    /// A document with this id is already indexed, see
+    /// [`InvertedIndex::try_add`](crate::InvertedIndex::try_add).
+    DuplicateDocument(usize),
+    /// No document has this id.
+    DocumentNotFound(usize),
+    /// A query could not be parsed or highlighted. The [`QueryError`] names the byte offset
+    /// or the term at fault.
+    InvalidQuery(QueryError),
+    /// A pattern matched more terms than the limit set with
+    /// [`IndexBuilder::regex_term_limit`](crate::IndexBuilder::regex_term_limit).
+    TooManyTerms { limit: usize },
+    /// A [numeric field](crate::InvertedIndex::add_numeric) was given NaN, which no range
+    /// can hold.
+    NotANumber(String),
+    /// A query names a field no document has, see
+    /// [`InvertedIndex::query_multi_match`](crate::InvertedIndex::query_multi_match).
+    UnknownField(String),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Io { path, source } => {
+                write!(f, "cannot read {}: {}", path.display(), source)
+            }
+            IndexError::InvalidUtf8 { path } => {
+                write!(f, "{} is not valid UTF-8", path.display())
+            }
+            IndexError::Read(source) => write!(f, "read failed: {}", source),
+            IndexError::Write(source) => write!(f, "write failed: {}", source),
+            IndexError::MissingColumn(column) => write!(f, "no column named \"{}\"", column),
+            IndexError::InvalidId { line, value } => {
+                write!(f, "line {}: invalid document id \"{}\"", line, value)
+            }
+            IndexError::Corrupt(reason) => write!(f, "corrupt index: {}", reason),
+            IndexError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "unsupported index format version {} (supported: {})",
+                found, supported
+            ),
+            IndexError::ChecksumMismatch {
+                section,
+                expected,
+                found,
+            } => write!(
+                f,
+                "corrupt index: {} checksum is {:08x}, expected {:08x}",
+                section, found, expected
+            ),
+            IndexError::DuplicateDocument(id) => write!(f, "document {} is already indexed", id),
+            IndexError::DocumentNotFound(id) => write!(f, "no document {}", id),
+            IndexError::InvalidQuery(err) => write!(f, "invalid query: {}", err),
+            IndexError::TooManyTerms { limit } => {
+                write!(f, "pattern matches more than {} terms", limit)
+            }
+            IndexError::NotANumber(field) => {
+                write!(f, "numeric field \"{}\" cannot hold NaN", field)
+            }
+            IndexError::UnknownField(name) => write!(f, "no field named \"{}\"", name),
+        }
+    }
+}
+
+impl Error for IndexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IndexError::Io { source, .. }
+            | IndexError::Read(source)
+            | IndexError::Write(source) => Some(source),
+            IndexError::InvalidQuery(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<QueryError> for IndexError {
+    fn from(err: QueryError) -> Self {
+        IndexError::InvalidQuery(err)
+    }
+}
@@ -0,0 +1,157 @@
+//! Breaking a ranked score down into the contributions it was computed from.
+
+use crate::index::InvertedIndex;
+use crate::query::Query;
+use crate::search::idf;
+use std::fmt;
+
+/// How a document's score came about, see [`InvertedIndex::explain`].
+///
+/// Each node's `value` is computed from its `details` as its `description` says, down to
+/// leaves holding the raw statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub value: f64,
+    pub description: String,
+    pub details: Vec<Explanation>,
+}
+
+impl Explanation {
+    fn leaf(value: f64, description: String) -> Self {
+        Self {
+            value,
+            description,
+            details: Vec::new(),
+        }
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(
+            f,
+            "{:indent$}{:.4} = {}",
+            "",
+            self.value,
+            self.description,
+            indent = depth * 2
+        )?;
+        for detail in &self.details {
+            writeln!(f)?;
+            detail.write_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Explanation {
+    /// Renders the tree with one node per line, details indented below their node.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+impl InvertedIndex {
+    /// Explains the score [`search_query`](Self::search_query) gives document `doc_id` for
+    /// `query`.
+    ///
+    /// # Returns
+    /// `None` if the document does not match `query`. Otherwise the sum of one weight per
+    /// query term the document contains, each the product of the term frequency, the
+    /// inverse document frequency and the boost the query gives the term with `^`. The root
+    /// value equals the score of the document's hit exactly.
+    ///
+    /// # Notes
+    /// This ranking has no length normalization, so none is shown. Field boosts are out of
+    /// scope: they only apply to [`query_ranked_boosted`](Self::query_ranked_boosted) and
+    /// [`query_multi_match`](Self::query_multi_match), whose scores this does not explain.
+    pub fn explain(&self, query: &Query, doc_id: usize) -> Option<Explanation> {
+        if self.matching_docs(query).binary_search(&doc_id).is_err() {
+            return None;
+        }
+        let doc_count = self.documents.len();
+        let mut score = 0.0;
+        let mut details = Vec::new();
+        for (term, boost) in self.scoring_terms(query) {
+            let Some(postings) = self.live_postings(&term) else {
+                continue;
+            };
+            let Ok(i) = postings.binary_search_by_key(&doc_id, |posting| posting.doc_id) else {
+                continue;
+            };
+            let tf = postings[i].term_freq() as f64;
+            let idf = idf(doc_count, postings.len());
+            let weight = tf * idf * boost;
+            score += weight;
+            details.push(Explanation {
+                value: weight,
+                description: format!("weight of \"{}\", tf * idf * boost", term),
+                details: vec![
+                    Explanation::leaf(tf, "tf, occurrences in the document".to_string()),
+                    Explanation::leaf(
+                        idf,
+                        format!(
+                            "idf, 1 + ln(N / df) with N = {} documents, df = {}",
+                            doc_count,
+                            postings.len()
+                        ),
+                    ),
+                    Explanation::leaf(boost, "boost, from the query".to_string()),
+                ],
+            });
+        }
+        Some(Explanation {
+            value: score,
+            description: format!("score of document {}, sum of:", doc_id),
+            details,
+        })
+    }
+}
+
+#[test]
+fn explain_test() {
+    use crate::SearchOptions;
+
+    let mut index = InvertedIndex::new();
+    index.add(3, "Rust is fast");
+    index.add(7, "Rust and more Rust, fast and safe");
+    index.add(9, "Go is simple");
+
+    let query = Query::parse("rust fast -go").unwrap();
+    let hits = index.search_query(&query, &SearchOptions::default());
+    assert_eq!(hits.len(), 2);
+    for hit in &hits {
+        let explanation = index.explain(&query, hit.doc_id).unwrap();
+        assert_eq!(explanation.value, hit.score);
+        assert_eq!(explanation.details.len(), 2);
+    }
+    assert_eq!(index.explain(&query, 9), None);
+    assert_eq!(index.explain(&query, 100), None);
+
+    let explanation = index.explain(&query, 7).unwrap();
+    let rendered = explanation.to_string();
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines.len(), 9);
+    assert_eq!(
+        lines[0],
+        format!("{:.4} = score of document 7, sum of:", hits[0].score)
+    );
+    assert!(lines[1].starts_with("  "), "{rendered}");
+    assert!(lines[1].ends_with("= weight of \"rust\", tf * idf * boost"));
+    assert_eq!(lines[2], "    2.0000 = tf, occurrences in the document");
+    assert_eq!(lines[4], "    1.0000 = boost, from the query");
+
+    // A boost scales the term's weight and is shown as its own factor.
+    let boosted = Query::parse("rust^2.5 fast").unwrap();
+    let hits = index.search_query(&boosted, &SearchOptions::default());
+    let explanation = index.explain(&boosted, 7).unwrap();
+    assert_eq!(explanation.value, hits[0].score);
+    let rust = &explanation.details[0];
+    assert_eq!(rust.details[2].value, 2.5);
+    assert_eq!(
+        rust.value,
+        rust.details[0].value * rust.details[1].value * 2.5
+    );
+
+    // Matching without any scored term still explains a score of 0.
+    let query = Query::parse("NOT go").unwrap();
+    assert_eq!(index.explain(&query, 3).unwrap().value, 0.0);
+}
@@ -0,0 +1,161 @@
+//! Counting the values of a field across the documents matching a query.
+
+use crate::index::{Document, InvertedIndex};
+use crate::query::Query;
+use std::collections::HashMap;
+
+/// The bucket of [`InvertedIndex::facet`] counting the matches without the field.
+pub const MISSING_FACET: &str = "(none)";
+
+impl Document {
+    /// The text of the field called `name` in `content`, the document's content, for
+    /// documents added with [`add_fields`](InvertedIndex::add_fields). The first field of
+    /// that name if there are several.
+    pub(crate) fn field_value<'a>(&self, content: &'a str, name: &str) -> Option<&'a str> {
+        let i = self.fields.iter().position(|(field, _)| field == name)?;
+        // After each field, add_fields records an empty token range at the byte where its
+        // text ends, in the position skipped before the next field.
+        let end_of = |i: usize| {
+            let next = self.fields.get(i + 1).map_or(self.offsets.len(), |f| f.1);
+            self.offsets.get(next.checked_sub(1)?).map(|&(end, _)| end)
+        };
+        // The fields are joined by a newline.
+        let start = match i {
+            0 => 0,
+            _ => end_of(i - 1)? + 1,
+        };
+        content.get(start..end_of(i)?)
+    }
+}
+
+impl InvertedIndex {
+    /// Counts how many documents matching `query` have each value of a field, such as the
+    /// number of results per "language".
+    ///
+    /// # Parameters
+    /// - `query`: The query selecting the documents, as for
+    ///   [`matching_docs`](Self::matching_docs).
+    /// - `field`: The field name given to [`add_fields`](Self::add_fields). Its whole text is
+    ///   the value, compared exactly.
+    /// - `include_missing`: Whether matches without the field, including documents added
+    ///   with [`add`](Self::add), are counted under [`MISSING_FACET`] instead of skipped.
+    ///
+    /// # Returns
+    /// `(value, count)` pairs, the highest count first and ties in ascending value order.
+    ///
+    /// # Notes
+    /// The values are read from the field boundaries stored with each document, so faceting
+    /// costs one lookup per match and never re-analyzes content. With a
+    /// [document store](crate::IndexBuilder::document_store), every match is read from it.
+    pub fn facet(&self, query: &Query, field: &str, include_missing: bool) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for doc_id in self.matching_docs(query) {
+            let Some(doc) = self.documents.get(&doc_id) else {
+                continue;
+            };
+            let content = self.content(doc);
+            let value = content
+                .as_deref()
+                .and_then(|content| doc.field_value(content, field));
+            match value {
+                Some(value) => *counts.entry(value.to_string()).or_insert(0) += 1,
+                None if include_missing => {
+                    *counts.entry(MISSING_FACET.to_string()).or_insert(0) += 1
+                }
+                None => {}
+            }
+        }
+        let mut facets: Vec<(String, usize)> = counts.into_iter().collect();
+        facets.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        facets
+    }
+}
+
+#[test]
+fn field_value_test() {
+    let mut index = InvertedIndex::new();
+    index.add_fields(
+        1,
+        &[("title", "Rust, fast"), ("empty", ""), ("language", "Rust")],
+    );
+    index.add_fields(2, &[("language", "go")]);
+    let doc = &index.documents[&1];
+    let content = index.get(1).unwrap();
+    assert_eq!(doc.field_value(&content, "title"), Some("Rust, fast"));
+    assert_eq!(doc.field_value(&content, "empty"), Some(""));
+    assert_eq!(doc.field_value(&content, "language"), Some("Rust"));
+    assert_eq!(doc.field_value(&content, "body"), None);
+    let content = index.get(2).unwrap();
+    assert_eq!(
+        index.documents[&2].field_value(&content, "language"),
+        Some("go")
+    );
+
+    // The boundaries are saved, so a loaded index reads the same values.
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    let content = loaded.get(1).unwrap();
+    assert_eq!(
+        loaded.documents[&1].field_value(&content, "language"),
+        Some("Rust")
+    );
+}
+
+#[test]
+fn facet_test() {
+    let mut index = InvertedIndex::new();
+    index.add_fields(
+        1,
+        &[("body", "a fast systems language"), ("language", "rust")],
+    );
+    index.add_fields(
+        2,
+        &[("body", "a simple systems language"), ("language", "go")],
+    );
+    index.add_fields(
+        3,
+        &[
+            ("body", "ownership in a systems language"),
+            ("language", "rust"),
+        ],
+    );
+    index.add_fields(
+        4,
+        &[("body", "a scripting language"), ("language", "python")],
+    );
+    index.add(5, "a plain systems language");
+
+    let query = Query::parse("systems").unwrap();
+    assert_eq!(
+        index.facet(&query, "language", false),
+        vec![("rust".to_string(), 2), ("go".to_string(), 1)]
+    );
+    assert_eq!(
+        index.facet(&query, "language", true),
+        vec![
+            ("rust".to_string(), 2),
+            (MISSING_FACET.to_string(), 1),
+            ("go".to_string(), 1),
+        ]
+    );
+    let query = Query::parse("language -fast").unwrap();
+    assert_eq!(
+        index.facet(&query, "language", false),
+        vec![
+            ("go".to_string(), 1),
+            ("python".to_string(), 1),
+            ("rust".to_string(), 1),
+        ]
+    );
+    assert!(index.facet(&query, "year", false).is_empty());
+
+    // The counts follow removals and replacements.
+    index.remove(3);
+    index.add_fields(2, &[("body", "a systems language"), ("language", "rust")]);
+    let query = Query::parse("systems").unwrap();
+    assert_eq!(
+        index.facet(&query, "language", false),
+        vec![("rust".to_string(), 2)]
+    );
+}
@@ -0,0 +1,353 @@
+//! Documents made of named fields, such as a title and a body, and ranking that weighs each
+//! match by the field it occurs in.
+
+use crate::analysis::TermBuffer;
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use crate::ranking::sort_by_score;
+use crate::search::idf;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// How [`InvertedIndex::query_multi_match`] combines the scores a document gets in each field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiMatch {
+    /// The sum of the field scores, so matching in several fields ranks higher.
+    #[default]
+    Sum,
+    /// The best field score, so a document counts as good as its best matching field.
+    BestFields,
+}
+
+impl InvertedIndex {
+    /// Indexes a document made of named fields.
+    ///
+    /// # Parameters
+    /// - `id`: The document id. A document with that id is replaced, as [`add`](Self::add) does.
+    /// - `fields`: `(name, text)` pairs. The texts, joined by newlines, become the document
+    ///   content.
+    ///
+    /// # Notes
+    /// Every field is analyzed like a plain document, but phrases never match across the
+    /// boundary between two fields. The field names are only used for weighing matches, see
+    /// [`query_ranked_boosted`](Self::query_ranked_boosted).
+    pub fn add_fields(&mut self, id: usize, fields: &[(&str, &str)]) {
+        self.record(LogEntry::Fields { id, fields });
+        let mut words = TermBuffer::default();
+        let mut offsets = Vec::new();
+        let mut starts = Vec::with_capacity(fields.len());
+        let mut position = 0;
+        let mut field_start = 0;
+        for (name, text) in fields {
+            starts.push((name.to_string(), position));
+            let first = offsets.len();
+            // Skip a position so that the last word of one field and the first of the next
+            // are not adjacent.
+            position = self
+                .analyzer
+                .analyze_into(text, position, &mut words, &mut offsets)
+                + 1;
+            for (start, end) in &mut offsets[first..] {
+                *start += field_start;
+                *end += field_start;
+            }
+            field_start += text.len();
+            offsets.push((field_start, field_start));
+            // The newline joining the fields.
+            field_start += 1;
+        }
+        let content = fields
+            .iter()
+            .map(|(_, text)| *text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let length = words.len();
+        self.index_terms(id, content, &mut words, offsets, length);
+        if let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) {
+            doc.fields = starts;
+        }
+    }
+
+    /// Ranks the documents containing any word of `query` by TF-IDF, with each occurrence
+    /// counted with the boost of its field.
+    ///
+    /// # Parameters
+    /// - `query`: The query text, analyzed like document text.
+    /// - `boosts`: Multipliers by field name. Fields without an entry, and documents not
+    ///   added with [`add_fields`](Self::add_fields), count with a boost of 1.0. So do
+    ///   occurrences without positions, as [imported](Self::import_term), unless the
+    ///   document has a single field.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order.
+    /// Without boosts the scores equal those of [`search`](Self::search).
+    pub fn query_ranked_boosted(
+        &self,
+        query: &str,
+        boosts: &HashMap<String, f64>,
+    ) -> Vec<(usize, f64)> {
+        let doc_count = self.documents.len();
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in self.query_terms(query) {
+            let Some(postings) = self.live_postings(&term) else {
+                continue;
+            };
+            let idf = idf(doc_count, postings.len());
+            for posting in postings.iter() {
+                let weighted_freq: f64 = if posting.positions.is_empty() {
+                    let boost = self
+                        .only_field(posting.doc_id)
+                        .and_then(|field| boosts.get(field))
+                        .copied()
+                        .unwrap_or(1.0);
+                    posting.term_freq() as f64 * boost
+                } else {
+                    posting
+                        .positions
+                        .iter()
+                        .map(|&position| self.field_boost(posting.doc_id, position, boosts))
+                        .sum()
+                };
+                *scores.entry(posting.doc_id).or_insert(0.0) += weighted_freq * idf;
+            }
+        }
+        sort_by_score(scores)
+    }
+
+    /// Ranks the documents matching any of `terms` in any of `fields` by TF-IDF, scoring each
+    /// field on its own.
+    ///
+    /// # Parameters
+    /// - `terms`: The query terms, each normalized like a [`query`](Self::query) term.
+    /// - `fields`: `(name, boost)` pairs. A field's score is the summed TF-IDF of the terms
+    ///   occurring in it, times its boost. Other fields are not searched.
+    /// - `mode`: How the field scores of a document make up its score.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order, or
+    /// [`IndexError::UnknownField`] for the first field name no document added with
+    /// [`add_fields`](Self::add_fields) has.
+    ///
+    /// # Notes
+    /// Documents added without fields never match. Occurrences without positions, as
+    /// [imported](Self::import_term), count in the document's only field; in a document with
+    /// several fields they make up one more field score, with a boost of 1.0.
+    pub fn query_multi_match(
+        &self,
+        terms: &[&str],
+        fields: &[(&str, f64)],
+        mode: MultiMatch,
+    ) -> Result<Vec<(usize, f64)>, IndexError> {
+        let known: HashSet<&str> = self
+            .documents
+            .values()
+            .flat_map(|doc| doc.fields.iter().map(|(name, _)| name.as_str()))
+            .collect();
+        if let Some((name, _)) = fields.iter().find(|(name, _)| !known.contains(name)) {
+            return Err(IndexError::UnknownField(name.to_string()));
+        }
+        let boosts: HashMap<&str, f64> = fields.iter().copied().collect();
+
+        let doc_count = self.documents.len();
+        let mut seen = HashSet::new();
+        let mut field_scores: HashMap<(usize, &str), f64> = HashMap::new();
+        for term in terms.iter().filter_map(|term| self.analyze_term(term)) {
+            if !seen.insert(term.clone()) {
+                continue;
+            }
+            let Some(postings) = self.live_postings(&term) else {
+                continue;
+            };
+            let idf = idf(doc_count, postings.len());
+            for posting in postings.iter() {
+                if posting.positions.is_empty() {
+                    let Some(doc) = self.documents.get(&posting.doc_id) else {
+                        continue;
+                    };
+                    let score = idf * posting.term_freq() as f64;
+                    match doc.fields.as_slice() {
+                        [] => {}
+                        [(field, _)] => {
+                            if let Some(boost) = boosts.get(field.as_str()) {
+                                *field_scores
+                                    .entry((posting.doc_id, field.as_str()))
+                                    .or_insert(0.0) += score * boost;
+                            }
+                        }
+                        // Not knowing the field, count the occurrences apart from all of
+                        // them, unboosted.
+                        _ => *field_scores.entry((posting.doc_id, "")).or_insert(0.0) += score,
+                    }
+                    continue;
+                }
+                for &position in &posting.positions {
+                    let Some(field) = self.field_at(posting.doc_id, position) else {
+                        continue;
+                    };
+                    if let Some(boost) = boosts.get(field) {
+                        *field_scores.entry((posting.doc_id, field)).or_insert(0.0) += idf * boost;
+                    }
+                }
+            }
+        }
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for ((id, _), score) in field_scores {
+            let total = scores.entry(id).or_insert(0.0);
+            match mode {
+                MultiMatch::Sum => *total += score,
+                MultiMatch::BestFields => *total = total.max(score),
+            }
+        }
+        Ok(sort_by_score(scores))
+    }
+
+    /// The name of the field of document `doc_id` that holds the token at `position`.
+    pub(crate) fn field_at(&self, doc_id: usize, position: usize) -> Option<&str> {
+        let fields = &self.documents.get(&doc_id)?.fields;
+        let i = fields.partition_point(|(_, start)| *start <= position);
+        i.checked_sub(1).map(|i| fields[i].0.as_str())
+    }
+
+    /// The name of the only field of document `doc_id`, `None` if it has none or several.
+    fn only_field(&self, doc_id: usize) -> Option<&str> {
+        match self.documents.get(&doc_id)?.fields.as_slice() {
+            [(name, _)] => Some(name),
+            _ => None,
+        }
+    }
+
+    fn field_boost(&self, doc_id: usize, position: usize, boosts: &HashMap<String, f64>) -> f64 {
+        self.field_at(doc_id, position)
+            .and_then(|field| boosts.get(field))
+            .copied()
+            .unwrap_or(1.0)
+    }
+}
+
+#[test]
+fn add_fields_test() {
+    let mut index = InvertedIndex::new();
+    index.add_fields(1, &[("title", "Rust"), ("body", "fast code")]);
+    assert_eq!(index.get(1).as_deref(), Some("Rust\nfast code"));
+    assert_eq!(index.field_at(1, 0), Some("title"));
+    assert_eq!(index.field_at(1, 2), Some("body"));
+    assert!(index.phrase_doc_ids("fast code").contains(&1));
+    assert!(index.phrase_doc_ids("rust fast").is_empty());
+
+    index.add(1, "plain again");
+    assert_eq!(index.field_at(1, 0), None);
+}
+
+#[test]
+fn query_ranked_boosted_test() {
+    let mut index = InvertedIndex::new();
+    index.add_fields(1, &[("title", "Cooking"), ("body", "Rust on old pans")]);
+    index.add_fields(2, &[("title", "Rust"), ("body", "A systems language")]);
+    index.add(3, "Nothing relevant");
+
+    let boosts = HashMap::from([("title".to_string(), 2.0)]);
+    let ranked = index.query_ranked_boosted("rust", &boosts);
+    assert_eq!(ranked.iter().map(|r| r.0).collect::<Vec<_>>(), vec![2, 1]);
+    assert_eq!(ranked[0].1, 2.0 * ranked[1].1);
+
+    // Without boosts both match once, so they tie and stay in id order.
+    let unboosted = index.query_ranked_boosted("rust", &HashMap::new());
+    assert_eq!(unboosted[0].0, 1);
+    assert_eq!(unboosted[0].1, unboosted[1].1);
+    let searched: Vec<(usize, f64)> = index
+        .search("rust", &Default::default())
+        .iter()
+        .map(|hit| (hit.doc_id, hit.score))
+        .collect();
+    assert_eq!(unboosted, searched);
+}
+
+#[test]
+fn query_multi_match_test() {
+    let mut index = InvertedIndex::new();
+    index.add_fields(1, &[("title", "Rust"), ("body", "Rust keeps memory safe")]);
+    index.add_fields(
+        2,
+        &[("title", "Rust"), ("body", "A guide to the borrow checker")],
+    );
+    index.add_fields(
+        3,
+        &[("title", "Gardening"), ("body", "Rust rust on the tools")],
+    );
+    index.add(4, "Rust without fields");
+    let fields = [("title", 2.0), ("body", 1.0)];
+    let ids = |ranked: &[(usize, f64)]| ranked.iter().map(|r| r.0).collect::<Vec<_>>();
+
+    // Matching in both fields adds up, so 1 beats 2 and 3.
+    let sum = index
+        .query_multi_match(&["rust"], &fields, MultiMatch::Sum)
+        .unwrap();
+    assert_eq!(ids(&sum), vec![1, 2, 3]);
+    assert_eq!(sum[0].1, 1.5 * sum[1].1);
+
+    // Only the best field counts: the title match of 1 and 2 and the two body matches of
+    // 3 are worth the same.
+    let best = index
+        .query_multi_match(&["RUST"], &fields, MultiMatch::BestFields)
+        .unwrap();
+    assert_eq!(ids(&best), vec![1, 2, 3]);
+    assert_eq!(best[0].1, best[1].1);
+    assert_eq!(best[0].1, best[2].1);
+    assert_eq!(best[0].1, sum[1].1);
+
+    let body = index
+        .query_multi_match(
+            &["rust", "borrow", "rust"],
+            &[("body", 1.0)],
+            MultiMatch::Sum,
+        )
+        .unwrap();
+    assert_eq!(ids(&body), vec![2, 3, 1]);
+
+    assert!(matches!(
+        index.query_multi_match(&["rust"], &[("title", 1.0), ("abstract", 1.0)], MultiMatch::Sum),
+        Err(IndexError::UnknownField(name)) if name == "abstract"
+    ));
+    assert_eq!(
+        index
+            .query_multi_match(&["python"], &fields, MultiMatch::Sum)
+            .unwrap(),
+        Vec::new()
+    );
+}
+
+#[test]
+fn imported_postings_ranked_by_field_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust guide");
+    index.add_fields(2, &[("body", "systems language")]);
+    index.add_fields(3, &[("title", "Go"), ("body", "simple")]);
+    assert_eq!(
+        index.import_term("rust".to_string(), vec![2, 3]).unwrap(),
+        2
+    );
+    let ids = |ranked: &[(usize, f64)]| ranked.iter().map(|r| r.0).collect::<Vec<_>>();
+
+    // The import counts once, in the only field of 2 and unboosted in 3.
+    let boosts = HashMap::from([("title".to_string(), 2.0), ("body".to_string(), 3.0)]);
+    let ranked = index.query_ranked_boosted("rust", &boosts);
+    assert_eq!(ids(&ranked), vec![2, 1, 3]);
+    assert_eq!(ranked[0].1, 3.0 * ranked[1].1);
+    assert_eq!(ranked[1].1, ranked[2].1);
+    let unboosted = index.query_ranked_boosted("rust", &HashMap::new());
+    let searched: Vec<(usize, f64)> = index
+        .search("rust", &Default::default())
+        .iter()
+        .map(|hit| (hit.doc_id, hit.score))
+        .collect();
+    assert_eq!(unboosted, searched);
+
+    let fields = [("title", 2.0), ("body", 3.0)];
+    let sum = index
+        .query_multi_match(&["rust"], &fields, MultiMatch::Sum)
+        .unwrap();
+    assert_eq!(ids(&sum), vec![2, 3]);
+    assert_eq!(sum[0].1, 3.0 * sum[1].1);
+}
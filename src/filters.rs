@@ -0,0 +1,101 @@
+//! Token filters dropping tokens that would pollute the vocabulary, such as hashes and
+//! single characters.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A custom token filter for [`IndexBuilder::token_filter`](crate::IndexBuilder::token_filter),
+/// returning `true` for the tokens to keep.
+///
+/// The predicate sees each token after case folding and before stemming.
+pub struct FilterFn(pub Box<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl fmt::Debug for FilterFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FilterFn(..)")
+    }
+}
+
+/// Which tokens the analyzer drops like stop words, see the token filter options of
+/// [`IndexBuilder`](crate::IndexBuilder).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TokenFilters {
+    /// Fewest characters a token must have.
+    pub(crate) min_length: usize,
+    /// Most characters a token may have.
+    pub(crate) max_length: Option<usize>,
+    /// Drop tokens made only of digits.
+    pub(crate) drop_numbers: bool,
+    /// Drop tokens this returns `false` for.
+    pub(crate) custom: Option<Arc<FilterFn>>,
+}
+
+impl TokenFilters {
+    /// Whether no token is ever dropped, to skip counting characters.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min_length <= 1
+            && self.max_length.is_none()
+            && !self.drop_numbers
+            && self.custom.is_none()
+    }
+
+    /// Whether the folded `token` passes every filter.
+    pub(crate) fn keep(&self, token: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let length = token.chars().count();
+        if length < self.min_length || self.max_length.is_some_and(|max| length > max) {
+            return false;
+        }
+        if self.drop_numbers && token.chars().all(char::is_numeric) {
+            return false;
+        }
+        self.custom.as_ref().is_none_or(|keep| (keep.0)(token))
+    }
+}
+
+#[test]
+fn token_filters_test() {
+    use crate::index::InvertedIndex;
+
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    let text = format!("commit {hash} by a user in 2024");
+    let build = || {
+        InvertedIndex::builder()
+            .min_token_length(2)
+            .max_token_length(40)
+            .drop_numbers(true)
+            .token_filter(FilterFn(Box::new(|token| token != "by")))
+            .build()
+    };
+    let mut index = build();
+    index.add(1, &text);
+    let mut batched = build();
+    batched.add_batch([(1, text.clone())]);
+    let mut streamed = build();
+    streamed.add_from_reader(1, text.as_bytes()).unwrap();
+
+    for index in [&index, &batched, &streamed] {
+        let mut terms: Vec<&str> = index.terms().collect();
+        terms.sort_unstable();
+        assert_eq!(terms, vec!["commit", "in", "user"]);
+    }
+    assert!(index.query(hash).is_empty());
+    assert!(index.query("a").is_empty());
+    assert!(index.query_ids("a 2024 by").is_empty());
+    assert_eq!(index.query_contains_all("commit by a user").len(), 1);
+
+    let mut bytes = Vec::new();
+    assert!(index.save(&mut bytes).is_err());
+    let mut plain = InvertedIndex::builder()
+        .min_token_length(2)
+        .drop_numbers(true)
+        .build();
+    plain.add(1, &text);
+    plain.save(&mut bytes).unwrap();
+    let mut loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    loaded.add(2, "b 42 again");
+    assert_eq!(loaded.query_ids("again"), vec![2]);
+    assert_eq!(loaded.term_count(), plain.term_count() + 1);
+}
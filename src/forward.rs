@@ -0,0 +1,152 @@
+//! The forward index: the terms of each document with their frequencies, so that similarity
+//! and removal need not re-analyze the stored content.
+
+use crate::index::InvertedIndex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Each distinct term of `words` with the number of times it occurs, in ascending term order.
+pub(crate) fn count_terms<'a>(words: impl IntoIterator<Item = &'a str>) -> Vec<(String, u32)> {
+    let mut counts: HashMap<&str, u32> = HashMap::new();
+    for word in words {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    let mut terms: Vec<(String, u32)> = counts
+        .into_iter()
+        .map(|(term, count)| (term.to_string(), count))
+        .collect();
+    terms.sort_unstable();
+    terms
+}
+
+impl InvertedIndex {
+    /// The terms indexed for a document and how often each occurs in it.
+    ///
+    /// # Returns
+    /// `(term, frequency)` pairs in ascending term order, without stop words and
+    /// [capped](crate::IndexBuilder::max_postings) or [removed](Self::remove_term) terms.
+    /// `None` if `doc_id` is not indexed or the
+    /// [forward index](crate::IndexBuilder::forward_index) is disabled.
+    pub fn doc_terms(&self, doc_id: usize) -> Option<impl Iterator<Item = (&str, u32)>> {
+        if !self.forward_index {
+            return None;
+        }
+        let doc = self.documents.get(&doc_id)?;
+        Some(doc.terms.iter().map(|(term, freq)| (term.as_str(), *freq)))
+    }
+
+    /// The length of a document as used for ranking: the number of its tokens, stop words
+    /// left out. `None` if `doc_id` is not indexed.
+    pub fn doc_len(&self, doc_id: usize) -> Option<usize> {
+        self.documents.get(&doc_id).map(|doc| doc.length)
+    }
+
+    /// Drops `term` from the forward entries of the documents in its posting list.
+    pub(crate) fn forget_term(&mut self, term: &str, doc_ids: impl IntoIterator<Item = usize>) {
+        if !self.forward_index {
+            return;
+        }
+        for id in doc_ids {
+            let Some(doc) = self.documents.get_mut(&id) else {
+                continue;
+            };
+            if let Ok(i) = doc
+                .terms
+                .binary_search_by(|(other, _)| other.as_str().cmp(term))
+            {
+                Arc::make_mut(doc).terms.remove(i);
+            }
+        }
+    }
+
+    /// Fills in the forward entries of every document from the posting lists, for indexes
+    /// built without them.
+    pub(crate) fn rebuild_forward_index(&mut self) {
+        let mut terms: HashMap<usize, Vec<(String, u32)>> = HashMap::new();
+        for (term, postings) in &self.indexes {
+            for posting in postings.iter() {
+                terms
+                    .entry(posting.doc_id)
+                    .or_default()
+                    .push((term.clone(), posting.term_freq() as u32));
+            }
+        }
+        for (id, mut terms) in terms {
+            terms.sort_unstable();
+            if let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) {
+                doc.terms = terms;
+            }
+        }
+    }
+}
+
+#[test]
+fn doc_terms_test() {
+    let mut index = InvertedIndex::builder().stop_words(["is"]).build();
+    index.add(1, "Rust is fast, rust is safe");
+    index.add(2, "");
+    let terms = |index: &InvertedIndex, id| -> Option<Vec<(String, u32)>> {
+        Some(
+            index
+                .doc_terms(id)?
+                .map(|(term, freq)| (term.to_string(), freq))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        terms(&index, 1),
+        Some(vec![
+            ("fast".to_string(), 1),
+            ("rust".to_string(), 2),
+            ("safe".to_string(), 1)
+        ])
+    );
+    assert_eq!(index.doc_len(1), Some(4));
+    assert_eq!(terms(&index, 2), Some(Vec::new()));
+    assert_eq!(index.doc_len(2), Some(0));
+    assert_eq!(terms(&index, 3), None);
+    assert_eq!(index.doc_len(3), None);
+
+    index.update(1, "slow rust").unwrap();
+    assert_eq!(
+        terms(&index, 1),
+        Some(vec![("rust".to_string(), 1), ("slow".to_string(), 1)])
+    );
+    index.add(3, "rust again");
+    index.remove_term("rust");
+    assert_eq!(terms(&index, 1), Some(vec![("slow".to_string(), 1)]));
+    assert_eq!(terms(&index, 3), Some(vec![("again".to_string(), 1)]));
+
+    // Removing by the forward entries leaves the same postings as a full scan.
+    let mut forward = InvertedIndex::new();
+    let mut scanned = InvertedIndex::builder().forward_index(false).build();
+    for index in [&mut forward, &mut scanned] {
+        index.add(1, "rust and go");
+        index.add(2, "go and python");
+        index.add(3, "rust only");
+        index.remove(1);
+        index.remove(3);
+    }
+    assert_eq!(forward.indexes, scanned.indexes);
+    assert!(!forward.indexes.contains_key("rust"));
+    assert!(scanned.doc_terms(2).is_none());
+    assert_eq!(scanned.doc_len(2), Some(3));
+    assert!(forward.memory_usage().forward_index > 0);
+    assert_eq!(scanned.memory_usage().forward_index, 0);
+    assert_eq!(forward.stats().forward_index_entries, 3);
+}
+
+#[test]
+fn forward_index_cap_test() {
+    let mut index = InvertedIndex::builder().max_postings(1).build();
+    index.add(1, "common rare");
+    index.add(2, "common other");
+    assert_eq!(
+        index.doc_terms(1).unwrap().collect::<Vec<_>>(),
+        vec![("rare", 1)]
+    );
+    assert_eq!(
+        index.doc_terms(2).unwrap().collect::<Vec<_>>(),
+        vec![("other", 1)]
+    );
+}
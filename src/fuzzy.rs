@@ -0,0 +1,78 @@
+//! Approximate term matching by edit distance.
+
+use crate::index::InvertedIndex;
+use std::cmp::Reverse;
+
+impl InvertedIndex {
+    /// The indexed term closest to `term`, to offer as "Did you mean …?" after a query
+    /// found nothing.
+    ///
+    /// # Returns
+    /// The vocabulary term with the smallest Levenshtein (edit) distance to the
+    /// analyzed `term`, preferring the one in more documents on a tie, then the
+    /// alphabetically first. `None` if `term` analyzes to nothing or no term is within
+    /// 1 edit for words of up to 3 characters, 2 for up to 7 and 3 beyond.
+    ///
+    /// # Notes
+    /// Suggestions are given in the most frequent original
+    /// [spelling](crate::IndexBuilder::surface_forms) of the term, or as indexed, so
+    /// lowercased and, with stemming on, stemmed, when spellings are not kept.
+    pub fn suggest(&self, term: &str) -> Option<String> {
+        let term = self.analyze_term(term)?;
+        let len = term.chars().count();
+        let max_distance = (len / 4 + 1).min(3);
+        self.indexes
+            .iter()
+            // Phrase terms are not words to suggest.
+            .filter(|(candidate, _)| !candidate.contains(' '))
+            .filter(|(candidate, _)| candidate.chars().count().abs_diff(len) <= max_distance)
+            .filter_map(|(candidate, postings)| {
+                let distance = levenshtein(&term, candidate);
+                let doc_freq = self.live_len(postings);
+                (distance <= max_distance && doc_freq > 0)
+                    .then_some((distance, doc_freq, candidate))
+            })
+            .min_by_key(|&(distance, doc_freq, candidate)| (distance, Reverse(doc_freq), candidate))
+            .map(|(_, _, candidate)| self.display_form(candidate).to_string())
+    }
+}
+
+/// The number of single-character insertions, deletions and substitutions turning `a`
+/// into `b`.
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[test]
+fn levenshtein_test() {
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(levenshtein("", "abc"), 3);
+    assert_eq!(levenshtein("rust", "rust"), 0);
+    assert_eq!(levenshtein("héllo", "hello"), 1);
+}
+
+#[test]
+fn suggest_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Programming in Rust");
+    index.add(2, "Rust programs and dust");
+    index.add(3, "dust everywhere");
+
+    assert_eq!(index.suggest("programing").as_deref(), Some("Programming"));
+    assert_eq!(index.suggest("rust").as_deref(), Some("Rust"));
+    // "must" is one edit from both, "dust" and "rust" are in two documents, "dust" sorts first.
+    assert_eq!(index.suggest("must").as_deref(), Some("dust"));
+    assert_eq!(index.suggest("xyzzy"), None);
+    assert_eq!(index.suggest("!!"), None);
+}
@@ -0,0 +1,559 @@
+use colored::{Color, Colorize};
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// How highlighted matches look, mapped onto the ANSI styles of the `colored` crate.
+///
+/// The codes are only emitted when `colored` decides the output supports them, see
+/// [`colored::control::ShouldColorize`]: by default not when stdout is not a terminal or
+/// `NO_COLOR` is set. Highlighting then leaves the content as it is. For output that never
+/// contains them, see [`IndexBuilder::no_color`](crate::IndexBuilder::no_color).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighlightStyle {
+    pub color: Color,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+impl Default for HighlightStyle {
+    /// Plain purple, the style of [`highlight`].
+    fn default() -> Self {
+        Self {
+            color: Color::Magenta,
+            bold: false,
+            underline: false,
+        }
+    }
+}
+
+impl HighlightStyle {
+    /// `text` wrapped in this style's ANSI codes.
+    pub fn paint(&self, text: &str) -> String {
+        let mut painted = text.color(self.color);
+        if self.bold {
+            painted = painted.bold();
+        }
+        if self.underline {
+            painted = painted.underline();
+        }
+        painted.to_string()
+    }
+}
+
+/// Which occurrences of a term get highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighlightMode {
+    /// Every occurrence, including inside longer words ("rust" in "trustworthy").
+    #[default]
+    Substring,
+    /// Only occurrences that form a whole word, using the same word boundaries as `tokenize`.
+    WholeWord,
+}
+
+/// A term's highlighting pattern compiled once, to apply to many documents.
+///
+/// The free functions like [`highlight`] compile their pattern on every call, which
+/// dominates highlighting a long result list. The index's own highlighting queries build
+/// one `Highlighter` per query.
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    regex: Regex,
+    mode: HighlightMode,
+    style: Option<HighlightStyle>,
+    limit: Option<usize>,
+}
+
+impl Highlighter {
+    /// Compiles `term`, a case-insensitive regular expression, to highlight every
+    /// occurrence in the default style.
+    pub fn new(term: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: term_regex(term)?,
+            mode: HighlightMode::Substring,
+            style: Some(HighlightStyle::default()),
+            limit: None,
+        })
+    }
+
+    /// Compiles a pattern matching any of `terms`, preferring the longest at a position, so
+    /// that highlighting one term never touches the codes inserted for another. Without
+    /// terms it highlights nothing.
+    pub fn for_terms(terms: &[&str]) -> Result<Self, regex::Error> {
+        // Longer terms first, so that "programming" wins over "program" at the same position.
+        let mut terms = terms.to_vec();
+        terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+        let pattern = if terms.is_empty() {
+            // A class no character belongs to.
+            r"[^\s\S]".to_string()
+        } else {
+            format!("(?:{})", terms.join("|"))
+        };
+        Self::new(&pattern)
+    }
+
+    /// Highlights the occurrences selected by `mode` instead of all of them.
+    pub fn mode(mut self, mode: HighlightMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Paints the occurrences in `style`, or leaves content as it is with `None`.
+    pub fn style(mut self, style: Option<HighlightStyle>) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Highlights only the first `limit` occurrences in each content and leaves the rest
+    /// plain, so that a term occurring hundreds of times does not bloat the result. `None`
+    /// highlights all of them and `Some(0)` none.
+    pub fn max_highlights(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Highlights `content`, borrowing it unchanged when nothing is highlighted or color is
+    /// disabled.
+    pub fn highlight<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        let Some(style) = self.style.as_ref().filter(|_| colorize()) else {
+            return Cow::Borrowed(content);
+        };
+        let paint = |caps: &regex::Captures| style.paint(&caps[0]);
+        match (self.mode, self.limit) {
+            (_, Some(0)) => Cow::Borrowed(content),
+            (HighlightMode::Substring, None) => self.regex.replace_all(content, paint),
+            (HighlightMode::Substring, Some(limit)) => self.regex.replacen(content, limit, paint),
+            (HighlightMode::WholeWord, limit) => {
+                let mut matches = self
+                    .regex
+                    .find_iter(content)
+                    .filter(|m| is_whole_word(content, m.start(), m.end()))
+                    .take(limit.unwrap_or(usize::MAX))
+                    .peekable();
+                if matches.peek().is_none() {
+                    return Cow::Borrowed(content);
+                }
+                let mut highlighted = String::with_capacity(content.len());
+                let mut last = 0;
+                for m in matches {
+                    highlighted.push_str(&content[last..m.start()]);
+                    highlighted.push_str(&style.paint(m.as_str()));
+                    last = m.end();
+                }
+                highlighted.push_str(&content[last..]);
+                Cow::Owned(highlighted)
+            }
+        }
+    }
+
+    /// Like [`highlight`](Self::highlight), but tags the n-th occurrence with `[n]`, see
+    /// [`highlight_numbered`]. Without a style or with color disabled the occurrences are
+    /// only numbered.
+    ///
+    /// # Returns
+    /// The highlighted content and the number of highlighted occurrences.
+    pub fn highlight_numbered(&self, content: &str) -> (String, usize) {
+        let mut count = 0;
+        let number = |caps: &regex::Captures| {
+            count += 1;
+            match self.style.as_ref().filter(|_| colorize()) {
+                Some(style) => format!("{}[{}]", style.paint(&caps[0]), count),
+                None => format!("{}[{}]", &caps[0], count),
+            }
+        };
+        let highlighted = match self.limit {
+            Some(0) => Cow::Borrowed(content),
+            Some(limit) => self.regex.replacen(content, limit, number),
+            None => self.regex.replace_all(content, number),
+        };
+        (highlighted.into_owned(), count)
+    }
+}
+
+/// Paints the byte ranges `spans` of `content`, which must be ascending, non-overlapping and
+/// on character boundaries. Empty spans are skipped, and without a style, color or any span
+/// `content` is borrowed as it is.
+pub(crate) fn highlight_spans<'a>(
+    content: &'a str,
+    spans: &[(usize, usize)],
+    style: Option<&HighlightStyle>,
+) -> Cow<'a, str> {
+    let Some(style) = style.filter(|_| colorize()) else {
+        return Cow::Borrowed(content);
+    };
+    let mut spans = spans.iter().filter(|(start, end)| start < end).peekable();
+    if spans.peek().is_none() {
+        return Cow::Borrowed(content);
+    }
+    let mut highlighted = String::with_capacity(content.len());
+    let mut last = 0;
+    for &(start, end) in spans {
+        highlighted.push_str(&content[last..start]);
+        highlighted.push_str(&style.paint(&content[start..end]));
+        last = end;
+    }
+    highlighted.push_str(&content[last..]);
+    Cow::Owned(highlighted)
+}
+
+/// Highlights the byte `ranges` of `content` with the color of [`highlight`], e.g. the
+/// token offsets of a phrase match.
+///
+/// The ranges may come in any order. Overlapping and adjacent ones are merged first, so
+/// each stretch of highlighted text gets a single pair of color codes and the output is
+/// built in one pass. Empty ranges are skipped.
+///
+/// # Panics
+/// If a range ends past `content` or does not start and end on a character boundary.
+pub fn highlight_ranges(content: &str, ranges: &[Range<usize>]) -> String {
+    let mut sorted: Vec<&Range<usize>> = ranges.iter().filter(|range| !range.is_empty()).collect();
+    sorted.sort_unstable_by_key(|range| range.start);
+    let mut spans: Vec<(usize, usize)> = Vec::with_capacity(sorted.len());
+    for range in sorted {
+        match spans.last_mut() {
+            Some(last) if last.1 >= range.start => last.1 = last.1.max(range.end),
+            _ => spans.push((range.start, range.end)),
+        }
+    }
+    highlight_spans(content, &spans, Some(&HighlightStyle::default())).into_owned()
+}
+
+/// Highlights all occurrences of `term` in `content` with a <font color"purple">purple</font> color.
+///
+/// When `term` does not occur in `content`, or color is disabled because stdout is not a
+/// terminal or `NO_COLOR` is set, the content is returned borrowed without allocating.
+///
+/// # Notes
+/// `term` is matched literally, ignoring case, so characters like `(` or `*` need no
+/// escaping. To highlight a regular expression, use a [`Highlighter`].
+pub fn highlight<'a>(term: &str, content: &'a str) -> Cow<'a, str> {
+    highlight_with_mode(term, content, HighlightMode::Substring)
+}
+
+/// Like [`highlight`], but only highlights the occurrences selected by `mode`.
+pub fn highlight_with_mode<'a>(term: &str, content: &'a str, mode: HighlightMode) -> Cow<'a, str> {
+    literal(&[term]).mode(mode).highlight(content)
+}
+
+/// Like [`highlight`], but paints the occurrences in `style`.
+pub fn highlight_styled<'a>(term: &str, content: &'a str, style: &HighlightStyle) -> Cow<'a, str> {
+    literal(&[term]).style(Some(*style)).highlight(content)
+}
+
+/// Whether `colored` emits ANSI codes, following its override, `NO_COLOR` and whether
+/// stdout is a terminal.
+fn colorize() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// A highlighter for the literal text of any of `terms`, as the free functions take them.
+fn literal(terms: &[&str]) -> Highlighter {
+    let escaped: Vec<String> = terms.iter().map(|term| regex::escape(term)).collect();
+    let escaped: Vec<&str> = escaped.iter().map(String::as_str).collect();
+    Highlighter::for_terms(&escaped).expect("escaped terms are a valid pattern")
+}
+
+/// Compiles the case-insensitive pattern the highlighters search for.
+fn term_regex(term: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!(r"(?i){}", term))
+}
+
+/// Like [`highlight`], but tags the n-th highlighted occurrence with `[n]`, counting from 1,
+/// e.g. for a "jump to match" UI.
+///
+/// # Returns
+/// The highlighted content and the number of occurrences.
+pub fn highlight_numbered(term: &str, content: &str) -> (String, usize) {
+    literal(&[term]).highlight_numbered(content)
+}
+
+/// Highlights all occurrences of any of `terms` in `content`, in a single pass so that
+/// highlighting one term never touches the color codes inserted for another. Like
+/// [`highlight`], the terms are matched literally.
+pub fn highlight_terms<'a>(terms: &[&str], content: &'a str) -> Cow<'a, str> {
+    literal(terms).highlight(content)
+}
+
+/// Bytes of context kept on each side of the first match in a [`snippet`].
+const SNIPPET_CONTEXT: usize = 60;
+
+/// The part of `content` around the first occurrence of any of `words`, on one line.
+///
+/// The window keeps [`SNIPPET_CONTEXT`] bytes before the match and twice that after it,
+/// collapses whitespace and marks cut ends with `...`. Without a match it starts at the
+/// beginning of `content`.
+pub fn snippet(content: &str, words: &[&str]) -> String {
+    let escaped: Vec<String> = words.iter().map(|word| regex::escape(word)).collect();
+    let start = Regex::new(&format!("(?i){}", escaped.join("|")))
+        .ok()
+        .filter(|_| !words.is_empty())
+        .and_then(|regex| regex.find(content))
+        .map_or(0, |m| m.start());
+    let mut from = start.saturating_sub(SNIPPET_CONTEXT);
+    while !content.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (start + 2 * SNIPPET_CONTEXT).min(content.len());
+    while !content.is_char_boundary(to) {
+        to += 1;
+    }
+    let mut snippet = content[from..to]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if from > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if to < content.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+/// `highlighted` without the ANSI escape sequences highlighting inserted, e.g. to return
+/// the plain text of a [`highlight`]ed query result.
+///
+/// Every control sequence of the form `ESC [ ... final byte` is removed, whatever style
+/// produced it, and the rest of the text is kept as it is.
+pub fn strip_highlight(highlighted: &str) -> String {
+    let mut plain = String::with_capacity(highlighted.len());
+    let mut chars = highlighted.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\u{1b}' || chars.peek() != Some(&'[') {
+            plain.push(ch);
+            continue;
+        }
+        chars.next();
+        // Parameter and intermediate bytes up to the final byte, '@' to '~'.
+        for ch in chars.by_ref() {
+            if ('@'..='~').contains(&ch) {
+                break;
+            }
+        }
+    }
+    plain
+}
+
+/// Whether `content[start..end]` is neither preceded nor followed by an alphanumeric character.
+fn is_whole_word(content: &str, start: usize, end: usize) -> bool {
+    let before = content[..start].chars().next_back();
+    let after = content[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+#[test]
+fn highlight_test() {
+    // `colored` disables itself when stdout is not a terminal, e.g. under `cargo test`.
+    let _color = crate::test_util::color_guard(true);
+    assert_eq!(
+        highlight("programming", "I like programming with Rust Programming"),
+        "I like \u{1b}[35mprogramming\u{1b}[0m with Rust \u{1b}[35mProgramming\u{1b}[0m"
+    );
+}
+
+#[test]
+fn highlight_no_color_test() {
+    let _color = crate::test_util::color_guard(false);
+    let content = "I like programming with Rust Programming";
+    let highlighted = highlight("programming", content);
+    assert!(matches!(highlighted, Cow::Borrowed(_)));
+    assert_eq!(highlighted, content);
+    assert_eq!(highlight_terms(&["rust", "like"], content), content);
+    assert_eq!(
+        highlight_numbered("rust", content),
+        ("I like programming with Rust[1] Programming".to_string(), 1)
+    );
+    assert_eq!(highlight_ranges(content, &[0..1, 7..18]), content);
+}
+
+#[test]
+fn highlight_ranges_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "Rust is fast and safe";
+    let highlighted = highlight_ranges(content, &[5..12, 0..7, 17..21, 16..17, 3..3]);
+    assert_eq!(
+        highlighted,
+        "\u{1b}[35mRust is fast\u{1b}[0m and\u{1b}[35m safe\u{1b}[0m"
+    );
+    assert_eq!(highlighted.matches("\u{1b}[35m").count(), 2);
+    assert_eq!(strip_highlight(&highlighted), content);
+    assert_eq!(highlight_ranges(content, &[]), content);
+}
+
+#[test]
+fn highlight_styled_test() {
+    let _color = crate::test_util::color_guard(true);
+    let style = HighlightStyle {
+        color: Color::Red,
+        bold: true,
+        underline: true,
+    };
+    assert_eq!(
+        highlight_styled("rust", "Rust is fun", &style),
+        "\u{1b}[1;4;31mRust\u{1b}[0m is fun"
+    );
+    let style = HighlightStyle {
+        color: Color::Green,
+        bold: true,
+        underline: false,
+    };
+    assert_eq!(
+        highlight_styled("fun", "Rust is fun", &style),
+        "Rust is \u{1b}[1;32mfun\u{1b}[0m"
+    );
+    assert_eq!(
+        highlight_styled("rust", "Rust", &HighlightStyle::default()),
+        highlight("rust", "Rust")
+    );
+}
+
+#[test]
+fn highlighter_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "Rust, trustworthy rust";
+    let highlighter = Highlighter::new("rust").unwrap();
+    for _ in 0..2 {
+        assert_eq!(highlighter.highlight(content), highlight("rust", content));
+    }
+    assert_eq!(
+        highlighter.highlight_numbered(content),
+        highlight_numbered("rust", content)
+    );
+    let whole = highlighter.clone().mode(HighlightMode::WholeWord);
+    assert_eq!(
+        whole.highlight(content),
+        highlight_with_mode("rust", content, HighlightMode::WholeWord)
+    );
+    assert!(matches!(
+        highlighter.clone().style(None).highlight(content),
+        Cow::Borrowed(_)
+    ));
+    let capped = highlighter.clone().max_highlights(Some(1));
+    assert_eq!(
+        capped
+            .clone()
+            .mode(HighlightMode::WholeWord)
+            .highlight(content),
+        "\u{1b}[35mRust\u{1b}[0m, trustworthy rust"
+    );
+    assert_eq!(
+        capped.clone().style(None).highlight_numbered(content),
+        ("Rust[1], trustworthy rust".to_string(), 1)
+    );
+    assert!(matches!(
+        capped.max_highlights(Some(0)).highlight(content),
+        Cow::Borrowed(_)
+    ));
+    assert_eq!(
+        highlighter.style(None).highlight_numbered(content).0,
+        "Rust[1], trust[2]worthy rust[3]"
+    );
+    assert!(Highlighter::new("f(x").is_err());
+
+    let terms = Highlighter::for_terms(&["program", "programming"]).unwrap();
+    assert_eq!(
+        terms.highlight("programming"),
+        highlight_terms(&["program", "programming"], "programming")
+    );
+    let nothing = Highlighter::for_terms(&[]).unwrap();
+    assert!(matches!(nothing.highlight(content), Cow::Borrowed(_)));
+}
+
+#[test]
+fn highlight_without_match_borrows_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "Rust is fast";
+    let highlighted = highlight("python", content);
+    assert!(matches!(highlighted, Cow::Borrowed(s) if std::ptr::eq(s, content)));
+    assert!(matches!(highlight("rust", content), Cow::Owned(_)));
+}
+
+#[test]
+fn highlight_whole_word_test() {
+    let _color = crate::test_util::color_guard(true);
+    assert_eq!(
+        highlight_with_mode("rust", "trust rust, Rust!", HighlightMode::WholeWord),
+        "trust \u{1b}[35mrust\u{1b}[0m, \u{1b}[35mRust\u{1b}[0m!"
+    );
+    assert!(matches!(
+        highlight_with_mode("rust", "trustworthy", HighlightMode::WholeWord),
+        Cow::Borrowed("trustworthy")
+    ));
+}
+
+#[test]
+fn highlight_numbered_test() {
+    let _color = crate::test_util::color_guard(false);
+    assert_eq!(
+        highlight_numbered("rust", "Rust, rust and trust"),
+        ("Rust[1], rust[2] and trust[3]".to_string(), 3)
+    );
+    assert_eq!(highlight_numbered("go", "Rust"), ("Rust".to_string(), 0));
+}
+
+#[test]
+fn highlight_terms_test() {
+    let _color = crate::test_util::color_guard(true);
+    assert_eq!(
+        highlight_terms(&["rust", "is"], "Rust is fun"),
+        "\u{1b}[35mRust\u{1b}[0m \u{1b}[35mis\u{1b}[0m fun"
+    );
+    assert_eq!(
+        highlight_terms(&["program", "programming"], "programming"),
+        "\u{1b}[35mprogramming\u{1b}[0m"
+    );
+    assert!(matches!(
+        highlight_terms(&[], "Rust"),
+        Cow::Borrowed("Rust")
+    ));
+}
+
+#[test]
+fn highlight_literal_terms_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "f(x) = a[b] * 2";
+    assert_eq!(
+        highlight("(", content),
+        "f\u{1b}[35m(\u{1b}[0mx) = a[b] * 2"
+    );
+    assert_eq!(
+        highlight_styled("a[b", content, &HighlightStyle::default()),
+        "f(x) = \u{1b}[35ma[b\u{1b}[0m] * 2"
+    );
+    assert_eq!(highlight_numbered("*", content).1, 1);
+    assert_eq!(
+        highlight_terms(&["(", "*"], content),
+        "f\u{1b}[35m(\u{1b}[0mx) = a[b] \u{1b}[35m*\u{1b}[0m 2"
+    );
+    assert!(matches!(highlight(".", content), Cow::Borrowed(_)));
+}
+
+#[test]
+fn strip_highlight_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "Rust, rust and trustworthy RUST: é 🦀 [0m";
+    let highlighted = highlight("rust", content);
+    assert_ne!(highlighted, content);
+    assert_eq!(strip_highlight(&highlighted), content);
+
+    let style = HighlightStyle {
+        color: Color::TrueColor { r: 1, g: 2, b: 3 },
+        bold: true,
+        underline: true,
+    };
+    let highlighted = highlight_styled("rust", content, &style);
+    assert_eq!(strip_highlight(&highlighted), content);
+    // A lone escape that starts no sequence is kept.
+    assert_eq!(strip_highlight("a\u{1b}b"), "a\u{1b}b");
+}
+
+#[test]
+fn snippet_test() {
+    assert_eq!(snippet("Rust is\n  fast", &["fast"]), "Rust is fast");
+    let content = format!("{} needle {}", "x ".repeat(50), "y ".repeat(100));
+    let cut = snippet(&content, &["NEEDLE"]);
+    assert!(cut.starts_with("...x x"), "{cut}");
+    assert!(cut.ends_with("y y..."), "{cut}");
+    assert!(cut.contains(" needle "));
+    assert_eq!(snippet("é🦀é", &["x"]), "é🦀é");
+}
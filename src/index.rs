@@ -0,0 +1,1561 @@
+use crate::analysis::{Analyzer, TermBuffer};
+use crate::autocomplete::TermDictionary;
+use crate::cache::QueryCache;
+use crate::compress::ContentCache;
+use crate::docstore::{Content, DocStore};
+use crate::error::IndexError;
+use crate::forward::count_terms;
+use crate::highlight::{HighlightMode, HighlightStyle, Highlighter, highlight_spans};
+use crate::ngram::NgramIndex;
+use crate::numeric::NumericField;
+use crate::oplog::{LogEntry, OpLog};
+use crate::postings::{Intersection, Posting, intersect_sorted, union_sorted};
+use crate::query::QueryError;
+use crate::ranking::{Combine, Scorer};
+use crate::search::{SearchHit, SearchOptions, idf, paginate, sort_hits};
+use crate::surface::SurfaceForms;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Define a structure to represent documents for easy access and management.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Document {
+    pub(crate) id: usize,
+    /// The text, read with [`InvertedIndex::content`].
+    pub(crate) content: Content,
+    /// Number of tokens the content was split into.
+    pub(crate) length: usize,
+    /// The file the content was read from, for documents added from a directory.
+    pub(crate) path: Option<PathBuf>,
+    /// For documents added with [`add_fields`](InvertedIndex::add_fields), each field name
+    /// with the position of the field's first token, in order. Empty otherwise.
+    pub(crate) fields: Vec<(String, usize)>,
+    /// The byte range in `content` of the token at each position, stop words included, so
+    /// that a posting's positions locate its occurrences. Positions between fields have an
+    /// empty range.
+    pub(crate) offsets: Vec<(usize, usize)>,
+    /// The metadata given to [`add_with_meta`](InvertedIndex::add_with_meta), such as the
+    /// author. Empty otherwise.
+    pub(crate) metadata: HashMap<String, String>,
+    /// Each indexed term of the document with its frequency, in ascending term order, see
+    /// [`doc_terms`](InvertedIndex::doc_terms). Empty with the forward index disabled.
+    pub(crate) terms: Vec<(String, u32)>,
+}
+
+/// The byte ranges in `doc` of the occurrences of a term at `positions` spanning `words`
+/// tokens each, merged where they overlap.
+pub(crate) fn occurrence_spans(
+    doc: &Document,
+    positions: &[usize],
+    words: usize,
+) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::with_capacity(positions.len());
+    for &position in positions {
+        let Some(&(start, end)) = doc.offsets.get(position) else {
+            continue;
+        };
+        let last = position + words.max(1) - 1;
+        let end = doc.offsets.get(last).map_or(end, |&(_, end)| end);
+        match spans.last_mut() {
+            Some(previous) if previous.1 >= start => previous.1 = previous.1.max(end),
+            _ => spans.push((start, end)),
+        }
+    }
+    spans
+}
+
+/// The outcome of [`InvertedIndex::query_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryStatus {
+    /// The term was never indexed, or every document with it was removed and the term
+    /// dropped, so a spelling suggestion may help.
+    NotInVocabulary,
+    /// The term is known but no stored document contains it: it is a stop word or a
+    /// [capped](crate::IndexBuilder::max_postings) term, or its documents were soft-deleted.
+    NoMatchingDocuments,
+    /// The highlighted contents [`query`](InvertedIndex::query) returns, never empty.
+    Matched(Vec<String>),
+}
+
+/// The InvertedIndex struct manages a set of indexed documents.
+pub struct InvertedIndex {
+    /// The in-memory index.
+    ///
+    /// key is the single `term` of the document's word tokenization.
+    /// value is the term's postings, sorted by document id. Shared with
+    /// [snapshots](Self::snapshot) until the term changes.
+    pub(crate) indexes: HashMap<String, Arc<Vec<Posting>>>,
+
+    /// Stores a mapping of the document id to the original document content, shared with
+    /// snapshots like the postings.
+    pub(crate) documents: HashMap<usize, Arc<Document>>,
+
+    /// Sum of the token counts of all stored documents.
+    pub(crate) total_tokens: usize,
+
+    /// Turns document content and queries into terms.
+    pub(crate) analyzer: Analyzer,
+
+    /// Total postings entries above which a multi-term ranked query scores its terms on
+    /// separate threads.
+    #[cfg(feature = "parallel")]
+    pub(crate) parallel_threshold: usize,
+
+    /// Incremented by every change to the documents or postings, so that cached results can
+    /// tell they are stale.
+    pub(crate) generation: u64,
+
+    /// Recent ranked query results, if enabled on the builder.
+    pub(crate) cache: Option<Mutex<QueryCache>>,
+
+    /// How query results highlight matches.
+    pub(crate) highlight_style: HighlightStyle,
+
+    /// Leave query results free of ANSI codes, whatever the terminal supports.
+    pub(crate) no_color: bool,
+
+    /// Most occurrences highlighted in each query result, `None` for all of them.
+    pub(crate) max_highlights: Option<usize>,
+
+    /// With soft deletion on, the deleted fraction of all documents above which
+    /// [`remove`](Self::remove) compacts the postings. `None` deletes immediately.
+    pub(crate) compact_ratio: Option<f64>,
+
+    /// Soft-deleted documents whose postings have not been compacted away yet.
+    pub(crate) tombstones: HashSet<usize>,
+
+    /// The sorted vocabulary [`autocomplete`](Self::autocomplete) searches by prefix.
+    pub(crate) dictionary: Mutex<TermDictionary>,
+
+    /// The change log opened with [`open_log`](Self::open_log), if any.
+    pub(crate) log: Option<OpLog>,
+
+    /// The trigrams of every term, if enabled on the builder.
+    pub(crate) ngrams: Option<NgramIndex>,
+
+    /// Most terms a [`query_regex`](Self::query_regex) pattern may match.
+    pub(crate) regex_term_limit: usize,
+
+    /// Most documents [`cooccurring_terms`](Self::cooccurring_terms) reads for one term.
+    pub(crate) cooccurrence_sample: usize,
+
+    /// The most documents a term is indexed for, if capped on the builder.
+    pub(crate) max_postings: Option<usize>,
+
+    /// Terms that exceeded `max_postings` and are treated as stop words since.
+    pub(crate) capped_terms: HashSet<String>,
+
+    /// The file holding the document contents, if configured on the builder. Shared with
+    /// snapshots.
+    pub(crate) store: Option<Arc<DocStore>>,
+
+    /// Keep the terms of each document in it, see [`doc_terms`](Self::doc_terms).
+    pub(crate) forward_index: bool,
+
+    /// Keep the document contents, see
+    /// [`IndexBuilder::store_content`](crate::IndexBuilder::store_content).
+    pub(crate) keep_content: bool,
+
+    /// The content length from which contents are kept compressed, `None` if disabled on
+    /// the builder.
+    pub(crate) compress_above: Option<usize>,
+
+    /// Recently decompressed contents.
+    pub(crate) decompressed: Mutex<ContentCache>,
+
+    /// The original spellings of each term, `None` if disabled on the builder.
+    pub(crate) surface_forms: Option<SurfaceForms>,
+
+    /// The values of each numeric field, see [`add_numeric`](Self::add_numeric).
+    pub(crate) numeric: HashMap<String, NumericField>,
+}
+
+impl Default for InvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InvertedIndex {
+    pub fn new() -> InvertedIndex {
+        Self {
+            indexes: HashMap::new(),
+            documents: HashMap::new(),
+            total_tokens: 0,
+            analyzer: Analyzer::default(),
+            #[cfg(feature = "parallel")]
+            parallel_threshold: crate::builder::DEFAULT_PARALLEL_THRESHOLD,
+            generation: 0,
+            cache: None,
+            highlight_style: HighlightStyle::default(),
+            no_color: false,
+            max_highlights: None,
+            compact_ratio: None,
+            tombstones: HashSet::new(),
+            dictionary: Mutex::default(),
+            log: None,
+            ngrams: None,
+            regex_term_limit: crate::builder::DEFAULT_REGEX_TERM_LIMIT,
+            cooccurrence_sample: crate::builder::DEFAULT_COOCCURRENCE_SAMPLE,
+            max_postings: None,
+            capped_terms: HashSet::new(),
+            store: None,
+            forward_index: true,
+            keep_content: true,
+            compress_above: None,
+            decompressed: Mutex::default(),
+            surface_forms: Some(SurfaceForms::default()),
+            numeric: HashMap::new(),
+        }
+    }
+
+    /// Normalizes a single query term the same way `add` normalizes document words.
+    ///
+    /// A term made of several words is normalized word by word into the phrase term
+    /// [`add_with_phrases`](Self::add_with_phrases) indexes. Returns `None` for stop words,
+    /// which are never indexed, and for terms over the
+    /// [postings cap](crate::IndexBuilder::max_postings).
+    pub(crate) fn analyze_term(&self, term: &str) -> Option<String> {
+        self.analyzer
+            .analyze_phrase(term)
+            .filter(|term| !self.capped_terms.contains(term))
+    }
+
+    /// The style query results are highlighted in, `None` to leave them plain.
+    pub(crate) fn style(&self) -> Option<&HighlightStyle> {
+        (!self.no_color).then_some(&self.highlight_style)
+    }
+
+    /// The leading `spans` a query result highlights, see
+    /// [`IndexBuilder::max_highlights_per_doc`](crate::IndexBuilder::max_highlights_per_doc).
+    pub(crate) fn capped_spans<'s>(&self, spans: &'s [(usize, usize)]) -> &'s [(usize, usize)] {
+        let limit = self.max_highlights.unwrap_or(usize::MAX);
+        &spans[..spans.len().min(limit)]
+    }
+
+    /// Looks up the live posting list of a query term after analyzing it.
+    pub(crate) fn term_postings(&self, term: &str) -> Option<Cow<'_, [Posting]>> {
+        self.analyze_term(term)
+            .and_then(|term| self.live_postings(&term))
+    }
+
+    /// Adds a document to the index.
+    ///
+    /// # Parameters
+    /// - `doc_id`: An identifier for the document.
+    /// - `content`: The text content of the document.
+    ///
+    /// # Notes
+    /// This method processes the document by lowercasing and tokenizing the text,
+    /// then updates the index to include words found in this document.
+    /// Stop words and stemming are applied as configured on the [`IndexBuilder`](crate::IndexBuilder).
+    /// Adding a document with an id that is already indexed replaces it.
+    ///
+    /// # Returns
+    /// The number of distinct terms indexed for the document. Content without a single
+    /// searchable word, such as an empty or whitespace-only string or only stop words, gives
+    /// 0; the document is stored all the same.
+    pub fn add(&mut self, id: usize, content: &str) -> usize {
+        self.record(LogEntry::Add { id, content });
+        self.index_document(id, content.to_string(), &mut TermBuffer::default())
+    }
+
+    /// Indexes `content` under `id`, replacing any document with that id.
+    ///
+    /// `words` is scratch space for the analyzed terms, so that batch callers can reuse one
+    /// buffer for every document. Returns the number of distinct terms indexed.
+    pub(crate) fn index_document(
+        &mut self,
+        id: usize,
+        content: String,
+        words: &mut TermBuffer,
+    ) -> usize {
+        words.clear();
+        let mut offsets = Vec::new();
+        self.analyzer.analyze_into(&content, 0, words, &mut offsets);
+        let length = words.len();
+        self.index_terms(id, content, words, offsets, length)
+    }
+
+    /// Indexes already analyzed `(position, term)` pairs under `id`, storing `content` as the
+    /// document's text with the byte `offsets` of its tokens and replacing any document with
+    /// that id. Clears `words` and returns the number of distinct terms indexed.
+    ///
+    /// `length` is the document length used for ranking, which need not be `words.len()`
+    /// when extra terms were derived from the tokens.
+    pub(crate) fn index_terms(
+        &mut self,
+        id: usize,
+        content: String,
+        words: &mut TermBuffer,
+        offsets: Vec<(usize, usize)>,
+        length: usize,
+    ) -> usize {
+        self.remove_document(id);
+        // The postings of a soft-deleted document with this id would collide with the new ones.
+        if self.tombstones.remove(&id) {
+            self.purge(&HashSet::from([id]));
+        }
+        self.generation += 1;
+        let mut forward = if self.forward_index {
+            count_terms(words.iter().map(|(_, word)| word))
+        } else {
+            Vec::new()
+        };
+        if let Some(forms) = &mut self.surface_forms {
+            for (position, word) in words.iter() {
+                // Phrase terms have no spelling of their own.
+                if let Some(&(start, end)) = offsets.get(position)
+                    && !word.contains(' ')
+                    && !self.capped_terms.contains(word)
+                {
+                    forms.insert(word, &content[start..end]);
+                }
+            }
+        }
+        let mut terms = 0;
+        for (position, word) in words.iter() {
+            if self.capped_terms.contains(word) {
+                continue;
+            }
+            if let Some(max) = self.max_postings {
+                let postings = self
+                    .indexes
+                    .get(word)
+                    .map_or(&[][..], |postings| postings.as_slice());
+                if postings.len() >= max
+                    && postings
+                        .binary_search_by_key(&id, |posting| posting.doc_id)
+                        .is_err()
+                {
+                    self.cap_term(word.to_string());
+                    continue;
+                }
+            }
+            // Only a new term's key is allocated.
+            if !self.indexes.contains_key(word) {
+                if let Some(ngrams) = &mut self.ngrams {
+                    ngrams.insert(word);
+                }
+                self.indexes.insert(word.to_string(), Arc::default());
+            }
+            let postings = Arc::make_mut(self.indexes.get_mut(word).expect("inserted above"));
+            match postings.binary_search_by_key(&id, |posting| posting.doc_id) {
+                Ok(i) => postings[i].positions.push(position),
+                Err(i) => {
+                    terms += 1;
+                    postings.insert(
+                        i,
+                        Posting {
+                            doc_id: id,
+                            positions: vec![position],
+                        },
+                    );
+                }
+            }
+        }
+        words.clear();
+        if !self.capped_terms.is_empty() {
+            forward.retain(|(term, _)| !self.capped_terms.contains(term));
+        }
+
+        self.total_tokens += length;
+        self.documents.insert(
+            id,
+            Arc::new(Document {
+                id,
+                content: self.store_content(content),
+                length,
+                path: None,
+                fields: Vec::new(),
+                offsets: self.store_offsets(offsets),
+                metadata: HashMap::new(),
+                terms: forward,
+            }),
+        );
+        terms
+    }
+
+    /// Like [`add`](Self::add), but refuses to replace a document.
+    ///
+    /// # Returns
+    /// [`IndexError::DuplicateDocument`], leaving the index untouched, if a document with
+    /// this id is already indexed.
+    pub fn try_add(&mut self, id: usize, content: &str) -> Result<(), IndexError> {
+        if self.documents.contains_key(&id) {
+            return Err(IndexError::DuplicateDocument(id));
+        }
+        self.add(id, content);
+        Ok(())
+    }
+
+    /// Replaces the content of an already indexed document.
+    ///
+    /// # Returns
+    /// [`IndexError::DocumentNotFound`], leaving the index untouched, if no document has
+    /// this id.
+    pub fn update(&mut self, id: usize, content: &str) -> Result<(), IndexError> {
+        if !self.documents.contains_key(&id) {
+            return Err(IndexError::DocumentNotFound(id));
+        }
+        self.add(id, content);
+        Ok(())
+    }
+
+    /// Removes a document from the index.
+    ///
+    /// # Returns
+    /// `true` if the document was indexed. Terms left without any document are dropped.
+    ///
+    /// # Notes
+    /// This visits only the document's terms, found through the
+    /// [forward index](crate::IndexBuilder::forward_index), or scans the whole vocabulary
+    /// when that is disabled. With [soft deletion](crate::IndexBuilder::soft_delete)
+    /// only the content is dropped and the postings are skipped by queries until the next
+    /// [`compact`](Self::compact).
+    pub fn remove(&mut self, id: usize) -> bool {
+        if !self.documents.contains_key(&id) {
+            return false;
+        }
+        self.record(LogEntry::Remove { id });
+        self.remove_document(id)
+    }
+
+    /// Drops the postings of a term that would exceed the postings cap, and stops indexing it.
+    pub(crate) fn cap_term(&mut self, term: String) {
+        if let Some(postings) = self.indexes.remove(&term) {
+            if let Some(ngrams) = &mut self.ngrams {
+                ngrams.remove(&term);
+            }
+            self.forget_term(&term, postings.iter().map(|posting| posting.doc_id));
+        }
+        self.forget_surface_forms(&term);
+        self.capped_terms.insert(term);
+    }
+
+    /// Removes a document like [`remove`](Self::remove), without logging the change.
+    pub(crate) fn remove_document(&mut self, id: usize) -> bool {
+        let Some(doc) = self.documents.remove(&id) else {
+            return false;
+        };
+        self.total_tokens -= doc.length;
+        self.generation += 1;
+        self.count_surface_forms(&doc, false);
+        self.forget_numeric(id);
+        if let Some(ratio) = self.compact_ratio {
+            self.tombstone(id, ratio);
+            return true;
+        }
+        if self.forward_index {
+            for (term, _) in &doc.terms {
+                let Some(postings) = self.indexes.get_mut(term) else {
+                    continue;
+                };
+                if let Ok(i) = postings.binary_search_by_key(&id, |posting| posting.doc_id) {
+                    Arc::make_mut(postings).remove(i);
+                }
+                if postings.is_empty() {
+                    self.indexes.remove(term);
+                    if let Some(ngrams) = &mut self.ngrams {
+                        ngrams.remove(term);
+                    }
+                }
+            }
+            return true;
+        }
+        let ngrams = &mut self.ngrams;
+        self.indexes.retain(|term, postings| {
+            if let Ok(i) = postings.binary_search_by_key(&id, |posting| posting.doc_id) {
+                // Only the lists holding the document are copied away from snapshots.
+                Arc::make_mut(postings).remove(i);
+            }
+            if postings.is_empty()
+                && let Some(ngrams) = ngrams
+            {
+                ngrams.remove(term);
+            }
+            !postings.is_empty()
+        });
+        true
+    }
+
+    /// Makes a term unsearchable in every document, e.g. to purge an OCR artifact.
+    ///
+    /// `term` is normalized like a query term. The document contents and lengths are left as
+    /// they are, so only queries for the term change: they match nothing afterwards.
+    ///
+    /// # Returns
+    /// The number of documents the term appeared in, 0 if it was not indexed.
+    pub fn remove_term(&mut self, term: &str) -> usize {
+        let Some(term) = self.analyze_term(term) else {
+            return 0;
+        };
+        if !self.indexes.contains_key(&term) {
+            return 0;
+        }
+        self.record(LogEntry::RemoveTerm { term: &term });
+        self.remove_analyzed_term(&term)
+    }
+
+    /// Removes an analyzed term like [`remove_term`](Self::remove_term), without logging the
+    /// change.
+    pub(crate) fn remove_analyzed_term(&mut self, term: &str) -> usize {
+        let Some(postings) = self.indexes.remove(term) else {
+            return 0;
+        };
+        if let Some(ngrams) = &mut self.ngrams {
+            ngrams.remove(term);
+        }
+        self.forget_term(term, postings.iter().map(|posting| posting.doc_id));
+        self.forget_surface_forms(term);
+        self.generation += 1;
+        self.live_len(&postings)
+    }
+
+    /// Removes every document and term, keeping the configuration.
+    pub fn clear(&mut self) {
+        self.record(LogEntry::Clear);
+        self.indexes.clear();
+        self.documents.clear();
+        self.tombstones.clear();
+        self.capped_terms.clear();
+        if let Some(ngrams) = &mut self.ngrams {
+            ngrams.clear();
+        }
+        if let Some(forms) = &mut self.surface_forms {
+            forms.forms.clear();
+        }
+        self.numeric.clear();
+        self.total_tokens = 0;
+        self.generation += 1;
+        self.analyzer.stem_cache.clear();
+    }
+
+    /// Removes every document for which `f(doc_id, content)` returns `false`.
+    ///
+    /// Unlike calling [`remove`](Self::remove) for each document, the posting lists are only
+    /// scanned once, however many documents are dropped. Terms left without any document are
+    /// dropped too. `content` is empty for documents whose content is
+    /// [not stored](crate::IndexBuilder::store_content) or cannot be read.
+    ///
+    /// # Returns
+    /// The number of removed documents.
+    pub fn retain(&mut self, mut f: impl FnMut(usize, &str) -> bool) -> usize {
+        let removed: HashSet<usize> = self
+            .documents
+            .values()
+            .filter(|doc| !f(doc.id, &self.content(doc).unwrap_or_default()))
+            .map(|doc| doc.id)
+            .collect();
+        for &id in &removed {
+            self.record(LogEntry::Remove { id });
+        }
+        self.remove_documents(&removed)
+    }
+
+    /// Removes the documents of `removed` like [`retain`](Self::retain), without logging
+    /// the change.
+    ///
+    /// # Returns
+    /// The number of removed documents.
+    pub(crate) fn remove_documents(&mut self, removed: &HashSet<usize>) -> usize {
+        if removed.is_empty() {
+            return 0;
+        }
+        self.generation += 1;
+        for id in removed {
+            if let Some(doc) = self.documents.remove(id) {
+                self.total_tokens -= doc.length;
+                self.count_surface_forms(&doc, false);
+                self.forget_numeric(*id);
+            }
+        }
+        self.purge(removed);
+        removed.len()
+    }
+
+    /// Returns the stored content of a document, borrowed from the index or read from the
+    /// [document store](crate::IndexBuilder::document_store).
+    ///
+    /// # Returns
+    /// `None` if no document has this id, or its content cannot be read from the store.
+    pub fn get(&self, doc_id: usize) -> Option<Cow<'_, str>> {
+        self.content(self.documents.get(&doc_id)?)
+    }
+
+    /// Iterates over every indexed term, in arbitrary order.
+    pub fn terms(&self) -> impl Iterator<Item = &str> {
+        self.indexes
+            .iter()
+            .filter(|(_, postings)| self.live_len(postings) > 0)
+            .map(|(term, _)| term.as_str())
+    }
+
+    /// Iterates over the ids of the documents that contain `term`, in ascending order.
+    ///
+    /// Returns `None` if the term was never indexed.
+    pub fn postings(&self, term: &str) -> Option<impl Iterator<Item = usize> + '_> {
+        self.term_postings(term)
+            .map(|postings| doc_ids(&postings).into_iter())
+    }
+
+    /// Iterates over every stored document as `(doc_id, content)`, in arbitrary order.
+    /// Documents whose content cannot be read from the document store are skipped.
+    pub fn documents(&self) -> impl Iterator<Item = (usize, Cow<'_, str>)> {
+        self.documents
+            .values()
+            .filter_map(|doc| Some((doc.id, self.content(doc)?)))
+    }
+
+    /// Returns the ids of the documents whose content satisfies `pred`, in ascending order,
+    /// for filters the terms cannot express, such as "longer than 100 characters".
+    ///
+    /// # Notes
+    /// This bypasses the index and tokenization and calls `pred` on the raw content of every
+    /// document, so it takes O(documents) time, and reads every content back from a
+    /// [document store](crate::IndexBuilder::document_store). Documents whose content cannot
+    /// be read are skipped.
+    pub fn documents_where<F: Fn(&str) -> bool>(&self, pred: F) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .documents()
+            .filter(|(_, content)| pred(content))
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Queries the index for documents containing a specified word and highlights them.
+    ///
+    /// # Parameters
+    /// - `term`: The search term (word).
+    ///
+    /// # Returns
+    /// A vector of document contents that contain the term, case-insensitively,
+    /// with all occurrences of the term highlighted in purple. Each matching document
+    /// appears exactly once, in ascending doc id order, however often it contains the term.
+    /// Documents whose content is not [stored](crate::IndexBuilder::store_content) are left
+    /// out.
+    pub fn query(&self, term: &str) -> Vec<String> {
+        match self.query_status(term) {
+            QueryStatus::Matched(results) => results,
+            QueryStatus::NotInVocabulary | QueryStatus::NoMatchingDocuments => Vec::new(),
+        }
+    }
+
+    /// Like [`query`](Self::query), but tells a term the index does not know from one
+    /// whose documents are all gone, e.g. to only offer spelling suggestions for the first.
+    ///
+    /// # Returns
+    /// [`QueryStatus::Matched`] with the highlighted contents if any document contains the
+    /// term. Otherwise [`QueryStatus::NoMatchingDocuments`] for stop words, capped terms and
+    /// terms of soft-deleted documents only, and [`QueryStatus::NotInVocabulary`] for terms
+    /// without a posting list and for text without any word.
+    pub fn query_status(&self, term: &str) -> QueryStatus {
+        let Some(analyzed) = self.analyzer.analyze_phrase(term) else {
+            return if self.analyzer.tokenizer.tokenize(term).is_empty() {
+                QueryStatus::NotInVocabulary
+            } else {
+                QueryStatus::NoMatchingDocuments
+            };
+        };
+        if self.capped_terms.contains(&analyzed) {
+            return QueryStatus::NoMatchingDocuments;
+        }
+        if !self.indexes.contains_key(&analyzed) {
+            return QueryStatus::NotInVocabulary;
+        }
+        let results: Vec<String> = self.query_highlighted_iter(term).collect();
+        if results.is_empty() {
+            QueryStatus::NoMatchingDocuments
+        } else {
+            QueryStatus::Matched(results)
+        }
+    }
+
+    /// Like [`query`](Self::query), but reports problems instead of panicking or silently
+    /// matching nothing.
+    ///
+    /// # Returns
+    /// [`QueryError::EmptyQuery`] if `term` contains no words,
+    /// [`QueryError::Highlight`] if the analyzed term is not a valid highlighting pattern,
+    /// e.g. `f(x` from a custom tokenizer, and [`QueryError::ContentNotStored`] if the index
+    /// does not [store contents](crate::IndexBuilder::store_content). A term made only of
+    /// stop words matches nothing.
+    pub fn try_query(&self, term: &str) -> Result<Vec<String>, QueryError> {
+        if self.analyzer.tokenizer.tokenize(term).is_empty() {
+            return Err(QueryError::EmptyQuery);
+        }
+        if !self.keep_content {
+            return Err(QueryError::ContentNotStored);
+        }
+        let Some(term) = self.analyze_term(term) else {
+            return Ok(Vec::new());
+        };
+        let highlighter = Highlighter::new(&term)
+            .map_err(|err| QueryError::Highlight {
+                term: term.clone(),
+                message: err.to_string(),
+            })?
+            .style(self.style().copied())
+            .max_highlights(self.max_highlights);
+        Ok(self
+            .analyzed_term_hits(&term)
+            .filter_map(|hit| Some(highlighter.highlight(hit.content.as_deref()?).into_owned()))
+            .collect())
+    }
+
+    /// Like [`query`](Self::query), but highlights the occurrences of the term selected by `mode`.
+    pub fn query_with_mode(&self, term: &str, mode: HighlightMode) -> Vec<String> {
+        self.highlighted_hits(term, mode).collect()
+    }
+
+    /// Like [`query`](Self::query), but numbers the highlighted occurrences within each
+    /// document, see [`highlight_numbered`](crate::highlight_numbered).
+    ///
+    /// # Returns
+    /// `(doc_id, match_count, highlighted)` for each matching document, in ascending doc id
+    /// order. `match_count` is the number of highlighted occurrences, which, like the
+    /// highlighting itself, includes the term inside longer words.
+    pub fn query_with_counts(&self, term: &str) -> Vec<(usize, usize, String)> {
+        let Some(term) = self.analyze_term(term) else {
+            return Vec::new();
+        };
+        let mut highlighter = None;
+        self.analyzed_term_hits(&term)
+            .filter_map(|hit| {
+                let (highlighted, count) = highlighter
+                    .get_or_insert_with(|| self.highlighter(&term))
+                    .highlight_numbered(hit.content.as_deref()?);
+                Some((hit.doc_id, count, highlighted))
+            })
+            .collect()
+    }
+
+    /// Like [`query`](Self::query), but highlights exactly the occurrences the index matched
+    /// instead of searching the content for the term.
+    ///
+    /// # Returns
+    /// The contents of the documents containing the term, in ascending doc id order. The
+    /// highlighted spans are the tokens indexed under the term, located by the byte offsets
+    /// recorded when the document was added, so they agree with the tokenizer and with
+    /// stemming: "running" is highlighted for "runs" with stemming on, but "rust" inside
+    /// "trustworthy" is not.
+    pub fn query_exact(&self, term: &str) -> Vec<String> {
+        let Some(postings) = self.term_postings(term) else {
+            return Vec::new();
+        };
+        postings
+            .iter()
+            .filter_map(|posting| {
+                let doc = self.documents.get(&posting.doc_id)?;
+                let content = self.content(doc)?;
+                let spans = self.match_offsets(posting.doc_id, &[term]);
+                let spans = self.capped_spans(&spans);
+                Some(highlight_spans(&content, spans, self.style()).into_owned())
+            })
+            .collect()
+    }
+
+    /// Where the index matched any of `terms` in a document, for callers doing their own
+    /// highlighting.
+    ///
+    /// # Returns
+    /// Byte ranges `(start, end)` into the content [`get`](Self::get) returns, ascending and
+    /// with overlapping or touching ranges merged. They are the tokens indexed under each
+    /// term, analyzed like a query term, located by the offsets recorded when the document
+    /// was added: they agree with the tokenizer and stemming, and always lie on character
+    /// boundaries. Empty if `doc_id` is not indexed or contains none of the terms.
+    pub fn match_offsets(&self, doc_id: usize, terms: &[&str]) -> Vec<(usize, usize)> {
+        let Some(doc) = self.documents.get(&doc_id) else {
+            return Vec::new();
+        };
+        let mut spans = Vec::new();
+        for term in terms {
+            let Some(term) = self.analyze_term(term) else {
+                continue;
+            };
+            let Some(postings) = self.indexes.get(&term) else {
+                continue;
+            };
+            let Ok(i) = postings.binary_search_by_key(&doc_id, |posting| posting.doc_id) else {
+                continue;
+            };
+            // A phrase term is indexed at the position of its first word.
+            let words = term.split(' ').count();
+            spans.extend(occurrence_spans(doc, &postings[i].positions, words));
+        }
+        spans.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if last.1 >= start => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// The forms a term takes in the documents containing it, as they were written.
+    ///
+    /// # Returns
+    /// `(doc_id, forms)` for each matching document in ascending doc id order, where
+    /// `forms` are the distinct original spellings of the occurrences, e.g. `"Rust"` and
+    /// `"RUST"` for `"rust"`, in order of first appearance. With stemming, the inflections
+    /// the stem was indexed from are reported.
+    pub fn query_original_terms(&self, term: &str) -> Vec<(usize, Vec<String>)> {
+        let Some(term) = self.analyze_term(term) else {
+            return Vec::new();
+        };
+        let Some(postings) = self.live_postings(&term) else {
+            return Vec::new();
+        };
+        let words = term.split(' ').count();
+        postings
+            .iter()
+            .filter_map(|posting| {
+                let doc = self.documents.get(&posting.doc_id)?;
+                let content = self.content(doc)?;
+                let mut forms: Vec<String> = Vec::new();
+                for (start, end) in occurrence_spans(doc, &posting.positions, words) {
+                    let form = &content[start..end];
+                    if !forms.iter().any(|seen| seen == form) {
+                        forms.push(form.to_string());
+                    }
+                }
+                Some((posting.doc_id, forms))
+            })
+            .collect()
+    }
+
+    /// Lazily yields the highlighted contents [`query`](Self::query) would return.
+    ///
+    /// A document is only highlighted when the iterator reaches it, so
+    /// `index.query_highlighted_iter("rust").take(10)` does the work for ten documents
+    /// however many contain the term.
+    pub fn query_highlighted_iter<'a>(
+        &'a self,
+        term: &str,
+    ) -> impl Iterator<Item = String> + use<'a> {
+        self.highlighted_hits(term, HighlightMode::Substring)
+    }
+
+    fn highlighted_hits<'a>(
+        &'a self,
+        term: &str,
+        mode: HighlightMode,
+    ) -> impl Iterator<Item = String> + use<'a> {
+        let term = self.analyze_term(term);
+        let hits = term
+            .as_deref()
+            .map(|term| self.analyzed_term_hits(term))
+            .into_iter()
+            .flatten();
+        let mut highlighter = None;
+        hits.filter_map(move |hit| {
+            let term = term.as_deref().unwrap_or_default();
+            let highlighted = highlighter
+                .get_or_insert_with(|| self.highlighter(term).mode(mode))
+                .highlight(hit.content.as_deref()?)
+                .into_owned();
+            Some(highlighted)
+        })
+    }
+
+    /// Compiles the highlighting of an analyzed `term` in this index's style, once per query
+    /// when its first hit is highlighted.
+    ///
+    /// # Notes
+    /// Panics if `term` is not a valid pattern, see [`try_query`](Self::try_query).
+    pub(crate) fn highlighter(&self, term: &str) -> Highlighter {
+        Highlighter::new(term)
+            .unwrap()
+            .style(self.style().copied())
+            .max_highlights(self.max_highlights)
+    }
+
+    /// Lazily walks the documents containing `term`.
+    ///
+    /// # Parameters
+    /// - `term`: The search term (word), matched case-insensitively.
+    ///
+    /// # Returns
+    /// An iterator of hits in ascending doc id order, scored by TF-IDF. The hits borrow
+    /// their content from the index, so nothing is cloned or highlighted up front.
+    pub fn query_iter<'a>(&'a self, term: &str) -> impl Iterator<Item = SearchHit<'a>> + use<'a> {
+        let term = self.analyze_term(term);
+        term.map(|term| self.analyzed_term_hits(&term))
+            .into_iter()
+            .flatten()
+    }
+
+    /// The hits of an already analyzed term.
+    fn analyzed_term_hits<'a>(
+        &'a self,
+        term: &str,
+    ) -> impl Iterator<Item = SearchHit<'a>> + use<'a> {
+        let postings = self.live_postings(term).unwrap_or_default();
+        let idf = idf(self.documents.len(), postings.len());
+        (0..postings.len()).filter_map(move |i| {
+            let posting = &postings[i];
+            let doc = self.documents.get(&posting.doc_id)?;
+            Some(SearchHit {
+                doc_id: doc.id,
+                score: posting.term_freq() as f64 * idf,
+                content: self.content(doc),
+            })
+        })
+    }
+
+    /// Lazily walks the documents containing every one of `terms`.
+    ///
+    /// The posting lists are advanced in lockstep, so no intermediate id list is built.
+    ///
+    /// # Returns
+    /// An iterator of hits in ascending doc id order, scored by their summed TF-IDF.
+    /// It is empty when `terms` is empty or any term was never indexed.
+    pub fn query_all_iter<'a>(
+        &'a self,
+        terms: &[&str],
+    ) -> impl Iterator<Item = SearchHit<'a>> + use<'a> {
+        let lists: Option<Vec<_>> = terms
+            .iter()
+            .map(|term| {
+                self.term_postings(term).map(|postings| {
+                    let idf = idf(self.documents.len(), postings.len());
+                    (postings, idf)
+                })
+            })
+            .collect();
+        Intersection::new(lists.unwrap_or_default()).filter_map(move |(doc_id, score)| {
+            let doc = self.documents.get(&doc_id)?;
+            Some(SearchHit {
+                doc_id: doc.id,
+                score,
+                content: self.content(doc),
+            })
+        })
+    }
+
+    /// Runs a ranked query over every word of `query`.
+    ///
+    /// # Parameters
+    /// - `query`: Free text; a document matches if it contains any of its words, or
+    ///   `options.minimum_should_match` of them.
+    /// - `options`: Result filtering, ordering and pagination.
+    ///
+    /// # Returns
+    /// The matching documents with their TF-IDF score, ordered by `options.sort_by`.
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Vec<SearchHit<'_>> {
+        // Ranked by descending score with ascending ids breaking ties, which also gives the
+        // stable sorts below a deterministic tie-break.
+        let combine = match options.minimum_should_match {
+            0 | 1 => Combine::Any,
+            min => Combine::AtLeast(min),
+        };
+        let mut hits = self.rank(query, Scorer::TfIdf, combine);
+        sort_hits(self, &mut hits, &options.sort_by);
+        paginate(self, hits, options)
+    }
+
+    /// Returns the ids of the documents that contain every one of `terms`.
+    ///
+    /// # Returns
+    /// A sorted, de-duplicated list of document ids. It is empty when `terms` is empty,
+    /// and also when any term was never indexed, since no document can contain it.
+    pub fn intersect(&self, terms: &[&str]) -> Vec<usize> {
+        let mut lists = Vec::with_capacity(terms.len());
+        for term in terms {
+            match self.term_postings(term) {
+                Some(postings) => lists.push(doc_ids(&postings)),
+                None => return Vec::new(),
+            }
+        }
+        // Starting from the shortest list keeps every intermediate result small.
+        lists.sort_by_key(Vec::len);
+        let mut lists = lists.into_iter();
+        let first = lists.next().unwrap_or_default();
+        lists.fold(first, |acc, ids| intersect_sorted(&acc, &ids))
+    }
+
+    /// Returns the ids of the documents that contain at least one of `terms`.
+    ///
+    /// # Returns
+    /// A sorted, de-duplicated list of document ids. Unknown terms contribute nothing.
+    pub fn union(&self, terms: &[&str]) -> Vec<usize> {
+        terms
+            .iter()
+            .filter_map(|term| self.term_postings(term))
+            .fold(Vec::new(), |acc, postings| {
+                union_sorted(&acc, &doc_ids(&postings))
+            })
+    }
+}
+
+/// Collects the document ids of a posting list, keeping their ascending order.
+fn doc_ids(postings: &[Posting]) -> Vec<usize> {
+    postings.iter().map(|posting| posting.doc_id).collect()
+}
+
+#[cfg(test)]
+pub(crate) fn sort_fixture() -> InvertedIndex {
+    let mut index = InvertedIndex::new();
+    index.add(3, "Rust rust rust");
+    index.add(1, "Rust and Go");
+    index.add(4, "Go only");
+    index.add(2, "Rust Rust and Go");
+    index
+}
+
+#[cfg(test)]
+fn hit_ids(hits: &[SearchHit<'_>]) -> Vec<usize> {
+    hits.iter().map(|hit| hit.doc_id).collect()
+}
+
+#[test]
+fn search_sort_by_score_test() {
+    let index = sort_fixture();
+    let hits = index.search("rust", &SearchOptions::default());
+    assert_eq!(hit_ids(&hits), vec![3, 2, 1]);
+
+    // "go" hits 1, 2 and 4 with the same score, which keeps them in id order.
+    let hits = index.search("go", &SearchOptions::default());
+    assert_eq!(hit_ids(&hits), vec![1, 2, 4]);
+}
+
+#[test]
+fn search_sort_by_doc_id_test() {
+    let index = sort_fixture();
+    let asc = SearchOptions {
+        sort_by: crate::SortBy::DocIdAsc,
+        ..SearchOptions::default()
+    };
+    assert_eq!(hit_ids(&index.search("rust go", &asc)), vec![1, 2, 3, 4]);
+
+    let desc = SearchOptions {
+        sort_by: crate::SortBy::DocIdDesc,
+        ..SearchOptions::default()
+    };
+    assert_eq!(hit_ids(&index.search("rust go", &desc)), vec![4, 3, 2, 1]);
+}
+
+#[test]
+fn search_sort_by_numeric_field_test() {
+    use crate::{SortBy, SortOrder};
+
+    let mut index = sort_fixture();
+    index.add_numeric(1, "year", 2010.0).unwrap();
+    index.add_numeric(3, "year", 2010.0).unwrap();
+    index.add_numeric(4, "year", 2000.0).unwrap();
+    let by_score = hit_ids(&index.search("rust go", &SearchOptions::default()));
+    assert_eq!(by_score, vec![2, 3, 1, 4]);
+    let query = crate::Query::parse("rust OR go").unwrap();
+    let sorted = |field: &str, order| {
+        let options = SearchOptions {
+            sort_by: SortBy::NumericField(field.to_string(), order),
+            ..SearchOptions::default()
+        };
+        let ids = hit_ids(&index.search("rust go", &options));
+        assert_eq!(hit_ids(&index.search_query(&query, &options)), ids);
+        ids
+    };
+    // 1 and 3 share a year and keep their score order; 2 has none and comes last.
+    assert_eq!(sorted("year", SortOrder::Asc), vec![4, 3, 1, 2]);
+    assert_eq!(sorted("year", SortOrder::Desc), vec![3, 1, 4, 2]);
+    assert_eq!(sorted("pages", SortOrder::Desc), by_score);
+}
+
+#[test]
+fn search_limit_offset_test() {
+    let index = sort_fixture();
+    let options = SearchOptions {
+        sort_by: crate::SortBy::DocIdAsc,
+        limit: Some(2),
+        offset: 1,
+        ..SearchOptions::default()
+    };
+    assert_eq!(hit_ids(&index.search("rust go", &options)), vec![2, 3]);
+
+    let past_end = SearchOptions {
+        offset: 10,
+        ..SearchOptions::default()
+    };
+    assert!(index.search("rust go", &past_end).is_empty());
+}
+
+#[test]
+fn search_minimum_should_match_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust rust rust");
+    index.add(2, "rust and go");
+    index.add(3, "rust go python");
+    index.add(4, "go python java");
+    index.add(5, "nothing relevant");
+
+    let search = |query: &str, min: usize| {
+        let options = SearchOptions {
+            minimum_should_match: min,
+            ..SearchOptions::default()
+        };
+        index
+            .search(query, &options)
+            .iter()
+            .map(|hit| (hit.doc_id, hit.score))
+            .collect::<Vec<_>>()
+    };
+    let query = "rust go python java";
+    let any = search(query, 0);
+    assert_eq!(search(query, 1), any);
+    assert_eq!(any.len(), 4);
+    // Repeating "rust" does not get document 1 past two terms.
+    let ids: Vec<usize> = search(query, 2).iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, vec![4, 3, 2]);
+    // The scores are those of the plain OR query.
+    for (id, score) in search(query, 2) {
+        assert!(any.contains(&(id, score)));
+    }
+    assert_eq!(
+        search("rust go python", 3),
+        index.query_all("rust go python")
+    );
+    assert!(search(query, 5).is_empty());
+    assert!(search("rust rust go", 3).is_empty());
+}
+
+#[test]
+fn intersect_test() {
+    let index = sort_fixture();
+    assert_eq!(index.intersect(&["Rust", "go"]), vec![1, 2]);
+    assert_eq!(index.intersect(&["rust"]), vec![1, 2, 3]);
+    assert!(index.intersect(&["rust", "python"]).is_empty());
+    assert!(index.intersect(&[]).is_empty());
+}
+
+#[test]
+fn union_test() {
+    let index = sort_fixture();
+    assert_eq!(index.union(&["only", "RUST"]), vec![1, 2, 3, 4]);
+    assert_eq!(index.union(&["python", "only"]), vec![4]);
+    assert!(index.union(&[]).is_empty());
+}
+
+#[test]
+fn query_iter_borrows_content_test() {
+    let index = sort_fixture();
+    let hits: Vec<_> = index.query_iter("RUST").take(2).collect();
+    assert_eq!(hit_ids(&hits), vec![1, 2]);
+    for hit in &hits {
+        assert!(matches!(hit.content, Some(Cow::Borrowed(_))));
+        let Content::Memory(stored) = &index.documents[&hit.doc_id].content else {
+            panic!("content not in memory");
+        };
+        assert!(std::ptr::eq(
+            hit.content.as_deref().unwrap().as_ptr(),
+            stored.as_ptr()
+        ));
+    }
+    assert_eq!(index.query_iter("python").count(), 0);
+}
+
+#[test]
+fn query_all_iter_test() {
+    let index = sort_fixture();
+    let hits: Vec<_> = index.query_all_iter(&["rust", "and", "go"]).collect();
+    assert_eq!(hit_ids(&hits), vec![1, 2]);
+    let Content::Memory(stored) = &index.documents[&2].content else {
+        panic!("content not in memory");
+    };
+    assert!(std::ptr::eq(
+        hits[1].content.as_deref().unwrap().as_ptr(),
+        stored.as_ptr()
+    ));
+    assert!(hits[1].score > hits[0].score);
+
+    assert_eq!(index.query_all_iter(&["rust", "python"]).count(), 0);
+    assert_eq!(index.query_all_iter(&[]).count(), 0);
+}
+
+#[test]
+fn add_returns_term_count_test() {
+    let mut index = InvertedIndex::builder().stop_words(["the", "a"]).build();
+    assert_eq!(index.add(1, "Rust rust RUST and Go"), 3);
+    assert_eq!(index.add(2, ""), 0);
+    assert_eq!(index.add(3, "   \t\n"), 0);
+    assert_eq!(index.add(4, "The a, the!"), 0);
+    // Unsearchable documents are stored all the same.
+    assert_eq!(index.get(2).as_deref(), Some(""));
+    assert_eq!(index.get(3).as_deref(), Some("   \t\n"));
+    assert_eq!(index.stats().document_count, 4);
+    // Replacing a document counts its new terms only.
+    assert_eq!(index.add(1, "go"), 1);
+}
+
+#[test]
+fn remove_test() {
+    let mut index = sort_fixture();
+    assert!(index.remove(4));
+    assert!(!index.remove(4));
+    assert_eq!(index.union(&["go", "only"]), vec![1, 2]);
+    assert!(!index.indexes.contains_key("only"));
+}
+
+#[test]
+fn try_add_and_update_test() {
+    let mut index = InvertedIndex::new();
+    assert!(index.try_add(1, "Rust").is_ok());
+    let err = index.try_add(1, "Go").unwrap_err();
+    assert!(matches!(err, IndexError::DuplicateDocument(1)));
+    assert_eq!(err.to_string(), "document 1 is already indexed");
+    assert_eq!(index.get(1).as_deref(), Some("Rust"));
+
+    assert!(index.update(1, "Go").is_ok());
+    let err = index.update(2, "Go").unwrap_err();
+    assert!(matches!(err, IndexError::DocumentNotFound(2)));
+    assert_eq!(err.to_string(), "no document 2");
+    assert_eq!(index.get(1).as_deref(), Some("Go"));
+    assert_eq!(index.get(2).as_deref(), None);
+}
+
+#[test]
+fn update_scrubs_stored_terms_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust and go");
+    index.add(2, "go and python");
+    let stored: Vec<&str> = index.documents[&1]
+        .terms
+        .iter()
+        .map(|(term, _)| term.as_str())
+        .collect();
+    assert_eq!(stored, ["and", "go", "rust"]);
+
+    index.update(1, "zig").unwrap();
+    assert!(!index.indexes.contains_key("rust"));
+    assert_eq!(index.query_ids("go"), vec![2]);
+    assert_eq!(index.query_ids("and"), vec![2]);
+    assert_eq!(index.query_ids("zig"), vec![1]);
+    assert_eq!(index.validate(), Ok(()));
+
+    // Only the stored terms are visited, so a term left out of them keeps its posting.
+    Arc::make_mut(index.documents.get_mut(&2).unwrap())
+        .terms
+        .retain(|(term, _)| term != "python");
+    index.update(2, "go").unwrap();
+    assert_eq!(index.query_ids("and"), Vec::<usize>::new());
+    assert_eq!(index.query_ids("python"), vec![2]);
+}
+
+#[test]
+fn documents_where_test() {
+    let mut index = InvertedIndex::builder().stop_words(["the"]).build();
+    index.add(3, &"long ".repeat(30));
+    index.add(1, "short");
+    index.add(2, "The the the");
+    index.add(4, &"longer ".repeat(20));
+    assert_eq!(
+        index.documents_where(|content| content.len() > 100),
+        vec![3, 4]
+    );
+    // Stop words are still part of the content.
+    assert_eq!(
+        index.documents_where(|content| content.starts_with("The")),
+        vec![2]
+    );
+    assert!(index.documents_where(|_| false).is_empty());
+    index.remove(3);
+    assert_eq!(
+        index.documents_where(|content| content.len() > 100),
+        vec![4]
+    );
+}
+
+#[test]
+fn remove_term_test() {
+    let mut index = sort_fixture();
+    assert_eq!(index.remove_term("Rust"), 3);
+    assert!(index.query("rust").is_empty());
+    assert!(index.search("rust", &SearchOptions::default()).is_empty());
+    assert_eq!(index.get(1).as_deref(), Some("Rust and Go"));
+    assert_eq!(index.remove_term("rust"), 0);
+    assert_eq!(index.remove_term("python"), 0);
+    assert_eq!(index.union(&["go"]), vec![1, 2, 4]);
+    assert_eq!(index.stats().average_document_length, 3.0);
+}
+
+#[test]
+fn iteration_test() {
+    let index = sort_fixture();
+    let mut terms: Vec<&str> = index.terms().collect();
+    terms.sort_unstable();
+    assert_eq!(terms, vec!["and", "go", "only", "rust"]);
+
+    let postings: Vec<usize> = index.postings("Rust").unwrap().collect();
+    assert_eq!(postings, vec![1, 2, 3]);
+    assert!(index.postings("python").is_none());
+
+    let mut documents: Vec<(usize, Cow<'_, str>)> = index.documents().collect();
+    documents.sort_unstable();
+    assert_eq!(documents[0], (1, Cow::Borrowed("Rust and Go")));
+    assert_eq!(documents.len(), 4);
+
+    assert_eq!(index.get(4).as_deref(), Some("Go only"));
+    assert!(matches!(index.get(4), Some(Cow::Borrowed(_))));
+    assert_eq!(index.get(5).as_deref(), None);
+}
+
+#[test]
+fn retain_test() {
+    let mut index = InvertedIndex::new();
+    for id in 0..10 {
+        let parity = if id % 2 == 0 { "even" } else { "odd" };
+        index.add(id, &format!("number {parity}"));
+    }
+    assert_eq!(
+        index.retain(|id, content| id < 5 || content.contains("even")),
+        3
+    );
+    assert_eq!(index.retain(|id, _| id % 2 == 0), 2);
+
+    assert_eq!(index.union(&["number"]), vec![0, 2, 4, 6, 8]);
+    assert!(index.postings("odd").is_none());
+    assert_eq!(index.stats().document_count, 5);
+    assert_eq!(index.total_term_freq("number"), 5);
+    assert_eq!(index.average_document_length(), 2.0);
+    assert_eq!(index.retain(|_, _| true), 0);
+}
+
+#[test]
+fn query_with_mode_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::new();
+    index.add(1, "trust rust");
+    assert_eq!(
+        index.query_with_mode("rust", HighlightMode::WholeWord),
+        vec!["trust \u{1b}[35mrust\u{1b}[0m"]
+    );
+    assert_eq!(
+        index.query("rust"),
+        vec!["t\u{1b}[35mrust\u{1b}[0m \u{1b}[35mrust\u{1b}[0m"]
+    );
+}
+
+#[test]
+fn query_returns_each_document_once_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust, Rust and more RUST");
+    index.add(2, "go");
+    assert_eq!(index.query("rust").len(), 1);
+
+    // Re-adding the same id replaces the document instead of duplicating it.
+    index.add(1, "rust rust");
+    assert_eq!(index.query("rust").len(), 1);
+}
+
+#[test]
+fn query_with_counts_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust");
+    index.add(2, "rust, Rust and more rust");
+    index.add(3, "Go");
+
+    assert_eq!(
+        index.query_with_counts("rust"),
+        vec![
+            (1, 1, "Rust[1]".to_string()),
+            (2, 3, "rust[1], Rust[2] and more rust[3]".to_string()),
+        ]
+    );
+    assert!(index.query_with_counts("python").is_empty());
+}
+
+#[test]
+fn query_highlighted_iter_test() {
+    let _color = crate::test_util::color_guard(true);
+    let index = sort_fixture();
+    let mut results = index.query_highlighted_iter("Rust");
+    assert_eq!(
+        results.next().as_deref(),
+        Some("\u{1b}[35mRust\u{1b}[0m and Go")
+    );
+    assert_eq!(results.count(), 2);
+    assert_eq!(
+        index
+            .query_highlighted_iter("rust")
+            .take(2)
+            .collect::<Vec<_>>(),
+        index.query("rust")[..2]
+    );
+    assert_eq!(index.query_highlighted_iter("python").count(), 0);
+}
+
+#[test]
+fn query_status_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::builder()
+        .stop_words(["the"])
+        .soft_delete(1.0)
+        .build();
+    index.add(1, "The Rust book");
+    index.add(2, "The Go book");
+    index.add(3, "Python");
+
+    assert_eq!(
+        index.query_status("rust"),
+        QueryStatus::Matched(vec!["The Rust book".to_string()])
+    );
+    assert_eq!(index.query_status("java"), QueryStatus::NotInVocabulary);
+    assert_eq!(index.query_status("!?"), QueryStatus::NotInVocabulary);
+    assert_eq!(index.query_status("the"), QueryStatus::NoMatchingDocuments);
+    index.remove(2);
+    assert_eq!(index.query_status("go"), QueryStatus::NoMatchingDocuments);
+    assert!(index.query("go").is_empty());
+    index.compact();
+    assert_eq!(index.query_status("go"), QueryStatus::NotInVocabulary);
+
+    let mut capped = InvertedIndex::builder().max_postings(1).build();
+    capped.add(1, "common");
+    capped.add(2, "common");
+    assert_eq!(
+        capped.query_status("common"),
+        QueryStatus::NoMatchingDocuments
+    );
+}
+
+#[test]
+fn try_query_test() {
+    /// Splits on whitespace only, keeping punctuation such as `f(x` in the tokens.
+    #[derive(Debug)]
+    struct Whitespace;
+    impl crate::Tokenizer for Whitespace {
+        fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+            text.split_whitespace().collect()
+        }
+    }
+
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is fast");
+    assert_eq!(
+        index.try_query("rust"),
+        Ok(vec!["Rust is fast".to_string()])
+    );
+    assert_eq!(index.try_query("python"), Ok(Vec::new()));
+    assert_eq!(index.try_query(""), Err(QueryError::EmptyQuery));
+    assert_eq!(index.try_query(" ?! "), Err(QueryError::EmptyQuery));
+
+    let mut index = InvertedIndex::builder().tokenizer(Whitespace).build();
+    index.add(1, "call f(x and return");
+    assert!(matches!(
+        index.try_query("F(x"),
+        Err(QueryError::Highlight { term, .. }) if term == "f(x"
+    ));
+}
+
+#[test]
+fn max_highlights_per_doc_test() {
+    let _color = crate::test_util::color_guard(true);
+    let content = "rust, Rust and more rust";
+    let mut index = InvertedIndex::builder().max_highlights_per_doc(2).build();
+    index.add(1, content);
+    let capped = "\u{1b}[35mrust\u{1b}[0m, \u{1b}[35mRust\u{1b}[0m and more rust";
+    assert_eq!(index.query("rust"), vec![capped]);
+    assert_eq!(index.query_exact("rust"), vec![capped]);
+
+    let mut none = InvertedIndex::builder().max_highlights_per_doc(0).build();
+    none.add(1, content);
+    assert_eq!(none.query("rust"), vec![content]);
+    assert_eq!(none.query_exact("rust"), vec![content]);
+}
+
+#[test]
+fn match_offsets_test() {
+    let mut index = InvertedIndex::builder().stemming(true).build();
+    let content = "🦀Running runs, Café trustworthy rust";
+    index.add(1, content);
+    let offsets = index.match_offsets(1, &["run", "CAFÉ", "rust"]);
+    let matched: Vec<&str> = offsets
+        .iter()
+        .map(|&(start, end)| &content[start..end])
+        .collect();
+    assert_eq!(matched, ["Running", "runs", "Café", "rust"]);
+    assert_eq!(offsets[0], (4, 11));
+    assert!(index.match_offsets(1, &["python"]).is_empty());
+    assert!(index.match_offsets(2, &["rust"]).is_empty());
+
+    // A phrase and its first word overlap and come back as one range.
+    let mut index = InvertedIndex::new();
+    index.add_with_phrases(1, "I love New York", &["new york"]);
+    assert_eq!(index.match_offsets(1, &["new", "New York"]), vec![(7, 15)]);
+}
+
+#[test]
+fn query_exact_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::builder().stemming(true).build();
+    index.add(1, "🦀Rust🦀 runs, trustworthy and running.");
+    index.add(2, "Café crème: élan vital, café!");
+    index.add_fields(3, &[("title", "Running"), ("body", "é run")]);
+    index
+        .add_from_reader(4, "first line\nrun é\n".as_bytes())
+        .unwrap();
+
+    assert_eq!(
+        index.query_exact("rust"),
+        vec!["🦀\u{1b}[35mRust\u{1b}[0m🦀 runs, trustworthy and running."]
+    );
+    assert_eq!(
+        index.query_exact("café"),
+        vec!["\u{1b}[35mCafé\u{1b}[0m crème: élan vital, \u{1b}[35mcafé\u{1b}[0m!"]
+    );
+    assert_eq!(
+        index.query_exact("run"),
+        vec![
+            "🦀Rust🦀 \u{1b}[35mruns\u{1b}[0m, trustworthy and \u{1b}[35mrunning\u{1b}[0m.",
+            "\u{1b}[35mRunning\u{1b}[0m\né \u{1b}[35mrun\u{1b}[0m",
+            "first line\n\u{1b}[35mrun\u{1b}[0m é\n",
+        ]
+    );
+    assert!(index.query_exact("python").is_empty());
+
+    let mut index = InvertedIndex::new();
+    index.add_with_phrases(1, "I love New York, ünd New York loves me", &["new york"]);
+    assert_eq!(
+        index.query_exact("New York"),
+        vec!["I love \u{1b}[35mNew York\u{1b}[0m, ünd \u{1b}[35mNew York\u{1b}[0m loves me"]
+    );
+}
+
+#[test]
+fn query_original_terms_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust, rust and RUST. Rust!");
+    index.add(2, "I like rust");
+    index.add(3, "Go");
+    assert_eq!(
+        index.query_original_terms("rust"),
+        vec![
+            (
+                1,
+                vec!["Rust".to_string(), "rust".to_string(), "RUST".to_string()]
+            ),
+            (2, vec!["rust".to_string()]),
+        ]
+    );
+    assert!(index.query_original_terms("python").is_empty());
+
+    let mut index = InvertedIndex::builder().stemming(true).build();
+    index.add(1, "Running runs; he RAN and running");
+    assert_eq!(
+        index.query_original_terms("run"),
+        vec![(
+            1,
+            vec![
+                "Running".to_string(),
+                "runs".to_string(),
+                "running".to_string()
+            ]
+        )]
+    );
+}
@@ -0,0 +1,170 @@
+use crate::analysis::TermBuffer;
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::json::Json;
+use crate::oplog::LogEntry;
+use std::io::BufRead;
+
+/// The outcome of a bulk ingestion such as [`InvertedIndex::add_jsonl`].
+#[derive(Debug, Default, PartialEq)]
+pub struct IngestReport {
+    /// Number of records that were indexed.
+    pub added: usize,
+    /// Number of records that were left out.
+    pub skipped: usize,
+    /// The 1-based line number and reason of every skipped record.
+    pub errors: Vec<(usize, String)>,
+}
+
+impl IngestReport {
+    fn skip(&mut self, line: usize, reason: String) {
+        self.skipped += 1;
+        self.errors.push((line, reason));
+    }
+}
+
+impl InvertedIndex {
+    /// Indexes a JSON Lines stream holding one JSON object per line.
+    ///
+    /// # Parameters
+    /// - `reader`: The input. Blank lines are ignored.
+    /// - `id_field`: The member holding the document id, either a non-negative integer or a
+    ///   string of one. Integers are read exactly; one written with a fraction or exponent
+    ///   counts only up to 2^53.
+    /// - `text_fields`: The string members to index, joined by newlines in this order into
+    ///   the document content. Members that are missing or not strings are left out.
+    ///
+    /// # Returns
+    /// How many records were added and skipped. Lines that are not valid UTF-8 or JSON, are
+    /// not objects, or lack a valid id are skipped and described in
+    /// [`IngestReport::errors`] without stopping the ingestion. Fails only if reading from
+    /// `reader` fails.
+    pub fn add_jsonl<R: BufRead>(
+        &mut self,
+        mut reader: R,
+        id_field: &str,
+        text_fields: &[&str],
+    ) -> Result<IngestReport, IndexError> {
+        let mut report = IngestReport::default();
+        let mut words = TermBuffer::default();
+        let mut line = Vec::new();
+        let mut number = 0;
+        loop {
+            line.clear();
+            if reader
+                .read_until(b'\n', &mut line)
+                .map_err(IndexError::Read)?
+                == 0
+            {
+                return Ok(report);
+            }
+            number += 1;
+            let Ok(text) = std::str::from_utf8(&line) else {
+                report.skip(number, "line is not valid UTF-8".to_string());
+                continue;
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            let record = match Json::parse(text) {
+                Ok(record @ Json::Object(_)) => record,
+                Ok(_) => {
+                    report.skip(number, "line is not a JSON object".to_string());
+                    continue;
+                }
+                Err(err) => {
+                    report.skip(number, format!("invalid JSON: {}", err));
+                    continue;
+                }
+            };
+            let Some(id) = record.get(id_field).and_then(parse_id) else {
+                report.skip(number, format!("missing or invalid \"{}\"", id_field));
+                continue;
+            };
+            let content = text_fields
+                .iter()
+                .filter_map(|field| match record.get(field) {
+                    Some(Json::String(text)) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.record(LogEntry::Add {
+                id,
+                content: &content,
+            });
+            self.index_document(id, content, &mut words);
+            report.added += 1;
+        }
+    }
+}
+
+/// Reads a document id from a JSON number or a string of digits. A number with a fraction
+/// or exponent, such as `7.0` or `1e3`, is only taken up to 2^53, beyond which an `f64` no
+/// longer tells neighbouring integers apart.
+pub(crate) fn parse_id(value: &Json) -> Option<usize> {
+    const EXACT: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+    match value {
+        Json::Integer(n) => usize::try_from(*n).ok(),
+        Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= EXACT => Some(*n as usize),
+        Json::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[test]
+fn add_jsonl_fixture_test() {
+    let fixture = include_str!("../tests/fixtures/mixed.jsonl");
+    let mut index = InvertedIndex::new();
+    let report = index
+        .add_jsonl(fixture.as_bytes(), "id", &["title", "body"])
+        .unwrap();
+
+    assert_eq!(report.added, 4);
+    assert_eq!(report.skipped, 4);
+    let lines: Vec<usize> = report.errors.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![4, 5, 6, 8]);
+
+    assert_eq!(
+        index.get(1).as_deref(),
+        Some("Rust\nRust is a systems programming language.")
+    );
+    assert_eq!(index.get(2).as_deref(), Some("Go\nGo is simple."));
+    assert_eq!(
+        index.get(5).as_deref(),
+        Some("Only a body, \"quoted\" and escapedé.")
+    );
+    assert_eq!(index.union(&["python"]), vec![6]);
+}
+
+#[test]
+fn add_jsonl_large_ids_test() {
+    let input = concat!(
+        "{\"id\": 9007199254740993, \"text\": \"odd\"}\n",
+        "{\"id\": 9007199254740992, \"text\": \"even\"}\n",
+        "{\"id\": 1e3, \"text\": \"exponent\"}\n",
+        "{\"id\": 1e17, \"text\": \"inexact\"}\n",
+        "{\"id\": 18446744073709551616, \"text\": \"too large\"}\n",
+    );
+    let mut index = InvertedIndex::new();
+    let report = index.add_jsonl(input.as_bytes(), "id", &["text"]).unwrap();
+    assert_eq!(report.added, 3);
+    let lines: Vec<usize> = report.errors.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![4, 5]);
+    assert_eq!(index.get(9007199254740993).as_deref(), Some("odd"));
+    assert_eq!(index.get(9007199254740992).as_deref(), Some("even"));
+    assert_eq!(index.get(1000).as_deref(), Some("exponent"));
+}
+
+#[test]
+fn add_jsonl_invalid_utf8_test() {
+    let input: &[u8] = b"{\"id\": 1, \"text\": \"\xff\"}\n{\"id\": 2, \"text\": \"ok\"}";
+    let mut index = InvertedIndex::new();
+    let report = index.add_jsonl(input, "id", &["text"]).unwrap();
+    assert_eq!(report.added, 1);
+    assert_eq!(
+        report.errors,
+        vec![(1, "line is not valid UTF-8".to_string())]
+    );
+    assert_eq!(index.get(2).as_deref(), Some("ok"));
+}
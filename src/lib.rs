@@ -0,0 +1,95 @@
+//! A small in-memory inverted index with highlighted query results.
+
+mod analysis;
+mod autocomplete;
+mod batch;
+mod boolean;
+mod builder;
+mod cache;
+mod compress;
+mod cooccurrence;
+mod csv;
+mod directory;
+mod docstore;
+mod error;
+mod explain;
+mod facets;
+mod fields;
+mod filters;
+mod forward;
+mod fuzzy;
+mod highlight;
+mod index;
+mod json;
+mod jsonl;
+mod matrix;
+mod memory;
+mod metadata;
+mod ngram;
+mod numeric;
+mod oplog;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod pattern;
+mod persist;
+mod phrase;
+mod postings;
+mod profile;
+mod query;
+mod ranking;
+mod reader;
+mod search;
+#[cfg(feature = "serve")]
+mod serve;
+mod sharded;
+mod shared;
+mod similar;
+mod snapshot;
+mod snippets;
+mod stats;
+mod stemmer;
+mod stop_words;
+mod surface;
+#[cfg(test)]
+mod test_util;
+mod tokenizer;
+mod tombstones;
+mod transfer;
+mod validate;
+
+pub use autocomplete::Suggestion;
+pub use builder::IndexBuilder;
+pub use cache::CacheStats;
+pub use colored::Color;
+pub use directory::{DirIndexOptions, DirIndexReport};
+pub use error::IndexError;
+pub use explain::Explanation;
+pub use facets::MISSING_FACET;
+pub use fields::MultiMatch;
+pub use filters::FilterFn;
+pub use highlight::{
+    HighlightMode, HighlightStyle, Highlighter, highlight, highlight_numbered, highlight_ranges,
+    highlight_styled, highlight_terms, highlight_with_mode, snippet, strip_highlight,
+};
+pub use index::{InvertedIndex, QueryStatus};
+pub use json::quote as json_quote;
+pub use jsonl::IngestReport;
+pub use matrix::{MatrixFormat, MatrixLayout, MatrixValues};
+pub use memory::MemoryBreakdown;
+pub use pattern::RegexMatches;
+pub use persist::IndexSection;
+pub use profile::QueryProfile;
+pub use query::{Query, QueryError};
+pub use reader::InvalidUtf8;
+pub use search::{SearchHit, SearchOptions, SortBy, SortOrder};
+#[cfg(feature = "serve")]
+pub use serve::Server;
+pub use sharded::ShardedIndex;
+pub use shared::SharedIndex;
+pub use snapshot::IndexSnapshot;
+pub use stats::IndexStats;
+pub use stemmer::stem;
+pub use stop_words::{Language, StopWords};
+pub use tokenizer::{
+    SimpleTokenizer, Token, Tokenizer, WordTokenizer, tokenize, tokenize_normalized, tokenize_vec,
+};
@@ -1,140 +1,367 @@
-use colored::Colorize;
-use regex::Regex;
-use std::collections::HashMap;
+use inverted_index::{
+    DirIndexOptions, IndexError, InvertedIndex, Query, SearchOptions, highlight_terms, json_quote,
+    snippet, tokenize_vec,
+};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
 
-fn main() {
-    // println!("Hello, world!");
+const USAGE: &str = "\
+usage:
+    inverted_index index <dir-or-file> [--out index.bin]
+    inverted_index search <index.bin> <query> [--limit 10] [--format plain|json|ansi]
+    inverted_index stats <index.bin>
+    inverted_index repl <index.bin>
+    inverted_index serve <index.bin> [--addr 127.0.0.1:8080]   (with the serve feature)
+    inverted_index demo";
 
-    let mut index = InvertedIndex::new();
-    index.add(1, "Rust is safe and fast.");
-    index.add(2, "Rust is a systems programming language.");
-    index.add(3, "Programming in Rust is fun.");
+/// `search` found nothing.
+const NO_RESULTS: u8 = 1;
+/// Bad arguments or a failed operation.
+const ERROR: u8 = 2;
 
-    // query "Rust"
-    let results = index.query("Rust");
-    for result in results {
-        println!("{}", result);
-    }
-
-    println!("");
-
-    // query "Programming"
-    let results = index.query("Programming");
-    for result in results {
-        println!("{}", result);
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("index") => index_command(&args[1..]),
+        Some("search") => search_command(&args[1..]),
+        Some("stats") => stats_command(&args[1..]),
+        Some("repl") => repl_command(&args[1..]),
+        #[cfg(feature = "serve")]
+        Some("serve") => serve_command(&args[1..]),
+        Some("demo") => {
+            demo();
+            Ok(true)
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(NO_RESULTS),
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::from(ERROR)
+        }
     }
 }
 
-/// Define a structure to represent documents for easy access and management.c
-struct Document {
-    id: usize,
-    content: String,
+/// Command-line arguments split into positional arguments and `--option value` pairs.
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
 }
 
-/// The InvertedIndex struct manages a set of indexed documents.
-struct InvertedIndex {
-    /// The in-memory index.
-    ///
-    /// value is the single `term` of the document's word tokenization.
-    /// key is a vector of document ids.
-    indexes: HashMap<String, Vec<usize>>,
+impl<'a> Args<'a> {
+    /// Parses `args`, accepting only the given option names.
+    fn parse(args: &'a [String], names: &[&str]) -> Result<Self, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if names.contains(&name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{} needs a value\n\n{}", name, USAGE))?;
+                    parsed.options.push((name, value.as_str()));
+                }
+                Some(_) => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+                None => parsed.positional.push(arg.as_str()),
+            }
+        }
+        Ok(parsed)
+    }
 
-    /// Stores a mapping of the document id to the original document content
-    documents: HashMap<usize, Document>,
+    /// The last value given for `--name`.
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }
 }
 
-impl InvertedIndex {
-    fn new() -> InvertedIndex {
-        Self {
-            indexes: HashMap::new(),
-            documents: HashMap::new(),
+fn index_command(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &["out"])?;
+    let [input] = args.positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    let out = args.option("out").unwrap_or("index.bin");
+    let input = Path::new(input);
+
+    let mut index = InvertedIndex::new();
+    if input.is_dir() {
+        let opts = DirIndexOptions {
+            recursive: true,
+            ..DirIndexOptions::default()
+        };
+        let report = index
+            .index_dir(input, opts)
+            .map_err(|err| err.to_string())?;
+        for err in &report.skipped {
+            eprintln!("skipped: {}", err);
         }
+    } else {
+        let file = File::open(input).map_err(|err| format!("{}: {}", input.display(), err))?;
+        index
+            .add_from_reader(0, BufReader::new(file))
+            .map_err(|err| format!("{}: {}", input.display(), err))?;
     }
+    index
+        .save_to(Path::new(out))
+        .map_err(|err| err.to_string())?;
+    println!(
+        "indexed {} documents into {}",
+        index.stats().document_count,
+        out
+    );
+    Ok(true)
+}
 
-    /// Adds a document to the index.
-    ///
-    /// # Parameters
-    /// - `doc_id`: An identifier for the document.
-    /// - `content`: The text content of the document.
-    ///
-    /// # Notes
-    /// This method processes the document by lowercasing and tokenizing the text,
-    /// then updates the index to include words found in this document.
-    pub fn add(&mut self, id: usize, content: &str) {
-        let content_lowercase = content.to_lowercase();
-        let words = tokenize(&content_lowercase);
-        words.iter().for_each(|word| {
-            self.indexes
-                .entry(word.to_string())
-                .or_insert(Vec::new())
-                .push(id);
-        });
-
-        self.documents.insert(
-            id,
-            Document {
-                id,
-                content: content.to_string(),
-            },
-        );
-    }
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Plain,
+    Json,
+    Ansi,
+}
+
+fn search_command(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &["limit", "format"])?;
+    let [path, query] = args.positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    let limit = match args.option("limit") {
+        Some(limit) => limit
+            .parse()
+            .map_err(|_| format!("invalid --limit {}", limit))?,
+        None => 10,
+    };
+    let format = match args.option("format").unwrap_or("plain") {
+        "plain" => Format::Plain,
+        "json" => Format::Json,
+        "ansi" => Format::Ansi,
+        other => return Err(format!("unknown --format {}\n\n{}", other, USAGE)),
+    };
+    colored::control::set_override(format == Format::Ansi);
+
+    let index = InvertedIndex::open(Path::new(path)).map_err(|err| err.to_string())?;
+    let options = SearchOptions {
+        limit: Some(limit),
+        ..SearchOptions::default()
+    };
+    let query = Query::parse(query).map_err(|err| err.to_string())?;
+    let hits = index.search_query(&query, &options);
+    let words: Vec<&str> = query
+        .positive_terms()
+        .into_iter()
+        .flat_map(tokenize_vec)
+        .collect();
 
-    /// Queries the index for documents containing a specified word and highlights them.
-    ///
-    /// # Parameters
-    /// - `term`: The search term (word).
-    ///
-    /// # Returns
-    /// A vector of document contents that contain the term, case-insensitively,
-    /// with all occurrences of the term highlighted in purple.
-    fn query(&self, term: &str) -> Vec<String> {
-        let term_lowercase = term.to_lowercase();
-        if let Some(doc_ids) = self.indexes.get(&term_lowercase) {
-            doc_ids
-                .iter()
-                .filter_map(|doc_id| {
-                    self.documents
-                        .get(&doc_id)
-                        .map(|doc| highlight(&term_lowercase, &doc.content))
-                })
-                .collect()
-        } else {
-            Vec::new()
+    let mut json = Vec::new();
+    for hit in &hits {
+        let snippet = snippet(hit.content.as_deref().unwrap_or_default(), &words);
+        let path = index.document_path(hit.doc_id);
+        match format {
+            Format::Json => json.push(format!(
+                "{{\"id\":{},\"score\":{},\"path\":{},\"snippet\":{}}}",
+                hit.doc_id,
+                hit.score,
+                path.map_or("null".to_string(), |path| json_quote(
+                    &path.to_string_lossy()
+                )),
+                json_quote(&snippet)
+            )),
+            Format::Plain | Format::Ansi => {
+                let snippet = highlight_terms(&words, &snippet).into_owned();
+                match path {
+                    Some(path) => println!(
+                        "{}\t{:.4}\t{}\t{}",
+                        hit.doc_id,
+                        hit.score,
+                        path.display(),
+                        snippet
+                    ),
+                    None => println!("{}\t{:.4}\t{}", hit.doc_id, hit.score, snippet),
+                }
+            }
         }
     }
+    if format == Format::Json {
+        println!("[{}]", json.join(","));
+    }
+    Ok(!hits.is_empty())
+}
+
+fn stats_command(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &[])?;
+    let [path] = args.positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    let index = InvertedIndex::open(Path::new(path)).map_err(|err| err.to_string())?;
+    println!("{}", index.stats());
+    Ok(true)
 }
 
-/// Break a string into words
-fn tokenize(text: &str) -> Vec<&str> {
-    text.split(|ch: char| !ch.is_alphanumeric())
-        .filter(|s| !s.is_empty())
-        .collect()
+/// Serves the index over HTTP until the process is killed.
+#[cfg(feature = "serve")]
+fn serve_command(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &["addr"])?;
+    let [path] = args.positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    let addr = args.option("addr").unwrap_or("127.0.0.1:8080");
+    let index = InvertedIndex::open(Path::new(path)).map_err(|err| err.to_string())?;
+    let server = inverted_index::Server::bind(addr, index.into())
+        .map_err(|err| format!("cannot listen on {}: {}", addr, err))?;
+    eprintln!("listening on http://{}", addr);
+    server.run().map_err(|err| err.to_string())?;
+    Ok(true)
 }
 
-/// Highlights all occurrences of `term` in `content` with a <font color"purple">purple</font> color.
-fn highlight(term: &str, content: &str) -> String {
-    let regex = Regex::new(&format!(r"(?i){}", term)).unwrap();
-    let highlighted_content = regex
-        .replace_all(content, |caps: &regex::Captures| {
-            caps[0].to_string().purple().to_string()
-        })
-        .to_string();
+const REPL_HELP: &str = "\
+each line is a query, e.g. `rust -go`, `\"systems language\"` or `(rust OR go) fast`
+    :stats           index statistics
+    :terms <prefix>  indexed terms starting with <prefix>
+    :doc <id>        print a document
+    :history         the lines entered so far
+    :help            this message
+    :quit            leave";
+
+/// Results printed per query in the repl.
+const REPL_LIMIT: usize = 10;
 
-    highlighted_content
+/// Reads queries and commands from stdin until `:quit` or the end of the input. Bad lines
+/// print an error and the session goes on.
+fn repl_command(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &[])?;
+    let [path] = args.positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    let index = InvertedIndex::open(Path::new(path)).map_err(|err| err.to_string())?;
+    colored::control::set_override(std::env::var_os("NO_COLOR").is_none());
+
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("{}\n", REPL_HELP);
+    }
+    let mut history: Vec<String> = Vec::new();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("> ");
+            io::stdout().flush().map_err(|err| err.to_string())?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        history.push(line.to_string());
+        match repl_line(&index, line, &history) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(message) => eprintln!("error: {}", message),
+        }
+    }
+    Ok(true)
 }
 
-#[test]
-fn tokenize_test() {
-    assert_eq!(
-        tokenize("This is\nhedon's tokenize function."),
-        vec!["This", "is", "hedon", "s", "tokenize", "function"]
-    )
+/// Runs one repl line, returning `false` to end the session.
+fn repl_line(index: &InvertedIndex, line: &str, history: &[String]) -> Result<bool, String> {
+    let Some(command) = line.strip_prefix(':') else {
+        let query = Query::parse(line).map_err(|err| err.to_string())?;
+        let options = SearchOptions {
+            limit: Some(REPL_LIMIT),
+            ..SearchOptions::default()
+        };
+        let hits = index.search_query(&query, &options);
+        let words: Vec<&str> = query
+            .positive_terms()
+            .into_iter()
+            .flat_map(tokenize_vec)
+            .collect();
+        for hit in &hits {
+            let snippet = snippet(hit.content.as_deref().unwrap_or_default(), &words);
+            let snippet = highlight_terms(&words, &snippet).into_owned();
+            println!("{}\t{:.4}\t{}", hit.doc_id, hit.score, snippet);
+        }
+        if hits.is_empty() {
+            println!("no results");
+        }
+        return Ok(true);
+    };
+    let (name, argument) = command
+        .split_once(char::is_whitespace)
+        .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+    match name {
+        "quit" | "q" => return Ok(false),
+        "help" => println!("{}", REPL_HELP),
+        "stats" => println!("{}", index.stats()),
+        "history" => {
+            for (i, entry) in history.iter().enumerate() {
+                println!("{:>4}  {}", i + 1, entry);
+            }
+        }
+        "terms" => {
+            let mut terms: Vec<&str> = index
+                .terms()
+                .filter(|term| term.starts_with(argument))
+                .collect();
+            terms.sort_unstable();
+            println!("{}", terms.join("\n"));
+        }
+        "doc" => {
+            let id: usize = argument
+                .parse()
+                .map_err(|_| format!("invalid document id \"{}\"", argument))?;
+            let content = index
+                .get(id)
+                .ok_or_else(|| IndexError::DocumentNotFound(id).to_string())?;
+            if let Some(path) = index.document_path(id) {
+                println!("{}", path.display());
+            }
+            println!("{}", content);
+        }
+        _ => return Err(format!("unknown command :{}, try :help", name)),
+    }
+    Ok(true)
 }
 
-#[test]
-fn highlight_test() {
-    assert_eq!(
-        highlight("programming", "I like programming with Rust Programming"),
-        "I like \u{1b}[35mprogramming\u{1b}[0m with Rust \u{1b}[35mProgramming\u{1b}[0m"
-    );
+fn demo() {
+    // println!("Hello, world!");
+
+    // Keep escape codes out of the output when it is piped into a file.
+    let mut index = InvertedIndex::builder()
+        .no_color(!io::stdout().is_terminal())
+        .build();
+    index.add(1, "Rust is safe and fast.");
+    index.add(2, "Rust is a systems programming language.");
+    index.add(3, "Programming in Rust is fun.");
+    println!("{}\n", index.stats());
+
+    // Unlike `add`, `try_add` refuses to replace a document.
+    if let Err(err) = index.try_add(1, "Rust again.") {
+        println!("error: {}\n", err);
+    }
+
+    // query "Rust"
+    let results = index.query("Rust");
+    for result in results {
+        println!("{}", result);
+    }
+
+    println!();
+
+    // query "Programming"
+    let results = index.query("Programming");
+    for result in results {
+        println!("{}", result);
+    }
 }
@@ -0,0 +1,286 @@
+//! Exporting the index as a sparse term-document matrix, e.g. for a machine learning
+//! pipeline.
+//!
+//! Terms are numbered from 0 in ascending order and documents from 0 in ascending doc id
+//! order. [`InvertedIndex::export_terms`] and [`InvertedIndex::export_documents`] write those
+//! numberings, so the matrix can be mapped back to terms and doc ids.
+
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::postings::Posting;
+use crate::search::idf;
+use std::borrow::Cow;
+use std::io::{self, BufWriter, Write};
+
+/// How [`InvertedIndex::export_matrix`] lays out the entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatrixLayout {
+    /// The Matrix Market coordinate format, with a row per term and a column per document,
+    /// numbered from 1 as the format requires.
+    #[default]
+    MatrixMarket,
+    /// CSV with a `term_id,doc_id,value` header and one triplet per line, where `doc_id` is
+    /// the document's own id.
+    Csv,
+}
+
+/// The value of each entry of an exported matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatrixValues {
+    /// The number of occurrences of the term in the document, an integer.
+    #[default]
+    TermFrequency,
+    /// The term frequency times the inverse document frequency, `tf * (1 + ln(N / df))`.
+    TfIdf,
+}
+
+/// Options of [`InvertedIndex::export_matrix`]. The default is a Matrix Market file of term
+/// frequencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatrixFormat {
+    pub layout: MatrixLayout,
+    pub values: MatrixValues,
+}
+
+impl InvertedIndex {
+    /// Writes the term-document matrix, one entry per term and document containing it.
+    ///
+    /// # Returns
+    /// [`IndexError::Write`] if writing fails.
+    ///
+    /// # Notes
+    /// Entries are sorted by term id, then by document, so exporting the same index twice
+    /// gives the same bytes. Soft-deleted documents are left out.
+    pub fn export_matrix<W: Write>(
+        &self,
+        writer: W,
+        format: MatrixFormat,
+    ) -> Result<(), IndexError> {
+        self.write_matrix(BufWriter::new(writer), format)
+            .map_err(IndexError::Write)
+    }
+
+    fn write_matrix(&self, mut out: impl Write, format: MatrixFormat) -> io::Result<()> {
+        let terms = self.live_terms();
+        let doc_ids = self.sorted_doc_ids();
+        let doc_count = self.documents.len();
+        let value = |posting: &Posting, doc_freq: usize| match format.values {
+            MatrixValues::TermFrequency => posting.term_freq().to_string(),
+            MatrixValues::TfIdf => {
+                (posting.term_freq() as f64 * idf(doc_count, doc_freq)).to_string()
+            }
+        };
+        match format.layout {
+            MatrixLayout::MatrixMarket => {
+                let field = match format.values {
+                    MatrixValues::TermFrequency => "integer",
+                    MatrixValues::TfIdf => "real",
+                };
+                let entries: usize = terms.iter().map(|(_, postings)| postings.len()).sum();
+                writeln!(out, "%%MatrixMarket matrix coordinate {field} general")?;
+                writeln!(out, "{} {} {}", terms.len(), doc_ids.len(), entries)?;
+                for (term_id, (_, postings)) in terms.iter().enumerate() {
+                    for posting in postings.iter() {
+                        // Every live posting belongs to a stored document.
+                        let column = doc_ids.binary_search(&posting.doc_id).unwrap_or_default();
+                        let value = value(posting, postings.len());
+                        writeln!(out, "{} {} {}", term_id + 1, column + 1, value)?;
+                    }
+                }
+            }
+            MatrixLayout::Csv => {
+                writeln!(out, "term_id,doc_id,value")?;
+                for (term_id, (_, postings)) in terms.iter().enumerate() {
+                    for posting in postings.iter() {
+                        let value = value(posting, postings.len());
+                        writeln!(out, "{},{},{}", term_id, posting.doc_id, value)?;
+                    }
+                }
+            }
+        }
+        out.flush()
+    }
+
+    /// Writes the terms of [`export_matrix`](Self::export_matrix) as CSV with a
+    /// `term_id,term` header, in term id order. Terms holding a comma, quote or line break
+    /// are quoted.
+    ///
+    /// # Returns
+    /// [`IndexError::Write`] if writing fails.
+    pub fn export_terms<W: Write>(&self, writer: W) -> Result<(), IndexError> {
+        let mut out = BufWriter::new(writer);
+        let terms = self.live_terms();
+        write_csv(
+            &mut out,
+            "term_id,term",
+            terms.iter().map(|(term, _)| csv_field(term)),
+        )
+    }
+
+    /// Writes the documents of [`export_matrix`](Self::export_matrix) as CSV with a
+    /// `doc_index,doc_id` header, where `doc_index` numbers the Matrix Market columns from 0.
+    ///
+    /// # Returns
+    /// [`IndexError::Write`] if writing fails.
+    pub fn export_documents<W: Write>(&self, writer: W) -> Result<(), IndexError> {
+        let mut out = BufWriter::new(writer);
+        write_csv(&mut out, "doc_index,doc_id", self.sorted_doc_ids())
+    }
+
+    /// The terms with live postings in ascending order, numbered by their position.
+    fn live_terms(&self) -> Vec<(&str, Cow<'_, [Posting]>)> {
+        let mut terms: Vec<(&str, Cow<'_, [Posting]>)> = self
+            .indexes
+            .keys()
+            .filter_map(|term| Some((term.as_str(), self.live_postings(term)?)))
+            .collect();
+        terms.sort_unstable_by_key(|(term, _)| *term);
+        terms
+    }
+
+    fn sorted_doc_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.documents.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Writes `header`, then each of `values` after its 0-based number.
+fn write_csv<T: std::fmt::Display>(
+    out: &mut impl Write,
+    header: &str,
+    values: impl IntoIterator<Item = T>,
+) -> Result<(), IndexError> {
+    let write = || {
+        writeln!(out, "{header}")?;
+        for (i, value) in values.into_iter().enumerate() {
+            writeln!(out, "{i},{value}")?;
+        }
+        out.flush()
+    };
+    write().map_err(IndexError::Write)
+}
+
+/// `value` as a CSV field, quoted if it needs to be.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[test]
+fn export_matrix_round_trip_test() {
+    use std::collections::HashMap;
+
+    let mut index = InvertedIndex::new();
+    index.add(10, "Rust is fast, Rust is safe");
+    index.add(3, "Go is simple");
+    index.add(7, "fast and simple");
+
+    let export = |format: MatrixFormat| {
+        let mut bytes = Vec::new();
+        index.export_matrix(&mut bytes, format).unwrap();
+        String::from_utf8(bytes).unwrap()
+    };
+    let mut terms = Vec::new();
+    index.export_terms(&mut terms).unwrap();
+    let terms: Vec<String> = String::from_utf8(terms)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split_once(',').unwrap().1.to_string())
+        .collect();
+    let mut docs = Vec::new();
+    index.export_documents(&mut docs).unwrap();
+    let docs: Vec<usize> = String::from_utf8(docs)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split_once(',').unwrap().1.parse().unwrap())
+        .collect();
+    assert_eq!(docs, vec![3, 7, 10]);
+
+    let expected: HashMap<(String, usize), usize> = index
+        .indexes
+        .iter()
+        .flat_map(|(term, postings)| {
+            postings
+                .iter()
+                .map(|posting| ((term.clone(), posting.doc_id), posting.term_freq()))
+        })
+        .collect();
+
+    let market = export(MatrixFormat::default());
+    let mut lines = market.lines();
+    assert_eq!(
+        lines.next(),
+        Some("%%MatrixMarket matrix coordinate integer general")
+    );
+    assert_eq!(
+        lines.next(),
+        Some(format!("{} 3 {}", terms.len(), expected.len()).as_str())
+    );
+    let mut counts = HashMap::new();
+    let mut previous = (0, 0);
+    for line in lines {
+        let fields: Vec<usize> = line
+            .split(' ')
+            .map(|field| field.parse().unwrap())
+            .collect();
+        assert!((fields[0], fields[1]) > previous, "{line}");
+        previous = (fields[0], fields[1]);
+        counts.insert(
+            (terms[fields[0] - 1].clone(), docs[fields[1] - 1]),
+            fields[2],
+        );
+    }
+    assert_eq!(counts, expected);
+
+    let csv = export(MatrixFormat {
+        layout: MatrixLayout::Csv,
+        values: MatrixValues::TermFrequency,
+    });
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("term_id,doc_id,value"));
+    let counts: HashMap<(String, usize), usize> = lines
+        .map(|line| {
+            let fields: Vec<usize> = line
+                .split(',')
+                .map(|field| field.parse().unwrap())
+                .collect();
+            ((terms[fields[0]].clone(), fields[1]), fields[2])
+        })
+        .collect();
+    assert_eq!(counts, expected);
+
+    let tf_idf = export(MatrixFormat {
+        layout: MatrixLayout::Csv,
+        values: MatrixValues::TfIdf,
+    });
+    let rust = terms.iter().position(|term| term == "rust").unwrap();
+    let line = format!("{},10,{}", rust, 2.0 * idf(3, 1));
+    assert!(tf_idf.lines().any(|l| l == line), "{tf_idf}");
+    assert_eq!(
+        tf_idf,
+        export(MatrixFormat {
+            layout: MatrixLayout::Csv,
+            values: MatrixValues::TfIdf
+        })
+    );
+    assert!(
+        export(MatrixFormat {
+            layout: MatrixLayout::MatrixMarket,
+            values: MatrixValues::TfIdf
+        })
+        .starts_with("%%MatrixMarket matrix coordinate real general\n")
+    );
+}
+
+#[test]
+fn csv_field_test() {
+    assert_eq!(csv_field("rust"), "rust");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
@@ -0,0 +1,209 @@
+use crate::docstore::Content;
+use crate::index::{Document, InvertedIndex};
+use crate::ngram::NgramIndex;
+use crate::postings::Posting;
+use crate::surface::SurfaceForms;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// Estimated heap usage of an index, in bytes, see [`InvertedIndex::memory_usage`].
+///
+/// The numbers count allocated capacity, not just what is in use, so slack left behind by
+/// removals shows up until [`InvertedIndex::shrink`] is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBreakdown {
+    /// The term hash table and the term strings.
+    pub term_dictionary: usize,
+    /// The posting lists of all terms.
+    pub postings: usize,
+    /// The document hash table, the stored contents and file paths.
+    pub documents: usize,
+    /// The trigram table of the [n-gram index](crate::IndexBuilder::ngram_index), which
+    /// holds a copy of each term per trigram in it. 0 when it is disabled.
+    pub ngrams: usize,
+    /// The terms and frequencies of each document kept by the
+    /// [forward index](crate::IndexBuilder::forward_index). 0 when it is disabled.
+    pub forward_index: usize,
+    /// The original spellings of the terms kept for
+    /// [display](crate::IndexBuilder::surface_forms). 0 when they are not kept.
+    pub surface_forms: usize,
+}
+
+impl MemoryBreakdown {
+    pub fn total(&self) -> usize {
+        self.term_dictionary
+            + self.postings
+            + self.documents
+            + self.ngrams
+            + self.forward_index
+            + self.surface_forms
+    }
+}
+
+impl fmt::Display for MemoryBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "term dictionary: {} bytes", self.term_dictionary)?;
+        writeln!(f, "postings:        {} bytes", self.postings)?;
+        writeln!(f, "documents:       {} bytes", self.documents)?;
+        if self.ngrams > 0 {
+            writeln!(f, "n-grams:         {} bytes", self.ngrams)?;
+        }
+        if self.forward_index > 0 {
+            writeln!(f, "forward index:   {} bytes", self.forward_index)?;
+        }
+        if self.surface_forms > 0 {
+            writeln!(f, "surface forms:   {} bytes", self.surface_forms)?;
+        }
+        write!(f, "total:           {} bytes", self.total())
+    }
+}
+
+/// Bytes allocated by a hash map's table: one entry plus one control byte per bucket.
+fn table_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+}
+
+impl InvertedIndex {
+    /// Estimates how many bytes the term dictionary, the postings and the document store use.
+    pub fn memory_usage(&self) -> MemoryBreakdown {
+        let term_dictionary =
+            table_bytes(&self.indexes) + self.indexes.keys().map(String::capacity).sum::<usize>();
+        let postings = self
+            .indexes
+            .values()
+            .map(|postings| {
+                size_of::<Vec<Posting>>()
+                    + postings.capacity() * size_of::<Posting>()
+                    + postings
+                        .iter()
+                        .map(|posting| posting.positions.capacity() * size_of::<usize>())
+                        .sum::<usize>()
+            })
+            .sum();
+        let documents = table_bytes(&self.documents)
+            + self
+                .documents
+                .values()
+                .map(|doc| {
+                    size_of::<Document>()
+                        + match &doc.content {
+                            Content::Memory(content) => content.capacity(),
+                            Content::Compressed { bytes, .. } => bytes.len(),
+                            Content::Stored { .. } | Content::Omitted => 0,
+                        }
+                        + doc.path.as_ref().map_or(0, |path| path.as_os_str().len())
+                        + doc.fields.capacity() * size_of::<(String, usize)>()
+                        + doc.offsets.capacity() * size_of::<(usize, usize)>()
+                        + doc
+                            .fields
+                            .iter()
+                            .map(|(name, _)| name.capacity())
+                            .sum::<usize>()
+                        + table_bytes(&doc.metadata)
+                        + doc
+                            .metadata
+                            .iter()
+                            .map(|(key, value)| key.capacity() + value.capacity())
+                            .sum::<usize>()
+                })
+                .sum::<usize>();
+        let forward_index = self
+            .documents
+            .values()
+            .map(|doc| {
+                doc.terms.capacity() * size_of::<(String, u32)>()
+                    + doc
+                        .terms
+                        .iter()
+                        .map(|(term, _)| term.capacity())
+                        .sum::<usize>()
+            })
+            .sum();
+        MemoryBreakdown {
+            term_dictionary,
+            postings,
+            documents,
+            ngrams: self.ngrams.as_ref().map_or(0, NgramIndex::memory_usage),
+            forward_index,
+            surface_forms: self
+                .surface_forms
+                .as_ref()
+                .map_or(0, SurfaceForms::memory_usage),
+        }
+    }
+
+    /// Releases the slack capacity held by the index, e.g. after many removals.
+    ///
+    /// Terms with an empty posting list are dropped. Query results are unaffected. Posting
+    /// lists and documents still shared with a [snapshot](Self::snapshot) are left as they
+    /// are, since shrinking them would mean copying them.
+    pub fn shrink(&mut self) {
+        self.indexes.retain(|_, postings| !postings.is_empty());
+        for postings in self.indexes.values_mut().filter_map(Arc::get_mut) {
+            postings.shrink_to_fit();
+            for posting in postings.iter_mut() {
+                posting.positions.shrink_to_fit();
+            }
+        }
+        self.indexes.shrink_to_fit();
+        for doc in self.documents.values_mut().filter_map(Arc::get_mut) {
+            if let Content::Memory(content) = &mut doc.content {
+                content.shrink_to_fit();
+            }
+            doc.offsets.shrink_to_fit();
+            doc.terms.shrink_to_fit();
+            doc.metadata.shrink_to_fit();
+        }
+        self.documents.shrink_to_fit();
+        self.analyzer.stem_cache.shrink_to_fit();
+        if let Some(ngrams) = &mut self.ngrams {
+            ngrams.shrink_to_fit();
+        }
+        if let Some(forms) = &mut self.surface_forms {
+            forms.shrink_to_fit();
+        }
+    }
+}
+
+#[test]
+fn shrink_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::new();
+    for id in 0..2000 {
+        index.add(id, &format!("common word{id} and more words"));
+    }
+    let queried = index.doc_freq("common");
+    let full = index.memory_usage();
+
+    index.retain(|id, _| id < 20);
+    let before = index.memory_usage();
+    let results_before = index.query("common");
+    index.shrink();
+    let after = index.memory_usage();
+
+    assert!(after.total() < before.total());
+    assert!(after.term_dictionary < before.term_dictionary);
+    assert!(after.documents < before.documents);
+    assert!(before.total() <= full.total());
+    assert_eq!(queried, 2000);
+    assert_eq!(index.query("common"), results_before);
+    assert_eq!(results_before.len(), 20);
+}
+
+#[test]
+fn memory_breakdown_display_test() {
+    let breakdown = MemoryBreakdown {
+        term_dictionary: 10,
+        postings: 20,
+        documents: 30,
+        ngrams: 0,
+        forward_index: 0,
+        surface_forms: 0,
+    };
+    assert_eq!(
+        breakdown.to_string(),
+        "term dictionary: 10 bytes\npostings:        20 bytes\ndocuments:       30 bytes\ntotal:           60 bytes"
+    );
+}
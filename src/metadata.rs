@@ -0,0 +1,114 @@
+//! Key-value metadata stored with documents, such as an author or tags, and filtering query
+//! results by it.
+
+use crate::analysis::TermBuffer;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+impl InvertedIndex {
+    /// Adds a document like [`add`](Self::add), storing `meta` with it.
+    ///
+    /// # Notes
+    /// The metadata is not indexed, so queries cannot match it, but
+    /// [`query_filtered`](Self::query_filtered) can select documents by it. Replacing the
+    /// document, with `add` or [`update`](Self::update), discards it.
+    pub fn add_with_meta(&mut self, id: usize, content: &str, meta: HashMap<String, String>) {
+        self.record(LogEntry::Meta {
+            id,
+            content,
+            meta: &meta,
+        });
+        self.index_document(id, content.to_string(), &mut TermBuffer::default());
+        if let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) {
+            doc.metadata = meta;
+        }
+    }
+
+    /// The metadata given to [`add_with_meta`](Self::add_with_meta) for document `id`, empty
+    /// for documents added otherwise, or `None` if no document has this id.
+    pub fn metadata(&self, id: usize) -> Option<&HashMap<String, String>> {
+        self.documents.get(&id).map(|doc| &doc.metadata)
+    }
+
+    /// Finds the documents containing `term` whose metadata passes `predicate`, e.g. the
+    /// "rust" documents written by a given author.
+    ///
+    /// # Returns
+    /// The ids of the matching documents in ascending order. Documents without metadata are
+    /// tested with an empty map.
+    pub fn query_filtered(
+        &self,
+        term: &str,
+        predicate: impl Fn(&HashMap<String, String>) -> bool,
+    ) -> Vec<usize> {
+        let Some(postings) = self.term_postings(term) else {
+            return Vec::new();
+        };
+        postings
+            .iter()
+            .filter(|posting| {
+                self.documents
+                    .get(&posting.doc_id)
+                    .is_some_and(|doc| predicate(&doc.metadata))
+            })
+            .map(|posting| posting.doc_id)
+            .collect()
+    }
+}
+
+#[test]
+fn query_filtered_test() {
+    let meta = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let mut index = InvertedIndex::new();
+    index.add_with_meta(
+        1,
+        "Rust ownership",
+        meta(&[("author", "alice"), ("year", "2021")]),
+    );
+    index.add_with_meta(
+        2,
+        "Rust lifetimes",
+        meta(&[("author", "bob"), ("year", "2023")]),
+    );
+    index.add_with_meta(
+        3,
+        "Rust traits",
+        meta(&[("author", "alice"), ("year", "2024")]),
+    );
+    index.add_with_meta(4, "Go channels", meta(&[("author", "alice")]));
+    index.add(5, "Rust without metadata");
+
+    let by_alice =
+        |meta: &HashMap<String, String>| meta.get("author").is_some_and(|a| a == "alice");
+    assert_eq!(index.query_filtered("rust", by_alice), vec![1, 3]);
+    assert_eq!(
+        index.query_filtered("rust", |meta| meta
+            .get("year")
+            .is_some_and(|year| year.as_str() >= "2023")),
+        vec![2, 3]
+    );
+    assert_eq!(
+        index.query_filtered("rust", |meta| meta.is_empty()),
+        vec![5]
+    );
+    assert_eq!(index.query_filtered("rust", |_| true).len(), 4);
+    assert!(index.query_filtered("python", |_| true).is_empty());
+    // The metadata itself is not searchable.
+    assert!(index.query("alice").is_empty());
+    assert_eq!(index.metadata(4).unwrap()["author"], "alice");
+    assert!(index.metadata(5).unwrap().is_empty());
+    assert_eq!(index.metadata(9), None);
+
+    // Replacing a document discards its metadata.
+    index.update(3, "Rust traits, revised").unwrap();
+    assert_eq!(index.query_filtered("rust", by_alice), vec![1]);
+    index.remove(1);
+    assert!(index.query_filtered("rust", by_alice).is_empty());
+}
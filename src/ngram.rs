@@ -0,0 +1,207 @@
+//! Substring search over the vocabulary, optionally sped up by a character n-gram index, see
+//! [`IndexBuilder::ngram_index`](crate::IndexBuilder::ngram_index).
+
+use crate::index::InvertedIndex;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+
+/// Characters per n-gram.
+const NGRAM_SIZE: usize = 3;
+
+/// Maps every trigram of the indexed terms to the terms containing it.
+#[derive(Debug, Default)]
+pub(crate) struct NgramIndex {
+    grams: HashMap<String, HashSet<String>>,
+}
+
+impl NgramIndex {
+    /// Records the trigrams of a term that was just added to the vocabulary.
+    pub(crate) fn insert(&mut self, term: &str) {
+        for gram in ngrams(term) {
+            self.grams.entry(gram).or_default().insert(term.to_string());
+        }
+    }
+
+    /// Forgets a term that was dropped from the vocabulary.
+    pub(crate) fn remove(&mut self, term: &str) {
+        for gram in ngrams(term) {
+            if let Some(terms) = self.grams.get_mut(&gram) {
+                terms.remove(term);
+                if terms.is_empty() {
+                    self.grams.remove(&gram);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.grams.clear();
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        for terms in self.grams.values_mut() {
+            terms.shrink_to_fit();
+        }
+        self.grams.shrink_to_fit();
+    }
+
+    /// The number of (trigram, term) entries.
+    pub(crate) fn len(&self) -> usize {
+        self.grams.values().map(HashSet::len).sum()
+    }
+
+    /// Estimated heap usage in bytes, counted like [`MemoryBreakdown`](crate::MemoryBreakdown).
+    pub(crate) fn memory_usage(&self) -> usize {
+        let entry = size_of::<String>() + 1;
+        self.grams.capacity() * (entry + size_of::<HashSet<String>>())
+            + self
+                .grams
+                .iter()
+                .map(|(gram, terms)| {
+                    gram.capacity()
+                        + terms.capacity() * entry
+                        + terms.iter().map(String::capacity).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    /// The terms containing every trigram of `fragment`, which has at least one.
+    fn candidates(&self, fragment: &str) -> HashSet<&str> {
+        let mut sets: Vec<&HashSet<String>> = Vec::new();
+        for gram in ngrams(fragment) {
+            match self.grams.get(&gram) {
+                Some(terms) => sets.push(terms),
+                None => return HashSet::new(),
+            }
+        }
+        sets.sort_by_key(|terms| terms.len());
+        let Some((first, rest)) = sets.split_first() else {
+            return HashSet::new();
+        };
+        first
+            .iter()
+            .filter(|term| rest.iter().all(|terms| terms.contains(*term)))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// The distinct character trigrams of `text`, none if it is shorter than a trigram.
+fn ngrams(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(NGRAM_SIZE)
+        .map(|gram| gram.iter().collect())
+        .collect()
+}
+
+impl InvertedIndex {
+    /// Finds the documents with a term containing `fragment`, e.g. "gram" for documents
+    /// with "programming" or "grammar".
+    ///
+    /// # Parameters
+    /// - `fragment`: Matched against the indexed terms, lowercased unless the index is case
+    ///   sensitive. It is not stemmed, and with stemming on the terms it is matched against
+    ///   are stems.
+    ///
+    /// # Returns
+    /// The ids of the matching documents in ascending order. Empty for an empty fragment.
+    ///
+    /// # Notes
+    /// With the [n-gram index](crate::IndexBuilder::ngram_index) enabled, candidate terms are
+    /// those containing every trigram of the fragment, and only they are checked. Fragments
+    /// shorter than a trigram, and indexes without n-grams, scan the whole vocabulary.
+    pub fn query_substring(&self, fragment: &str) -> Vec<usize> {
+        if fragment.is_empty() {
+            return Vec::new();
+        }
+        let fragment = if self.analyzer.case_sensitive {
+            fragment.to_string()
+        } else {
+            fragment.to_lowercase()
+        };
+        let terms: Vec<&str> = match &self.ngrams {
+            Some(ngrams) if fragment.chars().count() >= NGRAM_SIZE => ngrams
+                .candidates(&fragment)
+                .into_iter()
+                .filter(|term| term.contains(&fragment))
+                .collect(),
+            _ => self
+                .indexes
+                .keys()
+                .map(String::as_str)
+                .filter(|term| term.contains(&fragment))
+                .collect(),
+        };
+        let mut ids: Vec<usize> = terms
+            .into_iter()
+            .filter_map(|term| self.live_postings(term))
+            .flat_map(|postings| {
+                postings
+                    .iter()
+                    .map(|posting| posting.doc_id)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[test]
+fn ngrams_test() {
+    let mut grams: Vec<String> = ngrams("grammar").into_iter().collect();
+    grams.sort_unstable();
+    assert_eq!(grams, ["amm", "gra", "mar", "mma", "ram"]);
+    assert!(ngrams("go").is_empty());
+    assert_eq!(ngrams("日本語").len(), 1);
+}
+
+#[test]
+fn query_substring_test() {
+    let build = |ngram_index: bool| {
+        let mut index = InvertedIndex::builder().ngram_index(ngram_index).build();
+        index.add(1, "Programming in Rust");
+        index.add(2, "English grammar");
+        index.add(3, "A kilogram of flour");
+        index.add(4, "Go is simple");
+        index
+    };
+    let plain = build(false);
+    let mut index = build(true);
+    for fragment in [
+        "gram", "GRAM", "ram", "ra", "o", "grammar", "xyz", "mmi", "",
+    ] {
+        assert_eq!(
+            index.query_substring(fragment),
+            plain.query_substring(fragment),
+            "{fragment}"
+        );
+    }
+    assert_eq!(index.query_substring("gram"), vec![1, 2, 3]);
+    assert_eq!(index.query_substring("ra"), vec![1, 2, 3]);
+    assert_eq!(index.query_substring("o"), vec![1, 3, 4]);
+    assert!(index.query_substring("xyz").is_empty());
+
+    // The n-grams follow removals and replacements.
+    let entries = index.stats().ngram_entries;
+    assert!(entries > 0);
+    assert_eq!(plain.stats().ngram_entries, 0);
+    assert!(index.memory_usage().ngrams > 0);
+    index.remove(2);
+    assert_eq!(index.query_substring("gram"), vec![1, 3]);
+    index.update(3, "A kilo of flour").unwrap();
+    assert_eq!(index.query_substring("gram"), vec![1]);
+    assert!(index.stats().ngram_entries < entries);
+    index.remove_term("programming");
+    assert!(index.query_substring("gram").is_empty());
+    let mut fresh = InvertedIndex::builder().ngram_index(true).build();
+    fresh.add(1, "Programming in Rust");
+    fresh.remove_term("programming");
+    fresh.add(3, "A kilo of flour");
+    fresh.add(4, "Go is simple");
+    assert_eq!(index.stats().ngram_entries, fresh.stats().ngram_entries);
+    index.clear();
+    assert_eq!(index.stats().ngram_entries, 0);
+}
@@ -0,0 +1,183 @@
+//! Numeric values stored with documents, such as a year or a price, and range queries over
+//! them.
+
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::collections::{BTreeMap, HashMap};
+
+/// The values of one numeric field, see [`InvertedIndex::add_numeric`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct NumericField {
+    /// Each document's value.
+    pub(crate) values: HashMap<usize, f64>,
+    /// The ids holding each value in ascending order, keyed by [`sortable`] so that the keys
+    /// are ordered like the values.
+    sorted: BTreeMap<u64, Vec<usize>>,
+}
+
+impl NumericField {
+    /// Sets the value of document `id`, replacing its previous one.
+    pub(crate) fn insert(&mut self, id: usize, value: f64) {
+        if let Some(previous) = self.values.insert(id, value) {
+            self.unsort(id, previous);
+        }
+        let ids = self.sorted.entry(sortable(value)).or_default();
+        if let Err(i) = ids.binary_search(&id) {
+            ids.insert(i, id);
+        }
+    }
+
+    /// Drops the value of document `id`, if it has one.
+    fn remove(&mut self, id: usize) {
+        if let Some(value) = self.values.remove(&id) {
+            self.unsort(id, value);
+        }
+    }
+
+    fn unsort(&mut self, id: usize, value: f64) {
+        let key = sortable(value);
+        if let Some(ids) = self.sorted.get_mut(&key) {
+            if let Ok(i) = ids.binary_search(&id) {
+                ids.remove(i);
+            }
+            if ids.is_empty() {
+                self.sorted.remove(&key);
+            }
+        }
+    }
+}
+
+/// Maps `value` to an integer ordered like the numbers, flipping the sign bit of positive
+/// values and every bit of negative ones. `-0.0` maps like `0.0`.
+fn sortable(value: f64) -> u64 {
+    let bits = (value + 0.0).to_bits();
+    if bits >> 63 == 0 {
+        bits | 1 << 63
+    } else {
+        !bits
+    }
+}
+
+impl InvertedIndex {
+    /// Stores the number `value` as the `field` of document `id`, e.g. its year or price,
+    /// for [`query_range`](Self::query_range), replacing any value the field had.
+    ///
+    /// # Returns
+    /// [`IndexError::DocumentNotFound`] if no document has this id, and
+    /// [`IndexError::NotANumber`] if `value` is NaN, which has no place in a range.
+    ///
+    /// # Notes
+    /// The values are not indexed as terms, so text queries do not match them. Replacing
+    /// or removing the document discards them.
+    pub fn add_numeric(&mut self, id: usize, field: &str, value: f64) -> Result<(), IndexError> {
+        if !self.documents.contains_key(&id) {
+            return Err(IndexError::DocumentNotFound(id));
+        }
+        if value.is_nan() {
+            return Err(IndexError::NotANumber(field.to_string()));
+        }
+        self.record(LogEntry::Numeric { id, field, value });
+        match self.numeric.get_mut(field) {
+            Some(values) => values.insert(id, value),
+            None => {
+                let mut values = NumericField::default();
+                values.insert(id, value);
+                self.numeric.insert(field.to_string(), values);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the documents whose `field` holds a value from `min` to `max`, both included,
+    /// e.g. the documents from 2000 to 2010.
+    ///
+    /// # Returns
+    /// The ids of the matching documents in ascending order. Empty if no document has the
+    /// field, or if `min` is greater than `max` or either is NaN.
+    pub fn query_range(&self, field: &str, min: f64, max: f64) -> Vec<usize> {
+        let Some(values) = self.numeric.get(field) else {
+            return Vec::new();
+        };
+        if min.is_nan() || max.is_nan() || min > max {
+            return Vec::new();
+        }
+        let mut ids: Vec<usize> = values
+            .sorted
+            .range(sortable(min)..=sortable(max))
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Drops the numeric values of a document no longer stored.
+    pub(crate) fn forget_numeric(&mut self, id: usize) {
+        self.numeric.retain(|_, values| {
+            values.remove(id);
+            !values.values.is_empty()
+        });
+    }
+}
+
+#[test]
+fn query_range_test() {
+    let mut index = InvertedIndex::new();
+    for (id, year, price) in [
+        (1, 1999.0, 12.5),
+        (2, 2000.0, -3.0),
+        (3, 2005.0, 0.0),
+        (4, 2010.0, 99.99),
+        (5, 2011.0, f64::INFINITY),
+    ] {
+        index.add(id, &format!("Release {id}"));
+        index.add_numeric(id, "year", year).unwrap();
+        index.add_numeric(id, "price", price).unwrap();
+    }
+    // Both bounds are included.
+    assert_eq!(index.query_range("year", 2000.0, 2010.0), vec![2, 3, 4]);
+    assert_eq!(index.query_range("year", 2005.0, 2005.0), vec![3]);
+    assert_eq!(
+        index.query_range("year", 2000.5, 2004.5),
+        Vec::<usize>::new()
+    );
+    assert!(index.query_range("year", 2010.0, 2000.0).is_empty());
+    assert!(index.query_range("year", f64::NAN, 2000.0).is_empty());
+    assert!(index.query_range("pages", 0.0, 100.0).is_empty());
+    // Negative values, zeros of either sign and infinities sort like numbers.
+    assert_eq!(index.query_range("price", -5.0, -0.0), vec![2, 3]);
+    assert_eq!(index.query_range("price", 0.0, 50.0), vec![1, 3]);
+    assert_eq!(
+        index.query_range("price", f64::NEG_INFINITY, f64::INFINITY),
+        vec![1, 2, 3, 4, 5]
+    );
+    assert!(matches!(
+        index.add_numeric(9, "year", 2000.0),
+        Err(IndexError::DocumentNotFound(9))
+    ));
+    assert!(matches!(
+        index.add_numeric(1, "year", f64::NAN),
+        Err(IndexError::NotANumber(field)) if field == "year"
+    ));
+    assert_eq!(index.query_range("year", 1999.0, 1999.0), vec![1]);
+
+    // A new value replaces the old one, and removing or replacing a document drops its
+    // values.
+    index.add_numeric(1, "year", 2003.0).unwrap();
+    assert_eq!(index.query_range("year", 1990.0, 2006.0), vec![1, 2, 3]);
+    index.remove(2);
+    index.update(3, "Re-released").unwrap();
+    assert_eq!(index.query_range("year", 1990.0, 2006.0), vec![1]);
+    index.retain(|id, _| id != 1);
+    index.mark_deleted(4);
+    assert_eq!(index.query_range("year", 1990.0, 2020.0), vec![5]);
+
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.numeric, index.numeric);
+    assert_eq!(loaded.query_range("price", 100.0, f64::INFINITY), vec![5]);
+
+    index.clear();
+    assert!(index.numeric.is_empty());
+}
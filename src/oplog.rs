@@ -0,0 +1,565 @@
+//! An append-only log of changes next to a saved index, see [`InvertedIndex::open_log`].
+//!
+//! The log lives at the snapshot's path with `.log` appended. It starts with the magic bytes
+//! `IILG` and a little-endian `u32` format version, followed by one entry per change: a
+//! varint byte length, then the entry itself, encoded like [`save`](InvertedIndex::save)
+//! encodes integers and strings. An entry cut short by a crash is dropped on the next open.
+
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::persist::{Decoder, Encoder, corrupt};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"IILG";
+const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 8;
+
+/// A change recorded in the log, holding what replaying it needs. Terms are logged
+/// analyzed, so that replaying does not analyze them again.
+pub(crate) enum LogEntry<'a> {
+    Add {
+        id: usize,
+        content: &'a str,
+    },
+    Remove {
+        id: usize,
+    },
+    Clear,
+    Fields {
+        id: usize,
+        fields: &'a [(&'a str, &'a str)],
+    },
+    Phrases {
+        id: usize,
+        content: &'a str,
+        phrases: &'a [&'a str],
+    },
+    Path {
+        id: usize,
+        path: &'a Path,
+    },
+    RemoveTerm {
+        term: &'a str,
+    },
+    Meta {
+        id: usize,
+        content: &'a str,
+        meta: &'a HashMap<String, String>,
+    },
+    StopWords {
+        terms: &'a [String],
+    },
+    Numeric {
+        id: usize,
+        field: &'a str,
+        value: f64,
+    },
+    ImportTerm {
+        term: &'a str,
+        ids: &'a [usize],
+    },
+}
+
+impl LogEntry<'_> {
+    /// Writes the entry as its tag followed by its values.
+    fn encode(&self, out: &mut Encoder<Vec<u8>>) -> io::Result<()> {
+        match *self {
+            LogEntry::Add { id, content } => {
+                out.varint(0)?;
+                out.varint(id)?;
+                out.string(content)
+            }
+            LogEntry::Remove { id } => {
+                out.varint(1)?;
+                out.varint(id)
+            }
+            LogEntry::Clear => out.varint(2),
+            LogEntry::Fields { id, fields } => {
+                out.varint(3)?;
+                out.varint(id)?;
+                out.varint(fields.len())?;
+                for (name, text) in fields {
+                    out.string(name)?;
+                    out.string(text)?;
+                }
+                Ok(())
+            }
+            LogEntry::Phrases {
+                id,
+                content,
+                phrases,
+            } => {
+                out.varint(4)?;
+                out.varint(id)?;
+                out.string(content)?;
+                out.varint(phrases.len())?;
+                for phrase in phrases {
+                    out.string(phrase)?;
+                }
+                Ok(())
+            }
+            LogEntry::Path { id, path } => {
+                out.varint(5)?;
+                out.varint(id)?;
+                out.string(&path.to_string_lossy())
+            }
+            LogEntry::RemoveTerm { term } => {
+                out.varint(6)?;
+                out.string(term)
+            }
+            LogEntry::Meta { id, content, meta } => {
+                out.varint(7)?;
+                out.varint(id)?;
+                out.string(content)?;
+                let mut meta: Vec<_> = meta.iter().collect();
+                meta.sort_unstable();
+                out.varint(meta.len())?;
+                for (key, value) in meta {
+                    out.string(key)?;
+                    out.string(value)?;
+                }
+                Ok(())
+            }
+            LogEntry::StopWords { terms } => {
+                out.varint(8)?;
+                out.varint(terms.len())?;
+                for term in terms {
+                    out.string(term)?;
+                }
+                Ok(())
+            }
+            LogEntry::Numeric { id, field, value } => {
+                out.varint(9)?;
+                out.varint(id)?;
+                out.string(field)?;
+                out.f64(value)
+            }
+            LogEntry::ImportTerm { term, ids } => {
+                out.varint(10)?;
+                out.string(term)?;
+                out.varint(ids.len())?;
+                for &id in ids {
+                    out.varint(id)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The open log of an index, and where its snapshot is saved.
+#[derive(Debug)]
+pub(crate) struct OpLog {
+    snapshot: PathBuf,
+    log: PathBuf,
+    file: File,
+    /// The first failed write since the last sync or compaction. Later entries are not
+    /// written, since the log would have a gap.
+    error: Option<io::Error>,
+}
+
+impl InvertedIndex {
+    /// Opens the index saved at `path` together with its change log, for durable updates
+    /// without rewriting the whole file.
+    ///
+    /// # Parameters
+    /// - `path`: The snapshot written by [`compact_log`](Self::compact_log). The log is kept
+    ///   at the same path with `.log` appended. Missing files start an empty index with the
+    ///   default configuration.
+    ///
+    /// # Returns
+    /// The snapshot with every logged change replayed on top. From then on every change to
+    /// the documents or terms, from [`add`](Self::add) and [`remove`](Self::remove) to
+    /// [`retain`](Self::retain), [`clear`](Self::clear) and the bulk ingestion methods,
+    /// appends an entry to the log before changing the index. [`IndexError::Corrupt`] if
+    /// either file is invalid and [`IndexError::Io`] if they cannot be read.
+    ///
+    /// # Notes
+    /// An entry truncated by a crash while it was written is dropped. Entries reach the
+    /// operating system at once but are only flushed to disk by [`sync_log`](Self::sync_log).
+    pub fn open_log(path: &Path) -> Result<InvertedIndex, IndexError> {
+        let mut index = match InvertedIndex::open(path) {
+            Err(IndexError::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                InvertedIndex::new()
+            }
+            result => result?,
+        };
+
+        let log = log_path(path);
+        let io_error = |source| IndexError::Io {
+            path: log.clone(),
+            source,
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&log)
+            .map_err(io_error)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(io_error)?;
+        let valid = index.replay(&bytes)?;
+        if valid < bytes.len() as u64 {
+            file.set_len(valid).map_err(io_error)?;
+        }
+        if valid == 0 {
+            let mut header = MAGIC.to_vec();
+            header.extend_from_slice(&VERSION.to_le_bytes());
+            file.write_all(&header).map_err(io_error)?;
+        }
+        index.log = Some(OpLog {
+            snapshot: path.to_path_buf(),
+            log,
+            file,
+            error: None,
+        });
+        Ok(index)
+    }
+
+    /// Saves a snapshot of the index and empties its log, see
+    /// [`open_log`](Self::open_log).
+    ///
+    /// The snapshot is written to a temporary file and renamed over the old one, so a
+    /// crash leaves either snapshot in place, with a log that still applies to it. Does
+    /// nothing if the index has no log.
+    pub fn compact_log(&mut self) -> Result<(), IndexError> {
+        let Some(log) = &self.log else {
+            return Ok(());
+        };
+        let mut temporary = log.snapshot.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| IndexError::Io { path, source }
+        };
+        let file = File::create(&temporary).map_err(io_error(&temporary))?;
+        self.save(&file).map_err(io_error(&temporary))?;
+        file.sync_all().map_err(io_error(&temporary))?;
+        std::fs::rename(&temporary, &log.snapshot).map_err(io_error(&log.snapshot))?;
+
+        let Some(log) = &mut self.log else {
+            return Ok(());
+        };
+        log.file.set_len(HEADER_LEN).map_err(io_error(&log.log))?;
+        // Everything the failed writes missed is in the snapshot now.
+        log.error = None;
+        Ok(())
+    }
+
+    /// Flushes the log to disk.
+    ///
+    /// # Returns
+    /// The first error writing an entry since the last call or
+    /// [`compact_log`](Self::compact_log). Changes from then on are only in memory until the
+    /// next compaction. Does nothing if the index has no log.
+    pub fn sync_log(&mut self) -> Result<(), IndexError> {
+        let Some(log) = &mut self.log else {
+            return Ok(());
+        };
+        let path = log.log.clone();
+        let io_error = |source| IndexError::Io { path, source };
+        match log.error.take() {
+            Some(err) => Err(io_error(err)),
+            None => log.file.sync_data().map_err(io_error),
+        }
+    }
+
+    /// Appends `entry` to the log, if the index has one.
+    pub(crate) fn record(&mut self, entry: LogEntry<'_>) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        if log.error.is_some() {
+            return;
+        }
+        let mut payload = Encoder { writer: Vec::new() };
+        // Writing to a Vec cannot fail.
+        let _ = entry.encode(&mut payload);
+        let mut frame = Encoder {
+            writer: Vec::with_capacity(payload.writer.len() + 4),
+        };
+        let _ = frame.varint(payload.writer.len());
+        frame.writer.extend_from_slice(&payload.writer);
+        if let Err(err) = log.file.write_all(&frame.writer) {
+            log.error = Some(err);
+        }
+    }
+
+    /// Applies the entries of the log file `bytes`.
+    ///
+    /// # Returns
+    /// The length of the valid part of the file: 0 for a new or torn header, otherwise
+    /// up to the last complete entry.
+    fn replay(&mut self, bytes: &[u8]) -> Result<u64, IndexError> {
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        if bytes.len() < header.len() && header.starts_with(bytes) {
+            return Ok(0);
+        }
+        if !bytes.starts_with(MAGIC) {
+            return Err(corrupt("not an index log"));
+        }
+        if !bytes.starts_with(&header) {
+            return Err(corrupt("unknown log format version"));
+        }
+
+        let mut valid = header.len();
+        loop {
+            let mut rest = &bytes[valid..];
+            let Ok(len) = (Decoder { reader: &mut rest }).varint() else {
+                break;
+            };
+            if rest.len() < len {
+                break;
+            }
+            let mut entry = Decoder {
+                reader: &rest[..len],
+            };
+            self.apply(&mut entry)?;
+            if !entry.reader.is_empty() {
+                return Err(corrupt("invalid log entry"));
+            }
+            valid = bytes.len() - rest.len() + len;
+        }
+        Ok(valid as u64)
+    }
+}
+
+impl InvertedIndex {
+    /// Decodes one entry written by [`LogEntry::encode`] and applies it.
+    fn apply(&mut self, entry: &mut Decoder<&[u8]>) -> Result<(), IndexError> {
+        let invalid = |_| corrupt("invalid log entry");
+        match entry.varint()? {
+            0 => {
+                let id = entry.varint()?;
+                self.add(id, &entry.string()?);
+            }
+            1 => {
+                self.remove(entry.varint()?);
+            }
+            2 => self.clear(),
+            3 => {
+                let id = entry.varint()?;
+                let mut fields = Vec::new();
+                for _ in 0..entry.varint()? {
+                    fields.push((entry.string()?, entry.string()?));
+                }
+                let fields: Vec<(&str, &str)> = fields
+                    .iter()
+                    .map(|(name, text)| (name.as_str(), text.as_str()))
+                    .collect();
+                self.add_fields(id, &fields);
+            }
+            4 => {
+                let id = entry.varint()?;
+                let content = entry.string()?;
+                let mut phrases = Vec::new();
+                for _ in 0..entry.varint()? {
+                    phrases.push(entry.string()?);
+                }
+                let phrases: Vec<&str> = phrases.iter().map(String::as_str).collect();
+                self.add_with_phrases(id, &content, &phrases);
+            }
+            5 => {
+                let id = entry.varint()?;
+                let path = PathBuf::from(entry.string()?);
+                self.set_document_path(id, path);
+            }
+            6 => {
+                self.remove_analyzed_term(&entry.string()?);
+            }
+            7 => {
+                let id = entry.varint()?;
+                let content = entry.string()?;
+                let mut meta = HashMap::new();
+                for _ in 0..entry.varint()? {
+                    meta.insert(entry.string()?, entry.string()?);
+                }
+                self.add_with_meta(id, &content, meta);
+            }
+            8 => {
+                let mut terms = Vec::new();
+                for _ in 0..entry.varint()? {
+                    terms.push(entry.string()?);
+                }
+                self.stop_terms(terms);
+            }
+            9 => {
+                let id = entry.varint()?;
+                let field = entry.string()?;
+                let value = entry.f64()?;
+                self.add_numeric(id, &field, value).map_err(invalid)?;
+            }
+            10 => {
+                let term = entry.string()?;
+                let mut ids = Vec::new();
+                for _ in 0..entry.varint()? {
+                    ids.push(entry.varint()?);
+                }
+                self.import_term(term, ids).map_err(invalid)?;
+            }
+            _ => return Err(corrupt("unknown log entry")),
+        }
+        Ok(())
+    }
+}
+
+/// The log kept next to the snapshot at `path`.
+fn log_path(path: &Path) -> PathBuf {
+    let mut log = OsString::from(path.as_os_str());
+    log.push(".log");
+    PathBuf::from(log)
+}
+
+#[test]
+fn log_replay_test() {
+    let dir = crate::directory::test_dir("oplog");
+    let path = dir.join("index.bin");
+    let log = log_path(&path);
+    let contents = |index: &InvertedIndex| {
+        let mut docs: Vec<(usize, String)> = index
+            .documents
+            .values()
+            .map(|doc| (doc.id, index.content(doc).unwrap().into_owned()))
+            .collect();
+        docs.sort_unstable();
+        docs
+    };
+
+    let mut index = InvertedIndex::open_log(&path).unwrap();
+    index.add(1, "Rust is fast");
+    index.add(2, "Go is simple");
+    index.add(3, "draft");
+    index.update(3, "Rust and Go").unwrap();
+    index.remove(2);
+    assert!(!index.remove(9));
+    let expected = contents(&index);
+    // Dropped without compacting, like a crashed process.
+    drop(index);
+    assert!(!path.exists());
+    let mut index = InvertedIndex::open_log(&path).unwrap();
+    assert_eq!(contents(&index), expected);
+    assert_eq!(index.query_bm25("rust").len(), 2);
+
+    index.compact_log().unwrap();
+    assert_eq!(std::fs::metadata(&log).unwrap().len(), HEADER_LEN);
+    index.add(4, "after the snapshot");
+    let complete = std::fs::metadata(&log).unwrap().len();
+    index.add(5, "cut short by a crash");
+    index.sync_log().unwrap();
+    drop(index);
+
+    // Simulate a crash in the middle of writing the last entry.
+    let file = OpenOptions::new().write(true).open(&log).unwrap();
+    let full = file.metadata().unwrap().len();
+    file.set_len(full - 3).unwrap();
+    drop(file);
+    let mut index = InvertedIndex::open_log(&path).unwrap();
+    assert_eq!(index.get(4).as_deref(), Some("after the snapshot"));
+    assert_eq!(index.get(5).as_deref(), None);
+    assert_eq!(std::fs::metadata(&log).unwrap().len(), complete);
+    index.add(6, "appended after recovery");
+    drop(index);
+    let index = InvertedIndex::open_log(&path).unwrap();
+    let ids: Vec<usize> = contents(&index).into_iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![1, 3, 4, 6]);
+    drop(index);
+
+    std::fs::write(&log, b"not a log").unwrap();
+    assert!(matches!(
+        InvertedIndex::open_log(&path),
+        Err(IndexError::Corrupt(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn log_replays_every_change_test() {
+    let dir = crate::directory::test_dir("oplog_changes");
+    let path = dir.join("index.bin");
+    let documents = |index: &InvertedIndex| {
+        let mut docs: Vec<_> = index
+            .documents
+            .values()
+            .map(|doc| {
+                (
+                    doc.id,
+                    index.content(doc).unwrap().into_owned(),
+                    doc.path.clone(),
+                    doc.fields.clone(),
+                )
+            })
+            .collect();
+        docs.sort_unstable();
+        docs
+    };
+    let replayed = |index: &InvertedIndex| {
+        let replayed = InvertedIndex::open_log(&path).unwrap();
+        assert_eq!(replayed.indexes, index.indexes);
+        assert_eq!(documents(&replayed), documents(index));
+        assert_eq!(replayed.numeric, index.numeric);
+        assert_eq!(replayed.capped_terms, index.capped_terms);
+        replayed
+    };
+
+    let mut index = InvertedIndex::open_log(&path).unwrap();
+    index.add(1, "alpha beta");
+    index.add(2, "beta");
+    assert_eq!(index.retain(|id, _| id == 1), 1);
+    index.add_fields(3, &[("title", "gamma"), ("body", "delta")]);
+    let reopened = replayed(&index);
+    assert_eq!(reopened.union(&["beta"]), vec![1]);
+    assert_eq!(reopened.union(&["gamma"]), vec![3]);
+    drop(reopened);
+
+    index.clear();
+    let meta = HashMap::from([("author".to_string(), "alice".to_string())]);
+    index.add_with_meta(1, "rust notes", meta.clone());
+    index.add_with_phrases(2, "New York pizza", &["new york"]);
+    index
+        .add_csv(
+            "id,text\n3,Rust csv row\n".as_bytes(),
+            "id",
+            &["text"],
+            true,
+        )
+        .unwrap();
+    index
+        .add_jsonl(&br#"{"id": 4, "text": "jsonl rust"}"#[..], "id", &["text"])
+        .unwrap();
+    index
+        .add_from_reader(5, "read\nfrom rust".as_bytes())
+        .unwrap();
+    assert_eq!(index.remove_term("pizza"), 1);
+    index.add_numeric(1, "year", 2020.0).unwrap();
+    index.apply_stop_words(&["notes".to_string()]);
+    assert_eq!(index.import_term("rust".to_string(), vec![2]).unwrap(), 1);
+    #[cfg(feature = "parallel")]
+    index.add_batch_parallel(vec![
+        (6, "parallel rust".to_string()),
+        (3, "replaced row".to_string()),
+    ]);
+    drop(index);
+
+    let index = InvertedIndex::open_log(&path).unwrap();
+    let reopened = replayed(&index);
+    assert_eq!(reopened.metadata(1), Some(&meta));
+    assert_eq!(reopened.query("new york").len(), 1);
+    assert!(reopened.union(&["pizza"]).is_empty());
+    assert!(reopened.union(&["notes"]).is_empty());
+    assert_eq!(reopened.query_range("year", 2000.0, 2030.0), vec![1]);
+    let rust = if cfg!(feature = "parallel") {
+        vec![1, 2, 4, 5, 6]
+    } else {
+        vec![1, 2, 3, 4, 5]
+    };
+    assert_eq!(reopened.union(&["rust"]), rust);
+    assert!(reopened.union(&["alpha"]).is_empty());
+    drop((index, reopened));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
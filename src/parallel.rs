@@ -0,0 +1,260 @@
+//! Multi-threaded indexing, enabled by the `parallel` feature.
+//!
+//! Work is spread over scoped std threads, one per available core, rather than a rayon
+//! pool, so the feature adds no dependencies.
+
+use crate::analysis::Analyzer;
+use crate::forward::count_terms;
+use crate::index::{Document, InvertedIndex};
+use crate::oplog::LogEntry;
+use crate::postings::Posting;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::thread;
+
+/// The terms, document lengths, token offsets and forward entries of one thread's share of
+/// a batch.
+struct PartialIndex {
+    indexes: HashMap<String, Vec<Posting>>,
+    lengths: Vec<usize>,
+    offsets: Vec<Vec<(usize, usize)>>,
+    terms: Vec<Vec<(String, u32)>>,
+}
+
+fn build_partial(analyzer: &Analyzer, docs: &[(usize, String)], forward: bool) -> PartialIndex {
+    let mut indexes: HashMap<String, Vec<Posting>> = HashMap::new();
+    let mut lengths = Vec::with_capacity(docs.len());
+    let mut offsets = Vec::with_capacity(docs.len());
+    let mut terms = Vec::with_capacity(docs.len());
+    for (id, content) in docs {
+        let words = analyzer.analyze_positions(content);
+        lengths.push(words.len());
+        offsets.push(analyzer.token_offsets(content));
+        terms.push(if forward {
+            count_terms(words.iter().map(|(_, word)| word.as_str()))
+        } else {
+            Vec::new()
+        });
+        for (position, word) in words {
+            let postings = indexes.entry(word).or_default();
+            match postings.last_mut() {
+                Some(last) if last.doc_id == *id => last.positions.push(position),
+                _ => postings.push(Posting {
+                    doc_id: *id,
+                    positions: vec![position],
+                }),
+            }
+        }
+    }
+    PartialIndex {
+        indexes,
+        lengths,
+        offsets,
+        terms,
+    }
+}
+
+/// Number of worker threads to use for `jobs` units of work.
+pub(crate) fn worker_count(jobs: usize) -> usize {
+    thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(jobs)
+        .max(1)
+}
+
+impl InvertedIndex {
+    /// Builds an index of `docs` with the default configuration, tokenizing them on several
+    /// threads, see [`add_batch_parallel`](Self::add_batch_parallel).
+    ///
+    /// # Returns
+    /// The same index as adding every document in order with [`add`](Self::add): equal
+    /// posting lists, each sorted by document id, and of duplicate ids the last one wins.
+    ///
+    /// # Notes
+    /// `docs` is a plain [`IntoIterator`] rather than rayon's `IntoParallelIterator`, as the
+    /// feature is built on std threads. It is collected into a `Vec` before being split
+    /// between the threads, so the whole corpus is held in memory at once.
+    pub fn par_build<I>(docs: I) -> InvertedIndex
+    where
+        I: IntoIterator<Item = (usize, String)>,
+    {
+        let mut index = InvertedIndex::new();
+        index.add_batch_parallel(docs.into_iter().collect());
+        index
+    }
+
+    /// Adds a batch of documents, tokenizing them on several threads.
+    ///
+    /// Each thread builds a partial term-to-postings map for its share of the batch, and the
+    /// partial maps are then merged into the index on the calling thread. Queries behave
+    /// exactly as if every document had been passed to [`add`](Self::add) in order: existing
+    /// documents with the same id are replaced, and of duplicate ids within the batch the
+    /// last one wins.
+    pub fn add_batch_parallel(&mut self, docs: Vec<(usize, String)>) {
+        let mut latest: HashMap<usize, String> = HashMap::with_capacity(docs.len());
+        for (id, content) in docs {
+            latest.insert(id, content);
+        }
+        let docs: Vec<(usize, String)> = latest.into_iter().collect();
+
+        // Soft-deleted ids in the batch would leave stale postings next to the new ones.
+        if docs.iter().any(|(id, _)| self.tombstones.contains(id)) {
+            self.compact();
+        }
+        let replaced: HashSet<usize> = docs
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| self.documents.contains_key(id))
+            .collect();
+        self.remove_documents(&replaced);
+
+        for (id, content) in &docs {
+            self.record(LogEntry::Add { id: *id, content });
+        }
+
+        let chunk_size = docs.len().div_ceil(worker_count(docs.len())).max(1);
+        let analyzer = &self.analyzer;
+        let forward = self.forward_index;
+        let partials: Vec<PartialIndex> = thread::scope(|scope| {
+            let workers: Vec<_> = docs
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || build_partial(analyzer, chunk, forward)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("indexing thread panicked"))
+                .collect()
+        });
+
+        self.generation += 1;
+        let mut touched = HashSet::new();
+        let mut lengths = Vec::with_capacity(docs.len());
+        let mut offsets = Vec::with_capacity(docs.len());
+        let mut terms = Vec::with_capacity(docs.len());
+        for partial in partials {
+            for (term, postings) in partial.indexes {
+                if let Some(ngrams) = &mut self.ngrams
+                    && !self.indexes.contains_key(&term)
+                {
+                    ngrams.insert(&term);
+                }
+                Arc::make_mut(self.indexes.entry(term.clone()).or_default()).extend(postings);
+                touched.insert(term);
+            }
+            lengths.extend(partial.lengths);
+            offsets.extend(partial.offsets);
+            terms.extend(partial.terms);
+        }
+        for term in touched {
+            if let Some(postings) = self.indexes.get_mut(&term).map(Arc::make_mut) {
+                postings.sort_unstable_by_key(|posting| posting.doc_id);
+                let capped = self.capped_terms.contains(&term)
+                    || self.max_postings.is_some_and(|max| postings.len() > max);
+                if capped {
+                    self.cap_term(term);
+                }
+            }
+        }
+
+        let docs = docs.into_iter().zip(lengths).zip(offsets).zip(terms);
+        for ((((id, content), length), offsets), mut terms) in docs {
+            terms.retain(|(term, _)| !self.capped_terms.contains(term));
+            self.total_tokens += length;
+            let doc = Arc::new(Document {
+                id,
+                content: self.store_content(content),
+                length,
+                path: None,
+                fields: Vec::new(),
+                offsets: self.store_offsets(offsets),
+                metadata: HashMap::new(),
+                terms,
+            });
+            self.count_surface_forms(&doc, true);
+            self.documents.insert(id, doc);
+        }
+    }
+}
+
+#[test]
+fn parallel_matches_sequential_test() {
+    let _color = crate::test_util::color_guard(true);
+    let corpus: Vec<(usize, String)> = (0..500)
+        .map(|id| {
+            let content = format!("doc {} word{} shared term{} Rust", id, id % 7, id % 13);
+            (id * 7 % 500, content)
+        })
+        .collect();
+
+    let mut sequential = InvertedIndex::new();
+    sequential.add(3, "stale content that gets replaced");
+    sequential.add(9999, "untouched");
+    for (id, content) in &corpus {
+        sequential.add(*id, content);
+    }
+
+    let mut parallel = InvertedIndex::new();
+    parallel.add(3, "stale content that gets replaced");
+    parallel.add(9999, "untouched");
+    parallel.add_batch_parallel(corpus.clone());
+
+    assert_eq!(parallel.indexes, sequential.indexes);
+    assert_eq!(parallel.documents, sequential.documents);
+    assert_eq!(parallel.surface_forms, sequential.surface_forms);
+    assert_eq!(parallel.stats(), sequential.stats());
+    for term in ["rust", "word3", "term12", "stale", "untouched"] {
+        assert_eq!(parallel.query(term), sequential.query(term), "{term}");
+        assert_eq!(
+            parallel.query_exact(term),
+            sequential.query_exact(term),
+            "{term}"
+        );
+    }
+}
+
+#[test]
+fn add_batch_parallel_without_content_test() {
+    let mut index = InvertedIndex::builder().store_content(false).build();
+    index.add(1, "alpha beta");
+    index.add(2, "gamma");
+    index.add_batch_parallel(vec![(1, "delta".to_string()), (3, "alpha".to_string())]);
+    assert_eq!(index.query_ids("alpha"), vec![3]);
+    assert_eq!(index.query_ids("delta"), vec![1]);
+    assert_eq!(index.stats().document_count, 3);
+    assert_eq!(index.validate(), Ok(()));
+}
+
+#[test]
+fn par_build_test() {
+    let corpus: Vec<(usize, String)> = (0..2000)
+        .map(|id| {
+            let content = format!(
+                "item {} colour{} size{} Rust and Go",
+                id % 1500,
+                id % 5,
+                id % 11
+            );
+            // Ids repeat, so later documents replace earlier ones.
+            (id % 1500, content)
+        })
+        .collect();
+
+    let mut sequential = InvertedIndex::new();
+    for (id, content) in &corpus {
+        sequential.add(*id, content);
+    }
+    let parallel = InvertedIndex::par_build(corpus);
+
+    assert_eq!(parallel.indexes, sequential.indexes);
+    assert_eq!(parallel.stats(), sequential.stats());
+    assert_eq!(parallel.get(7), sequential.get(7));
+    assert_eq!(
+        parallel.query_bm25("colour3 rust"),
+        sequential.query_bm25("colour3 rust")
+    );
+    assert_eq!(
+        InvertedIndex::par_build(Vec::new()).stats().document_count,
+        0
+    );
+}
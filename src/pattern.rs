@@ -0,0 +1,171 @@
+//! Matching the vocabulary against regular expressions.
+
+use crate::error::IndexError;
+use crate::highlight::highlight_spans;
+use crate::index::{InvertedIndex, occurrence_spans};
+use crate::query::QueryError;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// The result of [`InvertedIndex::query_regex`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegexMatches {
+    /// The indexed terms the pattern matched, in ascending order.
+    pub terms: Vec<String>,
+    /// The documents containing any of them, in ascending order.
+    pub doc_ids: Vec<usize>,
+}
+
+impl InvertedIndex {
+    /// Finds the documents containing a term that matches `pattern`.
+    ///
+    /// # Parameters
+    /// - `pattern`: A regular expression in the syntax of the `regex` crate, matched against
+    ///   whole indexed terms: `gram` only matches the term "gram", `.*gram.*` any term
+    ///   containing it. Terms are lowercased unless the index is case sensitive, and stemmed
+    ///   with stemming on.
+    ///
+    /// # Returns
+    /// The matching terms and the union of their documents.
+    /// [`IndexError::InvalidQuery`] with a [`QueryError::Regex`] if the pattern does not
+    /// compile, and [`IndexError::TooManyTerms`] if it matches more terms than the
+    /// [configured limit](crate::IndexBuilder::regex_term_limit).
+    ///
+    /// # Notes
+    /// The pattern is tried on every term of the vocabulary, a linear scan, so a query costs
+    /// time proportional to the number of distinct terms whatever the pattern.
+    pub fn query_regex(&self, pattern: &str) -> Result<RegexMatches, IndexError> {
+        let regex = Regex::new(&format!("^(?:{pattern})$")).map_err(|err| QueryError::Regex {
+            pattern: pattern.to_string(),
+            message: err.to_string(),
+        })?;
+        let mut terms = Vec::new();
+        for (term, postings) in &self.indexes {
+            if !regex.is_match(term) || self.live_len(postings) == 0 {
+                continue;
+            }
+            if terms.len() == self.regex_term_limit {
+                return Err(IndexError::TooManyTerms {
+                    limit: self.regex_term_limit,
+                });
+            }
+            terms.push(term.clone());
+        }
+        terms.sort_unstable();
+
+        let mut doc_ids: Vec<usize> = terms
+            .iter()
+            .filter_map(|term| self.live_postings(term))
+            .flat_map(|postings| {
+                postings
+                    .iter()
+                    .map(|posting| posting.doc_id)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        doc_ids.sort_unstable();
+        doc_ids.dedup();
+        Ok(RegexMatches { terms, doc_ids })
+    }
+
+    /// Like [`query_regex`](Self::query_regex), but returns the contents of the matching
+    /// documents with the occurrences of the matched terms highlighted.
+    ///
+    /// # Returns
+    /// The highlighted contents in ascending doc id order, or the errors of
+    /// [`query_regex`](Self::query_regex).
+    pub fn query_regex_highlighted(&self, pattern: &str) -> Result<Vec<String>, IndexError> {
+        let matches = self.query_regex(pattern)?;
+        let mut positions: HashMap<usize, Vec<usize>> = HashMap::new();
+        for postings in matches
+            .terms
+            .iter()
+            .filter_map(|term| self.live_postings(term))
+        {
+            for posting in postings.iter() {
+                positions
+                    .entry(posting.doc_id)
+                    .or_default()
+                    .extend(&posting.positions);
+            }
+        }
+        Ok(matches
+            .doc_ids
+            .iter()
+            .filter_map(|doc_id| {
+                let doc = self.documents.get(doc_id)?;
+                let mut positions = positions.remove(doc_id)?;
+                positions.sort_unstable();
+                let spans = occurrence_spans(doc, &positions, 1);
+                let content = self.content(doc)?;
+                let spans = self.capped_spans(&spans);
+                Some(highlight_spans(&content, spans, self.style()).into_owned())
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn query_regex_test() {
+    let mut index = InvertedIndex::builder().regex_term_limit(3).build();
+    index.add(1, "Programming in Rust");
+    index.add(2, "English grammar");
+    index.add(3, "A kilogram of flour");
+    index.add(4, "Go is simple");
+
+    let matches = index.query_regex(".*gram.*").unwrap();
+    assert_eq!(matches.terms, ["grammar", "kilogram", "programming"]);
+    assert_eq!(matches.doc_ids, vec![1, 2, 3]);
+    // Anchored to the whole term.
+    assert_eq!(index.query_regex("gram").unwrap(), RegexMatches::default());
+    let matches = index.query_regex("g(o|rammar)").unwrap();
+    assert_eq!(matches.terms, ["go", "grammar"]);
+    assert_eq!(matches.doc_ids, vec![2, 4]);
+    // An alternation in the pattern stays inside the anchors.
+    assert_eq!(index.query_regex("rust|x").unwrap().terms, ["rust"]);
+
+    let err = index.query_regex("(unclosed").unwrap_err();
+    assert!(matches!(
+        err,
+        IndexError::InvalidQuery(QueryError::Regex { ref pattern, .. }) if pattern == "(unclosed"
+    ));
+    assert!(matches!(
+        index.query_regex(".*"),
+        Err(IndexError::TooManyTerms { limit: 3 })
+    ));
+    assert_eq!(
+        IndexError::TooManyTerms { limit: 3 }.to_string(),
+        "pattern matches more than 3 terms"
+    );
+}
+
+#[test]
+fn query_regex_highlighted_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Programming and processing");
+    index.add(2, "A proper program");
+    index.add(3, "Go is simple");
+
+    let purple = |text: &str| crate::HighlightStyle::default().paint(text);
+    assert_eq!(
+        index.query_regex_highlighted("pro.*ing").unwrap(),
+        vec![format!(
+            "{} and {}",
+            purple("Programming"),
+            purple("processing")
+        )]
+    );
+    assert_eq!(
+        index.query_regex_highlighted("pro.*").unwrap(),
+        vec![
+            format!("{} and {}", purple("Programming"), purple("processing")),
+            format!("A {} {}", purple("proper"), purple("program")),
+        ]
+    );
+    assert!(index.query_regex_highlighted("xyz.*").unwrap().is_empty());
+    assert!(matches!(
+        index.query_regex_highlighted("pro(").unwrap_err(),
+        IndexError::InvalidQuery(QueryError::Regex { .. })
+    ));
+}
@@ -0,0 +1,1161 @@
+//! Saving an index to a compact binary file and loading it back.
+//!
+//! The file starts with the magic bytes `IIDX` and a little-endian `u32` format version,
+//! followed by three sections:
+//!
+//! - the term dictionary: the analyzer configuration with the token filters and whether
+//!   the forward index, the surface forms and the contents are kept and from which length
+//!   contents are compressed, the postings cap with the terms that exceeded it, then each
+//!   term with the length of its posting list and its spellings, in ascending order;
+//! - the document store: the documents, with their contents, each plain or as its LZ4
+//!   block, paths, field boundaries,
+//!   token offsets, metadata, numeric fields and forward index entries, which name terms by
+//!   their dictionary position. An index without contents leaves out the contents and the
+//!   token offsets;
+//! - the postings: each term's posting list, in dictionary order.
+//!
+//! Every section is its byte length, its bytes and the little-endian CRC-32 of those bytes,
+//! so a damaged file is reported along with the section at fault. All integers inside the
+//! sections are LEB128 varints, except the numeric values which are little-endian `f64`s,
+//! and token positions and offsets are delta-encoded, so small ids and dense positions take
+//! a byte each.
+//!
+//! Files of an older version back to [`OLDEST_VERSION`] are migrated while loading:
+//!
+//! - 12: no postings cap or capped terms.
+//! - 11: as 12, and no compressed contents.
+//! - 10: as 11, and always the contents.
+//! - 9: as 10, and no numeric fields.
+//! - 8: as 9, and no surface forms, which are recounted from the contents.
+//! - 7: as 8, and no token filters, so none are applied.
+//! - 6: as 7, and no forward index, which is rebuilt from the postings.
+//! - 5: as 6, and no sections or checksums, with each term's postings right after it.
+//! - 4: as 5, and no document metadata.
+
+use crate::compress;
+use crate::docstore::Content;
+use crate::error::IndexError;
+use crate::index::{Document, InvertedIndex};
+use crate::postings::Posting;
+use crate::tokenizer::TokenizerKind;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"IIDX";
+/// The version written by [`InvertedIndex::save`].
+const VERSION: u32 = 13;
+/// The oldest version [`InvertedIndex::load`] still reads.
+const OLDEST_VERSION: u32 = 4;
+/// The first version split into checksummed sections.
+const SECTIONS_VERSION: u32 = 6;
+/// The first version saving the forward index.
+const FORWARD_VERSION: u32 = 7;
+/// The first version saving the token filters.
+const FILTERS_VERSION: u32 = 8;
+/// The first version saving the surface forms.
+const SURFACE_VERSION: u32 = 9;
+/// The first version saving the numeric fields.
+const NUMERIC_VERSION: u32 = 10;
+/// The first version able to leave out the contents.
+const CONTENT_VERSION: u32 = 11;
+/// The first version saving compressed contents.
+const COMPRESSED_VERSION: u32 = 12;
+/// The first version saving the postings cap and the capped terms.
+const CAPPED_VERSION: u32 = 13;
+
+/// A checksummed part of a saved index, named by [`IndexError::ChecksumMismatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexSection {
+    /// The analyzer configuration and the terms.
+    TermDictionary,
+    /// The documents and everything stored with them.
+    DocumentStore,
+    /// The posting lists.
+    Postings,
+}
+
+impl fmt::Display for IndexSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IndexSection::TermDictionary => "term dictionary",
+            IndexSection::DocumentStore => "document store",
+            IndexSection::Postings => "postings",
+        })
+    }
+}
+
+impl InvertedIndex {
+    /// Writes the analyzer configuration, the documents and the posting lists to `writer`.
+    ///
+    /// # Returns
+    /// An [`io::ErrorKind::InvalidInput`] error, before anything is written, if the index
+    /// uses a custom [`Tokenizer`](crate::Tokenizer) or [`FilterFn`](crate::FilterFn), since
+    /// loading could not recreate it.
+    ///
+    /// # Notes
+    /// The stop words, token filters, case sensitivity, stemming and tokenizer chosen on the
+    /// [`IndexBuilder`](crate::IndexBuilder) are saved, so a [`load`](Self::load)ed index
+    /// analyzes queries like the original. The query cache and soft delete settings are not,
+    /// and the postings of soft-deleted documents are left out.
+    ///
+    /// Each section is encoded in memory first to checksum it, so saving briefly takes
+    /// about as much extra memory as the file is large.
+    pub fn save(&self, writer: impl Write) -> io::Result<()> {
+        let Some(tokenizer) = self.analyzer.tokenizer_kind else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot save an index with a custom tokenizer",
+            ));
+        };
+        let filters = &self.analyzer.filters;
+        if filters.custom.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot save an index with a custom token filter",
+            ));
+        }
+        let mut dictionary = Encoder { writer: Vec::new() };
+        dictionary.varint(tokenizer as usize)?;
+        dictionary.varint(usize::from(self.analyzer.case_sensitive))?;
+        dictionary.varint(usize::from(self.analyzer.stemming))?;
+        let mut stop_words: Vec<&String> = self.analyzer.stop_words.iter().collect();
+        stop_words.sort_unstable();
+        dictionary.varint(stop_words.len())?;
+        for word in stop_words {
+            dictionary.string(word)?;
+        }
+        dictionary.varint(usize::from(self.forward_index))?;
+        dictionary.varint(filters.min_length)?;
+        // 0 for no maximum, which no token is shorter than.
+        dictionary.varint(filters.max_length.map_or(0, |max| max + 1))?;
+        dictionary.varint(usize::from(filters.drop_numbers))?;
+        dictionary.varint(usize::from(self.surface_forms.is_some()))?;
+        dictionary.varint(usize::from(self.keep_content))?;
+        // 0 for no compression, which no content is longer than.
+        dictionary.varint(self.compress_above.map_or(0, |min| min + 1))?;
+        // 0 for no cap, which no term exceeds.
+        dictionary.varint(self.max_postings.map_or(0, |max| max + 1))?;
+        let mut capped: Vec<&String> = self.capped_terms.iter().collect();
+        capped.sort_unstable();
+        dictionary.varint(capped.len())?;
+        for term in capped {
+            dictionary.string(term)?;
+        }
+
+        let mut terms: Vec<_> = self
+            .indexes
+            .keys()
+            .filter_map(|term| Some((term, self.live_postings(term)?)))
+            .collect();
+        terms.sort_unstable_by_key(|(term, _)| *term);
+        let term_ids: HashMap<&str, usize> = terms
+            .iter()
+            .enumerate()
+            .map(|(i, (term, _))| (term.as_str(), i))
+            .collect();
+
+        let mut documents = Encoder { writer: Vec::new() };
+        let mut ids: Vec<&usize> = self.documents.keys().collect();
+        ids.sort_unstable();
+        documents.varint(ids.len())?;
+        for id in ids {
+            let doc = &self.documents[id];
+            documents.varint(doc.id)?;
+            documents.varint(doc.length)?;
+            if let Content::Compressed { bytes, len } = &doc.content {
+                documents.varint(1)?;
+                documents.varint(*len)?;
+                documents.bytes(bytes)?;
+            } else if self.keep_content {
+                let content = self.content(doc).ok_or_else(|| {
+                    io::Error::other(format!(
+                        "cannot read document {} from the document store",
+                        doc.id
+                    ))
+                })?;
+                documents.varint(0)?;
+                documents.string(&content)?;
+            }
+            match &doc.path {
+                Some(path) => {
+                    documents.varint(1)?;
+                    documents.string(&path.to_string_lossy())?;
+                }
+                None => documents.varint(0)?,
+            }
+            documents.varint(doc.fields.len())?;
+            for (name, start) in &doc.fields {
+                documents.string(name)?;
+                documents.varint(*start)?;
+            }
+            if self.keep_content {
+                // The built-in tokenizers yield tokens in order, so the ranges ascend.
+                documents.varint(doc.offsets.len())?;
+                let mut previous = 0;
+                for &(start, end) in &doc.offsets {
+                    documents.varint(start - previous)?;
+                    documents.varint(end - start)?;
+                    previous = end;
+                }
+            }
+            let mut metadata: Vec<_> = doc.metadata.iter().collect();
+            metadata.sort_unstable();
+            documents.varint(metadata.len())?;
+            for (key, value) in metadata {
+                documents.string(key)?;
+                documents.string(value)?;
+            }
+            let mut numeric: Vec<(&String, f64)> = self
+                .numeric
+                .iter()
+                .filter_map(|(field, values)| Some((field, *values.values.get(&doc.id)?)))
+                .collect();
+            numeric.sort_unstable_by_key(|(field, _)| *field);
+            documents.varint(numeric.len())?;
+            for (field, value) in numeric {
+                documents.string(field)?;
+                documents.f64(value)?;
+            }
+            if self.forward_index {
+                // Terms are sorted like the dictionary, so their positions ascend.
+                let entries: Vec<(usize, u32)> = doc
+                    .terms
+                    .iter()
+                    .filter_map(|(term, freq)| Some((*term_ids.get(term.as_str())?, *freq)))
+                    .collect();
+                documents.varint(entries.len())?;
+                let mut previous = 0;
+                for (term, freq) in entries {
+                    documents.varint(term - previous)?;
+                    documents.varint(freq as usize)?;
+                    previous = term;
+                }
+            }
+        }
+
+        let mut postings = Encoder { writer: Vec::new() };
+        dictionary.varint(terms.len())?;
+        for (term, list) in terms {
+            dictionary.string(term)?;
+            dictionary.varint(list.len())?;
+            if let Some(surface) = &self.surface_forms {
+                let forms = surface
+                    .forms
+                    .get(term.as_str())
+                    .map_or(&[][..], Vec::as_slice);
+                dictionary.varint(forms.len())?;
+                for (form, count) in forms {
+                    dictionary.string(form)?;
+                    dictionary.varint(*count as usize)?;
+                }
+            }
+            for posting in list.iter() {
+                postings.varint(posting.doc_id)?;
+                postings.varint(posting.positions.len())?;
+                let mut previous = 0;
+                for &position in &posting.positions {
+                    postings.varint(position - previous)?;
+                    previous = position;
+                }
+            }
+        }
+
+        let mut out = Encoder {
+            writer: BufWriter::new(writer),
+        };
+        out.writer.write_all(MAGIC)?;
+        out.writer.write_all(&VERSION.to_le_bytes())?;
+        for section in [dictionary, documents, postings] {
+            out.section(&section.writer)?;
+        }
+        out.writer.flush()
+    }
+
+    /// Reads an index written by [`save`](Self::save), by this or an earlier release.
+    ///
+    /// # Returns
+    /// [`IndexError::UnsupportedVersion`] if the file format is newer than this build or too
+    /// old to migrate, [`IndexError::ChecksumMismatch`] if a section was damaged,
+    /// [`IndexError::Corrupt`] if the input is not an index file, is truncated or is
+    /// inconsistent, and [`IndexError::Read`] if reading fails.
+    ///
+    /// # Notes
+    /// Documents of a file written before metadata or numeric fields were saved have
+    /// none. Files
+    /// written before checksums were added load unverified, the forward index of a file
+    /// written before it was saved is rebuilt from the postings and the surface forms are
+    /// recounted from the contents.
+    pub fn load(reader: impl Read) -> Result<InvertedIndex, IndexError> {
+        InvertedIndex::read_index(reader, true)
+    }
+
+    fn read_index(reader: impl Read, verify: bool) -> Result<InvertedIndex, IndexError> {
+        let mut input = Decoder {
+            reader: BufReader::new(reader),
+        };
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(corrupt("not an index file"));
+        }
+        let mut version = [0; 4];
+        input.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if !(OLDEST_VERSION..=VERSION).contains(&version) {
+            return Err(IndexError::UnsupportedVersion {
+                found: version,
+                supported: VERSION,
+            });
+        }
+
+        let mut index = InvertedIndex::new();
+        if version < SECTIONS_VERSION {
+            read_analyzer(&mut input, &mut index)?;
+            read_documents(&mut input, &mut index, version)?;
+            for _ in 0..input.varint()? {
+                let term = input.string()?;
+                let count = input.varint()?;
+                let postings = read_postings(&mut input, count, &index)?;
+                insert_term(&mut index, term, postings)?;
+            }
+            index.rebuild_forward_index();
+            index.rebuild_surface_forms();
+            return Ok(index);
+        }
+
+        let mut dictionary = input.section(IndexSection::TermDictionary, verify)?;
+        let mut documents = input.section(IndexSection::DocumentStore, verify)?;
+        let mut postings = input.section(IndexSection::Postings, verify)?;
+        read_analyzer(&mut dictionary, &mut index)?;
+        if version >= FORWARD_VERSION {
+            index.forward_index = dictionary.flag()?;
+        }
+        if version >= FILTERS_VERSION {
+            let filters = &mut index.analyzer.filters;
+            filters.min_length = dictionary.varint()?;
+            filters.max_length = dictionary.varint()?.checked_sub(1);
+            filters.drop_numbers = dictionary.flag()?;
+        }
+        if version >= SURFACE_VERSION && !dictionary.flag()? {
+            index.surface_forms = None;
+        }
+        if version >= CONTENT_VERSION {
+            index.keep_content = dictionary.flag()?;
+        }
+        if version >= COMPRESSED_VERSION {
+            index.compress_above = dictionary.varint()?.checked_sub(1);
+        }
+        if version >= CAPPED_VERSION {
+            index.max_postings = dictionary.varint()?.checked_sub(1);
+            for _ in 0..dictionary.varint()? {
+                index.capped_terms.insert(dictionary.string()?);
+            }
+        }
+        let forward = read_documents(&mut documents, &mut index, version)?;
+        let mut names = Vec::new();
+        for _ in 0..dictionary.varint()? {
+            let term = dictionary.string()?;
+            let count = dictionary.varint()?;
+            if version >= SURFACE_VERSION
+                && let Some(surface) = &mut index.surface_forms
+            {
+                let mut forms = Vec::new();
+                for _ in 0..dictionary.varint()? {
+                    let form = dictionary.string()?;
+                    let count = u32::try_from(dictionary.varint()?)
+                        .ok()
+                        .filter(|&count| count > 0)
+                        .ok_or_else(|| corrupt("invalid surface forms"))?;
+                    forms.push((form, count));
+                }
+                if !forms.is_empty() {
+                    surface.forms.insert(term.clone(), forms);
+                }
+            }
+            let list = read_postings(&mut postings, count, &index)?;
+            if !forward.is_empty() {
+                names.push(term.clone());
+            }
+            insert_term(&mut index, term, list)?;
+        }
+        if version < FORWARD_VERSION {
+            index.rebuild_forward_index();
+        }
+        if version < SURFACE_VERSION {
+            index.rebuild_surface_forms();
+        }
+        for (id, entries) in forward {
+            let terms = entries
+                .into_iter()
+                .map(|(term, freq)| Some((names.get(term)?.clone(), freq)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| corrupt("invalid forward index"))?;
+            if let Some(doc) = index.documents.get_mut(&id).map(Arc::make_mut) {
+                doc.terms = terms;
+            }
+        }
+        for section in [dictionary, documents, postings] {
+            section.finish()?;
+        }
+        Ok(index)
+    }
+
+    /// Saves the index to the file at `path`, replacing it, see [`save`](Self::save).
+    pub fn save_to(&self, path: &Path) -> Result<(), IndexError> {
+        let io_error = |source| IndexError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = File::create(path).map_err(io_error)?;
+        self.save(file).map_err(io_error)
+    }
+
+    /// Loads an index from the file at `path`, see [`load`](Self::load).
+    pub fn open(path: &Path) -> Result<InvertedIndex, IndexError> {
+        let file = File::open(path).map_err(|source| IndexError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        InvertedIndex::load(file)
+    }
+
+    /// Like [`open`](Self::open), without verifying the checksums, to load large trusted
+    /// files faster.
+    ///
+    /// # Notes
+    /// A damaged file is still reported as [`IndexError::Corrupt`] where its contents are
+    /// inconsistent, but damage that leaves them consistent, such as a changed letter in a
+    /// document, goes unnoticed.
+    pub fn open_unchecked(path: &Path) -> Result<InvertedIndex, IndexError> {
+        let file = File::open(path).map_err(|source| IndexError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        InvertedIndex::read_index(file, false)
+    }
+}
+
+pub(crate) fn corrupt(reason: &str) -> IndexError {
+    IndexError::Corrupt(reason.to_string())
+}
+
+fn read_analyzer<R: Read>(
+    input: &mut Decoder<R>,
+    index: &mut InvertedIndex,
+) -> Result<(), IndexError> {
+    let analyzer = &mut index.analyzer;
+    let tokenizer =
+        TokenizerKind::from_code(input.varint()?).ok_or_else(|| corrupt("unknown tokenizer"))?;
+    analyzer.tokenizer = tokenizer.tokenizer();
+    analyzer.tokenizer_kind = Some(tokenizer);
+    analyzer.case_sensitive = input.flag()?;
+    analyzer.stemming = input.flag()?;
+    for _ in 0..input.varint()? {
+        analyzer.stop_words.insert(input.string()?);
+    }
+    Ok(())
+}
+
+/// A document's forward index entries as saved: `(dictionary position, frequency)` pairs.
+type ForwardEntries = Vec<(usize, u32)>;
+
+/// Reads the documents into `index`, returning each one's forward index entries to
+/// resolve once the terms are read.
+fn read_documents<R: Read>(
+    input: &mut Decoder<R>,
+    index: &mut InvertedIndex,
+    version: u32,
+) -> Result<Vec<(usize, ForwardEntries)>, IndexError> {
+    let mut forward = Vec::new();
+    for _ in 0..input.varint()? {
+        let id = input.varint()?;
+        let length = input.varint()?;
+        let content = if !index.keep_content {
+            None
+        } else if version < COMPRESSED_VERSION {
+            Some(Content::Memory(input.string()?))
+        } else {
+            match input.varint()? {
+                0 => Some(Content::Memory(input.string()?)),
+                1 => {
+                    let len = input.varint()?;
+                    let bytes = input.bytes()?;
+                    // Decompressed once, so that a damaged block fails the load rather than
+                    // every later read.
+                    if compress::decompress(&bytes, len)
+                        .is_none_or(|content| str::from_utf8(&content).is_err())
+                    {
+                        return Err(corrupt("invalid compressed content"));
+                    }
+                    Some(Content::Compressed {
+                        bytes: bytes.into(),
+                        len,
+                    })
+                }
+                _ => return Err(corrupt("invalid content marker")),
+            }
+        };
+        let content_len = match &content {
+            Some(Content::Memory(content)) => content.len(),
+            Some(Content::Compressed { len, .. }) => *len,
+            _ => 0,
+        };
+        let path = match input.varint()? {
+            0 => None,
+            1 => Some(PathBuf::from(input.string()?)),
+            _ => return Err(corrupt("invalid document path marker")),
+        };
+        let mut fields = Vec::new();
+        for _ in 0..input.varint()? {
+            let name = input.string()?;
+            let start = input.varint()?;
+            if fields.last().is_some_and(|(_, last)| *last > start) {
+                return Err(corrupt("invalid document fields"));
+            }
+            fields.push((name, start));
+        }
+        let mut offsets = Vec::new();
+        if content.is_some() {
+            let count = input.varint()?;
+            offsets.reserve(count.min(1 << 16));
+            let mut previous = 0usize;
+            for _ in 0..count {
+                let (gap, len) = (input.varint()?, input.varint()?);
+                let start = previous.checked_add(gap);
+                let end = start.and_then(|start| start.checked_add(len));
+                match (start, end) {
+                    (Some(start), Some(end)) if end <= content_len => {
+                        offsets.push((start, end));
+                        previous = end;
+                    }
+                    _ => return Err(corrupt("invalid token offsets")),
+                }
+            }
+        }
+        let mut metadata = HashMap::new();
+        if version >= 5 {
+            for _ in 0..input.varint()? {
+                let key = input.string()?;
+                if metadata.insert(key, input.string()?).is_some() {
+                    return Err(corrupt("duplicate metadata key"));
+                }
+            }
+        }
+        let mut numeric = Vec::new();
+        if version >= NUMERIC_VERSION {
+            for _ in 0..input.varint()? {
+                let field = input.string()?;
+                let value = input.f64()?;
+                if value.is_nan() || numeric.iter().any(|(other, _)| *other == field) {
+                    return Err(corrupt("invalid numeric fields"));
+                }
+                numeric.push((field, value));
+            }
+        }
+        if version >= FORWARD_VERSION && index.forward_index {
+            let count = input.varint()?;
+            let mut entries = Vec::with_capacity(count.min(1 << 16));
+            let mut term = 0usize;
+            for i in 0..count {
+                let delta = input.varint()?;
+                if i > 0 && delta == 0 {
+                    return Err(corrupt("invalid forward index"));
+                }
+                term = term
+                    .checked_add(delta)
+                    .ok_or_else(|| corrupt("invalid forward index"))?;
+                let freq =
+                    u32::try_from(input.varint()?).map_err(|_| corrupt("invalid forward index"))?;
+                entries.push((term, freq));
+            }
+            forward.push((id, entries));
+        }
+        index.total_tokens += length;
+        let doc = Document {
+            id,
+            content: content.unwrap_or(Content::Omitted),
+            length,
+            path,
+            fields,
+            offsets,
+            metadata,
+            terms: Vec::new(),
+        };
+        if index.documents.insert(id, Arc::new(doc)).is_some() {
+            return Err(corrupt("duplicate document id"));
+        }
+        for (field, value) in numeric {
+            index.numeric.entry(field).or_default().insert(id, value);
+        }
+    }
+    Ok(forward)
+}
+
+/// Reads a posting list of `count` entries, whose documents must all be in `index`.
+fn read_postings<R: Read>(
+    input: &mut Decoder<R>,
+    count: usize,
+    index: &InvertedIndex,
+) -> Result<Vec<Posting>, IndexError> {
+    let mut postings: Vec<Posting> = Vec::with_capacity(count.min(1 << 16));
+    for _ in 0..count {
+        let doc_id = input.varint()?;
+        if !index.documents.contains_key(&doc_id)
+            || postings.last().is_some_and(|last| last.doc_id >= doc_id)
+        {
+            return Err(corrupt("invalid posting list"));
+        }
+        let count = input.varint()?;
+        let mut positions = Vec::with_capacity(count.min(1 << 16));
+        let mut position = 0usize;
+        for i in 0..count {
+            let delta = input.varint()?;
+            if i > 0 && delta == 0 {
+                return Err(corrupt("invalid token positions"));
+            }
+            position = position
+                .checked_add(delta)
+                .ok_or_else(|| corrupt("invalid token positions"))?;
+            positions.push(position);
+        }
+        postings.push(Posting { doc_id, positions });
+    }
+    Ok(postings)
+}
+
+fn insert_term(
+    index: &mut InvertedIndex,
+    term: String,
+    postings: Vec<Posting>,
+) -> Result<(), IndexError> {
+    if postings.is_empty() || index.indexes.insert(term, Arc::new(postings)).is_some() {
+        return Err(corrupt("invalid term dictionary"));
+    }
+    Ok(())
+}
+
+/// The CRC-32 (IEEE 802.3) checksum of `bytes`.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !bytes.iter().fold(!0, |crc: u32, &byte| {
+        TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+pub(crate) struct Encoder<W: Write> {
+    pub(crate) writer: W,
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn varint(&mut self, mut value: usize) -> io::Result<()> {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.writer.write_all(&[byte]);
+            }
+            self.writer.write_all(&[byte | 0x80])?;
+        }
+    }
+
+    pub(crate) fn string(&mut self, s: &str) -> io::Result<()> {
+        self.bytes(s.as_bytes())
+    }
+
+    /// Writes `bytes` framed by their length.
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.varint(bytes.len())?;
+        self.writer.write_all(bytes)
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    /// Writes `bytes` framed by their length and followed by their checksum.
+    fn section(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.varint(bytes.len())?;
+        self.writer.write_all(bytes)?;
+        self.writer.write_all(&crc32(bytes).to_le_bytes())
+    }
+}
+
+pub(crate) struct Decoder<R: Read> {
+    pub(crate) reader: R,
+}
+
+impl<R: Read> Decoder<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), IndexError> {
+        self.reader.read_exact(buf).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                corrupt("unexpected end of file")
+            } else {
+                IndexError::Read(err)
+            }
+        })
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<usize, IndexError> {
+        let mut value = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let mut byte = [0];
+            self.read_exact(&mut byte)?;
+            let bits = usize::from(byte[0] & 0x7f);
+            if (bits << shift) >> shift != bits {
+                return Err(corrupt("integer overflow"));
+            }
+            value |= bits << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(corrupt("integer overflow"))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, IndexError> {
+        let mut bytes = [0; 8];
+        self.read_exact(&mut bytes)?;
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn flag(&mut self) -> Result<bool, IndexError> {
+        match self.varint()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt("invalid flag")),
+        }
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, IndexError> {
+        String::from_utf8(self.bytes()?).map_err(|_| corrupt("invalid UTF-8 string"))
+    }
+
+    /// Reads bytes written by [`Encoder::bytes`].
+    fn bytes(&mut self) -> Result<Vec<u8>, IndexError> {
+        let len = self.varint()?;
+        let mut bytes = Vec::with_capacity(len.min(1 << 20));
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)
+            .map_err(IndexError::Read)?;
+        if bytes.len() != len {
+            return Err(corrupt("unexpected end of file"));
+        }
+        Ok(bytes)
+    }
+
+    /// Reads a section written by [`Encoder::section`], checking its checksum if `verify`.
+    fn section(
+        &mut self,
+        section: IndexSection,
+        verify: bool,
+    ) -> Result<Decoder<Cursor<Vec<u8>>>, IndexError> {
+        let len = self.varint()?;
+        let mut bytes = Vec::with_capacity(len.min(1 << 20));
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)
+            .map_err(IndexError::Read)?;
+        if bytes.len() != len {
+            return Err(corrupt("unexpected end of file"));
+        }
+        let mut expected = [0; 4];
+        self.read_exact(&mut expected)?;
+        let expected = u32::from_le_bytes(expected);
+        let found = crc32(&bytes);
+        if verify && found != expected {
+            return Err(IndexError::ChecksumMismatch {
+                section,
+                expected,
+                found,
+            });
+        }
+        Ok(Decoder {
+            reader: Cursor::new(bytes),
+        })
+    }
+}
+
+impl Decoder<Cursor<Vec<u8>>> {
+    /// Checks that a section was read to its end.
+    fn finish(&self) -> Result<(), IndexError> {
+        if self.reader.position() != self.reader.get_ref().len() as u64 {
+            return Err(corrupt("trailing bytes in section"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn save_load_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is safe and fast.");
+    index.add(300, "Rust is a systems programming language, é 🦀.");
+    index.add(3, "Programming in Rust is fun.");
+    index.add_fields(4, &[("title", "Fields"), ("body", "are saved too")]);
+    if let Some(doc) = index.documents.get_mut(&3).map(Arc::make_mut) {
+        doc.path = Some(PathBuf::from("docs/fun.txt"));
+    }
+    let meta = HashMap::from([
+        ("author".to_string(), "alice".to_string()),
+        ("year".to_string(), "2024".to_string()),
+    ]);
+    index.add_with_meta(5, "Metadata is saved", meta.clone());
+
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+
+    assert_eq!(loaded.indexes, index.indexes);
+    assert_eq!(loaded.stats(), index.stats());
+    assert_eq!(loaded.get(300).as_deref(), index.get(300).as_deref());
+    assert_eq!(loaded.document_path(3), Some(Path::new("docs/fun.txt")));
+    assert_eq!(loaded.query("rust"), index.query("rust"));
+    assert_eq!(loaded.phrase_doc_ids("systems programming"), vec![300]);
+    assert_eq!(loaded.field_at(4, 2), Some("body"));
+    for id in [1, 300, 3, 4] {
+        assert_eq!(loaded.documents[&id].offsets, index.documents[&id].offsets);
+    }
+    assert_eq!(loaded.metadata(5), Some(&meta));
+    assert!(loaded.metadata(1).unwrap().is_empty());
+}
+
+#[test]
+fn load_migrates_old_versions_test() {
+    let _color = crate::test_util::color_guard(false);
+    // Saved by the release before document metadata.
+    let v4 = include_bytes!("../tests/fixtures/index_v4.bin");
+    assert_eq!(v4[4..8], 4u32.to_le_bytes());
+    let index = InvertedIndex::load(&v4[..]).unwrap();
+    assert_eq!(index.documents.len(), 3);
+    assert_eq!(index.get(2).as_deref(), Some("Go compiles quickly."));
+    assert_eq!(index.query("compiler").len(), 2);
+    assert!(index.query("the").is_empty());
+    assert_eq!(index.phrase_doc_ids("fast systems"), vec![7]);
+    assert_eq!(index.field_at(7, 0), Some("title"));
+    assert!(index.metadata(1).unwrap().is_empty());
+
+    // Saved by the release before checksums.
+    let v5 = include_bytes!("../tests/fixtures/index_v5.bin");
+    assert_eq!(v5[4..8], 5u32.to_le_bytes());
+    let v5 = InvertedIndex::load(&v5[..]).unwrap();
+    assert_eq!(v5.documents.len(), 4);
+    assert_eq!(v5.metadata(9).unwrap()["author"], "alice");
+    assert_eq!(v5.query("checker"), vec!["Notes on the borrow checker"]);
+    for id in [1, 2, 7] {
+        assert_eq!(v5.documents[&id], index.documents[&id]);
+    }
+
+    // Saved by the release before the forward index, which is rebuilt.
+    let v6 = include_bytes!("../tests/fixtures/index_v6.bin");
+    assert_eq!(v6[4..8], 6u32.to_le_bytes());
+    let v6 = InvertedIndex::load(&v6[..]).unwrap();
+    assert_eq!(v6.documents, v5.documents);
+    assert_eq!(
+        v6.doc_terms(9).unwrap().collect::<Vec<_>>(),
+        vec![("borrow", 1), ("checker", 1), ("note", 1), ("on", 1)]
+    );
+
+    // Saved by the release before token filters.
+    let v7 = include_bytes!("../tests/fixtures/index_v7.bin");
+    assert_eq!(v7[4..8], 7u32.to_le_bytes());
+    let v7 = InvertedIndex::load(&v7[..]).unwrap();
+    assert_eq!(v7.documents, v6.documents);
+    assert!(v7.analyzer.filters.is_empty());
+
+    // Saved by the release before surface forms, which are recounted.
+    let v8 = include_bytes!("../tests/fixtures/index_v8.bin");
+    assert_eq!(v8[4..8], 8u32.to_le_bytes());
+    let v8 = InvertedIndex::load(&v8[..]).unwrap();
+    assert_eq!(v8.documents, v7.documents);
+    assert_eq!(v8.autocomplete("rust", 1)[0].term, "Rust");
+    assert_eq!(v8.surface_forms, v7.surface_forms);
+
+    // Saved by the release before numeric fields.
+    let v9 = include_bytes!("../tests/fixtures/index_v9.bin");
+    assert_eq!(v9[4..8], 9u32.to_le_bytes());
+    let v9 = InvertedIndex::load(&v9[..]).unwrap();
+    assert_eq!(v9.documents, v8.documents);
+    assert_eq!(v9.surface_forms, v8.surface_forms);
+    assert!(v9.numeric.is_empty());
+
+    // Saved by the release before indexes without contents.
+    let v10 = include_bytes!("../tests/fixtures/index_v10.bin");
+    assert_eq!(v10[4..8], 10u32.to_le_bytes());
+    let v10 = InvertedIndex::load(&v10[..]).unwrap();
+    assert_eq!(v10.documents, v9.documents);
+    assert!(v10.keep_content);
+
+    // Saved by the release before compressed contents.
+    let v11 = include_bytes!("../tests/fixtures/index_v11.bin");
+    assert_eq!(v11[4..8], 11u32.to_le_bytes());
+    let v11 = InvertedIndex::load(&v11[..]).unwrap();
+    assert_eq!(v11.documents, v10.documents);
+    assert_eq!(v11.compress_above, None);
+
+    // Saved by the release before the postings cap was saved.
+    let v12 = include_bytes!("../tests/fixtures/index_v12.bin");
+    assert_eq!(v12[4..8], 12u32.to_le_bytes());
+    let v12 = InvertedIndex::load(&v12[..]).unwrap();
+    assert_eq!(v12.documents, v11.documents);
+    assert_eq!(v12.max_postings, None);
+    assert!(v12.capped_terms.is_empty());
+
+    // Saving writes the current version, which loads the same index.
+    for index in [index, v5, v6, v7, v8, v9, v10, v11, v12] {
+        let mut bytes = Vec::new();
+        index.save(&mut bytes).unwrap();
+        assert_eq!(bytes[4..8], VERSION.to_le_bytes());
+        let resaved = InvertedIndex::load(bytes.as_slice()).unwrap();
+        assert_eq!(resaved.indexes, index.indexes);
+        assert_eq!(resaved.documents, index.documents);
+        assert_eq!(resaved.stats(), index.stats());
+        assert_eq!(resaved.surface_forms, index.surface_forms);
+    }
+}
+
+#[test]
+fn crc32_test() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn load_detects_damaged_sections_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is safe and fast.");
+    index.add_fields(
+        2,
+        &[("title", "Checksums"), ("body", "catch damaged files")],
+    );
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+
+    // Find the sections from their framing.
+    let mut sections = Vec::new();
+    let mut rest = &bytes[8..];
+    for section in [
+        IndexSection::TermDictionary,
+        IndexSection::DocumentStore,
+        IndexSection::Postings,
+    ] {
+        let len = (Decoder { reader: &mut rest }).varint().unwrap();
+        let start = bytes.len() - rest.len();
+        sections.push((section, start..start + len));
+        rest = &rest[len + 4..];
+    }
+    assert!(rest.is_empty());
+
+    for (section, range) in sections {
+        let stored = u32::from_le_bytes(bytes[range.end..range.end + 4].try_into().unwrap());
+        for i in [range.start, range.start + range.len() / 2, range.end - 1] {
+            let mut damaged = bytes.clone();
+            damaged[i] ^= 0x10;
+            match InvertedIndex::load(damaged.as_slice()) {
+                Err(IndexError::ChecksumMismatch {
+                    section: at,
+                    expected,
+                    found,
+                }) => {
+                    assert_eq!(at, section, "{i}");
+                    assert_eq!(expected, stored);
+                    assert_eq!(found, crc32(&damaged[range.clone()]));
+                }
+                Err(err) => panic!("byte {i} of the {section}: {err}"),
+                Ok(_) => panic!("byte {i} of the {section} loaded"),
+            }
+            // Unverified, the damage is either noticed or loaded, but never panics.
+            let _ = InvertedIndex::read_index(damaged.as_slice(), false);
+        }
+        // A damaged checksum is a mismatch too.
+        let mut damaged = bytes.clone();
+        damaged[range.end] ^= 0x01;
+        assert!(matches!(
+            InvertedIndex::load(damaged.as_slice()),
+            Err(IndexError::ChecksumMismatch { section: at, .. }) if at == section
+        ));
+    }
+    assert_eq!(
+        IndexError::ChecksumMismatch {
+            section: IndexSection::Postings,
+            expected: 0xCBF4_3926,
+            found: 0x1a,
+        }
+        .to_string(),
+        "corrupt index: postings checksum is 0000001a, expected cbf43926"
+    );
+}
+
+#[test]
+fn load_rejects_unsupported_version_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust");
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    for version in [VERSION + 1, OLDEST_VERSION - 1, u32::MAX] {
+        bytes[4..8].copy_from_slice(&version.to_le_bytes());
+        let Err(err) = InvertedIndex::load(bytes.as_slice()) else {
+            panic!("version {version} loaded");
+        };
+        assert!(
+            matches!(
+                err,
+                IndexError::UnsupportedVersion { found, supported: VERSION } if found == version
+            ),
+            "{err}"
+        );
+    }
+    assert_eq!(
+        IndexError::UnsupportedVersion {
+            found: 9,
+            supported: 5
+        }
+        .to_string(),
+        "unsupported index format version 9 (supported: 5)"
+    );
+}
+
+#[test]
+fn save_load_config_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::builder()
+        .stop_words(["the", "is"])
+        .stemming(true)
+        .case_sensitive(true)
+        .tokenizer(crate::WordTokenizer)
+        .build();
+    index.add(1, "The state-of-the-art Programs is Here");
+    index.add(2, "the programmer's programs");
+
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    for query in [
+        "Programs",
+        "programs",
+        "program",
+        "state-of-the-art",
+        "the",
+        "Here",
+    ] {
+        assert_eq!(loaded.query(query), index.query(query), "{query}");
+        assert_eq!(loaded.query_bm25(query), index.query_bm25(query), "{query}");
+    }
+    assert_eq!(loaded.query("programs").len(), 1);
+    assert!(loaded.query("the").is_empty());
+
+    // Capped terms and detected stop words stay skipped in new documents.
+    let mut index = InvertedIndex::builder().max_postings(2).build();
+    index.add(1, "the a rust");
+    index.add(2, "the b");
+    index.add(3, "the c");
+    index.apply_stop_words(&["rust".to_string()]);
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let mut loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.max_postings, Some(2));
+    assert_eq!(loaded.capped_terms, index.capped_terms);
+    loaded.add(4, "the d rust");
+    assert!(loaded.query_ids("the").is_empty());
+    assert!(loaded.query_ids("rust").is_empty());
+    loaded.add(5, "d");
+    loaded.add(6, "d");
+    assert!(loaded.query_ids("d").is_empty());
+
+    #[derive(Debug)]
+    struct Custom;
+    impl crate::Tokenizer for Custom {
+        fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+            text.split_whitespace().collect()
+        }
+    }
+    let custom = InvertedIndex::builder().tokenizer(Custom).build();
+    let mut bytes = Vec::new();
+    let err = custom.save(&mut bytes).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(bytes.is_empty());
+}
+
+#[test]
+fn load_rejects_bad_input_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is safe and fast.");
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+
+    assert!(matches!(
+        InvertedIndex::load(&b"nope"[..]),
+        Err(IndexError::Corrupt(_))
+    ));
+    // Every truncation is reported as an error instead of a panic or a partial index.
+    for len in 0..bytes.len() {
+        assert!(
+            matches!(
+                InvertedIndex::load(&bytes[..len]),
+                Err(IndexError::Corrupt(_))
+            ),
+            "{len}"
+        );
+    }
+}
+
+#[test]
+fn save_to_open_test() {
+    let dir = crate::directory::test_dir("save_to");
+    let path = dir.join("index.bin");
+    let mut index = InvertedIndex::new();
+    index.add(4, "saved to disk");
+    index.save_to(&path).unwrap();
+    assert_eq!(
+        InvertedIndex::open(&path).unwrap().get(4).as_deref(),
+        Some("saved to disk")
+    );
+
+    // A letter changed in the document store only fails verification.
+    let mut bytes = std::fs::read(&path).unwrap();
+    let at = bytes.windows(8).position(|w| w == b"saved to").unwrap();
+    bytes[at] = b'p';
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(
+        InvertedIndex::open(&path),
+        Err(IndexError::ChecksumMismatch {
+            section: IndexSection::DocumentStore,
+            ..
+        })
+    ));
+    assert_eq!(
+        InvertedIndex::open_unchecked(&path)
+            .unwrap()
+            .get(4)
+            .as_deref(),
+        Some("paved to disk")
+    );
+    assert!(matches!(
+        InvertedIndex::open(&dir.join("missing.bin")),
+        Err(IndexError::Io { .. })
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
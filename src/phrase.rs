@@ -0,0 +1,239 @@
+use crate::analysis::TermBuffer;
+use crate::highlight::Highlighter;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use crate::postings::{Intersection, Posting};
+use std::borrow::Cow;
+
+impl InvertedIndex {
+    /// Adds a document like [`add`](Self::add), additionally indexing each occurrence of
+    /// `phrases` as a single term.
+    ///
+    /// # Parameters
+    /// - `id`: An identifier for the document.
+    /// - `content`: The text content of the document.
+    /// - `phrases`: Multi-word phrases such as `"New York"`, matched word by word after the
+    ///   same normalization as the content. Phrases of a single word add nothing.
+    ///
+    /// # Notes
+    /// A query for the whole phrase, e.g. `index.query("new york")`, then looks up the
+    /// combined term. Where phrases overlap, the one spanning the most words wins and the
+    /// others are not indexed for those words. The individual words are indexed as usual
+    /// either way, and the phrase terms do not count towards the document length.
+    pub fn add_with_phrases(&mut self, id: usize, content: &str, phrases: &[&str]) {
+        self.record(LogEntry::Phrases {
+            id,
+            content,
+            phrases,
+        });
+        let mut phrases: Vec<(Vec<(usize, String)>, String)> = phrases
+            .iter()
+            .map(|phrase| self.analyzer.analyze_positions(phrase))
+            .filter(|terms| terms.len() > 1)
+            .map(|mut terms| {
+                let first = terms[0].0;
+                for (position, _) in &mut terms {
+                    *position -= first;
+                }
+                let joined = terms
+                    .iter()
+                    .map(|(_, term)| term.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (terms, joined)
+            })
+            .collect();
+        phrases.sort_by_key(|(terms, _)| std::cmp::Reverse(phrase_span(terms)));
+
+        let mut words = TermBuffer::default();
+        let mut offsets = Vec::new();
+        self.analyzer
+            .analyze_into(content, 0, &mut words, &mut offsets);
+        let length = words.len();
+        let mut atoms = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let (start, _) = words.get(i);
+            let matched = phrases
+                .iter()
+                .find(|(terms, _)| phrase_at(&words, start, terms));
+            let Some((terms, joined)) = matched else {
+                i += 1;
+                continue;
+            };
+            atoms.push((start, joined.as_str()));
+            let end = start + phrase_span(terms);
+            i = words.search(end).unwrap_or_else(|after| after);
+        }
+        for (start, joined) in atoms {
+            words.push(start, joined);
+        }
+        self.index_terms(id, content.to_string(), &mut words, offsets, length);
+    }
+
+    /// Queries for documents containing the words of `phrase` next to each other, in order.
+    ///
+    /// # Parameters
+    /// - `phrase`: The words to match, normalized like [`query`](Self::query) terms. A stop
+    ///   word inside the phrase matches any single word at its position.
+    ///
+    /// # Returns
+    /// The contents of the matching documents in ascending doc id order, with the phrase's
+    /// words highlighted. Empty if the phrase has no indexed words.
+    pub fn query_phrase(&self, phrase: &str) -> Vec<String> {
+        let terms: Vec<String> = self
+            .analyzer
+            .analyze_positions(phrase)
+            .into_iter()
+            .map(|(_, term)| term)
+            .collect();
+        let terms: Vec<&str> = terms.iter().map(String::as_str).collect();
+        let mut highlighter = None;
+        self.phrase_doc_ids(phrase)
+            .into_iter()
+            .filter_map(|doc_id| self.get(doc_id))
+            .map(|content| {
+                let highlighter = highlighter.get_or_insert_with(|| {
+                    Highlighter::for_terms(&terms)
+                        .unwrap()
+                        .style(self.style().copied())
+                        .max_highlights(self.max_highlights)
+                });
+                highlighter.highlight(&content).into_owned()
+            })
+            .collect()
+    }
+
+    /// The ids of the documents matching `phrase`, in ascending order.
+    pub(crate) fn phrase_doc_ids(&self, phrase: &str) -> Vec<usize> {
+        let terms = self.analyzer.analyze_positions(phrase);
+        let Some(&(first, _)) = terms.first() else {
+            return Vec::new();
+        };
+        // Each term's posting list with its distance from the first term of the phrase.
+        let lists: Option<Vec<(usize, Cow<'_, [Posting]>)>> = terms
+            .iter()
+            .map(|(position, term)| Some((position - first, self.live_postings(term)?)))
+            .collect();
+        let Some(lists) = lists else {
+            return Vec::new();
+        };
+
+        let candidates = Intersection::new(
+            lists
+                .iter()
+                .map(|(_, postings)| (Cow::Borrowed(&postings[..]), 0.0))
+                .collect(),
+        );
+        candidates
+            .map(|(doc_id, _)| doc_id)
+            .filter(|&doc_id| {
+                let occurrences: Vec<(usize, &[usize])> = lists
+                    .iter()
+                    .filter_map(|(offset, postings)| {
+                        let i = postings
+                            .binary_search_by_key(&doc_id, |posting| posting.doc_id)
+                            .ok()?;
+                        Some((*offset, postings[i].positions.as_slice()))
+                    })
+                    .collect();
+                phrase_occurs(&occurrences)
+            })
+            .collect()
+    }
+}
+
+/// How many token positions a phrase covers, given its terms with offsets from its start.
+fn phrase_span(terms: &[(usize, String)]) -> usize {
+    terms.last().map_or(0, |(offset, _)| offset + 1)
+}
+
+/// Whether the position-sorted `words` hold the phrase made of `terms` and their offsets
+/// at position `start`.
+fn phrase_at(words: &TermBuffer, start: usize, terms: &[(usize, String)]) -> bool {
+    terms.iter().all(|(offset, term)| {
+        words
+            .search(start + offset)
+            .is_ok_and(|i| words.get(i).1 == term)
+    })
+}
+
+/// Whether some start position has every term at its offset in `occurrences`, given as
+/// `(offset from the phrase start, ascending positions of the term)`.
+fn phrase_occurs(occurrences: &[(usize, &[usize])]) -> bool {
+    let Some((&(first_offset, starts), rest)) = occurrences.split_first() else {
+        return false;
+    };
+    starts.iter().any(|&position| {
+        let start = position - first_offset;
+        rest.iter()
+            .all(|(offset, positions)| positions.binary_search(&(start + offset)).is_ok())
+    })
+}
+
+#[test]
+fn query_phrase_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is a systems programming language");
+    index.add(2, "programming systems in Rust");
+    index.add(3, "systems, programming and more systems programming");
+
+    assert_eq!(
+        index.query_phrase("Systems Programming"),
+        vec![
+            "Rust is a systems programming language",
+            "systems, programming and more systems programming"
+        ]
+    );
+    assert_eq!(index.phrase_doc_ids("programming systems"), vec![2]);
+    assert!(index.query_phrase("rust programming").is_empty());
+    assert!(index.query_phrase("unknown words").is_empty());
+    assert!(index.query_phrase("").is_empty());
+}
+
+#[test]
+fn query_phrase_with_stop_words_test() {
+    let mut index = InvertedIndex::builder().stop_words(["a", "the"]).build();
+    index.add(1, "Rust is a systems language");
+    index.add(2, "Rust is systems language");
+
+    // The stop word still takes up one position on both sides.
+    assert_eq!(index.phrase_doc_ids("is the systems"), vec![1]);
+    assert_eq!(index.phrase_doc_ids("is systems"), vec![2]);
+}
+
+#[test]
+fn add_with_phrases_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add_with_phrases(1, "I moved to New York last year", &["New York", "Rust"]);
+    index.add_with_phrases(2, "The new office is in York", &["New York"]);
+
+    assert_eq!(
+        index.query("New York"),
+        vec!["I moved to New York last year"]
+    );
+    assert_eq!(
+        index.postings("york").unwrap().collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(index.doc_freq("new york"), 1);
+    // Phrase terms are extra terms, not extra tokens.
+    assert_eq!(index.average_document_length(), 6.5);
+}
+
+#[test]
+fn add_with_phrases_longest_match_test() {
+    let mut index = InvertedIndex::builder().stop_words(["of"]).build();
+    index.add_with_phrases(
+        1,
+        "new york city and the bank of america",
+        &["new york", "york city", "new york city", "bank of america"],
+    );
+
+    assert_eq!(index.doc_freq("new york city"), 1);
+    assert_eq!(index.doc_freq("new york"), 0);
+    assert_eq!(index.doc_freq("york city"), 0);
+    assert_eq!(index.doc_freq("Bank of America"), 1);
+}
@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+/// A single entry of a term's posting list.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Posting {
+    pub(crate) doc_id: usize,
+    /// Token positions of the term's occurrences in the document, ascending. Empty for a
+    /// posting [imported](crate::InvertedIndex::import_term) without them.
+    pub(crate) positions: Vec<usize>,
+}
+
+impl Posting {
+    /// How many times the term occurs in the document, once if its positions are unknown.
+    pub(crate) fn term_freq(&self) -> usize {
+        self.positions.len().max(1)
+    }
+}
+
+/// Intersects two ascending, de-duplicated id lists.
+pub(crate) fn intersect_sorted(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Merges two ascending, de-duplicated id lists into their union.
+pub(crate) fn union_sorted(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                result.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                result.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+    result
+}
+
+#[test]
+fn intersect_sorted_test() {
+    assert_eq!(
+        intersect_sorted(&[1, 3, 5, 7], &[2, 3, 4, 7, 9]),
+        vec![3, 7]
+    );
+    assert!(intersect_sorted(&[1, 2], &[]).is_empty());
+}
+
+#[test]
+fn union_sorted_test() {
+    assert_eq!(union_sorted(&[1, 3, 5], &[2, 3, 6]), vec![1, 2, 3, 5, 6]);
+    assert_eq!(union_sorted(&[], &[4]), vec![4]);
+}
+
+/// Walks several posting lists in lockstep, yielding the documents present in all of them.
+///
+/// Each list carries a weight (its term's IDF) so that every yielded document comes with its
+/// summed TF-IDF score.
+pub(crate) struct Intersection<'a> {
+    lists: Vec<(Cow<'a, [Posting]>, f64)>,
+    cursors: Vec<usize>,
+}
+
+impl<'a> Intersection<'a> {
+    pub(crate) fn new(lists: Vec<(Cow<'a, [Posting]>, f64)>) -> Self {
+        let cursors = vec![0; lists.len()];
+        Self { lists, cursors }
+    }
+}
+
+impl Iterator for Intersection<'_> {
+    type Item = (usize, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.lists.is_empty() {
+            return None;
+        }
+        'candidates: loop {
+            let mut target = 0;
+            for (cursor, (postings, _)) in self.cursors.iter().zip(&self.lists) {
+                target = target.max(postings.get(*cursor)?.doc_id);
+            }
+            for (cursor, (postings, _)) in self.cursors.iter_mut().zip(&self.lists) {
+                *cursor += postings[*cursor..].partition_point(|posting| posting.doc_id < target);
+                match postings.get(*cursor) {
+                    Some(posting) if posting.doc_id == target => {}
+                    Some(_) => continue 'candidates,
+                    None => return None,
+                }
+            }
+            let mut score = 0.0;
+            for (cursor, (postings, idf)) in self.cursors.iter_mut().zip(&self.lists) {
+                score += postings[*cursor].term_freq() as f64 * idf;
+                *cursor += 1;
+            }
+            return Some((target, score));
+        }
+    }
+}
+
+#[test]
+fn intersection_iter_test() {
+    let list = |ids: &[usize]| -> Vec<Posting> {
+        ids.iter()
+            .map(|&doc_id| Posting {
+                doc_id,
+                positions: vec![0],
+            })
+            .collect()
+    };
+    let (a, b, c) = (
+        list(&[1, 2, 4, 8, 9]),
+        list(&[2, 3, 8, 9]),
+        list(&[0, 2, 9]),
+    );
+    let intersection = Intersection::new(vec![
+        (Cow::Borrowed(&a[..]), 1.0),
+        (Cow::Borrowed(&b[..]), 1.0),
+        (Cow::Borrowed(&c[..]), 0.5),
+    ]);
+    assert_eq!(intersection.collect::<Vec<_>>(), vec![(2, 2.5), (9, 2.5)]);
+}
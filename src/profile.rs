@@ -0,0 +1,123 @@
+//! Timing the phases of a query, see [`InvertedIndex::search_with_profile`].
+
+use crate::index::InvertedIndex;
+use crate::query::Query;
+use crate::search::{SearchHit, SearchOptions};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Where the time of one query went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueryProfile {
+    /// Analyzing the words and phrases and finding the documents containing each.
+    pub lookup: Duration,
+    /// Combining those document lists by the query's AND, OR and NOT.
+    pub intersection: Duration,
+    /// Scoring, sorting and paging the matching documents.
+    pub scoring: Duration,
+    /// Highlighting the results. [`search_with_profile`](InvertedIndex::search_with_profile)
+    /// does not highlight, so callers that do add their time here.
+    pub highlighting: Duration,
+    /// Posting list entries read while looking up and scoring terms.
+    pub postings_examined: usize,
+    /// Documents that matched the query and got a score.
+    pub candidates_scored: usize,
+    /// Hits returned after paging.
+    pub results: usize,
+}
+
+impl QueryProfile {
+    /// The profile as a JSON object, with durations in microseconds.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"lookup_us\":{},\"intersection_us\":{},\"scoring_us\":{},\"highlighting_us\":{},\
+             \"postings_examined\":{},\"candidates_scored\":{},\"results\":{}}}",
+            self.lookup.as_micros(),
+            self.intersection.as_micros(),
+            self.scoring.as_micros(),
+            self.highlighting.as_micros(),
+            self.postings_examined,
+            self.candidates_scored,
+            self.results
+        )
+    }
+}
+
+impl InvertedIndex {
+    /// Like [`search_query`](Self::search_query), but also measures each phase.
+    ///
+    /// # Returns
+    /// The same hits as `search_query`, and the profile of computing them. The timers only
+    /// run here, so unprofiled searches pay nothing for this.
+    pub fn search_with_profile(
+        &self,
+        query: &Query,
+        options: &SearchOptions,
+    ) -> (Vec<SearchHit<'_>>, QueryProfile) {
+        let mut profile = QueryProfile::default();
+
+        let start = Instant::now();
+        let mut leaves: HashMap<&str, Option<Vec<usize>>> = HashMap::new();
+        for text in query.leaves() {
+            leaves.entry(text).or_insert_with(|| {
+                profile.postings_examined += self.postings_len(&self.analyzer.analyze(text));
+                self.words_doc_ids(text)
+            });
+        }
+        profile.lookup = start.elapsed();
+
+        let start = Instant::now();
+        let matched = self
+            .evaluate(query, &mut |text| leaves.get(text).cloned().flatten())
+            .unwrap_or_default();
+        profile.intersection = start.elapsed();
+
+        let start = Instant::now();
+        let hits = self.rank_matches(query, &matched, options);
+        profile.scoring = start.elapsed();
+
+        let terms: Vec<String> = self
+            .scoring_terms(query)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect();
+        profile.postings_examined += self.postings_len(&terms);
+        profile.candidates_scored = matched.len();
+        profile.results = hits.len();
+        (hits, profile)
+    }
+
+    /// The total length of the posting lists of `terms`.
+    fn postings_len(&self, terms: &[String]) -> usize {
+        terms
+            .iter()
+            .filter_map(|term| self.indexes.get(term))
+            .map(|postings| postings.len())
+            .sum()
+    }
+}
+
+#[test]
+fn search_with_profile_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is fast");
+    index.add(2, "Rust and Go");
+    index.add(3, "Go is simple");
+
+    let query = Query::parse("rust OR go").unwrap();
+    let options = SearchOptions {
+        limit: Some(2),
+        ..SearchOptions::default()
+    };
+    let (hits, profile) = index.search_with_profile(&query, &options);
+    assert_eq!(hits, index.search_query(&query, &options));
+    // Two postings per term, read once to match and once to score.
+    assert_eq!(profile.postings_examined, 8);
+    assert_eq!(profile.candidates_scored, 3);
+    assert_eq!(profile.results, 2);
+    assert_eq!(profile.highlighting, Duration::ZERO);
+
+    let json = profile.to_json();
+    assert!(json.starts_with("{\"lookup_us\":"), "{json}");
+    assert!(json.ends_with("\"postings_examined\":8,\"candidates_scored\":3,\"results\":2}"));
+}
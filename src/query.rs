@@ -0,0 +1,599 @@
+//! A small boolean query language.
+//!
+//! ```text
+//! rust fast            both words (implicit AND)
+//! rust AND fast        the same
+//! rust OR go           either word
+//! rust -go             rust but not go, also written `rust NOT go`
+//! "systems language"   the words next to each other, in order
+//! (rust OR go) fast    parentheses group
+//! rust^2 fast^0.5      weights a word, phrase or group in the ranking
+//! ```
+//!
+//! `AND` binds tighter than `OR`, and the keywords are only recognized in upper case.
+
+use crate::index::InvertedIndex;
+use crate::postings::{intersect_sorted, union_sorted};
+use crate::ranking::Scorer;
+use crate::search::{SearchHit, SearchOptions, SortBy, paginate, sort_hits};
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// A parsed query, see [`Query::parse`] for the syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// A single word, normalized like the words of a document. A word the tokenizer splits
+    /// further, such as "e-mail", matches its parts as a phrase.
+    Term(String),
+    /// Words that must occur next to each other, in order.
+    Phrase(String),
+    /// Every subquery must match.
+    And(Vec<Query>),
+    /// At least one subquery must match.
+    Or(Vec<Query>),
+    /// The subquery must not match.
+    Not(Box<Query>),
+    /// Matches like the subquery, with the scores of its terms multiplied by the weight.
+    Boost(Box<Query>, f64),
+}
+
+/// Why a query could not be run.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum QueryError {
+    /// The query string is malformed at byte `offset`.
+    Syntax { message: String, offset: usize },
+    /// The query contains no words at all.
+    EmptyQuery,
+    /// The analyzed term is not a valid highlighting pattern.
+    Highlight { term: String, message: String },
+    /// The pattern of [`InvertedIndex::query_regex`](crate::InvertedIndex::query_regex)
+    /// is not a valid regular expression.
+    Regex { pattern: String, message: String },
+    /// There is no content to highlight, see
+    /// [`IndexBuilder::store_content`](crate::IndexBuilder::store_content).
+    ContentNotStored,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::Syntax { message, offset } => {
+                write!(f, "{} at byte {}", message, offset)
+            }
+            QueryError::EmptyQuery => write!(f, "empty query"),
+            QueryError::Highlight { term, message } => {
+                write!(f, "cannot highlight \"{}\": {}", term, message)
+            }
+            QueryError::Regex { pattern, message } => {
+                write!(f, "invalid pattern \"{}\": {}", pattern, message)
+            }
+            QueryError::ContentNotStored => {
+                write!(
+                    f,
+                    "cannot highlight: the index does not store document contents"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    /// Parses a query string, see the [module documentation](self) for the syntax.
+    ///
+    /// # Returns
+    /// [`QueryError::EmptyQuery`] for a blank `input`, and [`QueryError::Syntax`] if it is
+    /// malformed.
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let tokens = lex(input)?;
+        if tokens.is_empty() {
+            return Err(QueryError::EmptyQuery);
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: input.len(),
+        };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some((Token::Boost(_), offset)) => Err(syntax("unexpected boost", *offset)),
+            Some((_, offset)) => Err(syntax("unexpected ')'", *offset)),
+        }
+    }
+
+    /// The words and phrases the query looks for, leaving out negated ones. Useful to
+    /// highlight the results.
+    pub fn positive_terms(&self) -> Vec<&str> {
+        self.weighted_terms()
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    /// The non-negated words and phrases with the product of the boosts around each.
+    pub(crate) fn weighted_terms(&self) -> Vec<(&str, f64)> {
+        let mut terms = Vec::new();
+        self.collect_positive(1.0, &mut terms);
+        terms
+    }
+
+    /// Every word and phrase of the query, negated or not, in order.
+    pub(crate) fn leaves(&self) -> Vec<&str> {
+        match self {
+            Query::Term(text) | Query::Phrase(text) => vec![text],
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(Query::leaves).collect()
+            }
+            Query::Not(query) | Query::Boost(query, _) => query.leaves(),
+        }
+    }
+
+    fn collect_positive<'a>(&'a self, boost: f64, terms: &mut Vec<(&'a str, f64)>) {
+        match self {
+            Query::Term(text) | Query::Phrase(text) => terms.push((text, boost)),
+            Query::And(queries) | Query::Or(queries) => {
+                for query in queries {
+                    query.collect_positive(boost, terms);
+                }
+            }
+            Query::Not(_) => {}
+            Query::Boost(query, weight) => query.collect_positive(boost * weight, terms),
+        }
+    }
+}
+
+fn syntax(message: &str, offset: usize) -> QueryError {
+    QueryError::Syntax {
+        message: message.to_string(),
+        offset,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Boost(f64),
+}
+
+fn lex(input: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    // Where the last word, phrase or ')' ended, which a boost must directly follow.
+    let mut boostable_end = None;
+    while let Some(&(start, ch)) = chars.peek() {
+        match ch {
+            _ if ch.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                let token = if ch == '(' { Token::Open } else { Token::Close };
+                tokens.push((token, start));
+                if ch == ')' {
+                    boostable_end = Some(start + 1);
+                }
+            }
+            '"' => {
+                chars.next();
+                let from = start + 1;
+                let Some((end, _)) = chars.find(|&(_, ch)| ch == '"') else {
+                    return Err(syntax("unterminated phrase", start));
+                };
+                tokens.push((Token::Phrase(input[from..end].to_string()), start));
+                boostable_end = Some(end + 1);
+            }
+            '^' => {
+                chars.next();
+                if boostable_end != Some(start) {
+                    return Err(syntax("'^' must follow a term", start));
+                }
+                let end = word_end(input, &mut chars);
+                let weight = input[start + 1..end]
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|weight| weight.is_finite() && *weight >= 0.0)
+                    .ok_or_else(|| syntax("boost must be a non-negative number", start + 1))?;
+                tokens.push((Token::Boost(weight), start));
+                boostable_end = None;
+            }
+            _ => {
+                let end = word_end(input, &mut chars);
+                boostable_end = Some(end);
+                let word = &input[start..end];
+                match word {
+                    "AND" => tokens.push((Token::And, start)),
+                    "OR" => tokens.push((Token::Or, start)),
+                    "NOT" => tokens.push((Token::Not, start)),
+                    _ => match word.strip_prefix('-') {
+                        Some("") => return Err(syntax("'-' must precede a term", start)),
+                        Some(rest) => {
+                            tokens.push((Token::Not, start));
+                            tokens.push((Token::Word(rest.to_string()), start + 1));
+                        }
+                        None => tokens.push((Token::Word(word.to_string()), start)),
+                    },
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Advances `chars` past the rest of a word, returning the offset it ends at.
+fn word_end(input: &str, chars: &mut Peekable<CharIndices<'_>>) -> usize {
+    while let Some(&(i, ch)) = chars.peek() {
+        if ch.is_whitespace() || matches!(ch, '(' | ')' | '"' | '^') {
+            return i;
+        }
+        chars.next();
+    }
+    input.len()
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Length of the input, reported as the offset of errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |(_, offset)| *offset)
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            queries.push(self.and()?);
+        }
+        Ok(single_or(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Open | Token::Not) => {}
+                _ => break,
+            }
+            queries.push(self.unary()?);
+        }
+        Ok(single_or(queries, Query::And))
+    }
+
+    fn unary(&mut self) -> Result<Query, QueryError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.unary()?)));
+        }
+        let offset = self.offset();
+        let token = self.tokens.get_mut(self.pos).map(|(token, _)| token);
+        let query = match token {
+            Some(Token::Word(word)) => Query::Term(std::mem::take(word)),
+            Some(Token::Phrase(phrase)) => Query::Phrase(std::mem::take(phrase)),
+            Some(Token::Open) => {
+                self.pos += 1;
+                let query = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(syntax("expected ')'", self.offset()));
+                }
+                query
+            }
+            _ => return Err(syntax("expected a term", offset)),
+        };
+        self.pos += 1;
+        let mut query = query;
+        while let Some(&Token::Boost(weight)) = self.peek() {
+            self.pos += 1;
+            query = Query::Boost(Box::new(query), weight);
+        }
+        Ok(query)
+    }
+}
+
+/// Unwraps a single query instead of nesting it in a one-element group.
+fn single_or(mut queries: Vec<Query>, group: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        group(queries)
+    }
+}
+
+impl InvertedIndex {
+    /// The ids of the documents matching `query`, in ascending order.
+    ///
+    /// Words that are stop words constrain nothing: `the AND rust` matches like `rust`.
+    /// A query made only of negations matches every document not excluded.
+    pub fn matching_docs(&self, query: &Query) -> Vec<usize> {
+        self.evaluate(query, &mut |text| self.words_doc_ids(text))
+            .unwrap_or_default()
+    }
+
+    /// Runs a parsed query, ranking the matching documents.
+    ///
+    /// # Returns
+    /// The matching documents scored by the summed TF-IDF of the words they contain from
+    /// the query's non-negated words and phrases, ordered and paged by `options`. Matches
+    /// containing fewer of those distinct terms than `options.minimum_should_match` are
+    /// dropped.
+    pub fn search_query(&self, query: &Query, options: &SearchOptions) -> Vec<SearchHit<'_>> {
+        let matched = self.matching_docs(query);
+        self.rank_matches(query, &matched, options)
+    }
+
+    /// Scores the documents `matched` by `query`, in ascending id order, into hits.
+    pub(crate) fn rank_matches(
+        &self,
+        query: &Query,
+        matched: &[usize],
+        options: &SearchOptions,
+    ) -> Vec<SearchHit<'_>> {
+        let mut scores: HashMap<usize, (f64, usize)> =
+            matched.iter().map(|&id| (id, (0.0, 0))).collect();
+        for (term, boost) in self.scoring_terms(query) {
+            for (doc_id, score) in self.term_scores(&term, Scorer::TfIdf) {
+                if let Some((total, terms)) = scores.get_mut(&doc_id) {
+                    *total += score * boost;
+                    *terms += 1;
+                }
+            }
+        }
+        let mut hits: Vec<(usize, f64)> = matched
+            .iter()
+            .filter(|id| scores[id].1 >= options.minimum_should_match)
+            .map(|id| (*id, scores[id].0))
+            .collect();
+        // Matches come in id order, so the stable sorts keep ids ascending among equal
+        // scores, and hits the other orders tie in score order.
+        sort_hits(self, &mut hits, &SortBy::Score);
+        if options.sort_by != SortBy::Score {
+            sort_hits(self, &mut hits, &options.sort_by);
+        }
+        paginate(self, hits, options)
+    }
+
+    /// The distinct analyzed terms of the non-negated words and phrases of `query`, which
+    /// [`search_query`](Self::search_query) sums the boosted scores of, each with its boost.
+    /// A term occurring several times gets its highest boost.
+    pub(crate) fn scoring_terms(&self, query: &Query) -> Vec<(String, f64)> {
+        let mut terms: Vec<(String, f64)> = Vec::new();
+        for (text, boost) in query.weighted_terms() {
+            for term in self.query_terms(text) {
+                match terms.iter_mut().find(|(seen, _)| *seen == term) {
+                    Some((_, weight)) => *weight = weight.max(boost),
+                    None => terms.push((term, boost)),
+                }
+            }
+        }
+        terms
+    }
+
+    /// The sorted ids matching `query`, or `None` if it only consists of stop words and so
+    /// constrains nothing. `leaf` resolves each word or phrase like
+    /// [`words_doc_ids`](Self::words_doc_ids).
+    pub(crate) fn evaluate<F>(&self, query: &Query, leaf: &mut F) -> Option<Vec<usize>>
+    where
+        F: FnMut(&str) -> Option<Vec<usize>>,
+    {
+        match query {
+            Query::Term(text) | Query::Phrase(text) => leaf(text),
+            Query::And(queries) => {
+                let mut result: Option<Vec<usize>> = None;
+                let mut excluded = Vec::new();
+                for query in queries {
+                    let (ids, negated) = match query {
+                        Query::Not(inner) => (self.evaluate(inner, leaf), true),
+                        _ => (self.evaluate(query, leaf), false),
+                    };
+                    match (ids, negated) {
+                        (None, _) => {}
+                        (Some(ids), true) => excluded = union_sorted(&excluded, &ids),
+                        (Some(ids), false) => {
+                            result = Some(match result {
+                                Some(acc) => intersect_sorted(&acc, &ids),
+                                None => ids,
+                            });
+                        }
+                    }
+                }
+                if result.is_none() && excluded.is_empty() {
+                    return None;
+                }
+                let result = result.unwrap_or_else(|| self.all_doc_ids());
+                Some(difference_sorted(&result, &excluded))
+            }
+            Query::Or(queries) => queries
+                .iter()
+                .filter_map(|query| self.evaluate(query, leaf))
+                .reduce(|acc, ids| union_sorted(&acc, &ids)),
+            Query::Not(inner) => {
+                let excluded = self.evaluate(inner, leaf)?;
+                Some(difference_sorted(&self.all_doc_ids(), &excluded))
+            }
+            Query::Boost(inner, _) => self.evaluate(inner, leaf),
+        }
+    }
+
+    /// The documents containing the words of `text`, as a phrase if there are several.
+    pub(crate) fn words_doc_ids(&self, text: &str) -> Option<Vec<usize>> {
+        let terms = self.analyzer.analyze_positions(text);
+        match &terms[..] {
+            [] => None,
+            [(_, term)] => Some(
+                self.live_postings(term)
+                    .map(|postings| postings.iter().map(|posting| posting.doc_id).collect())
+                    .unwrap_or_default(),
+            ),
+            _ => Some(self.phrase_doc_ids(text)),
+        }
+    }
+
+    fn all_doc_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.documents.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// The ids of the ascending list `a` that are not in the ascending list `b`.
+fn difference_sorted(a: &[usize], b: &[usize]) -> Vec<usize> {
+    a.iter()
+        .copied()
+        .filter(|id| b.binary_search(id).is_err())
+        .collect()
+}
+
+#[test]
+fn parse_test() {
+    let term = |t: &str| Query::Term(t.to_string());
+    assert_eq!(Query::parse("rust"), Ok(term("rust")));
+    assert_eq!(
+        Query::parse("rust fast OR go -slow"),
+        Ok(Query::Or(vec![
+            Query::And(vec![term("rust"), term("fast")]),
+            Query::And(vec![term("go"), Query::Not(Box::new(term("slow")))]),
+        ]))
+    );
+    assert_eq!(
+        Query::parse("(rust OR go) AND NOT \"hello world\""),
+        Ok(Query::And(vec![
+            Query::Or(vec![term("rust"), term("go")]),
+            Query::Not(Box::new(Query::Phrase("hello world".to_string()))),
+        ]))
+    );
+    assert_eq!(
+        Query::parse("rust and or"),
+        Ok(Query::And(vec![term("rust"), term("and"), term("or")]))
+    );
+    let boost = |query: Query, weight: f64| Query::Boost(Box::new(query), weight);
+    assert_eq!(
+        Query::parse("rust^2 \"hello world\"^0.5 (go OR c)^3"),
+        Ok(Query::And(vec![
+            boost(term("rust"), 2.0),
+            boost(Query::Phrase("hello world".to_string()), 0.5),
+            boost(Query::Or(vec![term("go"), term("c")]), 3.0),
+        ]))
+    );
+    assert_eq!(
+        Query::parse("-slow^2"),
+        Ok(Query::Not(Box::new(boost(term("slow"), 2.0))))
+    );
+}
+
+#[test]
+fn parse_errors_test() {
+    let error = |input: &str| match Query::parse(input) {
+        Err(QueryError::Syntax { message, offset }) => (message, offset),
+        other => panic!("{input} gave {other:?}"),
+    };
+    assert_eq!(Query::parse(" "), Err(QueryError::EmptyQuery));
+    assert_eq!(error("rust AND"), ("expected a term".to_string(), 8));
+    assert_eq!(error("(rust"), ("expected ')'".to_string(), 5));
+    assert_eq!(error("rust)"), ("unexpected ')'".to_string(), 4));
+    assert_eq!(error("say \"hi"), ("unterminated phrase".to_string(), 4));
+    assert_eq!(error("OR rust"), ("expected a term".to_string(), 0));
+    assert_eq!(
+        error("rust - go"),
+        ("'-' must precede a term".to_string(), 5)
+    );
+    let invalid = "boost must be a non-negative number".to_string();
+    assert_eq!(error("rust^x"), (invalid.clone(), 5));
+    assert_eq!(error("rust^-1"), (invalid.clone(), 5));
+    assert_eq!(error("rust^"), (invalid.clone(), 5));
+    assert_eq!(error("rust^inf"), (invalid, 5));
+    assert_eq!(error("rust ^2"), ("'^' must follow a term".to_string(), 5));
+    assert_eq!(error("^2 rust"), ("'^' must follow a term".to_string(), 0));
+
+    let err = crate::IndexError::from(Query::parse("(rust").unwrap_err());
+    assert_eq!(err.to_string(), "invalid query: expected ')' at byte 5");
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn search_query_test() {
+    let mut index = InvertedIndex::builder().stop_words(["the"]).build();
+    index.add(1, "Rust is a systems programming language");
+    index.add(2, "Go is a systems language");
+    index.add(3, "Rust and Go, Rust and Go");
+    index.add(4, "Python e-mail");
+
+    let ids = |query: &str| {
+        let query = Query::parse(query).unwrap();
+        index
+            .search_query(&query, &SearchOptions::default())
+            .iter()
+            .map(|hit| hit.doc_id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("rust"), vec![3, 1]);
+    assert_eq!(ids("rust go"), vec![3]);
+    assert_eq!(ids("rust OR python"), vec![3, 4, 1]);
+    let two_terms = SearchOptions {
+        minimum_should_match: 2,
+        ..SearchOptions::default()
+    };
+    let query = Query::parse("rust OR go OR python").unwrap();
+    let hits = index.search_query(&query, &two_terms);
+    assert_eq!(
+        hits.iter().map(|hit| hit.doc_id).collect::<Vec<_>>(),
+        vec![3]
+    );
+    assert_eq!(ids("systems -rust"), vec![2]);
+    assert_eq!(ids("\"systems language\""), vec![2]);
+    assert_eq!(ids("the rust"), vec![3, 1]);
+    assert_eq!(ids("NOT rust"), vec![2, 4]);
+    assert_eq!(ids("e-mail"), vec![4]);
+    assert_eq!(ids("(rust OR go) -programming"), vec![3, 2]);
+    assert!(ids("the").is_empty());
+}
+
+#[test]
+fn boost_ranking_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "a rare word");
+    index.add(2, "a common word");
+    index.add(3, "common");
+    index.add(4, "common");
+
+    let ranking = |query: &str| {
+        let query = Query::parse(query).unwrap();
+        index
+            .search_query(&query, &SearchOptions::default())
+            .iter()
+            .map(|hit| (hit.doc_id, hit.score))
+            .collect::<Vec<_>>()
+    };
+    // Unboosted, the rarer term outweighs the common one.
+    let plain = ranking("rare OR common");
+    assert_eq!(plain[0].0, 1);
+    assert_eq!(ranking("rare^1 OR common"), plain);
+    // Lowering its boost lets the common term win.
+    let lowered = ranking("rare^0.5 OR common");
+    assert_eq!(lowered[0].0, 2);
+    assert_eq!(lowered.last(), Some(&(1, plain[0].1 * 0.5)));
+    // A zero boost still matches, without adding to the score.
+    assert_eq!(ranking("rare^0")[0], (1, 0.0));
+    // Nested boosts multiply.
+    assert_eq!(ranking("(rare^2)^3")[0], (1, plain[0].1 * 6.0));
+}
@@ -0,0 +1,513 @@
+use crate::cache::{CacheKey, CacheKind};
+use crate::index::InvertedIndex;
+use crate::query::QueryError;
+use crate::search::idf;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+/// BM25 term frequency saturation.
+const BM25_K1: f64 = 1.2;
+/// BM25 document length normalization.
+const BM25_B: f64 = 0.75;
+
+/// How a single term's occurrences in a document are turned into a score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Scorer {
+    TfIdf,
+    Bm25,
+}
+
+/// The collection-wide numbers a term's score depends on besides its document frequency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Corpus {
+    /// Number of live documents.
+    pub(crate) doc_count: usize,
+    /// Average document length in tokens.
+    pub(crate) average_length: f64,
+}
+
+/// How many of the query terms a document has to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Combine {
+    Any,
+    All,
+    /// At least this many distinct terms.
+    AtLeast(usize),
+    /// Any, with the summed score scaled by the fraction of the terms matched.
+    Coordinated,
+}
+
+impl InvertedIndex {
+    /// Ranks the documents containing any word of `query` with Okapi BM25.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order.
+    pub fn query_bm25(&self, query: &str) -> Vec<(usize, f64)> {
+        self.rank(query, Scorer::Bm25, Combine::Any)
+    }
+
+    /// Ranks the documents containing every word of `query` by their summed TF-IDF.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order.
+    /// Empty if any word was never indexed.
+    pub fn query_all(&self, query: &str) -> Vec<(usize, f64)> {
+        self.rank(query, Scorer::TfIdf, Combine::All)
+    }
+
+    /// Ranks the documents containing any word of `query` by their summed TF-IDF, scaled by
+    /// the fraction of the distinct query words each one contains.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order.
+    ///
+    /// # Notes
+    /// Like [`search`](Self::search), every term adds its TF-IDF, but a document matching
+    /// one of three terms keeps only a third of its sum. A document repeating one rare term
+    /// many times therefore no longer outranks a document matching the whole query, unless
+    /// its single-term score is more than three times as high.
+    pub fn query_ranked_multi(&self, query: &str) -> Vec<(usize, f64)> {
+        self.rank(query, Scorer::TfIdf, Combine::Coordinated)
+    }
+
+    /// Ranks the documents containing any word of `query` by their summed TF-IDF, like
+    /// [`search`](Self::search), keeping only the strong matches.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs scoring at or above `min_score`, highest score first and ties
+    /// in ascending doc id order. The threshold applies to the final scores, so it cuts the
+    /// same ranking that an unfiltered query returns.
+    pub fn query_ranked_threshold(&self, query: &str, min_score: f64) -> Vec<(usize, f64)> {
+        let mut ranked = self.rank(query, Scorer::TfIdf, Combine::Any);
+        ranked.truncate(ranked.partition_point(|(_, score)| *score >= min_score));
+        ranked
+    }
+
+    /// The `k` best documents [`search`](Self::search) ranks for `query`, without sorting
+    /// every match.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order, the
+    /// same as the first `k` of the full ranking.
+    ///
+    /// # Notes
+    /// The scores are kept in a heap of at most `k` entries, which takes O(n log k) time for
+    /// n matching documents instead of the O(n log n) of a full sort. The result is not
+    /// cached.
+    pub fn query_top_k(&self, query: &str, k: usize) -> Vec<(usize, f64)> {
+        let per_term = self.score_terms(&self.query_terms(query), Scorer::TfIdf);
+        top_k(merge_term_scores(per_term, Combine::Any), k)
+    }
+
+    /// Ranks the documents containing any of `terms` by their TF-IDF, with each term's
+    /// contribution multiplied by its weight, for manual relevance tuning.
+    ///
+    /// # Parameters
+    /// - `terms`: `(term, weight)` pairs. Terms are normalized like [`query`](Self::query)
+    ///   terms, so a multi-word term matches a phrase indexed with
+    ///   [`add_with_phrases`](Self::add_with_phrases). A term given twice counts with both
+    ///   weights.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order. With
+    /// all weights 1.0 the scores are those of [`search`](Self::search).
+    pub fn query_weighted(&self, terms: &[(&str, f64)]) -> Vec<(usize, f64)> {
+        let per_term = terms
+            .iter()
+            .filter_map(|(term, weight)| Some((self.analyze_term(term)?, *weight)))
+            .map(|(term, weight)| {
+                let mut scores = self.term_scores(&term, Scorer::TfIdf);
+                for (_, score) in &mut scores {
+                    *score *= weight;
+                }
+                scores
+            })
+            .collect();
+        sort_by_score(merge_term_scores(per_term, Combine::Any))
+    }
+
+    /// Like [`query_weighted`](Self::query_weighted), with the weights written into the
+    /// query: `"rust^2 programming"` counts "rust" twice.
+    ///
+    /// # Parameters
+    /// - `query`: Whitespace separated terms, each optionally followed by `^` and a
+    ///   non-negative weight. Terms without one weigh 1.0.
+    ///
+    /// # Returns
+    /// [`QueryError::EmptyQuery`] for a blank `query`, and [`QueryError::Syntax`] at the
+    /// offending term if a weight is not a non-negative number.
+    pub fn query_boosted(&self, query: &str) -> Result<Vec<(usize, f64)>, QueryError> {
+        let mut terms = Vec::new();
+        for word in query.split_whitespace() {
+            let offset = word.as_ptr() as usize - query.as_ptr() as usize;
+            let (term, weight) = match word.rsplit_once('^') {
+                Some((term, weight)) => {
+                    let weight = weight
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|weight| weight.is_finite() && *weight >= 0.0)
+                        .ok_or_else(|| QueryError::Syntax {
+                            message: format!("invalid weight in \"{}\"", word),
+                            offset,
+                        })?;
+                    (term, weight)
+                }
+                None => (word, 1.0),
+            };
+            terms.push((term, weight));
+        }
+        if terms.is_empty() {
+            return Err(QueryError::EmptyQuery);
+        }
+        Ok(self.query_weighted(&terms))
+    }
+
+    /// Analyzes `query` into its distinct terms, in first-occurrence order.
+    pub(crate) fn query_terms(&self, query: &str) -> Vec<String> {
+        let mut terms = self.analyzer.analyze(query);
+        let mut seen = std::collections::HashSet::new();
+        terms.retain(|term| !self.capped_terms.contains(term) && seen.insert(term.clone()));
+        terms
+    }
+
+    pub(crate) fn rank(&self, query: &str, scorer: Scorer, combine: Combine) -> Vec<(usize, f64)> {
+        let key = CacheKey {
+            kind: CacheKind::Ranked(scorer, combine),
+            terms: self.query_terms(query),
+        };
+        self.cached(key, |key| {
+            let per_term = self.score_terms(&key.terms, scorer);
+            sort_by_score(merge_term_scores(per_term, combine))
+        })
+    }
+
+    /// The contribution of one term to each document containing it, in ascending doc id order.
+    pub(crate) fn term_scores(&self, term: &str, scorer: Scorer) -> Vec<(usize, f64)> {
+        let corpus = Corpus {
+            doc_count: self.documents.len(),
+            average_length: self.average_document_length(),
+        };
+        let doc_freq = self
+            .live_postings(term)
+            .map_or(0, |postings| postings.len());
+        self.term_scores_in(term, scorer, &corpus, doc_freq)
+    }
+
+    /// Like [`term_scores`](Self::term_scores), with the document frequency `doc_freq` of
+    /// the term and the `corpus` statistics taken from a larger collection of documents
+    /// than this index, such as all shards of a [`ShardedIndex`](crate::ShardedIndex).
+    pub(crate) fn term_scores_in(
+        &self,
+        term: &str,
+        scorer: Scorer,
+        corpus: &Corpus,
+        doc_freq: usize,
+    ) -> Vec<(usize, f64)> {
+        let Some(postings) = self.live_postings(term) else {
+            return Vec::new();
+        };
+        let doc_count = corpus.doc_count;
+        match scorer {
+            Scorer::TfIdf => {
+                let idf = idf(doc_count, doc_freq);
+                postings
+                    .iter()
+                    .map(|posting| (posting.doc_id, posting.term_freq() as f64 * idf))
+                    .collect()
+            }
+            Scorer::Bm25 => {
+                let df = doc_freq as f64;
+                let idf = (1.0 + (doc_count as f64 - df + 0.5) / (df + 0.5)).ln();
+                let average_length = corpus.average_length.max(1.0);
+                postings
+                    .iter()
+                    .map(|posting| {
+                        let length =
+                            self.documents
+                                .get(&posting.doc_id)
+                                .map_or(0, |doc| doc.length) as f64;
+                        let tf = posting.term_freq() as f64;
+                        let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length);
+                        (posting.doc_id, idf * tf * (BM25_K1 + 1.0) / (tf + norm))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Scores every term separately. With the `parallel` feature, queries whose posting lists
+    /// hold more than the configured threshold of entries in total score each term on its
+    /// own scoped thread.
+    pub(crate) fn score_terms(&self, terms: &[String], scorer: Scorer) -> Vec<Vec<(usize, f64)>> {
+        #[cfg(feature = "parallel")]
+        {
+            let postings: usize = terms
+                .iter()
+                .filter_map(|term| self.indexes.get(term))
+                .map(|postings| postings.len())
+                .sum();
+            if terms.len() > 1 && postings > self.parallel_threshold {
+                return std::thread::scope(|scope| {
+                    let workers: Vec<_> = terms
+                        .iter()
+                        .map(|term| scope.spawn(move || self.term_scores(term, scorer)))
+                        .collect();
+                    workers
+                        .into_iter()
+                        .map(|worker| worker.join().expect("scoring thread panicked"))
+                        .collect()
+                });
+            }
+        }
+        terms
+            .iter()
+            .map(|term| self.term_scores(term, scorer))
+            .collect()
+    }
+}
+
+/// Sums the per-term scores of each document.
+///
+/// Contributions are added in term order whichever thread computed them, so the floating
+/// point results do not depend on how the work was split.
+pub(crate) fn merge_term_scores(
+    per_term: Vec<Vec<(usize, f64)>>,
+    combine: Combine,
+) -> HashMap<usize, f64> {
+    let term_count = per_term.len();
+    let mut scores: HashMap<usize, (f64, usize)> = HashMap::new();
+    for term_scores in per_term {
+        for (doc_id, score) in term_scores {
+            let entry = scores.entry(doc_id).or_default();
+            entry.0 += score;
+            entry.1 += 1;
+        }
+    }
+    scores
+        .into_iter()
+        .filter(|(_, (_, matched))| match combine {
+            Combine::Any | Combine::Coordinated => true,
+            Combine::All => *matched == term_count,
+            Combine::AtLeast(min) => *matched >= min,
+        })
+        .map(|(doc_id, (score, matched))| match combine {
+            Combine::Coordinated => (doc_id, score * matched as f64 / term_count as f64),
+            _ => (doc_id, score),
+        })
+        .collect()
+}
+
+/// A scored document, greater when it ranks higher: by score, then by lower doc id.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ranked(usize, f64);
+
+impl Eq for Ranked {}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.1.total_cmp(&other.1).then(other.0.cmp(&self.0))
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The `k` documents [`sort_by_score`] would put first, in its order.
+pub(crate) fn top_k(scores: HashMap<usize, f64>, k: usize) -> Vec<(usize, f64)> {
+    if k == 0 {
+        return Vec::new();
+    }
+    // A min-heap of the best documents so far, with the worst of them on top.
+    let mut heap: BinaryHeap<Reverse<Ranked>> = BinaryHeap::with_capacity(k.min(scores.len()));
+    for (doc_id, score) in scores {
+        let ranked = Reverse(Ranked(doc_id, score));
+        if heap.len() < k {
+            heap.push(ranked);
+        } else if let Some(mut worst) = heap.peek_mut()
+            && ranked < *worst
+        {
+            *worst = ranked;
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(Ranked(doc_id, score))| (doc_id, score))
+        .collect()
+}
+
+/// Orders scored documents by descending score, breaking ties by ascending doc id.
+pub(crate) fn sort_by_score(scores: HashMap<usize, f64>) -> Vec<(usize, f64)> {
+    let mut ranked: Vec<(usize, f64)> = scores.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    ranked
+}
+
+#[test]
+fn query_bm25_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust rust rust");
+    index.add(2, "rust and a much longer document about other things");
+    index.add(3, "go");
+    let ranked = index.query_bm25("rust");
+    assert_eq!(ranked.len(), 2);
+    assert_eq!(ranked[0].0, 1);
+    assert!(ranked[0].1 > ranked[1].1);
+    assert!(index.query_bm25("python").is_empty());
+}
+
+#[test]
+fn query_all_test() {
+    let index = crate::index::sort_fixture();
+    let ranked: Vec<usize> = index.query_all("rust go").iter().map(|r| r.0).collect();
+    assert_eq!(ranked, vec![2, 1]);
+    assert!(index.query_all("rust python").is_empty());
+}
+
+#[test]
+fn query_ranked_multi_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "borrow borrow borrow borrow borrow");
+    index.add(2, "rust ownership and borrow checking");
+    index.add(3, "rust ownership");
+    index.add(4, "rust is fast");
+    index.add(5, "ownership of a house");
+    index.add(6, "nothing relevant");
+
+    let ids = |ranked: &[(usize, f64)]| ranked.iter().map(|r| r.0).collect::<Vec<_>>();
+    // The five rare "borrow"s alone outscore the full match in a plain sum.
+    let summed = index.rank("rust ownership borrow", Scorer::TfIdf, Combine::Any);
+    assert_eq!(ids(&summed)[..2], [1, 2]);
+
+    let ranked = index.query_ranked_multi("rust ownership borrow");
+    assert_eq!(ids(&ranked), vec![2, 1, 3, 4, 5]);
+    // The full match keeps its sum, partial matches a share of theirs.
+    let full = summed.iter().find(|r| r.0 == 2).unwrap().1;
+    assert_eq!(ranked[0].1, full);
+    let single = summed.iter().find(|r| r.0 == 1).unwrap().1;
+    assert_eq!(ranked[1].1, single / 3.0);
+    // A single term scores like a plain query.
+    assert_eq!(
+        index.query_ranked_multi("rust"),
+        index.rank("rust", Scorer::TfIdf, Combine::Any)
+    );
+    assert!(index.query_ranked_multi("python").is_empty());
+}
+
+#[test]
+fn query_ranked_threshold_test() {
+    let index = crate::index::sort_fixture();
+    let all = index.query_ranked_threshold("rust go", f64::NEG_INFINITY);
+    assert_eq!(all.len(), 4);
+    let mut previous = all.len();
+    // Raising the threshold from the lowest score to the highest shrinks the result set.
+    for (_, score) in all.iter().rev() {
+        let strong = index.query_ranked_threshold("rust go", *score);
+        assert!(strong.len() <= previous);
+        assert!(strong.iter().all(|(_, s)| s >= score));
+        assert_eq!(strong[..], all[..strong.len()]);
+        previous = strong.len();
+    }
+    assert_eq!(
+        index.query_ranked_threshold("rust go", all[0].1 + 1.0),
+        vec![]
+    );
+}
+
+#[test]
+fn query_weighted_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust");
+    index.add(2, "programming programming");
+    index.add(3, "rust programming");
+    index.add(4, "python");
+
+    let ids = |ranked: Vec<(usize, f64)>| ranked.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    let plain = index.query_weighted(&[("rust", 1.0), ("programming", 1.0)]);
+    let search: Vec<(usize, f64)> = index
+        .search("rust programming", &crate::SearchOptions::default())
+        .iter()
+        .map(|hit| (hit.doc_id, hit.score))
+        .collect();
+    assert_eq!(plain, search);
+    assert_eq!(ids(plain), vec![2, 3, 1]);
+
+    // Boosting "rust" lets the document with only "rust" outrank the one with only
+    // "programming".
+    let boosted = index.query_weighted(&[("Rust", 3.0), ("programming", 1.0)]);
+    assert_eq!(ids(boosted.clone()), vec![3, 1, 2]);
+    let rust_only = index.query_weighted(&[("rust", 1.0)]);
+    assert_eq!(boosted[1].1, rust_only[0].1 * 3.0);
+    assert_eq!(index.query_boosted("rust^3 programming"), Ok(boosted));
+    assert_eq!(
+        ids(index.query_weighted(&[("rust", 0.0), ("python", 1.0)])),
+        vec![4, 1, 3]
+    );
+    assert!(index.query_weighted(&[("unknown", 5.0)]).is_empty());
+
+    assert_eq!(
+        index.query_boosted("rust programming^x"),
+        Err(QueryError::Syntax {
+            message: "invalid weight in \"programming^x\"".to_string(),
+            offset: 5
+        })
+    );
+    assert!(index.query_boosted("rust^-1").is_err());
+    assert_eq!(index.query_boosted("  "), Err(QueryError::EmptyQuery));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_ranking_matches_sequential_test() {
+    let mut seed: u64 = 42;
+    let mut next = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    let mut index = InvertedIndex::new();
+    for id in 0..300 {
+        let words: Vec<String> = (0..5 + next() % 20)
+            .map(|_| format!("w{}", next() % 15))
+            .collect();
+        index.add(id, &words.join(" "));
+    }
+    let query = "w1 w2 w3 w4 w5";
+
+    index.parallel_threshold = usize::MAX;
+    let (bm25, all) = (index.query_bm25(query), index.query_all("w1 w2"));
+    index.parallel_threshold = 0;
+    assert_eq!(index.query_bm25(query), bm25);
+    assert_eq!(index.query_all("w1 w2"), all);
+    assert!(!bm25.is_empty() && !all.is_empty());
+}
+
+#[test]
+fn query_top_k_test() {
+    let mut index = InvertedIndex::new();
+    for id in 0..500 {
+        let content = format!(
+            "{} rust{} go{}",
+            "rust ".repeat(id % 6),
+            " go".repeat(id % 4),
+            if id % 9 == 0 { " rare" } else { "" }
+        );
+        index.add(id, &content);
+    }
+    for query in ["rust", "rust go", "rare rust", "go", "absent"] {
+        let full = index.rank(query, Scorer::TfIdf, Combine::Any);
+        for k in [0, 1, 10, 83, 499, 1000] {
+            let mut expected = full.clone();
+            expected.truncate(k);
+            assert_eq!(index.query_top_k(query, k), expected, "{query} {k}");
+        }
+    }
+    assert_eq!(index.query_top_k("rust", 3).len(), 3);
+}
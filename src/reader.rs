@@ -0,0 +1,158 @@
+use crate::analysis::TermBuffer;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::io::{self, BufRead};
+
+/// How [`InvertedIndex::add_from_reader_with`] handles input that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidUtf8 {
+    /// Fail with an [`io::ErrorKind::InvalidData`] error, leaving the index untouched.
+    #[default]
+    Error,
+    /// Replace each invalid sequence with U+FFFD and keep reading.
+    Lossy,
+}
+
+impl InvertedIndex {
+    /// Adds a document whose content is read from `reader`, failing on invalid UTF-8.
+    ///
+    /// See [`add_from_reader_with`](Self::add_from_reader_with).
+    pub fn add_from_reader(&mut self, doc_id: usize, reader: impl BufRead) -> io::Result<()> {
+        self.add_from_reader_with(doc_id, reader, InvalidUtf8::Error)
+    }
+
+    /// Adds a document whose content is read from `reader`, one line at a time.
+    ///
+    /// # Parameters
+    /// - `doc_id`: An identifier for the document. An existing document with this id is
+    ///   replaced once the whole input has been read.
+    /// - `reader`: The document text.
+    /// - `invalid_utf8`: What to do with bytes that are not valid UTF-8.
+    ///
+    /// # Returns
+    /// Any error of the reader, or an [`io::ErrorKind::InvalidData`] error for invalid UTF-8
+    /// under [`InvalidUtf8::Error`]. On error the index is left unchanged.
+    ///
+    /// # Notes
+    /// Lines are tokenized as they are read, buffering one line of raw input at a time,
+    /// and token positions carry on from one line to the next, so
+    /// [`query_phrase`](Self::query_phrase) matches phrases that span a line break. The full
+    /// decoded text is still stored as the document content, exactly as [`add`](Self::add)
+    /// would store it, so that queries can highlight it.
+    pub fn add_from_reader_with(
+        &mut self,
+        doc_id: usize,
+        mut reader: impl BufRead,
+        invalid_utf8: InvalidUtf8,
+    ) -> io::Result<()> {
+        let mut content = String::new();
+        let mut words = TermBuffer::default();
+        let mut offsets = Vec::new();
+        let mut line = Vec::new();
+        let mut position = 0;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let start = content.len();
+            match std::str::from_utf8(&line) {
+                Ok(text) => content.push_str(text),
+                Err(err) if invalid_utf8 == InvalidUtf8::Error => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                }
+                Err(_) => content.push_str(&String::from_utf8_lossy(&line)),
+            }
+            // Lines end in a separator, so no token straddles two of them.
+            let first = offsets.len();
+            position =
+                self.analyzer
+                    .analyze_into(&content[start..], position, &mut words, &mut offsets);
+            for (from, to) in &mut offsets[first..] {
+                *from += start;
+                *to += start;
+            }
+        }
+        self.record(LogEntry::Add {
+            id: doc_id,
+            content: &content,
+        });
+        let length = words.len();
+        self.index_terms(doc_id, content, &mut words, offsets, length);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+/// Generates `lines` lines of filler text, with "boundary" ending one line and "crossing"
+/// starting the next one in the middle of the input.
+struct SyntheticText {
+    lines: usize,
+    next: usize,
+    pending: Vec<u8>,
+}
+
+#[cfg(test)]
+impl io::Read for SyntheticText {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() && self.next < self.lines {
+            let line = match self.next {
+                n if n == self.lines / 2 => "filler words before the boundary\n".to_string(),
+                n if n == self.lines / 2 + 1 => "crossing into the next line\n".to_string(),
+                n => format!("line {n} of the synthetic filler text stream\n"),
+            };
+            self.pending = line.into_bytes();
+            self.next += 1;
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+#[test]
+fn add_from_reader_test() {
+    let text = SyntheticText {
+        lines: 100_000,
+        next: 0,
+        pending: Vec::new(),
+    };
+    // A small buffer, so lines also get split across reads.
+    let reader = io::BufReader::with_capacity(16, text);
+    let mut index = InvertedIndex::new();
+    index.add_from_reader(7, reader).unwrap();
+
+    let content = index.get(7).unwrap();
+    assert!(content.len() > 4_000_000, "{}", content.len());
+    assert_eq!(
+        index.phrase_doc_ids("before the boundary crossing into"),
+        vec![7]
+    );
+    assert_eq!(index.phrase_doc_ids("stream line 42"), vec![7]);
+    assert!(index.phrase_doc_ids("boundary into").is_empty());
+
+    let mut added = InvertedIndex::new();
+    added.add(7, &content);
+    assert_eq!(added.indexes, index.indexes);
+}
+
+#[test]
+fn add_from_reader_invalid_utf8_test() {
+    let input: &[u8] = b"valid line\ninvalid \xff byte\n";
+    let mut index = InvertedIndex::new();
+    index.add(1, "untouched");
+
+    let err = index.add_from_reader(1, input).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(index.get(1).as_deref(), Some("untouched"));
+
+    index
+        .add_from_reader_with(1, input, InvalidUtf8::Lossy)
+        .unwrap();
+    assert_eq!(
+        index.get(1).as_deref(),
+        Some("valid line\ninvalid \u{fffd} byte\n")
+    );
+    assert_eq!(index.phrase_doc_ids("line invalid"), vec![1]);
+}
@@ -0,0 +1,115 @@
+use crate::index::InvertedIndex;
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+
+/// The order in which ranked search results are returned.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SortBy {
+    /// Highest score first; ties keep ascending doc id order.
+    #[default]
+    Score,
+    /// Ascending document id.
+    DocIdAsc,
+    /// Descending document id.
+    DocIdDesc,
+    /// The value of the named [numeric field](crate::InvertedIndex::add_numeric), e.g.
+    /// newest first with `NumericField("year".into(), SortOrder::Desc)`. Documents without
+    /// the field come last in either order, and hits with equal values keep their score
+    /// order.
+    NumericField(String, SortOrder),
+}
+
+/// The direction of [`SortBy::NumericField`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Smallest value first.
+    #[default]
+    Asc,
+    /// Largest value first.
+    Desc,
+}
+
+/// Options controlling how [`InvertedIndex::search`](crate::InvertedIndex::search) orders and
+/// pages its results.
+///
+/// Hits are filtered by `minimum_should_match` first, then sorted, then `offset` hits are
+/// skipped and at most `limit` are returned.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub sort_by: SortBy,
+    /// Maximum number of hits to return, `None` for all of them.
+    pub limit: Option<usize>,
+    /// Number of leading hits to skip.
+    pub offset: usize,
+    /// How many distinct query terms a document must contain to be a hit. A term occurring
+    /// several times counts once. 0 and 1 keep any match, the number of query terms requires
+    /// all of them, and more than that matches nothing. Hits are still scored on every term
+    /// they contain.
+    pub minimum_should_match: usize,
+}
+
+/// A single search result. The content is borrowed from the index, or read from the
+/// [document store](crate::IndexBuilder::document_store) if there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit<'a> {
+    pub doc_id: usize,
+    pub score: f64,
+    /// `None` if the index does not [store contents](crate::IndexBuilder::store_content),
+    /// or the content cannot be read from the document store.
+    pub content: Option<Cow<'a, str>>,
+}
+
+/// Sorts `(doc_id, score)` hits in place according to `sort_by`.
+///
+/// The sort is stable, so hits that compare equal keep their relative order.
+pub(crate) fn sort_hits(index: &InvertedIndex, hits: &mut [(usize, f64)], sort_by: &SortBy) {
+    match sort_by {
+        SortBy::Score => hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)),
+        SortBy::DocIdAsc => hits.sort_by_key(|hit| hit.0),
+        SortBy::DocIdDesc => hits.sort_by_key(|hit| Reverse(hit.0)),
+        SortBy::NumericField(field, order) => {
+            let values = index.numeric.get(field).map(|field| &field.values);
+            let value = |id: usize| values.and_then(|values| values.get(&id));
+            hits.sort_by(|a, b| match (value(a.0), value(b.0)) {
+                (Some(a), Some(b)) => {
+                    let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+                    match order {
+                        SortOrder::Asc => ordering,
+                        SortOrder::Desc => ordering.reverse(),
+                    }
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+    }
+}
+
+/// Applies `offset` and `limit` from `options` to an already sorted list of hits, and
+/// looks up the content of the remaining ones, so that only the returned page is read
+/// from a document store.
+pub(crate) fn paginate<'a>(
+    index: &'a InvertedIndex,
+    hits: Vec<(usize, f64)>,
+    options: &SearchOptions,
+) -> Vec<SearchHit<'a>> {
+    let limit = options.limit.unwrap_or(usize::MAX);
+    hits.into_iter()
+        .skip(options.offset)
+        .take(limit)
+        .filter_map(|(doc_id, score)| {
+            let doc = index.documents.get(&doc_id)?;
+            Some(SearchHit {
+                doc_id,
+                score,
+                content: index.content(doc),
+            })
+        })
+        .collect()
+}
+
+/// The inverse document frequency of a term that appears in `doc_freq` of `doc_count` documents.
+pub(crate) fn idf(doc_count: usize, doc_freq: usize) -> f64 {
+    1.0 + (doc_count as f64 / doc_freq as f64).ln()
+}
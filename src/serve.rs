@@ -0,0 +1,307 @@
+//! A small HTTP/1.1 JSON API over a [`SharedIndex`], one thread per connection.
+//!
+//! ```text
+//! GET    /search?q=<query>&limit=<n>   ranked hits with snippets, see Query::parse; with
+//!                                      &profile=true {"hits": [...], "profile": {...}}
+//! POST   /documents                    add or replace {"id": 1, "content": "..."}
+//! DELETE /documents/<id>               remove a document
+//! GET    /stats                        index statistics and the most common terms
+//! ```
+//!
+//! Errors are answered with a status code and a body like `{"error": "..."}`.
+
+use crate::highlight::snippet;
+use crate::json::{Json, quote};
+use crate::jsonl::parse_id;
+use crate::profile::QueryProfile;
+use crate::query::Query;
+use crate::search::SearchOptions;
+use crate::shared::SharedIndex;
+use crate::tokenizer::tokenize_vec;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Instant;
+
+/// Hits returned by `/search` when the request sets no `limit`.
+const DEFAULT_LIMIT: usize = 10;
+/// Largest request body accepted, in bytes.
+const MAX_BODY: usize = 16 << 20;
+
+/// An HTTP server answering search and update requests against a shared index.
+pub struct Server {
+    listener: TcpListener,
+    index: SharedIndex,
+}
+
+impl Server {
+    /// Listens on `addr`. Port 0 picks a free port, see [`local_addr`](Self::local_addr).
+    pub fn bind(addr: impl ToSocketAddrs, index: SharedIndex) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            index,
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until listening fails, handling each on its own thread so that
+    /// searches run concurrently.
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let index = self.index.clone();
+            std::thread::spawn(move || {
+                // A client that hangs up early is its own problem.
+                let _ = handle(&index, stream);
+            });
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    body: Vec<u8>,
+}
+
+/// A response: status code and JSON body.
+type Response = (u16, String);
+
+fn error(status: u16, message: &str) -> Response {
+    (status, format!("{{\"error\":{}}}", quote(message)))
+}
+
+fn handle(index: &SharedIndex, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let (status, body) = match read_request(&mut reader)? {
+        Ok(request) => route(index, &request),
+        Err(response) => response,
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Reads the request line, the headers and the body, or the response to a malformed
+/// request.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err(error(400, "malformed request line")));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            match value.trim().parse() {
+                Ok(value) if value <= MAX_BODY => length = value,
+                _ => return Ok(Err(error(400, "invalid Content-Length"))),
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request {
+        method,
+        target,
+        body,
+    }))
+}
+
+fn route(index: &SharedIndex, request: &Request) -> Response {
+    let target = request.target.as_str();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (request.method.as_str(), &segments[..]) {
+        ("GET", ["search"]) => search(index, query),
+        ("GET", ["stats"]) => stats(index),
+        ("POST", ["documents"]) => add_document(index, &request.body),
+        ("DELETE", ["documents", id]) => match id.parse() {
+            Ok(id) if index.remove(id) => (200, format!("{{\"id\":{}}}", id)),
+            Ok(id) => error(404, &format!("no document {}", id)),
+            Err(_) => error(400, &format!("invalid document id \"{}\"", id)),
+        },
+        (_, ["search" | "stats"] | ["documents", ..]) => error(405, "method not allowed"),
+        _ => error(404, "not found"),
+    }
+}
+
+fn search(index: &SharedIndex, query_string: &str) -> Response {
+    let mut text = None;
+    let mut limit = DEFAULT_LIMIT;
+    let mut profiled = false;
+    for pair in query_string.split('&') {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+        match name {
+            "q" => text = Some(value),
+            "limit" => match value.parse() {
+                Ok(value) => limit = value,
+                Err(_) => return error(400, &format!("invalid limit \"{}\"", value)),
+            },
+            "profile" => profiled = value == "true",
+            _ => {}
+        }
+    }
+    let Some(text) = text else {
+        return error(400, "missing parameter q");
+    };
+    let query = match Query::parse(&text) {
+        Ok(query) => query,
+        Err(err) => return error(400, &err.to_string()),
+    };
+    let words: Vec<&str> = query
+        .positive_terms()
+        .into_iter()
+        .flat_map(tokenize_vec)
+        .collect();
+    let options = SearchOptions {
+        limit: Some(limit),
+        ..SearchOptions::default()
+    };
+    let (hits, profile) = index.read(|index| {
+        let (hits, mut profile) = if profiled {
+            index.search_with_profile(&query, &options)
+        } else {
+            (
+                index.search_query(&query, &options),
+                QueryProfile::default(),
+            )
+        };
+        let start = profiled.then(Instant::now);
+        let hits = hits
+            .iter()
+            .map(|hit| {
+                let path = index.document_path(hit.doc_id);
+                format!(
+                    "{{\"id\":{},\"score\":{},\"path\":{},\"snippet\":{}}}",
+                    hit.doc_id,
+                    hit.score,
+                    path.map_or("null".to_string(), |path| quote(&path.to_string_lossy())),
+                    hit.content
+                        .as_deref()
+                        .map_or("null".to_string(), |content| quote(&snippet(
+                            content, &words
+                        )))
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Some(start) = start {
+            profile.highlighting = start.elapsed();
+        }
+        (hits, profile)
+    });
+    let hits = format!("[{}]", hits.join(","));
+    if profiled {
+        let body = format!("{{\"hits\":{},\"profile\":{}}}", hits, profile.to_json());
+        return (200, body);
+    }
+    (200, hits)
+}
+
+fn stats(index: &SharedIndex) -> Response {
+    let stats = index.read(|index| index.stats());
+    let longest = match &stats.longest_postings {
+        Some((term, len)) => format!("{{\"term\":{},\"documents\":{}}}", quote(term), len),
+        None => "null".to_string(),
+    };
+    let top_terms: Vec<String> = stats
+        .top_terms
+        .iter()
+        .map(|(term, len)| format!("{{\"term\":{},\"documents\":{}}}", quote(term), len))
+        .collect();
+    let body = format!(
+        "{{\"documents\":{},\"vocabulary_size\":{},\"total_postings\":{},\
+         \"average_document_length\":{},\"longest_postings\":{},\"top_terms\":[{}],\"deleted_documents\":{}}}",
+        stats.document_count,
+        stats.vocabulary_size,
+        stats.total_postings,
+        stats.average_document_length,
+        longest,
+        top_terms.join(","),
+        stats.deleted_documents
+    );
+    (200, body)
+}
+
+fn add_document(index: &SharedIndex, body: &[u8]) -> Response {
+    let Ok(body) = std::str::from_utf8(body) else {
+        return error(400, "body is not valid UTF-8");
+    };
+    let document = match Json::parse(body) {
+        Ok(document) => document,
+        Err(err) => return error(400, &format!("invalid JSON: {}", err)),
+    };
+    let Some(id) = document.get("id").and_then(parse_id) else {
+        return error(400, "\"id\" must be a non-negative integer");
+    };
+    let Some(Json::String(content)) = document.get("content") else {
+        return error(400, "\"content\" must be a string");
+    };
+    index.add(id, content);
+    (201, format!("{{\"id\":{}}}", id))
+}
+
+/// Decodes `%XX` escapes and `+` for spaces in a query string value. Malformed escapes
+/// are kept as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => out.push(b' '),
+            (byte, _) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "",
+    }
+}
+
+#[test]
+fn percent_decode_test() {
+    assert_eq!(percent_decode("rust+%22fast%22"), "rust \"fast\"");
+    assert_eq!(percent_decode("%C3%A9%2"), "é%2");
+    assert_eq!(percent_decode("100%zz"), "100%zz");
+}
@@ -0,0 +1,333 @@
+//! An index split into shards by document id, queried across all of them.
+
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::persist::corrupt;
+use crate::ranking::{Combine, Corpus, Scorer, merge_term_scores, sort_by_score};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The first line of a sharded index's manifest, naming its format.
+const MANIFEST_HEADER: &str = "inverted_index shards 1";
+/// The manifest's file name in the directory of a saved sharded index.
+const MANIFEST: &str = "manifest";
+
+/// An index split into a fixed number of independent [`InvertedIndex`] shards, to keep
+/// each one small on a very large corpus.
+///
+/// A document lives in the shard its id hashes to, so [`add`](Self::add) and
+/// [`remove`](Self::remove) touch a single shard. Queries go to every shard, on a thread
+/// each with the `parallel` feature, and their results are merged so that they equal
+/// those of one index holding every document: the ranked queries score with document
+/// frequencies and lengths summed over all shards.
+pub struct ShardedIndex {
+    shards: Vec<InvertedIndex>,
+}
+
+impl ShardedIndex {
+    /// An empty index of `shards` default-configured shards.
+    ///
+    /// # Panics
+    /// If `shards` is 0.
+    pub fn new(shards: usize) -> ShardedIndex {
+        Self::with_shards(shards, InvertedIndex::new)
+    }
+
+    /// An empty index of `shards` shards, each built by `make`, e.g.
+    /// `|| InvertedIndex::builder().stemming(true).build()`. Every shard must be configured
+    /// alike for the merged results to be consistent.
+    ///
+    /// # Panics
+    /// If `shards` is 0.
+    pub fn with_shards(shards: usize, make: impl FnMut() -> InvertedIndex) -> ShardedIndex {
+        assert!(shards > 0, "a sharded index needs at least one shard");
+        Self {
+            shards: std::iter::repeat_with(make).take(shards).collect(),
+        }
+    }
+
+    /// The shards, in the order documents are routed to them.
+    pub fn shards(&self) -> &[InvertedIndex] {
+        &self.shards
+    }
+
+    /// The number of documents in all shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.documents.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The position of the shard holding document `id`.
+    pub fn shard_of(&self, id: usize) -> usize {
+        (mix(id as u64) % self.shards.len() as u64) as usize
+    }
+
+    /// See [`InvertedIndex::add`].
+    pub fn add(&mut self, id: usize, content: &str) -> usize {
+        let shard = self.shard_of(id);
+        self.shards[shard].add(id, content)
+    }
+
+    /// See [`InvertedIndex::update`].
+    pub fn update(&mut self, id: usize, content: &str) -> Result<(), IndexError> {
+        let shard = self.shard_of(id);
+        self.shards[shard].update(id, content)
+    }
+
+    /// See [`InvertedIndex::remove`].
+    pub fn remove(&mut self, id: usize) -> bool {
+        let shard = self.shard_of(id);
+        self.shards[shard].remove(id)
+    }
+
+    /// See [`InvertedIndex::get`].
+    pub fn get(&self, id: usize) -> Option<Cow<'_, str>> {
+        self.shards[self.shard_of(id)].get(id)
+    }
+
+    /// See [`InvertedIndex::query_ids`].
+    pub fn query_ids(&self, term: &str) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .fan_out(|shard| shard.query_ids(term))
+            .into_iter()
+            .flatten()
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// See [`InvertedIndex::query`]. The highlighted contents are in ascending doc id order.
+    pub fn query(&self, term: &str) -> Vec<String> {
+        let mut hits: Vec<(usize, String)> = self
+            .fan_out(|shard| {
+                let Some(analyzed) = shard.analyze_term(term) else {
+                    return Vec::new();
+                };
+                let highlighter = shard.highlighter(&analyzed);
+                shard
+                    .query_iter(term)
+                    .filter_map(|hit| {
+                        let highlighted = highlighter.highlight(hit.content.as_deref()?);
+                        Some((hit.doc_id, highlighted.into_owned()))
+                    })
+                    .collect()
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        hits.sort_unstable_by_key(|(id, _)| *id);
+        hits.into_iter().map(|(_, content)| content).collect()
+    }
+
+    /// Ranks the documents containing any word of `query` by their summed TF-IDF, the
+    /// scores of [`InvertedIndex::search`].
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order.
+    pub fn query_ranked(&self, query: &str) -> Vec<(usize, f64)> {
+        self.rank(query, Scorer::TfIdf)
+    }
+
+    /// See [`InvertedIndex::query_bm25`].
+    pub fn query_bm25(&self, query: &str) -> Vec<(usize, f64)> {
+        self.rank(query, Scorer::Bm25)
+    }
+
+    /// Scores `query` in every shard with the statistics of all shards together.
+    fn rank(&self, query: &str, scorer: Scorer) -> Vec<(usize, f64)> {
+        // The shards analyze alike, so any of them can split the query.
+        let terms = self.shards[0].query_terms(query);
+        let doc_freqs: Vec<usize> = terms
+            .iter()
+            .map(|term| {
+                self.shards
+                    .iter()
+                    .filter_map(|shard| shard.live_postings(term))
+                    .map(|postings| postings.len())
+                    .sum()
+            })
+            .collect();
+        let doc_count = self.len();
+        let tokens: usize = self.shards.iter().map(|shard| shard.total_tokens).sum();
+        let corpus = Corpus {
+            doc_count,
+            average_length: if doc_count == 0 {
+                0.0
+            } else {
+                tokens as f64 / doc_count as f64
+            },
+        };
+        let scores: HashMap<usize, f64> = self
+            .fan_out(|shard| {
+                let per_term = terms
+                    .iter()
+                    .zip(&doc_freqs)
+                    .map(|(term, &doc_freq)| shard.term_scores_in(term, scorer, &corpus, doc_freq))
+                    .collect();
+                merge_term_scores(per_term, Combine::Any)
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        sort_by_score(scores)
+    }
+
+    /// Runs `f` on every shard, each on its own thread with the `parallel` feature.
+    fn fan_out<T: Send>(&self, f: impl Fn(&InvertedIndex) -> T + Sync) -> Vec<T> {
+        #[cfg(feature = "parallel")]
+        if self.shards.len() > 1 {
+            let f = &f;
+            return std::thread::scope(|scope| {
+                let workers: Vec<_> = self
+                    .shards
+                    .iter()
+                    .map(|shard| scope.spawn(move || f(shard)))
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().expect("shard query thread panicked"))
+                    .collect()
+            });
+        }
+        self.shards.iter().map(f).collect()
+    }
+
+    /// Saves every shard to its own file in the directory `dir`, created if missing, with
+    /// a manifest listing them, see [`InvertedIndex::save`].
+    ///
+    /// # Returns
+    /// [`IndexError::Io`] naming the file that could not be written.
+    pub fn save_to_dir(&self, dir: &Path) -> Result<(), IndexError> {
+        fs::create_dir_all(dir).map_err(|source| IndexError::Io {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        for (i, shard) in self.shards.iter().enumerate() {
+            shard.save_to(&dir.join(shard_file(i)))?;
+        }
+        // Written last, so an interrupted save leaves no manifest naming missing shards.
+        let path = dir.join(MANIFEST);
+        let manifest = format!("{MANIFEST_HEADER}\n{}\n", self.shards.len());
+        fs::write(&path, manifest).map_err(|source| IndexError::Io { path, source })
+    }
+
+    /// Loads a sharded index saved by [`save_to_dir`](Self::save_to_dir).
+    ///
+    /// # Returns
+    /// [`IndexError::Corrupt`] if the manifest is invalid or a document is in a shard its id
+    /// does not hash to, and the errors of [`InvertedIndex::open`] for the shard files.
+    pub fn open_dir(dir: &Path) -> Result<ShardedIndex, IndexError> {
+        let path = dir.join(MANIFEST);
+        let manifest =
+            fs::read_to_string(&path).map_err(|source| IndexError::Io { path, source })?;
+        let mut lines = manifest.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(corrupt("not a sharded index manifest"));
+        }
+        let count: usize = lines
+            .next()
+            .and_then(|line| line.parse().ok())
+            .filter(|&count| count > 0)
+            .ok_or_else(|| corrupt("invalid shard count"))?;
+        let mut index = ShardedIndex {
+            shards: Vec::with_capacity(count),
+        };
+        for i in 0..count {
+            index
+                .shards
+                .push(InvertedIndex::open(&dir.join(shard_file(i)))?);
+        }
+        for (i, shard) in index.shards.iter().enumerate() {
+            if shard.documents.keys().any(|&id| index.shard_of(id) != i) {
+                return Err(corrupt("document in the wrong shard"));
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// The file name of shard `i` in a saved sharded index.
+fn shard_file(i: usize) -> String {
+    format!("shard-{i}.bin")
+}
+
+/// Scatters consecutive ids over the shards (the SplitMix64 finalizer), the same way in
+/// every build so that saved shards stay valid.
+fn mix(id: u64) -> u64 {
+    let mut x = id;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[test]
+fn sharded_matches_single_test() {
+    let _color = crate::test_util::color_guard(true);
+    let build = || InvertedIndex::builder().stemming(true).build();
+    let mut single = build();
+    let mut sharded = ShardedIndex::with_shards(4, build);
+    for id in 0..200 {
+        let content = format!(
+            "doc {id} about {} and {} with Rust{}",
+            ["rust", "go", "python", "zig"][id % 4],
+            ["search", "indexing", "ranking"][id % 3],
+            " rust".repeat(id % 5)
+        );
+        single.add(id, &content);
+        sharded.add(id, &content);
+    }
+    for id in (0..200).step_by(7) {
+        single.remove(id);
+        sharded.remove(id);
+    }
+    single.update(3, "Go and ranking").unwrap();
+    sharded.update(3, "Go and ranking").unwrap();
+
+    assert_eq!(sharded.len(), single.documents.len());
+    assert!(
+        sharded
+            .shards()
+            .iter()
+            .all(|shard| !shard.documents.is_empty())
+    );
+    assert_eq!(sharded.get(3), single.get(3));
+    for query in [
+        "rust",
+        "go",
+        "ranking",
+        "rust search",
+        "zig indexing go",
+        "absent",
+    ] {
+        assert_eq!(sharded.query_ids(query), single.query_ids(query), "{query}");
+        assert_eq!(sharded.query(query), single.query(query), "{query}");
+        let searched: Vec<(usize, f64)> = single
+            .search(query, &Default::default())
+            .iter()
+            .map(|hit| (hit.doc_id, hit.score))
+            .collect();
+        assert_eq!(sharded.query_ranked(query), searched, "{query}");
+        assert_eq!(
+            sharded.query_bm25(query),
+            single.query_bm25(query),
+            "{query}"
+        );
+    }
+
+    let dir = crate::directory::test_dir("sharded");
+    sharded.save_to_dir(&dir).unwrap();
+    let loaded = ShardedIndex::open_dir(&dir).unwrap();
+    assert_eq!(loaded.shards().len(), 4);
+    assert_eq!(loaded.query_bm25("rust go"), sharded.query_bm25("rust go"));
+    fs::write(dir.join(MANIFEST), format!("{MANIFEST_HEADER}\n3\n")).unwrap();
+    assert!(matches!(
+        ShardedIndex::open_dir(&dir),
+        Err(IndexError::Corrupt(_))
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,116 @@
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::snapshot::IndexSnapshot;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// An [`InvertedIndex`] that can be queried from many threads while others modify it.
+///
+/// Cloning a `SharedIndex` is cheap and yields a handle to the same index. Every
+/// modification holds the write lock for its whole duration, so a query never
+/// sees a document whose postings are only partially updated.
+#[derive(Clone, Default)]
+pub struct SharedIndex {
+    inner: Arc<RwLock<InvertedIndex>>,
+}
+
+impl SharedIndex {
+    pub fn new(index: InvertedIndex) -> SharedIndex {
+        Self {
+            inner: Arc::new(RwLock::new(index)),
+        }
+    }
+
+    /// Runs `f` with shared read access to the index.
+    ///
+    /// Use this for anything returning borrowed data, such as
+    /// [`InvertedIndex::search`], and copy out what you need before returning.
+    pub fn read<R>(&self, f: impl FnOnce(&InvertedIndex) -> R) -> R {
+        f(&self.read_guard())
+    }
+
+    /// Runs `f` with exclusive write access to the index.
+    pub fn write<R>(&self, f: impl FnOnce(&mut InvertedIndex) -> R) -> R {
+        f(&mut self.write_guard())
+    }
+
+    /// See [`InvertedIndex::query`].
+    pub fn query(&self, term: &str) -> Vec<String> {
+        self.read(|index| index.query(term))
+    }
+
+    /// See [`InvertedIndex::snapshot`]. The read lock is only held while the snapshot is
+    /// taken, so a long scan of it does not hold up writers.
+    pub fn snapshot(&self) -> IndexSnapshot {
+        self.read(InvertedIndex::snapshot)
+    }
+
+    /// See [`InvertedIndex::add`].
+    pub fn add(&self, id: usize, content: &str) -> usize {
+        self.write(|index| index.add(id, content))
+    }
+
+    /// See [`InvertedIndex::update`].
+    pub fn update(&self, id: usize, content: &str) -> Result<(), IndexError> {
+        self.write(|index| index.update(id, content))
+    }
+
+    /// See [`InvertedIndex::remove`].
+    pub fn remove(&self, id: usize) -> bool {
+        self.write(|index| index.remove(id))
+    }
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, InvertedIndex> {
+        // A writer that panicked may have left the index half updated, so don't hide that.
+        self.inner
+            .read()
+            .expect("index lock poisoned by a panicking writer")
+    }
+
+    fn write_guard(&self) -> RwLockWriteGuard<'_, InvertedIndex> {
+        self.inner
+            .write()
+            .expect("index lock poisoned by a panicking writer")
+    }
+}
+
+impl From<InvertedIndex> for SharedIndex {
+    fn from(index: InvertedIndex) -> Self {
+        Self::new(index)
+    }
+}
+
+#[test]
+fn concurrent_readers_and_writer_test() {
+    let shared = SharedIndex::new(InvertedIndex::new());
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..200 {
+                    let (results, doc_freq) =
+                        shared.read(|index| (index.query("rust").len(), index.doc_freq("rust")));
+                    // Postings and documents are always updated together.
+                    assert_eq!(results, doc_freq);
+                    assert!(results >= last, "results shrank from {last} to {results}");
+                    last = results;
+                }
+            })
+        })
+        .collect();
+
+    let writer = {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            for id in 0..100 {
+                shared.add(id, "rust is fast");
+            }
+        })
+    };
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(shared.query("rust").len(), 100);
+}
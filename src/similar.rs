@@ -0,0 +1,221 @@
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::ranking::{Combine, Scorer, merge_term_scores, sort_by_score};
+use crate::search::idf;
+use std::collections::HashMap;
+
+/// How many of the source document's terms a "more like this" query is built from.
+const MORE_LIKE_THIS_TERMS: usize = 25;
+
+impl InvertedIndex {
+    /// Finds the documents most similar to an indexed one.
+    ///
+    /// # Parameters
+    /// - `doc_id`: The source document.
+    /// - `limit`: The maximum number of results.
+    ///
+    /// # Returns
+    /// `(doc_id, score)` pairs, highest score first and ties in ascending doc id order,
+    /// without the source document. Empty if no other document shares a term with it, as for
+    /// an empty document. [`IndexError::DocumentNotFound`] if `doc_id` is not indexed.
+    ///
+    /// # Notes
+    /// The source document's highest weighted TF-IDF terms are run as a ranked query
+    /// matching any of them. Terms no other document contains are left out, since they
+    /// cannot find anything.
+    pub fn more_like_this(
+        &self,
+        doc_id: usize,
+        limit: usize,
+    ) -> Result<Vec<(usize, f64)>, IndexError> {
+        if !self.documents.contains_key(&doc_id) {
+            return Err(IndexError::DocumentNotFound(doc_id));
+        }
+        let terms = self.top_document_terms(doc_id, MORE_LIKE_THIS_TERMS);
+        let per_term = self.score_terms(&terms, Scorer::TfIdf);
+        let mut ranked = sort_by_score(merge_term_scores(per_term, Combine::Any));
+        ranked.retain(|(id, _)| *id != doc_id);
+        ranked.truncate(limit);
+        Ok(ranked)
+    }
+
+    /// The cosine similarity of the TF-IDF vectors of two documents.
+    ///
+    /// # Returns
+    /// 1.0 for documents with the same terms in the same proportions, 0.0 for documents
+    /// without a term in common or without any term, and `None` if either id is not
+    /// indexed.
+    ///
+    /// # Notes
+    /// Each vector weighs a document's terms by their frequency in it times their inverse
+    /// document frequency, taken from the [forward index](crate::IndexBuilder::forward_index)
+    /// or, with that disabled, by re-analyzing the contents. The dot product only walks the terms of the document with fewer
+    /// of them, never the vocabulary.
+    pub fn similarity(&self, a: usize, b: usize) -> Option<f64> {
+        Some(cosine(&self.term_weights(a)?, &self.term_weights(b)?))
+    }
+
+    /// Finds the `k` documents most similar to `doc_id` by [`similarity`](Self::similarity),
+    /// comparing it with every other document.
+    ///
+    /// # Returns
+    /// `(doc_id, similarity)` pairs, most similar first and ties in ascending doc id order,
+    /// without the source document and documents sharing no term with it. `None` if
+    /// `doc_id` is not indexed.
+    pub fn nearest(&self, doc_id: usize, k: usize) -> Option<Vec<(usize, f64)>> {
+        let source = self.term_weights(doc_id)?;
+        let scores: HashMap<usize, f64> = self
+            .documents
+            .keys()
+            .filter(|&&id| id != doc_id)
+            .filter_map(|&id| {
+                let similarity = cosine(&source, &self.term_weights(id)?);
+                (similarity > 0.0).then_some((id, similarity))
+            })
+            .collect();
+        let mut ranked = sort_by_score(scores);
+        ranked.truncate(k);
+        Some(ranked)
+    }
+
+    /// The TF-IDF weight of every term of document `doc_id`, or `None` if it is not indexed.
+    fn term_weights(&self, doc_id: usize) -> Option<HashMap<String, f64>> {
+        let doc = self.documents.get(&doc_id)?;
+        let doc_count = self.documents.len();
+        let mut weights = HashMap::new();
+        if let Some(terms) = self.doc_terms(doc_id) {
+            for (term, freq) in terms {
+                let postings = &self.indexes[term];
+                weights.insert(
+                    term.to_string(),
+                    f64::from(freq) * idf(doc_count, self.live_len(postings)),
+                );
+            }
+            return Some(weights);
+        }
+        for term in self.analyzer.analyze(&self.content(doc)?) {
+            if weights.contains_key(&term) {
+                continue;
+            }
+            // Capped terms have no postings.
+            let Some(postings) = self.indexes.get(&term) else {
+                continue;
+            };
+            let Ok(i) = postings.binary_search_by_key(&doc_id, |posting| posting.doc_id) else {
+                continue;
+            };
+            let weight = postings[i].term_freq() as f64 * idf(doc_count, self.live_len(postings));
+            weights.insert(term, weight);
+        }
+        Some(weights)
+    }
+
+    /// The `n` terms of a document with the highest TF-IDF weight that also occur in other
+    /// documents, heaviest first and ties in term order.
+    pub(crate) fn top_document_terms(&self, doc_id: usize, n: usize) -> Vec<String> {
+        let doc_count = self.documents.len();
+        let mut weighted: Vec<(f64, &String)> = self
+            .indexes
+            .iter()
+            .filter_map(|(term, postings)| {
+                let doc_freq = self.live_len(postings);
+                if doc_freq <= 1 {
+                    return None;
+                }
+                let i = postings
+                    .binary_search_by_key(&doc_id, |posting| posting.doc_id)
+                    .ok()?;
+                let weight = postings[i].term_freq() as f64 * idf(doc_count, doc_freq);
+                Some((weight, term))
+            })
+            .collect();
+        weighted.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        weighted
+            .into_iter()
+            .take(n)
+            .map(|(_, term)| term.clone())
+            .collect()
+    }
+}
+
+/// The cosine of the angle between two term weight vectors, 0.0 if either is empty.
+fn cosine(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let (smaller, larger) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let dot: f64 = smaller
+        .iter()
+        .filter_map(|(term, weight)| Some(weight * larger.get(term)?))
+        .sum();
+    let norm = |vector: &HashMap<String, f64>| vector.values().map(|w| w * w).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        return 0.0;
+    }
+    // Rounding must not push identical documents past 1.
+    (dot / norms).min(1.0)
+}
+
+#[test]
+fn similarity_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust ownership and borrowing");
+    index.add(2, "Rust ownership, and borrowing!");
+    index.add(3, "rust ownership and lifetimes");
+    index.add(4, "cooking pasta at home");
+    index.add(5, "");
+    index.add(
+        6,
+        "rust rust ownership ownership and and borrowing borrowing",
+    );
+
+    let similarity = |a, b| index.similarity(a, b).unwrap();
+    assert!((similarity(1, 2) - 1.0).abs() < 1e-9);
+    assert!((similarity(1, 1) - 1.0).abs() < 1e-9);
+    // Scaling every term frequency keeps the direction.
+    assert!((similarity(1, 6) - 1.0).abs() < 1e-9);
+    assert_eq!(similarity(1, 4), 0.0);
+    assert_eq!(similarity(1, 5), 0.0);
+    assert_eq!(similarity(5, 5), 0.0);
+    let partial = similarity(1, 3);
+    assert!(partial > 0.0 && partial < 1.0);
+    assert_eq!(partial, similarity(3, 1));
+    assert_eq!(index.similarity(1, 42), None);
+    assert_eq!(index.similarity(42, 1), None);
+
+    let nearest = index.nearest(1, 10).unwrap();
+    let mut ids: Vec<usize> = nearest.iter().map(|(id, _)| *id).collect();
+    // 2 and 6 are both at 1.0, up to rounding in either direction.
+    ids[..2].sort_unstable();
+    assert_eq!(ids, vec![2, 6, 3]);
+    assert_eq!(nearest[2].1, partial);
+    assert_eq!(index.nearest(1, 1).unwrap().len(), 1);
+    assert!(index.nearest(4, 10).unwrap().is_empty());
+    assert_eq!(index.nearest(42, 10), None);
+    index.remove(2);
+    assert_eq!(index.similarity(1, 2), None);
+}
+
+#[test]
+fn more_like_this_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "rust ownership borrowing lifetimes compiler");
+    index.add(2, "rust borrowing lifetimes explained");
+    index.add(3, "rust compiler internals");
+    index.add(4, "cooking pasta at home");
+    index.add(5, "gardening unique words");
+    index.add(6, "");
+    index.add(7, "a");
+
+    let similar = index.more_like_this(1, 10).unwrap();
+    let ids: Vec<usize> = similar.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, vec![2, 3]);
+    assert!(similar[0].1 > similar[1].1);
+
+    assert_eq!(index.more_like_this(1, 1).unwrap().len(), 1);
+    assert!(index.more_like_this(5, 10).unwrap().is_empty());
+    assert!(index.more_like_this(6, 10).unwrap().is_empty());
+    assert!(index.more_like_this(7, 10).unwrap().is_empty());
+    assert!(matches!(
+        index.more_like_this(42, 10),
+        Err(IndexError::DocumentNotFound(42))
+    ));
+}
@@ -0,0 +1,143 @@
+//! Frozen views of an index that stay consistent while the index keeps changing.
+
+use crate::analysis::Analyzer;
+use crate::compress::ContentCache;
+use crate::index::InvertedIndex;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Mutex, PoisonError};
+
+/// A read-only copy of an index as it was when [`InvertedIndex::snapshot`] was taken.
+///
+/// It dereferences to an [`InvertedIndex`], so every query method is available, while
+/// nothing can change it. Later changes to the live index never show through.
+pub struct IndexSnapshot {
+    index: InvertedIndex,
+}
+
+impl Deref for IndexSnapshot {
+    type Target = InvertedIndex;
+
+    fn deref(&self) -> &InvertedIndex {
+        &self.index
+    }
+}
+
+impl InvertedIndex {
+    /// Takes a snapshot for long-running reads, e.g. an analytical scan on another thread,
+    /// while documents keep being added and removed.
+    ///
+    /// # Notes
+    /// The posting lists and documents are shared with the live index rather than copied.
+    /// A change to the index copies only the posting lists and documents it modifies, so
+    /// the memory of the two only diverges for changed terms and documents. The term and
+    /// document tables themselves are copied, which takes time linear in the vocabulary
+    /// size but not in the size of the postings.
+    ///
+    /// The snapshot does not log changes, has no query cache and scans the vocabulary for
+    /// [`query_substring`](Self::query_substring) instead of using the n-gram index. Query
+    /// results are the same as the live index's at the time of the snapshot.
+    pub fn snapshot(&self) -> IndexSnapshot {
+        let analyzer = &self.analyzer;
+        IndexSnapshot {
+            index: InvertedIndex {
+                indexes: self.indexes.clone(),
+                documents: self.documents.clone(),
+                total_tokens: self.total_tokens,
+                analyzer: Analyzer {
+                    tokenizer: analyzer.tokenizer.clone(),
+                    tokenizer_kind: analyzer.tokenizer_kind,
+                    case_sensitive: analyzer.case_sensitive,
+                    stop_words: analyzer.stop_words.clone(),
+                    filters: analyzer.filters.clone(),
+                    stemming: analyzer.stemming,
+                    // Only a speed-up at index time; queries stem without it.
+                    stem_cache: HashMap::new(),
+                },
+                #[cfg(feature = "parallel")]
+                parallel_threshold: self.parallel_threshold,
+                generation: self.generation,
+                cache: None,
+                highlight_style: self.highlight_style,
+                no_color: self.no_color,
+                max_highlights: self.max_highlights,
+                compact_ratio: self.compact_ratio,
+                tombstones: self.tombstones.clone(),
+                dictionary: Mutex::default(),
+                log: None,
+                ngrams: None,
+                regex_term_limit: self.regex_term_limit,
+                cooccurrence_sample: self.cooccurrence_sample,
+                max_postings: self.max_postings,
+                capped_terms: self.capped_terms.clone(),
+                store: self.store.clone(),
+                forward_index: self.forward_index,
+                keep_content: self.keep_content,
+                compress_above: self.compress_above,
+                decompressed: Mutex::new(ContentCache::new(
+                    self.decompressed
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .capacity(),
+                )),
+                surface_forms: self.surface_forms.clone(),
+                numeric: self.numeric.clone(),
+            },
+        }
+    }
+}
+
+#[test]
+fn snapshot_is_frozen_test() {
+    use std::sync::Arc;
+
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::builder().ngram_index(true).build();
+    index.add(1, "Rust is fast");
+    index.add(2, "Go is simple");
+    index.add(3, "Python is dynamic");
+    index.add(4, "Untouched text");
+    let snapshot = index.snapshot();
+    assert!(Arc::ptr_eq(
+        &index.indexes["rust"],
+        &snapshot.indexes["rust"]
+    ));
+
+    let frozen = |snapshot: &IndexSnapshot| {
+        (
+            snapshot.query("is"),
+            snapshot.query_bm25("rust go"),
+            snapshot.query_substring("ytho"),
+            snapshot.stats(),
+        )
+    };
+    let before = frozen(&snapshot);
+    for id in 5..200 {
+        index.add(id, &format!("Rust document {id} is new"));
+    }
+    index.remove(2);
+    index.update(3, "Python was dynamic").unwrap();
+    index.remove_term("fast");
+    index.compact();
+    assert_eq!(frozen(&snapshot), before);
+    assert_eq!(snapshot.get(2).as_deref(), Some("Go is simple"));
+    assert_eq!(snapshot.query("python"), vec!["Python is dynamic"]);
+    assert_eq!(snapshot.doc_freq("rust"), 1);
+    assert_eq!(index.doc_freq("rust"), 196);
+
+    // Only the changed terms were copied.
+    assert!(!Arc::ptr_eq(
+        &index.indexes["rust"],
+        &snapshot.indexes["rust"]
+    ));
+    assert!(Arc::ptr_eq(
+        &index.indexes["untouched"],
+        &snapshot.indexes["untouched"]
+    ));
+    assert!(Arc::ptr_eq(&index.documents[&4], &snapshot.documents[&4]));
+
+    index.clear();
+    assert_eq!(frozen(&snapshot), before);
+    let scan = std::thread::spawn(move || snapshot.query("is").len());
+    assert_eq!(scan.join().unwrap(), 3);
+}
@@ -0,0 +1,133 @@
+//! Result previews built from the tokens that matched.
+
+use crate::index::InvertedIndex;
+use crate::ranking::{Combine, Scorer};
+use std::collections::HashSet;
+
+impl InvertedIndex {
+    /// Ranks the documents like [`search`](Self::search) and cuts a preview out of each,
+    /// with every query term in it highlighted.
+    ///
+    /// # Parameters
+    /// - `query`: The query text, analyzed like document text.
+    /// - `context_words`: Tokens kept on each side of the center of the window, so a
+    ///   snippet spans at most `2 * context_words + 1` tokens.
+    ///
+    /// # Returns
+    /// `(doc_id, snippet)` pairs, best match first. Each window is the one containing the
+    /// most distinct query terms, then the most occurrences, then the earliest, centered on
+    /// the matches inside it. Whitespace is collapsed and cut ends are marked with `...`.
+    pub fn query_snippets_multi(&self, query: &str, context_words: usize) -> Vec<(usize, String)> {
+        let terms: HashSet<String> = self.query_terms(query).into_iter().collect();
+        self.rank(query, Scorer::TfIdf, Combine::Any)
+            .into_iter()
+            .filter_map(|(doc_id, _)| {
+                let content = self.get(doc_id)?;
+                Some((doc_id, self.multi_snippet(&content, &terms, context_words)))
+            })
+            .collect()
+    }
+
+    fn multi_snippet(&self, content: &str, terms: &HashSet<String>, context: usize) -> String {
+        let tokens = self.analyzer.tokenizer.tokenize(content);
+        let byte_range = |token: &str| {
+            let start = token.as_ptr() as usize - content.as_ptr() as usize;
+            start..start + token.len()
+        };
+        // (token index, term) of every token that is a query term.
+        let matches: Vec<(usize, String)> = tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(i, token)| {
+                let term = self.analyzer.normalize(token)?;
+                terms.contains(&term).then_some((i, term))
+            })
+            .collect();
+        if matches.is_empty() {
+            return String::new();
+        }
+
+        let width = 2 * context + 1;
+        let mut best: Option<(usize, usize, usize)> = None;
+        let mut best_span = (0, 0);
+        for (first, &(start, _)) in matches.iter().enumerate() {
+            let inside: Vec<&(usize, String)> = matches[first..]
+                .iter()
+                .take_while(|(i, _)| *i < start + width)
+                .collect();
+            let distinct = inside
+                .iter()
+                .map(|(_, term)| term)
+                .collect::<HashSet<_>>()
+                .len();
+            let key = (distinct, inside.len(), usize::MAX - start);
+            if best.is_none_or(|best| key > best) {
+                best = Some(key);
+                best_span = (start, inside.last().map_or(start, |(i, _)| *i));
+            }
+        }
+        let center = (best_span.0 + best_span.1) / 2;
+        let from = center
+            .saturating_sub(context)
+            .min(tokens.len().saturating_sub(width));
+        let to = (from + width).min(tokens.len());
+
+        let matched: HashSet<usize> = matches.iter().map(|(i, _)| *i).collect();
+        let mut snippet = String::new();
+        let mut last = byte_range(tokens[from]).start;
+        for (i, token) in tokens.iter().enumerate().take(to).skip(from) {
+            let range = byte_range(token);
+            snippet.push_str(&content[last..range.start]);
+            let token = &content[range.clone()];
+            match self.style() {
+                Some(style) if matched.contains(&i) => snippet.push_str(&style.paint(token)),
+                _ => snippet.push_str(token),
+            }
+            last = range.end;
+        }
+        let mut snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+        if from > 0 {
+            snippet.insert_str(0, "...");
+        }
+        if to < tokens.len() {
+            snippet.push_str("...");
+        }
+        snippet
+    }
+}
+
+#[test]
+fn query_snippets_multi_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(
+        1,
+        "Rust appears early. Then a long detour about nothing in particular at all, \
+         until fast and safe Rust code shows up together near the end.",
+    );
+    index.add(2, "Only   safe here");
+    index.add(3, "Nothing to see");
+
+    let snippets = index.query_snippets_multi("rust safe fast", 3);
+    assert_eq!(
+        snippets,
+        vec![
+            (1, "...all, until fast and safe Rust code...".to_string()),
+            (2, "Only safe here".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn query_snippets_multi_highlight_test() {
+    let _color = crate::test_util::color_guard(true);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Fast, safe and fun: Rust");
+    assert_eq!(
+        index.query_snippets_multi("rust fast", 10),
+        vec![(
+            1,
+            "\u{1b}[35mFast\u{1b}[0m, safe and fun: \u{1b}[35mRust\u{1b}[0m".to_string()
+        )]
+    );
+}
@@ -0,0 +1,255 @@
+use crate::docstore::Content;
+use crate::index::InvertedIndex;
+use crate::postings::Posting;
+use std::fmt;
+
+/// Terms listed in [`IndexStats::top_terms`].
+const STATS_TOP_TERMS: usize = 10;
+
+/// A summary of the size of an index, see [`InvertedIndex::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStats {
+    /// Number of live documents.
+    pub document_count: usize,
+    /// Soft-deleted documents whose postings await [`InvertedIndex::compact`].
+    pub deleted_documents: usize,
+    /// Number of distinct terms.
+    pub vocabulary_size: usize,
+    /// Number of (term, document) entries across all posting lists, including those of
+    /// deleted documents awaiting compaction.
+    pub total_postings: usize,
+    /// Average document length in tokens.
+    pub average_document_length: f64,
+    /// Bytes of document content held in memory, 0 when the contents are in a
+    /// [document store](crate::IndexBuilder::document_store) or
+    /// [not stored](crate::IndexBuilder::store_content).
+    pub content_bytes: usize,
+    /// The term with the longest posting list, in its most frequent original
+    /// [spelling](crate::IndexBuilder::surface_forms), and that list's length. Of terms
+    /// with lists as long, the first by normalized term wins.
+    pub longest_postings: Option<(String, usize)>,
+    /// The 10 terms found in the most documents with their document frequency, see
+    /// [`InvertedIndex::top_terms`]. Common words here are stop word candidates.
+    pub top_terms: Vec<(String, usize)>,
+    /// Number of (trigram, term) entries in the
+    /// [n-gram index](crate::IndexBuilder::ngram_index), 0 when it is disabled.
+    pub ngram_entries: usize,
+    /// Number of (document, term) entries in the
+    /// [forward index](crate::IndexBuilder::forward_index), 0 when it is disabled.
+    pub forward_index_entries: usize,
+    /// Number of (term, spelling) entries kept for
+    /// [display](crate::IndexBuilder::surface_forms), 0 when they are not kept.
+    pub surface_form_entries: usize,
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "documents:        {}", self.document_count)?;
+        if self.deleted_documents > 0 {
+            writeln!(f, "deleted:          {}", self.deleted_documents)?;
+        }
+        writeln!(f, "vocabulary size:  {}", self.vocabulary_size)?;
+        writeln!(f, "postings entries: {}", self.total_postings)?;
+        writeln!(
+            f,
+            "avg doc length:   {:.2} tokens",
+            self.average_document_length
+        )?;
+        if self.content_bytes > 0 {
+            writeln!(f, "content bytes:    {}", self.content_bytes)?;
+        }
+        if self.ngram_entries > 0 {
+            writeln!(f, "n-gram entries:   {}", self.ngram_entries)?;
+        }
+        if self.forward_index_entries > 0 {
+            writeln!(f, "forward entries:  {}", self.forward_index_entries)?;
+        }
+        if self.surface_form_entries > 0 {
+            writeln!(f, "surface forms:    {}", self.surface_form_entries)?;
+        }
+        match &self.longest_postings {
+            Some((term, len)) => writeln!(f, "longest postings: \"{term}\" ({len} documents)")?,
+            None => writeln!(f, "longest postings: -")?,
+        }
+        write!(f, "top terms:       ")?;
+        if self.top_terms.is_empty() {
+            return write!(f, " -");
+        }
+        for (i, (term, doc_freq)) in self.top_terms.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{separator}\"{term}\" ({doc_freq})")?;
+        }
+        Ok(())
+    }
+}
+
+impl InvertedIndex {
+    /// Collects size statistics over the whole index.
+    pub fn stats(&self) -> IndexStats {
+        let longest_postings = self
+            .indexes
+            .iter()
+            .map(|(term, postings)| (term, self.live_len(postings)))
+            .filter(|(_, len)| *len > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(term, len)| (self.display_form(term).to_string(), len));
+        IndexStats {
+            document_count: self.documents.len(),
+            deleted_documents: self.tombstones.len(),
+            vocabulary_size: self.indexes.len(),
+            total_postings: self.indexes.values().map(|postings| postings.len()).sum(),
+            average_document_length: self.average_document_length(),
+            content_bytes: self
+                .documents
+                .values()
+                .map(|doc| match &doc.content {
+                    Content::Memory(content) => content.len(),
+                    Content::Compressed { bytes, .. } => bytes.len(),
+                    Content::Stored { .. } | Content::Omitted => 0,
+                })
+                .sum(),
+            longest_postings,
+            top_terms: self.top_terms(STATS_TOP_TERMS),
+            ngram_entries: self.ngrams.as_ref().map_or(0, |ngrams| ngrams.len()),
+            forward_index_entries: self.documents.values().map(|doc| doc.terms.len()).sum(),
+            surface_form_entries: self.surface_forms.as_ref().map_or(0, |forms| forms.len()),
+        }
+    }
+
+    /// Returns how many documents contain `term`.
+    ///
+    /// `term` goes through the same normalization as a query, so `"Rust"` and `"rust"` agree.
+    pub fn doc_freq(&self, term: &str) -> usize {
+        self.term_postings(term)
+            .map_or(0, |postings| postings.len())
+    }
+
+    /// Returns how many times `term` occurs across all documents.
+    pub fn total_term_freq(&self, term: &str) -> usize {
+        self.term_postings(term)
+            .map_or(0, |postings| postings.iter().map(Posting::term_freq).sum())
+    }
+
+    /// Returns the number of distinct terms in the index.
+    pub fn term_count(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// Returns the average number of tokens per document, or `0.0` for an empty index.
+    pub fn average_document_length(&self) -> f64 {
+        if self.documents.is_empty() {
+            return 0.0;
+        }
+        self.total_tokens as f64 / self.documents.len() as f64
+    }
+
+    /// Returns the `n` terms found in the most documents, with their document frequency.
+    ///
+    /// Each term is given in its most frequent original
+    /// [spelling](crate::IndexBuilder::surface_forms). Terms with the same document
+    /// frequency are ordered by their normalized form, before the spellings are applied,
+    /// so `"and"` comes before `"Go"` but `"Go"` before `"only"`.
+    pub fn top_terms(&self, n: usize) -> Vec<(String, usize)> {
+        let mut terms: Vec<(String, usize)> = self
+            .indexes
+            .iter()
+            .map(|(term, postings)| (term.clone(), self.live_len(postings)))
+            .filter(|(_, doc_freq)| *doc_freq > 0)
+            .collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(n);
+        for (term, _) in &mut terms {
+            let form = self.display_form(term);
+            if form != term {
+                *term = form.to_string();
+            }
+        }
+        terms
+    }
+}
+
+#[test]
+fn term_frequency_test() {
+    let index = crate::index::sort_fixture();
+    assert_eq!(index.doc_freq("rust"), 3);
+    assert_eq!(index.doc_freq("Rust"), 3);
+    assert_eq!(index.doc_freq("only"), 1);
+    assert_eq!(index.doc_freq("python"), 0);
+
+    assert_eq!(index.total_term_freq("RUST"), 6);
+    assert_eq!(index.total_term_freq("go"), 3);
+    assert_eq!(index.total_term_freq("python"), 0);
+}
+
+#[test]
+fn term_count_and_top_terms_test() {
+    let index = crate::index::sort_fixture();
+    assert_eq!(index.term_count(), 4);
+    assert_eq!(
+        index.top_terms(3),
+        vec![
+            ("Go".to_string(), 3),
+            ("Rust".to_string(), 3),
+            ("and".to_string(), 2)
+        ]
+    );
+    assert_eq!(index.top_terms(10).len(), 4);
+}
+
+#[test]
+fn average_document_length_test() {
+    let mut index = InvertedIndex::new();
+    assert_eq!(index.average_document_length(), 0.0);
+
+    index.add(1, "one two");
+    index.add(2, "one two three four");
+    assert_eq!(index.average_document_length(), 3.0);
+
+    index.add(2, "one two three four five six");
+    assert_eq!(index.average_document_length(), 4.0);
+
+    assert!(index.remove(1));
+    assert_eq!(index.average_document_length(), 6.0);
+    assert!(index.remove(2));
+    assert_eq!(index.average_document_length(), 0.0);
+}
+
+#[test]
+fn stats_test() {
+    let mut index = crate::index::sort_fixture();
+    let stats = index.stats();
+    assert_eq!(stats.document_count, 4);
+    assert_eq!(stats.vocabulary_size, 4);
+    assert_eq!(stats.total_postings, 9);
+    assert_eq!(stats.average_document_length, 3.0);
+    assert_eq!(stats.longest_postings, Some(("Go".to_string(), 3)));
+
+    assert!(index.update(4, "Rust only").is_ok());
+    assert!(index.update(5, "Rust").is_err());
+    index.remove(1);
+    let stats = index.stats();
+    assert_eq!(stats.document_count, 3);
+    assert_eq!(stats.vocabulary_size, 4);
+    assert_eq!(stats.total_postings, 6);
+    assert_eq!(stats.average_document_length, 3.0);
+    assert_eq!(stats.longest_postings, Some(("Rust".to_string(), 3)));
+    assert_eq!(stats.top_terms, index.top_terms(10));
+    assert_eq!(
+        stats.to_string(),
+        "documents:        3\n\
+         vocabulary size:  4\n\
+         postings entries: 6\n\
+         avg doc length:   3.00 tokens\n\
+         content bytes:    39\n\
+         forward entries:  6\n\
+         surface forms:    5\n\
+         longest postings: \"Rust\" (3 documents)\n\
+         top terms:        \"Rust\" (3), \"and\" (1), \"Go\" (1), \"only\" (1)"
+    );
+    assert!(
+        InvertedIndex::new()
+            .stats()
+            .to_string()
+            .ends_with("top terms:        -")
+    );
+}
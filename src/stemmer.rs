@@ -0,0 +1,273 @@
+//! The Porter stemming algorithm for English words.
+//!
+//! See <https://tartarus.org/martin/PorterStemmer/def.txt> for the definition of each step.
+
+/// Reduces an English word to its stem, e.g. `"programming"` to `"program"`.
+///
+/// Words of two letters or fewer and words containing non-ASCII letters are returned unchanged.
+pub fn stem(word: &str) -> String {
+    #[cfg(test)]
+    STEM_CALLS.with(|calls| calls.set(calls.get() + 1));
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return word.to_string();
+    }
+    let mut stemmer = Stemmer {
+        b: word.as_bytes().to_vec(),
+        k: word.len() - 1,
+        j: 0,
+    };
+    stemmer.step1ab();
+    if stemmer.k > 0 {
+        stemmer.step1c();
+        stemmer.step2();
+        stemmer.step3();
+        stemmer.step4();
+        stemmer.step5();
+    }
+    stemmer.b.truncate(stemmer.k + 1);
+    // Only ASCII letters were ever written into the buffer.
+    String::from_utf8(stemmer.b).unwrap_or_else(|_| word.to_string())
+}
+
+#[cfg(test)]
+thread_local! {
+    /// How many times `stem` ran on the current thread, so tests can observe caching.
+    pub(crate) static STEM_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+struct Stemmer {
+    b: Vec<u8>,
+    /// Index of the last letter of the word being stemmed.
+    k: usize,
+    /// End of the stem (exclusive) once `ends` matched a suffix.
+    j: usize,
+}
+
+impl Stemmer {
+    fn is_consonant(&self, i: usize) -> bool {
+        match self.b[i] {
+            b'a' | b'e' | b'i' | b'o' | b'u' => false,
+            b'y' => i == 0 || !self.is_consonant(i - 1),
+            _ => true,
+        }
+    }
+
+    /// The number of vowel-consonant sequences in `b[..j]`.
+    fn measure(&self) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        while i < self.j && self.is_consonant(i) {
+            i += 1;
+        }
+        loop {
+            while i < self.j && !self.is_consonant(i) {
+                i += 1;
+            }
+            if i >= self.j {
+                return n;
+            }
+            while i < self.j && self.is_consonant(i) {
+                i += 1;
+            }
+            n += 1;
+        }
+    }
+
+    fn vowel_in_stem(&self) -> bool {
+        (0..self.j).any(|i| !self.is_consonant(i))
+    }
+
+    fn double_consonant(&self, i: usize) -> bool {
+        i >= 1 && self.b[i] == self.b[i - 1] && self.is_consonant(i)
+    }
+
+    /// Whether `b[i - 2..=i]` is consonant-vowel-consonant and the last letter is not w, x or y.
+    fn cvc(&self, i: usize) -> bool {
+        i >= 2
+            && self.is_consonant(i)
+            && !self.is_consonant(i - 1)
+            && self.is_consonant(i - 2)
+            && !matches!(self.b[i], b'w' | b'x' | b'y')
+    }
+
+    fn ends(&mut self, suffix: &str) -> bool {
+        let suffix = suffix.as_bytes();
+        let len = self.k + 1;
+        if suffix.len() > len || &self.b[len - suffix.len()..len] != suffix {
+            return false;
+        }
+        self.j = len - suffix.len();
+        true
+    }
+
+    fn set_to(&mut self, replacement: &str) {
+        self.b.truncate(self.j);
+        self.b.extend_from_slice(replacement.as_bytes());
+        self.k = self.b.len() - 1;
+    }
+
+    fn replace_if_measured(&mut self, replacement: &str) {
+        if self.measure() > 0 {
+            self.set_to(replacement);
+        }
+    }
+
+    /// Removes plurals and -ed or -ing.
+    fn step1ab(&mut self) {
+        if self.b[self.k] == b's' {
+            if self.ends("sses") {
+                self.k -= 2;
+            } else if self.ends("ies") {
+                self.set_to("i");
+            } else if self.k >= 1 && self.b[self.k - 1] != b's' {
+                self.k -= 1;
+            }
+            self.b.truncate(self.k + 1);
+        }
+        if self.ends("eed") {
+            if self.measure() > 0 {
+                self.k -= 1;
+                self.b.truncate(self.k + 1);
+            }
+        } else if (self.ends("ed") || self.ends("ing")) && self.vowel_in_stem() {
+            self.k = self.j - 1;
+            self.b.truncate(self.j);
+            if self.ends("at") {
+                self.set_to("ate");
+            } else if self.ends("bl") {
+                self.set_to("ble");
+            } else if self.ends("iz") {
+                self.set_to("ize");
+            } else if self.double_consonant(self.k) {
+                if !matches!(self.b[self.k], b'l' | b's' | b'z') {
+                    self.k -= 1;
+                    self.b.truncate(self.k + 1);
+                }
+            } else {
+                self.j = self.k + 1;
+                if self.measure() == 1 && self.cvc(self.k) {
+                    self.set_to("e");
+                }
+            }
+        }
+    }
+
+    /// Turns a terminal y into i when there is another vowel in the stem.
+    fn step1c(&mut self) {
+        if self.ends("y") && self.vowel_in_stem() {
+            self.b[self.k] = b'i';
+        }
+    }
+
+    /// Maps double suffixes to single ones, e.g. -ization to -ize.
+    fn step2(&mut self) {
+        const RULES: &[(&str, &str)] = &[
+            ("ational", "ate"),
+            ("tional", "tion"),
+            ("enci", "ence"),
+            ("anci", "ance"),
+            ("izer", "ize"),
+            ("bli", "ble"),
+            ("alli", "al"),
+            ("entli", "ent"),
+            ("eli", "e"),
+            ("ousli", "ous"),
+            ("ization", "ize"),
+            ("ation", "ate"),
+            ("ator", "ate"),
+            ("alism", "al"),
+            ("iveness", "ive"),
+            ("fulness", "ful"),
+            ("ousness", "ous"),
+            ("aliti", "al"),
+            ("iviti", "ive"),
+            ("biliti", "ble"),
+            ("logi", "log"),
+        ];
+        self.apply_rules(RULES);
+    }
+
+    /// Handles -ic-, -full, -ness etc.
+    fn step3(&mut self) {
+        const RULES: &[(&str, &str)] = &[
+            ("icate", "ic"),
+            ("ative", ""),
+            ("alize", "al"),
+            ("iciti", "ic"),
+            ("ical", "ic"),
+            ("ful", ""),
+            ("ness", ""),
+        ];
+        self.apply_rules(RULES);
+    }
+
+    fn apply_rules(&mut self, rules: &[(&str, &str)]) {
+        for (suffix, replacement) in rules {
+            if self.ends(suffix) {
+                self.replace_if_measured(replacement);
+                return;
+            }
+        }
+    }
+
+    /// Removes -ant, -ence etc. in context <c>vcvc<v>.
+    fn step4(&mut self) {
+        const SUFFIXES: &[&str] = &[
+            "al", "ance", "ence", "er", "ic", "able", "ible", "ant", "ement", "ment", "ent", "ion",
+            "ou", "ism", "ate", "iti", "ous", "ive", "ize",
+        ];
+        for suffix in SUFFIXES {
+            if self.ends(suffix) {
+                if *suffix == "ion" && (self.j == 0 || !matches!(self.b[self.j - 1], b's' | b't')) {
+                    return;
+                }
+                if self.measure() > 1 {
+                    self.k = self.j - 1;
+                    self.b.truncate(self.j);
+                }
+                return;
+            }
+        }
+    }
+
+    /// Removes a final -e and reduces -ll to -l when the measure allows it.
+    fn step5(&mut self) {
+        self.j = self.k;
+        if self.b[self.k] == b'e' {
+            let m = self.measure();
+            if m > 1 || (m == 1 && !self.cvc(self.k - 1)) {
+                self.k -= 1;
+            }
+        }
+        self.j = self.k;
+        if self.b[self.k] == b'l' && self.double_consonant(self.k) && self.measure() > 1 {
+            self.k -= 1;
+        }
+    }
+}
+
+#[test]
+fn stem_test() {
+    let cases = [
+        ("caresses", "caress"),
+        ("ponies", "poni"),
+        ("cats", "cat"),
+        ("agreed", "agre"),
+        ("plastered", "plaster"),
+        ("motoring", "motor"),
+        ("hopping", "hop"),
+        ("filing", "file"),
+        ("happy", "happi"),
+        ("relational", "relat"),
+        ("hopeful", "hope"),
+        ("adjustment", "adjust"),
+        ("controll", "control"),
+        ("programming", "program"),
+        ("programs", "program"),
+        ("is", "is"),
+        ("café", "café"),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(stem(word), expected, "stemming {word}");
+    }
+}
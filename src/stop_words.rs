@@ -0,0 +1,245 @@
+mod english;
+mod french;
+mod german;
+mod spanish;
+
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use std::collections::HashSet;
+
+/// A language with a built-in stop word list, see [`StopWords::for_language`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+/// Built-in stop-word lists, for use with [`InvertedIndex::with_stop_words`] or
+/// [`IndexBuilder::stop_words`](crate::IndexBuilder::stop_words).
+///
+/// A list can be extended with words of your own with
+/// [`IndexBuilder::extend_stop_words`](crate::IndexBuilder::extend_stop_words), or
+/// overridden by editing the set before passing it on:
+///
+/// ```
+/// use inverted_index::{InvertedIndex, Language, StopWords};
+///
+/// let mut stop_words = StopWords::merged(&[Language::French, Language::Spanish]);
+/// // Keep "pas" searchable.
+/// stop_words.remove("pas");
+/// let mut index = InvertedIndex::builder()
+///     .stop_words(stop_words)
+///     .extend_stop_words(["voilà"])
+///     .build();
+/// index.add(1, "Voilà, ce n'est pas le cas");
+/// assert_eq!(index.query_ids("pas"), vec![1]);
+/// assert!(index.query_ids("voilà").is_empty());
+/// assert!(index.query_ids("le").is_empty());
+/// ```
+pub struct StopWords;
+
+impl StopWords {
+    /// The built-in list of common function words of `language`, in lowercase.
+    pub fn for_language(language: Language) -> &'static [&'static str] {
+        match language {
+            Language::English => english::ENGLISH,
+            Language::French => french::FRENCH,
+            Language::German => german::GERMAN,
+            Language::Spanish => spanish::SPANISH,
+        }
+    }
+
+    /// The union of the built-in lists of `languages`, for a corpus mixing them.
+    pub fn merged(languages: &[Language]) -> HashSet<String> {
+        languages
+            .iter()
+            .flat_map(|&language| StopWords::for_language(language))
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    /// Common English function words such as "the", "is" and "and".
+    pub fn english() -> HashSet<String> {
+        StopWords::merged(&[Language::English])
+    }
+
+    /// Common French function words such as "le", "et" and "est".
+    pub fn french() -> HashSet<String> {
+        StopWords::merged(&[Language::French])
+    }
+
+    /// Common German function words such as "der", "und" and "ist".
+    pub fn german() -> HashSet<String> {
+        StopWords::merged(&[Language::German])
+    }
+
+    /// Common Spanish function words such as "el", "y" and "es".
+    pub fn spanish() -> HashSet<String> {
+        StopWords::merged(&[Language::Spanish])
+    }
+}
+
+/// Fewest documents a term must be in for [`InvertedIndex::detect_stop_words`] to report it,
+/// so that in a small corpus not every term passes the ratio.
+const MIN_STOP_WORD_DOCUMENTS: usize = 10;
+
+impl InvertedIndex {
+    /// Creates an empty index that skips `stop_words` when indexing and querying.
+    pub fn with_stop_words(stop_words: HashSet<String>) -> InvertedIndex {
+        InvertedIndex::builder().stop_words(stop_words).build()
+    }
+
+    /// Finds the stop word candidates of this corpus, such as "patient" in medical notes.
+    ///
+    /// # Parameters
+    /// - `df_ratio`: The fraction of all documents a term must be in more of, e.g. 0.5 for
+    ///   terms in more than half of them.
+    ///
+    /// # Returns
+    /// The indexed terms in more than `df_ratio` of the documents and in at least 10 of them,
+    /// the most common first and ties in term order.
+    /// Pass them to [`apply_stop_words`](Self::apply_stop_words) to stop indexing them.
+    pub fn detect_stop_words(&self, df_ratio: f64) -> Vec<String> {
+        let threshold = df_ratio * self.documents.len() as f64;
+        let mut terms: Vec<(&String, usize)> = self
+            .indexes
+            .iter()
+            .map(|(term, postings)| (term, self.live_len(postings)))
+            .filter(|&(_, doc_freq)| {
+                doc_freq >= MIN_STOP_WORD_DOCUMENTS && doc_freq as f64 > threshold
+            })
+            .collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        terms.into_iter().map(|(term, _)| term.clone()).collect()
+    }
+
+    /// Turns `words` into stop words: their postings are dropped and the memory they held
+    /// released with [`shrink`](Self::shrink), and later documents and queries skip them.
+    ///
+    /// Each word is taken as an indexed term if it is one, as returned by
+    /// [`detect_stop_words`](Self::detect_stop_words), and normalized like a query term
+    /// otherwise. The document contents and lengths are left as they are.
+    ///
+    /// # Notes
+    /// Like terms over the [postings cap](crate::IndexBuilder::max_postings), the words are
+    /// [saved](Self::save) with the index, so a loaded index keeps skipping them.
+    pub fn apply_stop_words(&mut self, words: &[String]) {
+        let terms: Vec<String> = words
+            .iter()
+            .filter_map(|word| {
+                if self.indexes.contains_key(word) {
+                    Some(word.clone())
+                } else {
+                    self.analyzer.normalize(word)
+                }
+            })
+            .collect();
+        self.record(LogEntry::StopWords { terms: &terms });
+        self.stop_terms(terms);
+    }
+
+    /// Turns analyzed `terms` into stop words like
+    /// [`apply_stop_words`](Self::apply_stop_words), without logging the change.
+    pub(crate) fn stop_terms(&mut self, terms: Vec<String>) {
+        for term in terms {
+            self.cap_term(term);
+        }
+        self.generation += 1;
+        self.shrink();
+    }
+}
+
+#[test]
+fn english_preset_test() {
+    let mut index = InvertedIndex::with_stop_words(StopWords::english());
+    index.add(1, "The index is fast");
+    assert!(index.query("the").is_empty());
+    assert!(index.query("is").is_empty());
+    assert_eq!(index.union(&["index", "fast"]), vec![1]);
+    assert_eq!(index.term_count(), 2);
+}
+
+#[test]
+fn language_lists_test() {
+    let french = StopWords::for_language(Language::French);
+    assert!(french.contains(&"avec"));
+    assert!(StopWords::for_language(Language::Spanish).contains(&"para"));
+    for language in [
+        Language::English,
+        Language::French,
+        Language::German,
+        Language::Spanish,
+    ] {
+        let list = StopWords::for_language(language);
+        assert!(list.iter().all(|word| *word == word.to_lowercase()));
+        assert_eq!(
+            StopWords::merged(&[language]).len(),
+            list.len(),
+            "{language:?}"
+        );
+    }
+    let merged = StopWords::merged(&[Language::French, Language::German]);
+    assert!(merged.contains("avec") && merged.contains("und"));
+
+    let mut french = InvertedIndex::builder()
+        .stop_words(StopWords::for_language(Language::French))
+        .build();
+    let mut plain = InvertedIndex::new();
+    for index in [&mut french, &mut plain] {
+        index.add(1, "Du pain avec du beurre");
+    }
+    assert!(french.query_ids("avec").is_empty());
+    assert_eq!(french.query_ids("pain"), vec![1]);
+    assert_eq!(plain.query_ids("avec"), vec![1]);
+}
+
+#[test]
+fn presets_test() {
+    assert!(StopWords::french().contains("le"));
+    assert!(StopWords::german().contains("für"));
+    assert!(!StopWords::english().contains("rust"));
+    assert!(StopWords::english().len() > 100);
+    assert!(StopWords::spanish().contains("el"));
+}
+
+#[test]
+fn detect_stop_words_test() {
+    let mut index = InvertedIndex::new();
+    for id in 0..20 {
+        let extra = match id % 4 {
+            0 => "",
+            _ => "patient",
+        };
+        index.add(id, &format!("the note{id} {extra}"));
+    }
+    index.add(20, "rare words");
+    assert_eq!(index.detect_stop_words(0.5), vec!["the", "patient"]);
+    assert_eq!(index.detect_stop_words(0.9), vec!["the"]);
+    assert!(index.detect_stop_words(1.0).is_empty());
+
+    // Three documents are too few to tell.
+    let mut tiny = InvertedIndex::new();
+    for id in 0..3 {
+        tiny.add(id, "the same words");
+    }
+    assert!(tiny.detect_stop_words(0.5).is_empty());
+
+    let before = index.memory_usage();
+    let detected = index.detect_stop_words(0.5);
+    index.apply_stop_words(&detected);
+    assert!(index.memory_usage().postings < before.postings);
+    assert!(index.query("the").is_empty());
+    assert!(index.query("Patient").is_empty());
+    assert_eq!(index.doc_freq("note3"), 1);
+    index.add(21, "the patient rests");
+    assert_eq!(index.query_ids("rests"), vec![21]);
+    assert!(!index.indexes.contains_key("patient"));
+    assert_eq!(index.doc_terms(21).unwrap().count(), 1);
+
+    // Other words are normalized first.
+    index.apply_stop_words(&["Rare".to_string()]);
+    assert!(index.query("rare").is_empty());
+    assert_eq!(index.query_ids("words"), vec![20]);
+}
@@ -0,0 +1,11 @@
+//! Common German function words.
+
+pub(super) static GERMAN: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "damit", "dann", "das", "dass", "dein", "dem", "den", "der", "des", "dich", "die", "dir",
+    "doch", "du", "durch", "ein", "eine", "einem", "einen", "einer", "eines", "er", "es", "euch",
+    "euer", "für", "hat", "hatte", "ich", "ihr", "ihre", "im", "in", "ist", "ja", "kein", "man",
+    "mein", "mich", "mir", "mit", "nach", "nicht", "noch", "nur", "ob", "oder", "ohne", "sein",
+    "sich", "sie", "sind", "so", "über", "um", "und", "uns", "unser", "unter", "vom", "von", "vor",
+    "war", "was", "weil", "wenn", "wer", "wie", "wir", "wird", "zu", "zum", "zur",
+];
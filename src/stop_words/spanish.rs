@@ -0,0 +1,11 @@
+//! Common Spanish function words.
+
+pub(super) static SPANISH: &[&str] = &[
+    "a", "al", "algo", "algunos", "ante", "antes", "como", "con", "contra", "cual", "cuando", "de",
+    "del", "desde", "donde", "durante", "e", "el", "ella", "ellas", "ellos", "en", "entre", "era",
+    "es", "esa", "esas", "ese", "eso", "esos", "esta", "estas", "este", "esto", "estos", "está",
+    "están", "fue", "fueron", "ha", "han", "hasta", "hay", "la", "las", "le", "les", "lo", "los",
+    "me", "mi", "mis", "muy", "más", "ni", "no", "nos", "nosotros", "o", "otra", "otro", "para",
+    "pero", "poco", "por", "porque", "que", "quien", "se", "ser", "si", "sin", "sobre", "son",
+    "su", "sus", "también", "te", "tu", "tus", "un", "una", "uno", "unos", "y", "ya", "yo", "él",
+];
@@ -0,0 +1,194 @@
+//! The spellings terms were indexed from, so that suggestions can show `"Rust"` rather than
+//! the lowercased `"rust"`.
+
+use crate::index::{Document, InvertedIndex};
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Each term's distinct original spellings with how often each occurs, see
+/// [`IndexBuilder::surface_forms`](crate::IndexBuilder::surface_forms).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SurfaceForms {
+    /// The spellings of each term with their occurrence counts, in order of first appearance.
+    pub(crate) forms: HashMap<String, Vec<(String, u32)>>,
+}
+
+impl SurfaceForms {
+    /// Counts an occurrence of `term` written as `form`.
+    pub(crate) fn insert(&mut self, term: &str, form: &str) {
+        let forms = match self.forms.get_mut(term) {
+            Some(forms) => forms,
+            None => self.forms.entry(term.to_string()).or_default(),
+        };
+        match forms.iter_mut().find(|(other, _)| other == form) {
+            Some((_, count)) => *count += 1,
+            None => forms.push((form.to_string(), 1)),
+        }
+    }
+
+    /// Uncounts an occurrence of `term` written as `form`, dropping spellings and terms
+    /// left without any.
+    fn remove(&mut self, term: &str, form: &str) {
+        let Some(forms) = self.forms.get_mut(term) else {
+            return;
+        };
+        if let Some(i) = forms.iter().position(|(other, _)| other == form) {
+            forms[i].1 -= 1;
+            if forms[i].1 == 0 {
+                forms.remove(i);
+            }
+        }
+        if forms.is_empty() {
+            self.forms.remove(term);
+        }
+    }
+
+    /// The most frequent spelling of `term`, the first seen on a tie, or `term` itself if
+    /// none was recorded.
+    pub(crate) fn display<'a>(&'a self, term: &'a str) -> &'a str {
+        let Some(forms) = self.forms.get(term) else {
+            return term;
+        };
+        // `max_by_key` keeps the last maximum, so search from the end for the first one.
+        forms
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map_or(term, |(form, _)| form.as_str())
+    }
+
+    /// The number of (term, spelling) entries.
+    pub(crate) fn len(&self) -> usize {
+        self.forms.values().map(Vec::len).sum()
+    }
+
+    /// Estimated heap usage in bytes, like [`InvertedIndex::memory_usage`].
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.forms.capacity() * (size_of::<String>() + size_of::<Vec<(String, u32)>>() + 1)
+            + self
+                .forms
+                .iter()
+                .map(|(term, forms)| {
+                    term.capacity()
+                        + forms.capacity() * size_of::<(String, u32)>()
+                        + forms.iter().map(|(form, _)| form.capacity()).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        for forms in self.forms.values_mut() {
+            forms.shrink_to_fit();
+        }
+        self.forms.shrink_to_fit();
+    }
+}
+
+impl InvertedIndex {
+    /// `term` in its most frequent original spelling, see
+    /// [`IndexBuilder::surface_forms`](crate::IndexBuilder::surface_forms).
+    pub(crate) fn display_form<'a>(&'a self, term: &'a str) -> &'a str {
+        self.surface_forms
+            .as_ref()
+            .map_or(term, |forms| forms.display(term))
+    }
+
+    /// Counts (`add` is `true`) or uncounts the spellings of a document's tokens, found
+    /// by normalizing each of them again.
+    pub(crate) fn count_surface_forms(&mut self, doc: &Document, add: bool) {
+        let Some(mut forms) = self.surface_forms.take() else {
+            return;
+        };
+        if let Some(content) = self.content(doc) {
+            // Positions between fields have an empty range.
+            for &(start, end) in doc.offsets.iter().filter(|(start, end)| start < end) {
+                let form = &content[start..end];
+                let Some(term) = self.analyzer.normalize(form) else {
+                    continue;
+                };
+                if self.capped_terms.contains(&term) {
+                    continue;
+                }
+                if add {
+                    forms.insert(&term, form);
+                } else {
+                    forms.remove(&term, form);
+                }
+            }
+        }
+        self.surface_forms = Some(forms);
+    }
+
+    /// Drops the spellings of a term no longer indexed.
+    pub(crate) fn forget_surface_forms(&mut self, term: &str) {
+        if let Some(forms) = &mut self.surface_forms {
+            forms.forms.remove(term);
+        }
+    }
+
+    /// Recounts the spellings of every document, for indexes loaded without them. Spellings
+    /// are seen in ascending document id order, which settles the ties.
+    pub(crate) fn rebuild_surface_forms(&mut self) {
+        if self.surface_forms.is_none() {
+            return;
+        }
+        self.surface_forms = Some(SurfaceForms::default());
+        let mut documents: Vec<_> = self.documents.values().cloned().collect();
+        documents.sort_unstable_by_key(|doc| doc.id);
+        for doc in documents {
+            self.count_surface_forms(&doc, true);
+        }
+    }
+}
+
+#[test]
+fn surface_forms_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust and rust, then Rust again");
+    index.add(2, "rust in go, GO and Go");
+    // "rust" and "Rust" are tied, and "Rust" was seen first.
+    assert_eq!(index.display_form("rust"), "Rust");
+    // "GO" and "Go" are tied after "go", which was seen first.
+    assert_eq!(index.display_form("go"), "go");
+    index.add(3, "Go");
+    assert_eq!(index.display_form("go"), "Go");
+    assert_eq!(index.display_form("unknown"), "unknown");
+
+    assert_eq!(index.autocomplete("RU", 1)[0].term, "Rust");
+    assert_eq!(index.suggest("rusd").as_deref(), Some("Rust"));
+    assert_eq!(index.top_terms(3)[1], ("Go".to_string(), 2));
+    // Matching is still case-insensitive.
+    assert_eq!(index.query_ids("RUST"), vec![1, 2]);
+
+    // Removing and updating documents uncounts their spellings.
+    index.update(2, "rust rust").unwrap();
+    assert_eq!(index.display_form("rust"), "rust");
+    index.remove(2);
+    assert_eq!(index.display_form("rust"), "Rust");
+    index.remove(1);
+    index.remove(3);
+    assert!(index.surface_forms.as_ref().unwrap().forms.is_empty());
+    index.shrink();
+    assert_eq!(index.memory_usage().surface_forms, 0);
+
+    let mut index = InvertedIndex::builder().stemming(true).build();
+    index.add(1, "Running and running, RUNS");
+    index.add_fields(2, &[("title", "Runs"), ("body", "runs")]);
+    assert_eq!(index.display_form("run"), "Running");
+    assert_eq!(index.stats().surface_form_entries, 6);
+    assert!(index.memory_usage().surface_forms > 0);
+
+    let mut bytes = Vec::new();
+    index.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.surface_forms, index.surface_forms);
+
+    let mut plain = InvertedIndex::builder().surface_forms(false).build();
+    plain.add(1, "Rust Rust");
+    assert_eq!(plain.autocomplete("r", 1)[0].term, "rust");
+    assert_eq!(plain.memory_usage().surface_forms, 0);
+    let mut bytes = Vec::new();
+    plain.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert!(loaded.surface_forms.is_none());
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the unit tests.
+
+use std::sync::{Mutex, MutexGuard};
+
+static COLOR: Mutex<()> = Mutex::new(());
+
+/// Forces `colored` on or off until the guard is dropped.
+///
+/// The override is process-wide, so tests that compare highlighted output hold this guard to
+/// keep tests running in parallel from flipping it underneath them.
+pub(crate) fn color_guard(enabled: bool) -> MutexGuard<'static, ()> {
+    let guard = COLOR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    colored::control::set_override(enabled);
+    guard
+}
@@ -0,0 +1,219 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// A word of a text, as yielded by [`tokenize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    /// The word, a slice of the text.
+    pub text: &'a str,
+    /// The number of words before this one.
+    pub position: usize,
+    /// Where the word starts in the text, in bytes.
+    pub start: usize,
+    /// Where the word ends in the text, in bytes.
+    pub end: usize,
+}
+
+/// Breaks a string into words at every character that is not alphanumeric, lazily, so that
+/// nothing is allocated.
+pub fn tokenize(text: &str) -> impl Iterator<Item = Token<'_>> {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .map(move |(position, word)| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            Token {
+                text: word,
+                position,
+                start,
+                end: start + word.len(),
+            }
+        })
+}
+
+/// The words [`tokenize`] yields, collected.
+pub fn tokenize_vec(text: &str) -> Vec<&str> {
+    tokenize(text).map(|token| token.text).collect()
+}
+
+/// Breaks a string into words like [`tokenize_vec`], lowercasing them in the same pass.
+///
+/// Each word is lowercased as it is copied out of `text`, so the only allocations are the
+/// returned strings, not a lowercase copy of the whole text to tokenize.
+pub fn tokenize_normalized(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, ch) in text.char_indices() {
+        if ch.is_alphanumeric() {
+            start.get_or_insert(i);
+        } else if let Some(from) = start.take() {
+            // Lowercasing the whole word, not char by char, keeps a final sigma.
+            tokens.push(text[from..i].to_lowercase());
+        }
+    }
+    if let Some(from) = start {
+        tokens.push(text[from..].to_lowercase());
+    }
+    tokens
+}
+
+/// Splits text into the tokens that get normalized into terms.
+///
+/// Choose one with [`IndexBuilder::tokenizer`](crate::IndexBuilder::tokenizer). The same
+/// tokenizer splits documents and queries.
+pub trait Tokenizer: fmt::Debug + Send + Sync {
+    /// The tokens of `text`, in order.
+    ///
+    /// Tokens should be slices of `text`: the index records where each one is to highlight
+    /// it, and cannot highlight a token that is not part of the text.
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str>;
+}
+
+/// Splits at every character that is not alphanumeric, like [`tokenize`]. The default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimpleTokenizer;
+
+impl Tokenizer for SimpleTokenizer {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        tokenize_vec(text)
+    }
+}
+
+/// Like [`SimpleTokenizer`], but keeps a hyphen or apostrophe between two alphanumeric
+/// characters inside the token, so "don't" and "state-of-the-art" are single tokens.
+///
+/// Any other punctuation still splits: "U.S.A." is "U", "S" and "A".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordTokenizer;
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        let mut chars = text.char_indices().peekable();
+        let mut previous_alphanumeric = false;
+        while let Some((i, ch)) = chars.next() {
+            let joiner = matches!(ch, '-' | '\'' | '\u{2019}')
+                && previous_alphanumeric
+                && chars.peek().is_some_and(|(_, next)| next.is_alphanumeric());
+            if ch.is_alphanumeric() || joiner {
+                start.get_or_insert(i);
+            } else if let Some(from) = start.take() {
+                tokens.push(&text[from..i]);
+            }
+            previous_alphanumeric = ch.is_alphanumeric();
+        }
+        if let Some(from) = start {
+            tokens.push(&text[from..]);
+        }
+        tokens
+    }
+}
+
+/// The built-in tokenizers, by the code a saved index records for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenizerKind {
+    Simple = 0,
+    Word = 1,
+}
+
+impl TokenizerKind {
+    /// Which built-in tokenizer `tokenizer` is, if any.
+    pub(crate) fn of<T: Tokenizer + 'static>(tokenizer: &T) -> Option<Self> {
+        let tokenizer: &dyn Any = tokenizer;
+        if tokenizer.is::<SimpleTokenizer>() {
+            Some(TokenizerKind::Simple)
+        } else if tokenizer.is::<WordTokenizer>() {
+            Some(TokenizerKind::Word)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn from_code(code: usize) -> Option<Self> {
+        match code {
+            0 => Some(TokenizerKind::Simple),
+            1 => Some(TokenizerKind::Word),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn tokenizer(self) -> Arc<dyn Tokenizer> {
+        match self {
+            TokenizerKind::Simple => Arc::new(SimpleTokenizer),
+            TokenizerKind::Word => Arc::new(WordTokenizer),
+        }
+    }
+}
+
+#[test]
+fn tokenize_test() {
+    assert_eq!(
+        tokenize_vec("This is\nhedon's tokenize function."),
+        vec!["This", "is", "hedon", "s", "tokenize", "function"]
+    )
+}
+
+#[test]
+fn token_iterator_test() {
+    let text = "¡Olá, 🦀 café!";
+    let tokens: Vec<Token> = tokenize(text).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token {
+                text: "Olá",
+                position: 0,
+                start: 2,
+                end: 6
+            },
+            Token {
+                text: "café",
+                position: 1,
+                start: 13,
+                end: 18
+            },
+        ]
+    );
+    assert!(
+        tokens
+            .iter()
+            .all(|token| &text[token.start..token.end] == token.text)
+    );
+    assert_eq!(tokenize(" ,. ").count(), 0);
+}
+
+#[test]
+fn tokenize_normalized_test() {
+    let text = "This is\nHEDON's Straße, ÉTÉ 2024.";
+    assert_eq!(
+        tokenize_normalized(text),
+        tokenize_vec(text)
+            .into_iter()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(tokenize_normalized("ΟΔΟΣ İ"), vec!["οδος", "i\u{307}"]);
+    assert!(tokenize_normalized(" ,. ").is_empty());
+}
+
+#[test]
+fn word_tokenizer_test() {
+    let cases: [(&str, &[&str]); 7] = [
+        ("don't", &["don't"]),
+        ("Don’t stop", &["Don’t", "stop"]),
+        ("e-mail me", &["e-mail", "me"]),
+        ("state-of-the-art", &["state-of-the-art"]),
+        ("U.S.A.", &["U", "S", "A"]),
+        ("'quoted' -dash- a--b", &["quoted", "dash", "a", "b"]),
+        ("rock 'n' roll's", &["rock", "n", "roll's"]),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(WordTokenizer.tokenize(text), expected, "{text}");
+    }
+    assert_eq!(
+        SimpleTokenizer.tokenize("don't e-mail"),
+        vec!["don", "t", "e", "mail"]
+    );
+}
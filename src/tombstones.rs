@@ -0,0 +1,246 @@
+//! Soft deletion, see [`IndexBuilder::soft_delete`](crate::IndexBuilder::soft_delete) and
+//! [`InvertedIndex::mark_deleted`].
+//!
+//! A soft-deleted document is dropped from the document store at once, but its postings stay
+//! until the next [`InvertedIndex::compact`]. Until then every query skips them, and document
+//! frequencies only count live documents, so results are the same as after a hard delete.
+
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use crate::postings::Posting;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+impl InvertedIndex {
+    /// Physically removes the postings of soft-deleted documents. Terms left without any
+    /// document are dropped. Query results are unaffected.
+    ///
+    /// With a [document store](crate::IndexBuilder::document_store), the store file is also
+    /// rewritten without the contents of removed and replaced documents, if it has any. A
+    /// store that cannot be rewritten keeps them.
+    ///
+    /// # Returns
+    /// The number of deleted documents purged.
+    pub fn compact(&mut self) -> usize {
+        let tombstones = std::mem::take(&mut self.tombstones);
+        self.purge(&tombstones);
+        self.compact_store();
+        tombstones.len()
+    }
+
+    /// Soft-deletes document `id`, whether or not [soft deletion](crate::IndexBuilder::soft_delete)
+    /// is configured: the content is dropped and every query skips the document from now on,
+    /// but its postings stay in place until [`compact`](Self::compact) is called. Unlike a
+    /// configured soft delete, this never compacts by itself.
+    ///
+    /// # Returns
+    /// `false` if no document has this id.
+    pub fn mark_deleted(&mut self, id: usize) -> bool {
+        if !self.documents.contains_key(&id) {
+            return false;
+        }
+        self.record(LogEntry::Remove { id });
+        if let Some(doc) = self.documents.remove(&id) {
+            self.total_tokens -= doc.length;
+            self.count_surface_forms(&doc, false);
+            self.forget_numeric(id);
+        }
+        self.generation += 1;
+        self.tombstones.insert(id);
+        true
+    }
+
+    /// Whether document `id` was soft-deleted and its postings are awaiting
+    /// [`compact`](Self::compact).
+    pub fn is_deleted(&self, id: usize) -> bool {
+        self.tombstones.contains(&id)
+    }
+
+    /// Soft-deletes the stored document `id`, compacting once deleted documents make up more
+    /// than the configured ratio of all documents.
+    pub(crate) fn tombstone(&mut self, id: usize, compact_ratio: f64) {
+        self.tombstones.insert(id);
+        let total = self.documents.len() + self.tombstones.len();
+        if self.tombstones.len() as f64 > compact_ratio * total as f64 {
+            self.compact();
+        }
+    }
+
+    /// Drops the postings of `ids` from every posting list, in one pass over the vocabulary.
+    pub(crate) fn purge(&mut self, ids: &HashSet<usize>) {
+        if ids.is_empty() {
+            return;
+        }
+        let ngrams = &mut self.ngrams;
+        self.indexes.retain(|term, postings| {
+            if postings.iter().any(|posting| ids.contains(&posting.doc_id)) {
+                Arc::make_mut(postings).retain(|posting| !ids.contains(&posting.doc_id));
+            }
+            if postings.is_empty()
+                && let Some(ngrams) = ngrams
+            {
+                ngrams.remove(term);
+            }
+            !postings.is_empty()
+        });
+    }
+
+    /// The postings of an analyzed term without those of soft-deleted documents, or `None`
+    /// if no live document contains the term.
+    ///
+    /// The list is only copied while documents are awaiting compaction.
+    pub(crate) fn live_postings(&self, term: &str) -> Option<Cow<'_, [Posting]>> {
+        let postings = self.indexes.get(term)?;
+        if self.tombstones.is_empty() {
+            return Some(Cow::Borrowed(postings));
+        }
+        let live: Vec<Posting> = postings
+            .iter()
+            .filter(|posting| !self.tombstones.contains(&posting.doc_id))
+            .cloned()
+            .collect();
+        (!live.is_empty()).then_some(Cow::Owned(live))
+    }
+
+    /// The number of live documents in `postings`, the document frequency of its term.
+    pub(crate) fn live_len(&self, postings: &[Posting]) -> usize {
+        if self.tombstones.is_empty() {
+            return postings.len();
+        }
+        postings
+            .iter()
+            .filter(|posting| !self.tombstones.contains(&posting.doc_id))
+            .count()
+    }
+}
+
+#[test]
+fn mark_deleted_test() {
+    let _color = crate::test_util::color_guard(false);
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is fast");
+    index.add(2, "Rust is safe");
+    index.add(3, "Go is simple");
+
+    assert!(index.mark_deleted(2));
+    assert!(index.is_deleted(2));
+    assert!(!index.is_deleted(1));
+    assert!(!index.mark_deleted(2));
+    assert!(!index.mark_deleted(9));
+    // Gone from results while its postings are still in place.
+    assert_eq!(index.query("rust"), vec!["Rust is fast"]);
+    assert!(index.query("safe").is_empty());
+    assert_eq!(index.indexes["rust"].len(), 2);
+    let stats = index.stats();
+    assert_eq!((stats.document_count, stats.deleted_documents), (2, 1));
+
+    assert_eq!(index.compact(), 1);
+    assert!(!index.is_deleted(2));
+    assert_eq!(index.indexes["rust"].len(), 1);
+    assert!(!index.indexes.contains_key("safe"));
+    // Adding the id again brings the document back.
+    index.mark_deleted(3);
+    index.add(3, "Go is back");
+    assert!(!index.is_deleted(3));
+    assert_eq!(index.query("go"), vec!["Go is back"]);
+}
+
+#[test]
+fn tombstoned_documents_never_match_test() {
+    use crate::{Query, SearchOptions};
+
+    let build = || {
+        let mut index = InvertedIndex::builder().soft_delete(1.0).build();
+        index.add(1, "Rust is fast and safe");
+        index.add(2, "Rust and Go are fast");
+        index.add(3, "Go is simple");
+        index.add(4, "New York has fast Rust programmers");
+        index
+    };
+    let mut soft = build();
+    let mut hard = build();
+    hard.compact_ratio = None;
+    assert!(soft.remove(2));
+    assert!(!soft.remove(2));
+    assert!(hard.remove(2));
+
+    let all = SearchOptions::default();
+    let search = |index: &InvertedIndex, text: &str| {
+        let query = Query::parse(text).unwrap();
+        let hits = index.search_query(&query, &all);
+        hits.iter()
+            .map(|hit| (hit.doc_id, hit.score))
+            .collect::<Vec<_>>()
+    };
+    let check = |index: &InvertedIndex| {
+        assert_eq!(index.query("rust"), hard.query("rust"));
+        assert_eq!(index.intersect(&["rust", "fast"]), vec![1, 4]);
+        assert_eq!(index.union(&["go"]), vec![3]);
+        assert_eq!(index.phrase_doc_ids("fast rust"), vec![4]);
+        assert!(index.query_phrase("go are fast").is_empty());
+        assert_eq!(index.query_bm25("fast"), hard.query_bm25("fast"));
+        assert_eq!(index.query_all("rust fast"), hard.query_all("rust fast"));
+        assert_eq!(
+            index.search("fast", &all).len(),
+            hard.search("fast", &all).len()
+        );
+        assert_eq!(search(index, "fast OR go"), search(&hard, "fast OR go"));
+        assert_eq!(index.doc_freq("rust"), 2);
+        assert_eq!(index.postings("go").unwrap().collect::<Vec<_>>(), vec![3]);
+        let ids: Vec<usize> = index
+            .query_all_iter(&["fast"])
+            .map(|hit| hit.doc_id)
+            .collect();
+        assert_eq!(ids, vec![1, 4]);
+        assert_eq!(index.query_min_should_match(&["go", "rust"], 2).len(), 0);
+    };
+    check(&soft);
+    assert_eq!(soft.get(2), None);
+    let mut bytes = Vec::new();
+    soft.save(&mut bytes).unwrap();
+    assert_eq!(
+        InvertedIndex::load(bytes.as_slice()).unwrap().indexes,
+        hard.indexes
+    );
+    assert!(!soft.terms().any(|term| term == "are"));
+    let stats = soft.stats();
+    assert_eq!((stats.document_count, stats.deleted_documents), (3, 1));
+
+    let before = soft.stats().total_postings;
+    assert_eq!(soft.compact(), 1);
+    check(&soft);
+    assert!(soft.stats().total_postings < before);
+    assert_eq!(soft.stats().total_postings, hard.stats().total_postings);
+    assert_eq!(soft.stats().deleted_documents, 0);
+    assert_eq!(soft.indexes, hard.indexes);
+}
+
+#[test]
+fn tombstone_compaction_threshold_test() {
+    let mut index = InvertedIndex::builder().soft_delete(0.5).build();
+    for id in 0..4 {
+        index.add(id, "shared words");
+    }
+    index.remove(0);
+    index.remove(1);
+    assert_eq!(index.stats().deleted_documents, 2);
+    // 3 of 4 deleted is over half, so this removal compacts.
+    index.remove(2);
+    assert_eq!(index.stats().deleted_documents, 0);
+    assert_eq!(index.stats().total_postings, 2);
+
+    // A ratio of 1.0 only compacts on demand.
+    let mut index = InvertedIndex::builder().soft_delete(1.0).build();
+    index.add(1, "shared words");
+    index.add(2, "shared words");
+    index.remove(1);
+    index.remove(2);
+    assert_eq!(index.stats().deleted_documents, 2);
+    // Re-adding a deleted id replaces its stale postings.
+    index.add(2, "words again");
+    assert_eq!(index.union(&["shared"]), Vec::<usize>::new());
+    assert_eq!(index.union(&["words"]), vec![2]);
+    assert_eq!(index.doc_freq("words"), 1);
+    assert_eq!(index.stats().deleted_documents, 1);
+}
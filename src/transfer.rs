@@ -0,0 +1,132 @@
+//! Copying a single term's posting list out of one index and into another, e.g. to move a
+//! hot term between nodes without saving the whole index.
+
+use crate::error::IndexError;
+use crate::index::InvertedIndex;
+use crate::oplog::LogEntry;
+use crate::postings::Posting;
+use std::sync::Arc;
+
+impl InvertedIndex {
+    /// An owned copy of the posting list of `term`, analyzed like a [`query`](Self::query)
+    /// term, to pass to [`import_term`](Self::import_term).
+    ///
+    /// # Returns
+    /// The analyzed term with the ids of the documents containing it, ascending and without
+    /// duplicates, soft-deleted documents left out. `None` if the term is not indexed.
+    pub fn export_term(&self, term: &str) -> Option<(String, Vec<usize>)> {
+        let term = self.analyze_term(term)?;
+        let ids = self
+            .live_postings(&term)?
+            .iter()
+            .map(|posting| posting.doc_id)
+            .collect();
+        Some((term, ids))
+    }
+
+    /// Merges a list from [`export_term`](Self::export_term) into the posting list of
+    /// `term`, which is taken as already analyzed, so that documents of `ids` match it.
+    ///
+    /// # Returns
+    /// The number of documents added to the list; the ids may be in any order and repeat,
+    /// and those already in the list are kept as they are. [`IndexError::DocumentNotFound`],
+    /// leaving the index untouched, if an id is not a stored document. A
+    /// [capped](crate::IndexBuilder::max_postings) term imports nothing.
+    ///
+    /// # Notes
+    /// A list of ids carries no token positions, so an imported document matches the term
+    /// like one containing it once, but never as part of a phrase, and is not highlighted.
+    /// Its length is unchanged.
+    pub fn import_term(&mut self, term: String, mut ids: Vec<usize>) -> Result<usize, IndexError> {
+        if let Some(&id) = ids.iter().find(|id| !self.documents.contains_key(id)) {
+            return Err(IndexError::DocumentNotFound(id));
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.is_empty() || self.capped_terms.contains(&term) {
+            return Ok(0);
+        }
+        self.record(LogEntry::ImportTerm {
+            term: &term,
+            ids: &ids,
+        });
+        if !self.indexes.contains_key(&term)
+            && let Some(ngrams) = &mut self.ngrams
+        {
+            ngrams.insert(&term);
+        }
+        let postings = Arc::make_mut(self.indexes.entry(term.clone()).or_default());
+        let mut added = Vec::new();
+        for id in ids {
+            if let Err(i) = postings.binary_search_by_key(&id, |posting| posting.doc_id) {
+                postings.insert(
+                    i,
+                    Posting {
+                        doc_id: id,
+                        positions: Vec::new(),
+                    },
+                );
+                added.push(id);
+            }
+        }
+        if self.forward_index {
+            for &id in &added {
+                let Some(doc) = self.documents.get_mut(&id).map(Arc::make_mut) else {
+                    continue;
+                };
+                if let Err(i) = doc
+                    .terms
+                    .binary_search_by(|(other, _)| other.as_str().cmp(&term))
+                {
+                    doc.terms.insert(i, (term.clone(), 1));
+                }
+            }
+        }
+        self.generation += 1;
+        Ok(added.len())
+    }
+}
+
+#[test]
+fn export_import_term_test() {
+    let mut source = InvertedIndex::builder().stemming(true).build();
+    let mut target = InvertedIndex::builder().stemming(true).build();
+    for index in [&mut source, &mut target] {
+        index.add(1, "Rust programs");
+        index.add(2, "Go programs");
+        index.add(3, "Python scripts");
+        index.add(4, "Zig programs");
+    }
+    source.add(3, "Python programs");
+    target.remove_term("program");
+    source.remove(4);
+
+    let (term, ids) = source.export_term("Programs").unwrap();
+    assert_eq!((term.as_str(), ids.as_slice()), ("program", &[1, 2, 3][..]));
+    assert_eq!(source.export_term("missing"), None);
+
+    assert_eq!(target.import_term(term.clone(), vec![3, 1, 3]).unwrap(), 2);
+    assert_eq!(target.import_term(term.clone(), ids.clone()).unwrap(), 1);
+    assert_eq!(target.import_term(term.clone(), ids).unwrap(), 0);
+    assert_eq!(target.query_ids("programs"), vec![1, 2, 3]);
+    assert!(target.query_phrase("python programs").is_empty());
+    assert_eq!(target.query_bm25("programs").len(), 3);
+    assert_eq!(target.doc_terms(3).unwrap().count(), 3);
+    assert_eq!(target.validate(), Ok(()));
+
+    // Unknown documents are refused before anything changes.
+    assert!(matches!(
+        target.import_term("new".to_string(), vec![1, 9]),
+        Err(IndexError::DocumentNotFound(9))
+    ));
+    assert!(target.export_term("new").is_none());
+
+    // Imported postings are removed with their documents and survive saving.
+    target.remove(1);
+    assert_eq!(target.query_ids("programs"), vec![2, 3]);
+    let mut bytes = Vec::new();
+    target.save(&mut bytes).unwrap();
+    let loaded = InvertedIndex::load(bytes.as_slice()).unwrap();
+    assert_eq!(loaded.indexes, target.indexes);
+    assert_eq!(loaded.validate(), Ok(()));
+}
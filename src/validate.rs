@@ -0,0 +1,199 @@
+//! Consistency checks of the internal invariants, to catch indexing bugs.
+
+use crate::index::InvertedIndex;
+use std::collections::HashSet;
+
+impl InvertedIndex {
+    /// Checks that the postings and the stored documents agree, e.g. in tests or after a
+    /// [`load`](Self::load).
+    ///
+    /// The checks are that:
+    /// - every posting names a stored or soft-deleted document, and no document is both;
+    /// - every posting list is non-empty and strictly ascending by document id, so without
+    ///   duplicates, and its positions, which only [imported](Self::import_term) postings
+    ///   lack, are strictly ascending and within the document;
+    /// - every term of a stored document has a posting for it, with the frequency the
+    ///   [forward index](crate::IndexBuilder::forward_index) records, and every posting
+    ///   appears in the forward index. Without the forward index each document is
+    ///   re-analyzed from its content instead, if [stored](crate::IndexBuilder::store_content);
+    /// - no [capped](crate::IndexBuilder::max_postings) term has postings;
+    /// - the total token count is the sum of the document lengths.
+    ///
+    /// # Returns
+    /// A description of each violation found, in term and then document id order.
+    ///
+    /// # Notes
+    /// This reads every posting, and the content of every document when the forward index
+    /// is disabled, so it is slow on a large index.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut violations = Vec::new();
+        let mut terms: Vec<&String> = self.indexes.keys().collect();
+        terms.sort_unstable();
+        for term in terms {
+            let postings = &self.indexes[term];
+            if postings.is_empty() {
+                violations.push(format!("term \"{term}\" has no postings"));
+            }
+            if self.capped_terms.contains(term) {
+                violations.push(format!("capped term \"{term}\" has postings"));
+            }
+            for (i, posting) in postings.iter().enumerate() {
+                let id = posting.doc_id;
+                if i > 0 && postings[i - 1].doc_id >= id {
+                    violations.push(format!(
+                        "term \"{term}\": posting for {id} is out of order or duplicated"
+                    ));
+                }
+                if posting.positions.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    violations.push(format!(
+                        "term \"{term}\": positions in {id} are not strictly ascending"
+                    ));
+                }
+                let Some(doc) = self.documents.get(&id) else {
+                    if !self.tombstones.contains(&id) {
+                        violations.push(format!(
+                            "term \"{term}\": posting for unknown document {id}"
+                        ));
+                    }
+                    continue;
+                };
+                // Documents without contents keep no offsets to check against.
+                if self.keep_content
+                    && posting
+                        .positions
+                        .last()
+                        .is_some_and(|&last| last >= doc.offsets.len())
+                {
+                    violations.push(format!(
+                        "term \"{term}\": positions in {id} are past the end of the document"
+                    ));
+                }
+                if self.forward_index
+                    && doc
+                        .terms
+                        .binary_search_by(|(other, _)| other.cmp(term))
+                        .is_err()
+                {
+                    violations.push(format!(
+                        "term \"{term}\": document {id} lacks it in the forward index"
+                    ));
+                }
+            }
+        }
+
+        let mut ids: Vec<&usize> = self.documents.keys().collect();
+        ids.sort_unstable();
+        for &id in ids {
+            let doc = &self.documents[&id];
+            if self.tombstones.contains(&id) {
+                violations.push(format!("document {id} is both stored and deleted"));
+            }
+            let expected: Vec<(String, Option<u32>)> = if self.forward_index {
+                if doc.terms.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    violations.push(format!("document {id}: forward index is not sorted"));
+                }
+                doc.terms
+                    .iter()
+                    .map(|(term, freq)| (term.clone(), Some(*freq)))
+                    .collect()
+            } else if !self.keep_content {
+                Vec::new()
+            } else {
+                let Some(content) = self.content(doc) else {
+                    violations.push(format!("document {id}: content cannot be read"));
+                    continue;
+                };
+                let terms: HashSet<String> = self
+                    .analyzer
+                    .analyze(&content)
+                    .into_iter()
+                    .filter(|term| !self.capped_terms.contains(term))
+                    .collect();
+                terms.into_iter().map(|term| (term, None)).collect()
+            };
+            for (term, freq) in expected {
+                let posting = self.indexes.get(&term).and_then(|postings| {
+                    let i = postings
+                        .binary_search_by_key(&id, |posting| posting.doc_id)
+                        .ok()?;
+                    Some(&postings[i])
+                });
+                match (posting, freq) {
+                    (None, _) => violations.push(format!(
+                        "document {id}: term \"{term}\" has no posting for it"
+                    )),
+                    (Some(posting), Some(freq)) if posting.term_freq() != freq as usize => {
+                        violations.push(format!(
+                            "document {id}: term \"{term}\" occurs {} times, the forward index says {freq}",
+                            posting.term_freq()
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let total: usize = self.documents.values().map(|doc| doc.length).sum();
+        if total != self.total_tokens {
+            violations.push(format!(
+                "total token count is {}, the documents add up to {total}",
+                self.total_tokens
+            ));
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[test]
+fn validate_test() {
+    use crate::postings::Posting;
+    use std::sync::Arc;
+
+    let mut index = InvertedIndex::builder().stop_words(["is"]).build();
+    index.add(1, "Rust is fast");
+    index.add(2, "rust rust everywhere");
+    index.add_fields(3, &[("title", "Go"), ("body", "is simple")]);
+    index.remove(3);
+    assert_eq!(index.validate(), Ok(()));
+
+    let postings = Arc::make_mut(index.indexes.get_mut("rust").unwrap());
+    postings.push(Posting {
+        doc_id: 2,
+        positions: vec![0, 1],
+    });
+    postings.push(Posting {
+        doc_id: 9,
+        positions: vec![0],
+    });
+    index.indexes.remove("fast");
+    index.total_tokens += 1;
+    assert_eq!(
+        index.validate(),
+        Err(vec![
+            "term \"rust\": posting for 2 is out of order or duplicated".to_string(),
+            "term \"rust\": posting for unknown document 9".to_string(),
+            "document 1: term \"fast\" has no posting for it".to_string(),
+            "total token count is 6, the documents add up to 5".to_string(),
+        ])
+    );
+
+    // Without the forward index the contents are re-analyzed.
+    let mut scanned = InvertedIndex::builder().forward_index(false).build();
+    scanned.add(1, "Rust is fast");
+    assert_eq!(scanned.validate(), Ok(()));
+    scanned.indexes.remove("is");
+    assert_eq!(
+        scanned.validate(),
+        Err(vec![
+            "document 1: term \"is\" has no posting for it".to_string()
+        ])
+    );
+
+    let loaded =
+        InvertedIndex::load(&include_bytes!("../tests/fixtures/index_v5.bin")[..]).unwrap();
+    assert_eq!(loaded.validate(), Ok(()));
+}
@@ -0,0 +1,162 @@
+//! Drives the `inverted_index` binary end to end.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_inverted_index"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "inverted_index_cli_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn index_search_stats_test() {
+    let dir = scratch_dir("search");
+    let docs = dir.join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("a.txt"), "Rust is safe and fast.").unwrap();
+    fs::write(docs.join("b.txt"), "Rust is a \"systems\" language.\nRust!").unwrap();
+    fs::write(docs.join("c.txt"), "Go is simple.").unwrap();
+    let index = dir.join("index.bin");
+    let index = index.to_str().unwrap();
+
+    let output = run(&["index", docs.to_str().unwrap(), "--out", index]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        format!("indexed 3 documents into {}", index)
+    );
+
+    let output = run(&["search", index, "rust", "--format", "json", "--limit", "1"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("[{\"id\":1,\"score\":"), "{}", stdout);
+    assert!(
+        stdout.contains("\"snippet\":\"Rust is a \\\"systems\\\" language. Rust!\""),
+        "{}",
+        stdout
+    );
+    assert_eq!(stdout.matches("\"id\"").count(), 1);
+
+    let output = run(&["search", index, "go"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("2\t"), "{}", stdout);
+    assert!(stdout.trim_end().ends_with("\tGo is simple."), "{}", stdout);
+    assert!(!stdout.contains('\u{1b}'));
+
+    let output = run(&["search", index, "go", "--format", "ansi"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\u{1b}[35mGo\u{1b}[0m"));
+
+    let output = run(&["search", index, "python"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["stats", index]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("documents:        3"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn errors_exit_with_two_test() {
+    let dir = scratch_dir("errors");
+    let missing = dir.join("missing.bin");
+    assert_eq!(
+        run(&["stats", missing.to_str().unwrap()]).status.code(),
+        Some(2)
+    );
+    assert_eq!(run(&[]).status.code(), Some(2));
+    assert_eq!(run(&["search", "x"]).status.code(), Some(2));
+    let output = run(&["search", "x", "q", "--format", "xml"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown --format xml"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn repl_test() {
+    let dir = scratch_dir("repl");
+    let docs = dir.join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("a.txt"), "Rust is safe and fast.").unwrap();
+    fs::write(docs.join("b.txt"), "Go is simple and fast.").unwrap();
+    let index = dir.join("index.bin");
+    let index = index.to_str().unwrap();
+    assert!(
+        run(&["index", docs.to_str().unwrap(), "--out", index])
+            .status
+            .success()
+    );
+
+    let repl = |input: &str, no_color: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_inverted_index"));
+        command
+            .args(["repl", index])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if no_color {
+            command.env("NO_COLOR", "1");
+        } else {
+            command.env_remove("NO_COLOR");
+        }
+        let mut child = command.spawn().unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let input = "fast -go\n(rust\n:bogus\n:terms s\n:doc 1\n:doc 9\n:history\n:quit\nrust\n";
+    let output = repl(input, true);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("0\t"), "{}", stdout);
+    assert!(lines[0].ends_with("\tRust is safe and fast."), "{}", stdout);
+    assert_eq!(
+        lines[1..4],
+        ["safe", "simple", docs.join("b.txt").to_str().unwrap()]
+    );
+    assert_eq!(lines[4], "Go is simple and fast.");
+    assert_eq!(lines[5].trim(), "1  fast -go");
+    // The session ended at :quit, before the last query.
+    assert_eq!(lines.len(), 12, "{}", stdout);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("error: expected ')' at byte 5"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("error: unknown command :bogus"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("error: no document 9"), "{}", stderr);
+
+    let output = repl("rust\npython\n", false);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\u{1b}[35mRust\u{1b}[0m"), "{}", stdout);
+    assert!(stdout.trim_end().ends_with("no results"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,88 @@
+//! Boots the HTTP server on an ephemeral port and exercises every endpoint.
+#![cfg(feature = "serve")]
+
+use inverted_index::{InvertedIndex, Server, SharedIndex};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+/// Sends one request and returns the status code and the body.
+fn request(addr: SocketAddr, method: &str, target: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        target,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+#[test]
+fn serve_test() {
+    let mut index = InvertedIndex::new();
+    index.add(1, "Rust is safe and fast.");
+    index.add(2, "Go is simple.");
+    let shared = SharedIndex::new(index);
+    let server = Server::bind("127.0.0.1:0", shared.clone()).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let (status, body) = request(addr, "GET", "/search?q=rust+OR+go&limit=1", "");
+    assert_eq!(status, 200);
+    assert!(body.starts_with("[{\"id\":"), "{}", body);
+    assert_eq!(body.matches("\"snippet\"").count(), 1);
+
+    let (status, body) = request(addr, "GET", "/search?q=%22is+simple%22", "");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"id\":2,"), "{}", body);
+    assert!(body.contains("\"snippet\":\"Go is simple.\""), "{}", body);
+
+    let (status, body) = request(addr, "GET", "/search?q=go&profile=true", "");
+    assert_eq!(status, 200);
+    assert!(body.starts_with("{\"hits\":[{\"id\":2,"), "{}", body);
+    assert!(body.contains("\"profile\":{\"lookup_us\":"), "{}", body);
+    assert!(
+        body.ends_with("\"candidates_scored\":1,\"results\":1}}"),
+        "{}",
+        body
+    );
+
+    let (status, body) = request(addr, "GET", "/search?q=(rust", "");
+    assert_eq!(status, 400);
+    assert_eq!(body, "{\"error\":\"expected ')' at byte 5\"}");
+
+    let document = r#"{"id": 3, "content": "Rust and \"Go\""}"#;
+    assert_eq!(request(addr, "POST", "/documents", document).0, 201);
+    assert_eq!(
+        shared
+            .read(|index| index.get(3).map(String::from))
+            .as_deref(),
+        Some("Rust and \"Go\"")
+    );
+    assert_eq!(request(addr, "POST", "/documents", "{\"id\": -1}").0, 400);
+
+    let (status, body) = request(addr, "GET", "/stats", "");
+    assert_eq!(status, 200);
+    assert!(body.starts_with("{\"documents\":3,"), "{}", body);
+    assert!(body.contains("\"top_terms\":[{\"term\":"), "{}", body);
+
+    assert_eq!(
+        request(addr, "DELETE", "/documents/1", ""),
+        (200, "{\"id\":1}".to_string())
+    );
+    assert_eq!(request(addr, "DELETE", "/documents/1", "").0, 404);
+    assert_eq!(request(addr, "DELETE", "/documents/x", "").0, 400);
+    assert_eq!(
+        request(addr, "GET", "/search?q=safe", ""),
+        (200, "[]".to_string())
+    );
+    assert_eq!(request(addr, "GET", "/nowhere", "").0, 404);
+    assert_eq!(request(addr, "PUT", "/stats", "").0, 405);
+}